@@ -9,17 +9,27 @@
 
 extern crate criterion;
 use core::fmt;
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use oram::DefaultOram;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use oram::eviction::{EvictionStrategy, GreedyEviction, LevelByLevelEviction};
+use oram::linear_time_oram::LinearTimeOram;
+use oram::oblivious::{bitonic_sort_by_keys, oblivious_shuffle};
+use oram::path_oram::{
+    DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK, DEFAULT_RECURSION_CUTOFF,
+    DEFAULT_STASH_OVERFLOW_SIZE,
+};
+use oram::ring_oram::RingOram;
+use oram::{DefaultOram, PathOram};
 use rand::CryptoRng;
 use rand::RngCore;
 use std::mem;
 use std::time::Duration;
 
-use oram::BlockSize;
 use oram::BlockValue;
+use oram::PositionBlock;
 use oram::{Address, Oram};
+use oram::{BlockSize, BucketSize, RecursionCutoff};
 use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::Instant;
 
 const CAPACITIES_TO_BENCHMARK: [Address; 3] = [1 << 14, 1 << 16, 1 << 20];
 
@@ -38,12 +48,65 @@ impl<const B: BlockSize> Benchmarkable for DefaultOram<BlockValue<B>> {
     }
 }
 
+// A Path ORAM with default parameters and eviction strategy `E`, used to compare eviction strategies.
+type StrategyOram<const B: BlockSize, E> =
+    PathOram<BlockValue<B>, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK, E>;
+
+impl<const B: BlockSize, E: EvictionStrategy> Benchmarkable for StrategyOram<B, E> {
+    fn short_name() -> String {
+        let strategy_name = std::any::type_name::<E>().rsplit("::").next().unwrap();
+        format!("PathOram<{}>", strategy_name)
+    }
+
+    fn new<R: CryptoRng + RngCore>(capacity: Address, rng: &mut R) -> Self {
+        Self::new_with_parameters(
+            capacity,
+            rng,
+            DEFAULT_STASH_OVERFLOW_SIZE,
+            DEFAULT_RECURSION_CUTOFF,
+        )
+        .unwrap()
+    }
+}
+
+// The number of dummy slots per bucket of the benchmarked Ring ORAM.
+const RING_ORAM_DUMMY_SLOTS: usize = 6;
+
+type BenchmarkRingOram<const B: BlockSize> =
+    RingOram<BlockValue<B>, DEFAULT_BLOCKS_PER_BUCKET, RING_ORAM_DUMMY_SLOTS>;
+
+impl<const B: BlockSize> Benchmarkable for BenchmarkRingOram<B> {
+    fn short_name() -> String {
+        "RingOram".into()
+    }
+
+    fn new<R: CryptoRng + RngCore>(capacity: Address, rng: &mut R) -> Self {
+        Self::new_with_parameters(
+            capacity,
+            rng,
+            DEFAULT_STASH_OVERFLOW_SIZE,
+            DEFAULT_RECURSION_CUTOFF,
+        )
+        .unwrap()
+    }
+}
+
+// A Path ORAM of 8-byte blocks with default parameters, used to compare recursion cutoffs.
+// Its blocks are small so that the largest benchmarked capacity fits in memory (about 2 GB, mostly tree),
+// and so that the position map accounts for as large a share of each access as it does in any configuration.
+type CutoffOram = PathOram<u64, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK>;
+
+const RECURSION_CUTOFFS_TO_BENCHMARK: [RecursionCutoff; 5] =
+    [1 << 8, 1 << 10, 1 << 12, 1 << 14, 1 << 16];
+const CUTOFF_CAPACITIES_TO_BENCHMARK: [Address; 3] = [1 << 16, 1 << 20, 1 << 24];
+
 // Here, all benchmarks are run for linear and path ORAMs, and block sizes of 64 and 4096.
 criterion_group!(
     name = benches;
-    config = Criterion::default().warm_up_time(Duration::new(0, 1_000_000_00)).measurement_time(Duration::new(0, 1_000_000_00)).sample_size(10);
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
     targets =
     benchmark_read::<DefaultOram<BlockValue<4096>>>,
+    benchmark_read_into::<DefaultOram<BlockValue<4096>>>,
     benchmark_write::<DefaultOram<BlockValue<4096>>>,
     benchmark_initialization::<DefaultOram<BlockValue<4096>>>,
     benchmark_random_operations::<4096, DefaultOram<BlockValue<4096>>>,
@@ -53,7 +116,295 @@ criterion_group!(
     benchmark_random_operations::<64, DefaultOram<BlockValue<64>>>,
 );
 
-criterion_main!(benches);
+// Compares the available eviction strategies on otherwise identical Path ORAMs.
+criterion_group!(
+    name = eviction_strategies;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets =
+    benchmark_read::<StrategyOram<64, GreedyEviction>>,
+    benchmark_read::<StrategyOram<64, LevelByLevelEviction>>,
+    benchmark_random_operations::<64, StrategyOram<64, GreedyEviction>>,
+    benchmark_random_operations::<64, StrategyOram<64, LevelByLevelEviction>>,
+);
+
+// Compares Ring ORAM, which reads one block per bucket, against Path ORAM, which reads whole buckets,
+// for small and large blocks.
+criterion_group!(
+    name = ring_versus_path;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets =
+    benchmark_read::<BenchmarkRingOram<64>>,
+    benchmark_read::<StrategyOram<64, GreedyEviction>>,
+    benchmark_read::<BenchmarkRingOram<4096>>,
+    benchmark_read::<StrategyOram<4096, GreedyEviction>>,
+    benchmark_random_operations::<64, BenchmarkRingOram<64>>,
+    benchmark_random_operations::<64, StrategyOram<64, GreedyEviction>>,
+);
+
+criterion_group!(
+    name = position_map_base;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets = benchmark_position_map_base,
+);
+
+// Measures the stash's address scan, which dominates accesses to ORAMs with small blocks and large stashes,
+// and the stash's scan and eviction sort with 4 KB blocks, whose values they should not touch.
+criterion_group!(
+    name = stash_scan;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets = benchmark_stash_scan, benchmark_stash_large_blocks,
+);
+
+// Measures path reads of large blocks, which are bound by memory bandwidth and so sensitive to the layout of physical memory.
+// Run with and without `--features hugepages` to measure the effect of huge pages. With 4 KB blocks,
+// each bucket is 16 KB, so this also measures the cost of any copy of a bucket made on the way to the stash.
+criterion_group!(
+    name = memory_layout;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets =
+    benchmark_read::<StrategyOram<4096, GreedyEviction>>,
+);
+
+// Sweeps the bucket size Z and the stash overflow size. See also `examples/parameter_sweep.rs`,
+// which additionally reports physical memory traffic and stash occupancy.
+criterion_group!(
+    name = parameter_sweep;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets =
+    benchmark_parameters::<2>,
+    benchmark_parameters::<3>,
+    benchmark_parameters::<4>,
+    benchmark_parameters::<5>,
+);
+
+// Measures the oblivious sorting and shuffling exported by `oram::oblivious`.
+criterion_group!(
+    name = oblivious;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets = benchmark_oblivious_sort_and_shuffle,
+);
+
+// Compares recursion cutoffs end to end, and prints a table attributing each access's physical memory traffic
+// to the levels of the position map. See the README for how to read it.
+criterion_group!(
+    name = recursion_cutoffs;
+    config = Criterion::default().warm_up_time(Duration::new(0, 100_000_000)).measurement_time(Duration::new(0, 100_000_000)).sample_size(10);
+    targets = benchmark_recursion_cutoffs,
+);
+
+criterion_main!(
+    benches,
+    eviction_strategies,
+    ring_versus_path,
+    position_map_base,
+    recursion_cutoffs,
+    stash_scan,
+    memory_layout,
+    parameter_sweep,
+    oblivious
+);
+
+// Benchmarks the linear-time ORAM used as the base case of the position map,
+// at sizes up to the default recursion cutoff, with a callback updating a single position as the position map does.
+fn benchmark_position_map_base(c: &mut Criterion) {
+    let mut group = c.benchmark_group("PositionMapBase::update");
+    let mut rng = StdRng::seed_from_u64(0);
+    for recursion_cutoff in [1 << 10, 1 << 12, DEFAULT_RECURSION_CUTOFF] {
+        let mut oram =
+            LinearTimeOram::<PositionBlock<DEFAULT_POSITIONS_PER_BLOCK>>::new(recursion_cutoff)
+                .unwrap();
+        group.bench_function(BenchmarkId::from_parameter(recursion_cutoff), |b| {
+            b.iter(|| {
+                let address = rng.gen_range(0..recursion_cutoff);
+                oram.access(
+                    address,
+                    |block| {
+                        let mut result = *block;
+                        result.data[0] = 1;
+                        result
+                    },
+                    &mut rng,
+                )
+            })
+        });
+    }
+}
+
+// Benchmarks random reads from a `CutoffOram` at each recursion cutoff and capacity,
+// then prints a summary table of the mean latency and the physical bytes per access of each configuration,
+// split between the tree, the Path ORAM levels of the position map, and its linear base case.
+fn benchmark_recursion_cutoffs(c: &mut Criterion) {
+    const SUMMARY_ACCESSES: u32 = 1_000;
+
+    let mut group = c.benchmark_group("PathOram::recursion_cutoff");
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut summary = Vec::new();
+    for capacity in CUTOFF_CAPACITIES_TO_BENCHMARK {
+        for recursion_cutoff in RECURSION_CUTOFFS_TO_BENCHMARK {
+            let mut oram = CutoffOram::new_with_parameters(
+                capacity,
+                &mut rng,
+                DEFAULT_STASH_OVERFLOW_SIZE,
+                recursion_cutoff,
+            )
+            .unwrap();
+            group.bench_function(
+                BenchmarkId::from_parameter(format!(
+                    "(Capacity: 2^{} Cutoff: 2^{})",
+                    capacity.ilog2(),
+                    recursion_cutoff.ilog2()
+                )),
+                |b| {
+                    b.iter(|| {
+                        let address = rng.gen_range(0..capacity);
+                        oram.read(address, &mut rng)
+                    })
+                },
+            );
+
+            let start = Instant::now();
+            for _ in 0..SUMMARY_ACCESSES {
+                let address = rng.gen_range(0..capacity);
+                oram.read(address, &mut rng).unwrap();
+            }
+            let mean_latency = start.elapsed() / SUMMARY_ACCESSES;
+
+            // The last level of the recursion is its linear base case.
+            let profile = oram.recursion_profile().unwrap();
+            let (base, recursive_levels) = profile.split_last().unwrap();
+            summary.push(RecursionCutoffRow {
+                capacity,
+                recursion_cutoff,
+                recursion_depth: recursive_levels.len(),
+                base_blocks: base.block_capacity,
+                mean_latency,
+                total_bytes: oram.physical_bytes_per_access().unwrap(),
+                recursive_bytes: recursive_levels
+                    .iter()
+                    .map(|level| level.physical_bytes_per_access)
+                    .sum(),
+                base_bytes: base.physical_bytes_per_access,
+            });
+        }
+    }
+    group.finish();
+
+    println!("\nRecursion cutoffs (8-byte blocks, {SUMMARY_ACCESSES} random reads each):");
+    println!("| Capacity | Cutoff | Depth | Base blocks | Mean latency (µs) | Bytes per access | Position map bytes (Path ORAM levels + base) |");
+    println!("|---|---|---|---|---|---|---|");
+    for row in summary {
+        println!(
+            "| 2^{} | 2^{} | {} | {} | {:.1} | {} | {} + {} |",
+            row.capacity.ilog2(),
+            row.recursion_cutoff.ilog2(),
+            row.recursion_depth,
+            row.base_blocks,
+            row.mean_latency.as_secs_f64() * 1e6,
+            row.total_bytes,
+            row.recursive_bytes,
+            row.base_bytes,
+        );
+    }
+}
+
+// Benchmarks sorting 8-byte items by 8-byte keys, and shuffling 8-byte items, from a thousand to a million items.
+fn benchmark_oblivious_sort_and_shuffle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("oblivious");
+    let mut rng = StdRng::seed_from_u64(0);
+    for n in [1_000, 10_000, 100_000, 1_000_000] {
+        let items: Vec<u64> = (0..n).map(|_| rng.gen()).collect();
+        let keys: Vec<u64> = (0..n).map(|_| rng.gen()).collect();
+        group.bench_function(BenchmarkId::new("bitonic_sort_by_keys", n), |b| {
+            b.iter_batched(
+                || (items.clone(), keys.clone()),
+                |(mut items, mut keys)| bitonic_sort_by_keys(&mut items, &mut keys),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("oblivious_shuffle", n), |b| {
+            b.iter_batched(
+                || items.clone(),
+                |mut items| oblivious_shuffle(&mut items, &mut rng),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+// Benchmarks reads from a Path ORAM of 8-byte blocks at several stash overflow sizes.
+// Run with `--features simd` on a nightly toolchain to compare the vectorized scan against the scalar one.
+fn benchmark_stash_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("PathOram::stash_scan");
+    let mut rng = StdRng::seed_from_u64(0);
+    let capacity = 1 << 14;
+    for overflow_size in [DEFAULT_STASH_OVERFLOW_SIZE, 160, 640] {
+        let mut oram = PathOram::<u64, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK>::new_with_parameters(
+            capacity,
+            &mut rng,
+            overflow_size,
+            DEFAULT_RECURSION_CUTOFF,
+        )
+        .unwrap();
+        group.bench_function(BenchmarkId::from_parameter(overflow_size), |b| {
+            b.iter(|| oram.read(black_box(0), &mut rng))
+        });
+    }
+}
+
+// Benchmarks reads from a Path ORAM of 4 KB blocks at several stash overflow sizes.
+// Every access scans the stash for the requested address and sorts it for eviction. The stash keeps block metadata
+// apart from values, so the scan reads only metadata, and the sort compares only metadata and level assignments.
+fn benchmark_stash_large_blocks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("PathOram<4096>::stash");
+    let mut rng = StdRng::seed_from_u64(0);
+    let capacity = 1 << 10;
+    for overflow_size in [DEFAULT_STASH_OVERFLOW_SIZE, 160] {
+        let mut oram = StrategyOram::<4096, GreedyEviction>::new_with_parameters(
+            capacity,
+            &mut rng,
+            overflow_size,
+            DEFAULT_RECURSION_CUTOFF,
+        )
+        .unwrap();
+        group.bench_function(BenchmarkId::from_parameter(overflow_size), |b| {
+            b.iter(|| oram.read(black_box(0), &mut rng))
+        });
+    }
+}
+
+// Benchmarks random accesses to Path ORAMs of 64-byte blocks with bucket size `Z`, at several overflow sizes and capacities.
+fn benchmark_parameters<const Z: BucketSize>(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("PathOram<Z={}>::random_operations", Z));
+    let mut rng = StdRng::seed_from_u64(0);
+    for capacity in [1 << 14, 1 << 18] {
+        for overflow_size in [20, 40, 80, 160] {
+            let mut oram =
+                PathOram::<BlockValue<64>, Z, DEFAULT_POSITIONS_PER_BLOCK>::new_with_parameters(
+                    capacity,
+                    &mut rng,
+                    overflow_size,
+                    DEFAULT_RECURSION_CUTOFF,
+                )
+                .unwrap();
+            group.bench_function(
+                BenchmarkId::from_parameter(format!(
+                    "(Capacity: {} Overflow: {})",
+                    capacity, overflow_size
+                )),
+                |b| {
+                    b.iter(|| {
+                        let address = rng.gen_range(0..capacity);
+                        if rng.gen::<bool>() {
+                            oram.write(address, BlockValue::default(), &mut rng)
+                        } else {
+                            oram.read(address, &mut rng)
+                        }
+                    })
+                },
+            );
+        }
+    }
+}
 
 fn benchmark_initialization<T: Oram + Benchmarkable>(c: &mut Criterion) {
     let mut group = c.benchmark_group(T::short_name() + "::initialization");
@@ -85,6 +436,23 @@ fn benchmark_read<T: Oram + Benchmarkable>(c: &mut Criterion) {
     }
 }
 
+// As `benchmark_read`, but reading into a buffer with `read_into`.
+fn benchmark_read_into<T: Oram + Benchmarkable>(c: &mut Criterion) {
+    let mut group = c.benchmark_group(T::short_name() + "::read_into");
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut out = T::V::default();
+    for capacity in CAPACITIES_TO_BENCHMARK.iter() {
+        let mut oram = T::new(*capacity, &mut rng);
+        group.bench_function(
+            BenchmarkId::from_parameter(ReadWriteParameters {
+                capacity: *capacity,
+                block_size: mem::size_of::<T::V>(),
+            }),
+            |b| b.iter(|| oram.read_into(0, black_box(&mut out), &mut rng)),
+        );
+    }
+}
+
 fn benchmark_write<T: Oram + Benchmarkable>(c: &mut Criterion) {
     let mut group = c.benchmark_group(T::short_name() + "::write");
     let mut rng = StdRng::seed_from_u64(0);
@@ -109,7 +477,7 @@ fn benchmark_random_operations<const B: BlockSize, T: Oram<V = BlockValue<B>> +
     for capacity in CAPACITIES_TO_BENCHMARK {
         let mut oram = T::new(capacity, &mut rng);
 
-        let number_of_operations_to_run = 64 as usize;
+        let number_of_operations_to_run = 64;
 
         let block_size = B;
         let capacity = oram.block_capacity().unwrap();
@@ -123,8 +491,8 @@ fn benchmark_random_operations<const B: BlockSize, T: Oram<V = BlockValue<B>> +
         let mut read_versus_write_randomness = vec![false; number_of_operations_to_run];
         let capacity_usize: usize = capacity.try_into().unwrap();
         let mut value_randomness = vec![0u8; block_size * capacity_usize];
-        for i in 0..number_of_operations_to_run {
-            index_randomness[i] = rng.gen_range(0..capacity);
+        for index in index_randomness.iter_mut() {
+            *index = rng.gen_range(0..capacity);
         }
 
         rng.fill(&mut read_versus_write_randomness[..]);
@@ -210,3 +578,14 @@ impl fmt::Display for RandomOperationsParameters {
         )
     }
 }
+
+struct RecursionCutoffRow {
+    capacity: Address,
+    recursion_cutoff: RecursionCutoff,
+    recursion_depth: usize,
+    base_blocks: Address,
+    mean_latency: Duration,
+    total_bytes: u64,
+    recursive_bytes: u64,
+    base_bytes: u64,
+}
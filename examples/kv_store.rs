@@ -0,0 +1,322 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A small persistent key-value store over an ORAM.
+//!
+//! Usage: `kv_store <store file> [command ...]`, where each command is one of
+//! `put <key> <value>`, `get <key>`, `del <key>`, or `stats`. With no commands on the command line,
+//! commands are read from standard input, one per line (as they must be if a value is itself a command name).
+//! For example, `cargo run --example kv_store -- store.kv put alice 42 get alice stats`.
+//!
+//! Each key hashes to `PROBES` addresses of a [`CodecOram`] over a [`PathOram`], and every `put`, `get`, and `del`
+//! (whether or not it finds its key) reads and then rewrites exactly those `PROBES` addresses,
+//! so the ORAM's access pattern reveals neither the keys nor which of these commands was run.
+//!
+//! The crate does not serialize Path ORAM snapshots to bytes, so the store file holds the logical contents,
+//! one tab-separated record per line, which are loaded into a new ORAM on startup and saved on exit by
+//! reading every address in order. The file is plaintext: a real deployment would seal it to the enclave.
+
+use oram::codec::{CodecOram, PaddedCodec};
+use oram::path_oram::{
+    default_overflow_size, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK,
+    DEFAULT_RECURSION_CUTOFF,
+};
+use oram::{Address, BlockValue, OramError, PathOram};
+use rand::rngs::OsRng;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::BufRead;
+use std::process::ExitCode;
+
+const CAPACITY: Address = 1024;
+const BLOCK_SIZE: usize = 64;
+const PROBES: u64 = 4;
+
+type Codec = PaddedCodec<BLOCK_SIZE>;
+type StoreOram =
+    PathOram<BlockValue<BLOCK_SIZE>, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK>;
+
+/// The errors a command can fail with.
+enum StoreError {
+    Usage(String),
+    Full(String),
+    Oram(OramError),
+    Io(std::io::Error),
+}
+
+impl From<OramError> for StoreError {
+    fn from(error: OramError) -> Self {
+        StoreError::Oram(error)
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(error: std::io::Error) -> Self {
+        StoreError::Io(error)
+    }
+}
+
+// Returns a message for `error` that a user of the store can act on.
+fn describe(error: &StoreError) -> String {
+    match error {
+        StoreError::Usage(message) => message.clone(),
+        StoreError::Full(key) => {
+            format!("no room for key {key:?}: every slot it hashes to is taken")
+        }
+        StoreError::Io(error) => format!("could not access the store file: {error}"),
+        StoreError::Oram(error) => match error {
+            OramError::AddressOutOfBoundsError { .. }
+            | OramError::PartitionAddressOutOfBoundsError { .. }
+            | OramError::AddressCollisionError { .. }
+            | OramError::QueueFull { .. }
+            | OramError::ProbeSequenceFull { .. }
+            | OramError::KeyLimitReached { .. }
+            | OramError::NoncesExhausted
+            | OramError::IntegerConversionError(_) => {
+                format!("internal error (please report it): {error}")
+            }
+            OramError::InvalidConfigurationError { .. } => {
+                format!(
+                    "the record is too large: keys and values total at most {} bytes",
+                    Codec::MAX_PAYLOAD_SIZE - 1
+                )
+            }
+            OramError::IntegrityError { .. } | OramError::BucketIntegrityError { .. } => {
+                format!("the ORAM's memory is corrupt, so the store was not saved: {error}")
+            }
+            OramError::MemoryBudgetExceeded { .. } => format!("out of memory: {error}"),
+            OramError::RngError(_) => format!("could not draw randomness: {error}"),
+            OramError::ReseedRequired | OramError::Poisoned => {
+                format!("the ORAM is unusable after an earlier failure: {error}")
+            }
+        },
+    }
+}
+
+struct Store {
+    oram: CodecOram<BLOCK_SIZE, StoreOram>,
+    commands: u64,
+}
+
+// A record is stored as the length of its key (one byte), the key, and the value. An empty payload is a free slot.
+fn encode_record(key: &str, value: &str) -> Result<Vec<u8>, StoreError> {
+    let key_length = u8::try_from(key.len())
+        .map_err(|_| StoreError::Usage(format!("key {key:?} is too long")))?;
+    let mut record = vec![key_length];
+    record.extend_from_slice(key.as_bytes());
+    record.extend_from_slice(value.as_bytes());
+    Ok(record)
+}
+
+fn decode_record(record: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&key_length, rest) = record.split_first()?;
+    (rest.len() >= key_length.into()).then(|| rest.split_at(key_length.into()))
+}
+
+fn probe_addresses(key: &str) -> Vec<Address> {
+    (0..PROBES)
+        .map(|probe| {
+            let mut hasher = DefaultHasher::new();
+            (key, probe).hash(&mut hasher);
+            hasher.finish() % CAPACITY
+        })
+        .collect()
+}
+
+impl Store {
+    fn new() -> Result<Self, StoreError> {
+        Ok(Self {
+            oram: CodecOram::new(StoreOram::new_with_parameters(
+                CAPACITY,
+                &mut OsRng,
+                default_overflow_size(CAPACITY, DEFAULT_BLOCKS_PER_BUCKET),
+                DEFAULT_RECURSION_CUTOFF,
+            )?),
+            commands: 0,
+        })
+    }
+
+    // Reads and then rewrites every probe address of `key`, replacing the record of `key` (or, if it has none,
+    // the first free slot) by `update`, if it is `Some`. Returns the previous value of `key`, if any.
+    fn probe(&mut self, key: &str, update: Option<Vec<u8>>) -> Result<Option<String>, StoreError> {
+        self.commands += 1;
+        let addresses = probe_addresses(key);
+        let mut records = Vec::new();
+        for address in &addresses {
+            records.push(self.oram.read_bytes(*address, &mut OsRng)?);
+        }
+
+        let found = records.iter().position(|record| {
+            decode_record(record).is_some_and(|(stored_key, _)| stored_key == key.as_bytes())
+        });
+        let previous = found.map(|index| {
+            let (_, value) = decode_record(&records[index]).unwrap();
+            String::from_utf8_lossy(value).into_owned()
+        });
+        let target = found.or_else(|| records.iter().position(Vec::is_empty));
+        match (&update, target) {
+            (Some(record), Some(index)) => records[index] = record.clone(),
+            // Deleting or reading an absent key needs no slot.
+            (Some(record), None) if !record.is_empty() => {
+                return Err(StoreError::Full(key.to_string()))
+            }
+            _ => {}
+        }
+
+        for (address, record) in addresses.iter().zip(&records) {
+            self.oram.write_bytes(*address, record, &mut OsRng)?;
+        }
+        Ok(previous)
+    }
+
+    fn execute(&mut self, command: &[&str]) -> Result<String, StoreError> {
+        match command {
+            ["put", key, value] => {
+                let record = encode_record(key, value)?;
+                if record.len() > Codec::MAX_PAYLOAD_SIZE {
+                    return Err(PaddedCodec::<BLOCK_SIZE>::encode(&record)
+                        .unwrap_err()
+                        .into());
+                }
+                Ok(match self.probe(key, Some(record))? {
+                    Some(previous) => format!("updated {key} (was {previous})"),
+                    None => format!("inserted {key}"),
+                })
+            }
+            ["get", key] => Ok(self
+                .probe(key, None)?
+                .unwrap_or_else(|| format!("{key} not found"))),
+            ["del", key] => Ok(match self.probe(key, Some(Vec::new()))? {
+                Some(_) => format!("deleted {key}"),
+                None => format!("{key} not found"),
+            }),
+            ["stats"] => {
+                let path_oram = self.oram.inner_mut();
+                let cost = path_oram.expected_physical_accesses_per_op()?;
+                Ok(format!(
+                    "capacity: {CAPACITY} slots of {} bytes\ncommands: {}\nORAM accesses per command: {}\n\
+                     blocks read per ORAM access: {}\nstash occupancy: {}\nmemory footprint: {} bytes",
+                    Codec::MAX_PAYLOAD_SIZE,
+                    self.commands,
+                    2 * PROBES,
+                    cost.data_reads + cost.position_map_reads,
+                    path_oram.stash_occupancy(),
+                    path_oram.memory_footprint()?,
+                ))
+            }
+            _ => Err(StoreError::Usage(format!(
+                "unknown command {:?}: expected put <key> <value>, get <key>, del <key>, or stats",
+                command.join(" ")
+            ))),
+        }
+    }
+
+    fn load(path: &str) -> Result<Self, StoreError> {
+        let mut store = Self::new()?;
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        for line in contents.lines() {
+            let (key, value) = line
+                .split_once('\t')
+                .ok_or_else(|| StoreError::Usage(format!("{path} is not a store file")))?;
+            store.probe(key, Some(encode_record(key, value)?))?;
+        }
+        store.commands = 0;
+        Ok(store)
+    }
+
+    fn save(&mut self, path: &str) -> Result<(), StoreError> {
+        let mut contents = String::new();
+        for address in 0..CAPACITY {
+            let record = self.oram.read_bytes(address, &mut OsRng)?;
+            if let Some((key, value)) = decode_record(&record) {
+                contents += &format!(
+                    "{}\t{}\n",
+                    String::from_utf8_lossy(key),
+                    String::from_utf8_lossy(value)
+                );
+            }
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+// Splits `arguments` into commands, each starting with a command name.
+fn split_commands<'a>(arguments: &[&'a str]) -> Vec<Vec<&'a str>> {
+    let mut commands: Vec<Vec<&str>> = Vec::new();
+    for argument in arguments {
+        match commands.last_mut() {
+            Some(command) if !["put", "get", "del", "stats"].contains(argument) => {
+                command.push(argument)
+            }
+            _ => commands.push(vec![argument]),
+        }
+    }
+    commands
+}
+
+fn run(path: &str, commands: Vec<Vec<String>>) -> Result<bool, StoreError> {
+    let mut store = Store::load(path)?;
+    let mut all_succeeded = true;
+    for command in commands {
+        let command: Vec<&str> = command.iter().map(String::as_str).collect();
+        if command.iter().any(|word| word.contains(['\t', '\n'])) {
+            eprintln!("error: keys and values cannot contain tabs or newlines");
+            all_succeeded = false;
+            continue;
+        }
+        match store.execute(&command) {
+            Ok(output) => println!("{output}"),
+            Err(error @ StoreError::Oram(OramError::Poisoned)) => return Err(error),
+            Err(error) => {
+                eprintln!("error: {}", describe(&error));
+                all_succeeded = false;
+            }
+        }
+    }
+    store.save(path)?;
+    Ok(all_succeeded)
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let Some((path, arguments)) = arguments.split_first() else {
+        eprintln!(
+            "usage: kv_store <store file> [put <key> <value> | get <key> | del <key> | stats] ..."
+        );
+        return ExitCode::from(2);
+    };
+
+    let commands: Vec<Vec<String>> = if arguments.is_empty() {
+        std::io::stdin()
+            .lock()
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.split_whitespace().map(str::to_string).collect())
+            .collect()
+    } else {
+        let arguments: Vec<&str> = arguments.iter().map(String::as_str).collect();
+        split_commands(&arguments)
+            .into_iter()
+            .map(|command| command.into_iter().map(str::to_string).collect())
+            .collect()
+    };
+
+    match run(path, commands) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {}", describe(&error));
+            ExitCode::FAILURE
+        }
+    }
+}
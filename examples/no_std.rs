@@ -0,0 +1,52 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An example of using ORAM in a `no_std` environment with `alloc`, such as an enclave SDK.
+//!
+//! Without the `std` feature the crate has no source of randomness of its own,
+//! so the caller supplies a cryptographically secure RNG: here, a ChaCha20 RNG
+//! seeded from the platform's entropy source.
+//! Everything outside `main` uses only `core` and `alloc`;
+//! `main` uses `std` to stand in for the platform, so that the example runs on the host.
+
+#![no_std]
+
+extern crate alloc;
+extern crate std;
+
+use alloc::vec::Vec;
+use oram::{Address, BlockValue, DefaultOram, Oram, OramError};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+const DB_SIZE: Address = 64;
+
+// Obliviously looks up each of `queries` in a database holding `i` at address `i`.
+fn serve<R: RngCore + CryptoRng>(
+    queries: &[Address],
+    rng: &mut R,
+) -> Result<Vec<BlockValue<8>>, OramError> {
+    let mut oram = DefaultOram::<BlockValue<8>>::new(DB_SIZE, rng)?;
+    for i in 0..DB_SIZE {
+        oram.write(i, BlockValue::new(i.to_le_bytes()), rng)?;
+    }
+
+    queries.iter().map(|query| oram.read(*query, rng)).collect()
+}
+
+fn main() -> Result<(), OramError> {
+    // On a real platform, the seed would come from a hardware entropy source such as `RDRAND`.
+    let mut seed = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut seed);
+    let mut rng = ChaCha20Rng::from_seed(seed);
+
+    let results = serve(&[3, 14, 15], &mut rng)?;
+    assert_eq!(results[1], BlockValue::new(14u64.to_le_bytes()));
+    std::println!("Read {} blocks obliviously.", results.len());
+
+    Ok(())
+}
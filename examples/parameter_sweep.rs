@@ -0,0 +1,115 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Compares Path ORAM bucket sizes and stash overflow sizes.
+//!
+//! For each combination of parameters, runs a seeded random workload and reports
+//! the mean access latency, the physical memory traffic per access, and the stash high-water mark.
+//! Prints a markdown table, or CSV if run with `--csv`. Run in release mode, e.g.,
+//! `cargo run --release --example parameter_sweep -- --csv > sweep.csv`.
+
+use oram::path_oram::{DEFAULT_POSITIONS_PER_BLOCK, DEFAULT_RECURSION_CUTOFF};
+use oram::{Address, BlockValue, BucketSize, Oram, OramError, PathOram, StashSize};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+const CAPACITIES: [Address; 2] = [1 << 14, 1 << 18];
+const OVERFLOW_SIZES: [StashSize; 4] = [20, 40, 80, 160];
+const ACCESSES_PER_CONFIGURATION: u32 = 10_000;
+
+type Block = BlockValue<64>;
+
+struct Row {
+    bucket_size: BucketSize,
+    overflow_size: StashSize,
+    capacity: Address,
+    mean_latency: Duration,
+    physical_bytes_per_access: u64,
+    max_stash_occupancy: StashSize,
+}
+
+fn measure<const Z: BucketSize>(
+    capacity: Address,
+    overflow_size: StashSize,
+) -> Result<Row, OramError> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut oram = PathOram::<Block, Z, DEFAULT_POSITIONS_PER_BLOCK>::new_with_parameters(
+        capacity,
+        &mut rng,
+        overflow_size,
+        DEFAULT_RECURSION_CUTOFF,
+    )?;
+
+    let mut total_latency = Duration::ZERO;
+    let mut max_stash_occupancy = 0;
+    for _ in 0..ACCESSES_PER_CONFIGURATION {
+        let address = rng.gen_range(0..capacity);
+        let start = Instant::now();
+        if rng.gen::<bool>() {
+            oram.write(address, Block::default(), &mut rng)?;
+        } else {
+            oram.read(address, &mut rng)?;
+        }
+        total_latency += start.elapsed();
+        max_stash_occupancy = max_stash_occupancy.max(oram.stash_occupancy());
+    }
+
+    Ok(Row {
+        bucket_size: Z,
+        overflow_size,
+        capacity,
+        mean_latency: total_latency / ACCESSES_PER_CONFIGURATION,
+        physical_bytes_per_access: oram.physical_bytes_per_access()?,
+        max_stash_occupancy,
+    })
+}
+
+fn main() -> Result<(), OramError> {
+    let csv = std::env::args().any(|arg| arg == "--csv");
+
+    if csv {
+        println!("z,overflow_size,capacity,mean_latency_ns,physical_bytes_per_access,max_stash_occupancy");
+    } else {
+        println!("| Z | Overflow size | Capacity | Mean latency (µs) | Physical bytes per access | Max stash occupancy |");
+        println!("|---|---|---|---|---|---|");
+    }
+
+    for capacity in CAPACITIES {
+        for overflow_size in OVERFLOW_SIZES {
+            for row in [
+                measure::<2>(capacity, overflow_size)?,
+                measure::<3>(capacity, overflow_size)?,
+                measure::<4>(capacity, overflow_size)?,
+                measure::<5>(capacity, overflow_size)?,
+            ] {
+                if csv {
+                    println!(
+                        "{},{},{},{},{},{}",
+                        row.bucket_size,
+                        row.overflow_size,
+                        row.capacity,
+                        row.mean_latency.as_nanos(),
+                        row.physical_bytes_per_access,
+                        row.max_stash_occupancy
+                    );
+                } else {
+                    println!(
+                        "| {} | {} | 2^{} | {:.1} | {} | {} |",
+                        row.bucket_size,
+                        row.overflow_size,
+                        row.capacity.ilog2(),
+                        row.mean_latency.as_secs_f64() * 1e6,
+                        row.physical_bytes_per_access,
+                        row.max_stash_occupancy
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
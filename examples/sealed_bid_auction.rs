@@ -0,0 +1,34 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An example of using ORAM to track the highest bid on each item of a sealed-bid auction,
+//! without revealing which items are bid on or whether a bid raised the highest bid.
+
+use oram::{ct_cmp::ct_max, Address, DefaultOram, Oram, OramError};
+use rand::{rngs::OsRng, Rng};
+
+const NUM_ITEMS: Address = 64;
+
+fn main() -> Result<(), OramError> {
+    let mut rng = OsRng;
+    let mut highest_bids = DefaultOram::<u64>::new(NUM_ITEMS, &mut rng)?;
+
+    let num_bids = 100;
+    for _ in 0..num_bids {
+        let item = rng.gen_range(0..NUM_ITEMS);
+        let bid = rng.gen_range(1..1000);
+
+        // A callback such as `|highest| if bid > *highest { bid } else { *highest }` would branch on the highest bid.
+        highest_bids.access(item, |highest| ct_max(highest, &bid), &mut rng)?;
+    }
+
+    for item in 0..NUM_ITEMS {
+        println!("Item {}: {}", item, highest_bids.read(item, &mut rng)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Runs a soak test (see the `oram::soak` module), printing a line per checkpoint.
+//!
+//! Run in release mode, e.g., `cargo run --release --features soak --example soak -- --operations 10000000`.
+//! The options `--seed`, `--operations`, `--capacity`, `--check-interval`, and `--max-memory-growth` (in bytes)
+//! override the defaults in `SoakConfig`. On failure, prints the seed with which to reproduce it and exits with status 1.
+
+use oram::soak::{self, SoakConfig};
+use std::process::ExitCode;
+
+fn parse_args() -> Result<SoakConfig, String> {
+    let mut config = SoakConfig::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value: u64 = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", flag))?
+            .parse()
+            .map_err(|error| format!("invalid value for {}: {}", flag, error))?;
+        match flag.as_str() {
+            "--seed" => config.seed = value,
+            "--operations" => config.operations = value,
+            "--capacity" => config.block_capacity = value,
+            "--check-interval" => config.check_interval = value,
+            "--max-memory-growth" => config.max_memory_growth = value,
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    if config.check_interval == 0 {
+        return Err("--check-interval must be positive".to_string());
+    }
+    Ok(config)
+}
+
+fn main() -> ExitCode {
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+    println!("{:?}", config);
+    println!("operations,stash_high_water_mark,resident_bytes,physical_accesses,physical_bytes");
+
+    let result = soak::run(config, |checkpoint| {
+        println!(
+            "{},{},{},{},{}",
+            checkpoint.operations,
+            checkpoint.stash_high_water_mark,
+            checkpoint
+                .resident_bytes
+                .map_or_else(String::new, |bytes| bytes.to_string()),
+            checkpoint.physical_accesses,
+            checkpoint.physical_bytes
+        );
+    });
+
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("{}", failure);
+            ExitCode::FAILURE
+        }
+    }
+}
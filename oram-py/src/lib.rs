@@ -0,0 +1,215 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Python bindings for the `oram` crate, intended for prototyping oblivious access patterns.
+//!
+//! The `oram_py` module exposes an `Oram` class wrapping a [`DefaultOram`] of 4096-byte blocks,
+//! and an exception hierarchy mirroring [`oram::OramError`].
+//! Like the Rust crate, the ORAM provides security guarantees only inside a secure enclave;
+//! in an ordinary Python process, it is a tool for experimentation.
+
+#![warn(clippy::doc_markdown, missing_docs)]
+
+use oram::{Address, BlockValue, DefaultOram, Oram as _, StashSize};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+    types::{PyBytes, PyDict},
+};
+use rand::rngs::OsRng;
+
+/// The size in bytes of the blocks stored by the underlying ORAM. Smaller blocks are zero-padded to this size.
+const MAX_BLOCK_SIZE: usize = 4096;
+
+create_exception!(
+    oram_py,
+    OramError,
+    PyException,
+    "The base class of errors raised by the ORAM."
+);
+create_exception!(
+    oram_py,
+    IntegerConversionError,
+    OramError,
+    "Raised on arithmetic errors arising from conversions between integer types."
+);
+create_exception!(
+    oram_py,
+    AddressOutOfBoundsError,
+    OramError,
+    "Raised on an access to an address not less than the ORAM capacity."
+);
+create_exception!(
+    oram_py,
+    InvalidConfigurationError,
+    OramError,
+    "Raised when an ORAM is created with invalid parameters."
+);
+
+fn to_py_err(error: oram::OramError) -> PyErr {
+    let message = error.to_string();
+    match error {
+        oram::OramError::IntegerConversionError(_) => IntegerConversionError::new_err(message),
+        oram::OramError::AddressOutOfBoundsError { .. }
+        | oram::OramError::PartitionAddressOutOfBoundsError { .. } => {
+            AddressOutOfBoundsError::new_err(message)
+        }
+        oram::OramError::InvalidConfigurationError { .. } => {
+            InvalidConfigurationError::new_err(message)
+        }
+        // The byte ORAMs exposed to Python are not remapped.
+        oram::OramError::AddressCollisionError { .. } => OramError::new_err(message),
+        // Nor do they authenticate their blocks.
+        oram::OramError::IntegrityError { .. } => OramError::new_err(message),
+        // Corruption is detected only with the `checksummed` feature.
+        oram::OramError::BucketIntegrityError { .. } => OramError::new_err(message),
+        // Nor do they set memory budgets.
+        oram::OramError::MemoryBudgetExceeded { .. } => OramError::new_err(message),
+        oram::OramError::Poisoned | oram::OramError::ReseedRequired => OramError::new_err(message),
+        // Nor are they scheduled.
+        oram::OramError::QueueFull { .. } => OramError::new_err(message),
+        // Nor keyed by hashes.
+        oram::OramError::ProbeSequenceFull { .. } | oram::OramError::KeyLimitReached { .. } => {
+            OramError::new_err(message)
+        }
+        // Nor encrypted.
+        oram::OramError::NoncesExhausted => OramError::new_err(message),
+        oram::OramError::RngError(_) => OramError::new_err(message),
+    }
+}
+
+/// An oblivious RAM storing `capacity` blocks of `block_size` bytes, initialized to zero.
+///
+/// Accesses release the GIL, so other Python threads may run while an access is in progress.
+#[pyclass(module = "oram_py")]
+struct Oram {
+    oram: DefaultOram<BlockValue<MAX_BLOCK_SIZE>>,
+    block_size: usize,
+    access_count: u64,
+    max_stash_occupancy: StashSize,
+    total_stash_occupancy: u64,
+}
+
+impl Oram {
+    fn record_access(&mut self) {
+        let occupancy = self.oram.stash_occupancy();
+        self.access_count += 1;
+        self.max_stash_occupancy = self.max_stash_occupancy.max(occupancy);
+        self.total_stash_occupancy += occupancy;
+    }
+}
+
+#[pymethods]
+impl Oram {
+    /// Creates an ORAM storing `capacity` blocks of `block_size` bytes, where `capacity` is a power of two
+    /// and `0 < block_size <= 4096`.
+    #[new]
+    fn new(py: Python<'_>, capacity: Address, block_size: usize) -> PyResult<Self> {
+        if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+            return Err(to_py_err(oram::OramError::InvalidConfigurationError {
+                parameter_name: "Block size".to_string(),
+                parameter_value: block_size.to_string(),
+            }));
+        }
+
+        let oram = py
+            .allow_threads(|| DefaultOram::new(capacity, &mut OsRng))
+            .map_err(to_py_err)?;
+        Ok(Self {
+            oram,
+            block_size,
+            access_count: 0,
+            max_stash_occupancy: 0,
+            total_stash_occupancy: 0,
+        })
+    }
+
+    /// Obliviously reads the block at `address`.
+    fn read<'py>(&mut self, py: Python<'py>, address: Address) -> PyResult<Bound<'py, PyBytes>> {
+        let oram = &mut self.oram;
+        let value = py
+            .allow_threads(|| oram.read(address, &mut OsRng))
+            .map_err(to_py_err)?;
+        self.record_access();
+        Ok(PyBytes::new(py, &value.data[..self.block_size]))
+    }
+
+    /// Obliviously writes `data`, which must be exactly `block_size` bytes long, to the block at `address`.
+    fn write(&mut self, py: Python<'_>, address: Address, data: &[u8]) -> PyResult<()> {
+        if data.len() != self.block_size {
+            return Err(PyValueError::new_err(format!(
+                "Expected {} bytes, got {}.",
+                self.block_size,
+                data.len()
+            )));
+        }
+        let mut value = BlockValue::default();
+        value.data[..self.block_size].copy_from_slice(data);
+
+        let oram = &mut self.oram;
+        py.allow_threads(|| oram.write(address, value, &mut OsRng))
+            .map_err(to_py_err)?;
+        self.record_access();
+        Ok(())
+    }
+
+    /// Returns the number of successful reads and writes made so far.
+    fn access_count(&self) -> u64 {
+        self.access_count
+    }
+
+    /// Returns a dict describing the number of real blocks in the stash overflow:
+    /// its current value (`occupancy`), and its maximum (`max_occupancy`) and mean (`mean_occupancy`)
+    /// over all accesses made so far.
+    fn stash_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let mean_occupancy = if self.access_count == 0 {
+            0.0
+        } else {
+            self.total_stash_occupancy as f64 / self.access_count as f64
+        };
+
+        let stats = PyDict::new(py);
+        stats.set_item("occupancy", self.oram.stash_occupancy())?;
+        stats.set_item("max_occupancy", self.max_stash_occupancy)?;
+        stats.set_item("mean_occupancy", mean_occupancy)?;
+        Ok(stats)
+    }
+
+    /// The size in bytes of the blocks read and written.
+    #[getter]
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The number of blocks stored.
+    #[getter]
+    fn capacity(&self) -> PyResult<Address> {
+        self.oram.block_capacity().map_err(to_py_err)
+    }
+}
+
+/// Python bindings for the `oram` crate.
+#[pymodule]
+fn oram_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<Oram>()?;
+    m.add("OramError", py.get_type::<OramError>())?;
+    m.add(
+        "IntegerConversionError",
+        py.get_type::<IntegerConversionError>(),
+    )?;
+    m.add(
+        "AddressOutOfBoundsError",
+        py.get_type::<AddressOutOfBoundsError>(),
+    )?;
+    m.add(
+        "InvalidConfigurationError",
+        py.get_type::<InvalidConfigurationError>(),
+    )?;
+    Ok(())
+}
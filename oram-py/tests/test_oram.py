@@ -0,0 +1,79 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is dual-licensed under either the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree or the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree. You may select, at your option, one of the above-listed licenses.
+
+"""Tests for the `oram_py` bindings. Run with `pytest` after `maturin develop`."""
+
+import random
+
+import pytest
+
+from oram_py import (
+    AddressOutOfBoundsError,
+    InvalidConfigurationError,
+    Oram,
+    OramError,
+)
+
+
+@pytest.mark.parametrize("capacity, block_size", [(64, 16), (1024, 64), (8192, 4096)])
+def test_random_workload(capacity, block_size):
+    rng = random.Random(0)
+    oram = Oram(capacity, block_size)
+    mirror = [bytes(block_size)] * capacity
+
+    operations = 1000
+    for _ in range(operations):
+        address = rng.randrange(capacity)
+        if rng.random() < 0.5:
+            assert oram.read(address) == mirror[address]
+        else:
+            data = rng.getrandbits(8 * block_size).to_bytes(block_size, "little")
+            oram.write(address, data)
+            mirror[address] = data
+
+    for address in range(capacity):
+        assert oram.read(address) == mirror[address]
+    assert oram.access_count() == operations + capacity
+
+
+def test_stash_stats():
+    oram = Oram(1024, 8)
+    stats = oram.stash_stats()
+    assert stats == {"occupancy": 0, "max_occupancy": 0, "mean_occupancy": 0.0}
+
+    for address in range(100):
+        oram.write(address, bytes(8))
+    stats = oram.stash_stats()
+    assert 0 <= stats["occupancy"] <= stats["max_occupancy"]
+    assert 0.0 <= stats["mean_occupancy"] <= stats["max_occupancy"]
+
+
+def test_properties():
+    oram = Oram(64, 32)
+    assert oram.capacity == 64
+    assert oram.block_size == 32
+
+
+def test_out_of_bounds_address():
+    oram = Oram(64, 8)
+    with pytest.raises(AddressOutOfBoundsError):
+        oram.read(64)
+    with pytest.raises(OramError):
+        oram.write(1000, bytes(8))
+    assert oram.access_count() == 0
+
+
+@pytest.mark.parametrize("capacity, block_size", [(3000, 8), (64, 0), (64, 4097)])
+def test_invalid_configuration(capacity, block_size):
+    with pytest.raises(InvalidConfigurationError):
+        Oram(capacity, block_size)
+
+
+def test_wrong_length_write():
+    oram = Oram(64, 8)
+    with pytest.raises(ValueError):
+        oram.write(0, bytes(7))
@@ -7,7 +7,8 @@
 
 //! Block and bucket structures for Path ORAM.
 
-use crate::{BlockSize, OramBlock};
+use crate::{checksum::Checksum, BlockSize, OramBlock, OramError};
+use alloc::vec::Vec;
 use subtle::{Choice, ConditionallySelectable};
 
 use rand::{
@@ -17,8 +18,8 @@ use rand::{
 
 use crate::BucketSize;
 
-use crate::{utils::TreeIndex, Address};
-use subtle::ConstantTimeEq;
+use crate::tree::TreeIndex;
+use crate::utils::IndexWord;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 /// An `OramBlock` consisting of unstructured bytes.
@@ -39,16 +40,36 @@ impl<const B: BlockSize> Default for BlockValue<B> {
     }
 }
 
-impl<const B: BlockSize> OramBlock for BlockValue<B> {}
+impl<const B: BlockSize> OramBlock for BlockValue<B> {
+    fn update_checksum(&self, checksum: &mut Checksum) {
+        checksum.update(&self.data);
+    }
+}
 
+// `conditional_assign` and `conditional_swap` work in place, a byte at a time, so that they never copy a whole value
+// onto the stack: the defaults would, and the stash sort swaps values up to `MAX_VALUE_SIZE` bytes long.
 impl<const B: BlockSize> ConditionallySelectable for BlockValue<B> {
     fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
-        let mut result = BlockValue::default();
-        for i in 0..B {
-            result.data[i] = u8::conditional_select(&a.data[i], &b.data[i], choice);
-        }
+        let mut result = *a;
+        result.conditional_assign(b, choice);
         result
     }
+
+    fn conditional_assign(&mut self, other: &Self, choice: Choice) {
+        let mask = u8::conditional_select(&0, &u8::MAX, choice);
+        for (byte, other_byte) in self.data.iter_mut().zip(&other.data) {
+            *byte ^= mask & (*byte ^ *other_byte);
+        }
+    }
+
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+        let mask = u8::conditional_select(&0, &u8::MAX, choice);
+        for (a_byte, b_byte) in a.data.iter_mut().zip(b.data.iter_mut()) {
+            let difference = mask & (*a_byte ^ *b_byte);
+            *a_byte ^= difference;
+            *b_byte ^= difference;
+        }
+    }
 }
 
 impl<const B: BlockSize> Distribution<BlockValue<B>> for Standard {
@@ -61,63 +82,124 @@ impl<const B: BlockSize> Distribution<BlockValue<B>> for Standard {
     }
 }
 
+/// A zero-sized `OramBlock` carrying no value.
+///
+/// A Path ORAM of `Unit`s stores only block metadata, and costs (in time and in memory) only what that metadata costs.
+/// Its accesses are real ORAM accesses, so it can, e.g., generate cover traffic, or measure the overhead of
+/// the metadata paths of a parameterization. It cannot record anything about an address: to obliviously track
+/// membership (whether an address is in a set), store a `u8` flag instead, which costs one byte per block more.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unit;
+
+impl OramBlock for Unit {}
+
+impl ConditionallySelectable for Unit {
+    fn conditional_select(_a: &Self, _b: &Self, _choice: Choice) -> Self {
+        Unit
+    }
+}
+
+impl Distribution<Unit> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, _rng: &mut R) -> Unit {
+        Unit
+    }
+}
+
 #[derive(Clone, Copy, Default, PartialEq)]
 /// A Path ORAM block combines an `OramBlock` V with two metadata fields; its ORAM `address` and its `position` in the tree.
-pub(crate) struct PathOramBlock<V> {
+/// Both are stored as an `I`.
+///
+/// With the `checksummed` feature, a block also carries a checksum of its other fields.
+/// Only the checksums of blocks in the tree are meaningful: blocks are checksummed by `new` as they are written to the tree,
+/// and the stash does not store checksums.
+pub(crate) struct PathOramBlock<V, I = TreeIndex> {
     pub value: V,
-    pub address: Address,
-    pub position: TreeIndex,
+    pub address: I,
+    pub position: I,
+    #[cfg(feature = "checksummed")]
+    pub checksum: u32,
 }
 
-impl<V: OramBlock> PathOramBlock<V> {
-    const DUMMY_ADDRESS: Address = Address::MAX;
-    const DUMMY_POSITION: TreeIndex = 0;
+impl<V: OramBlock, I: IndexWord> PathOramBlock<V, I> {
+    /// The address of a dummy block. It is reserved: a Path ORAM's capacity is at most `I::MAX`,
+    /// and addresses at or beyond the capacity are rejected, so no real block has this address.
+    const DUMMY_ADDRESS: I = I::MAX;
+    /// The position of a dummy block, which is what marks a block as a dummy, since 0 is not a tree index.
+    const DUMMY_POSITION: I = I::ZERO;
 
-    pub fn dummy() -> Self {
+    /// Returns a block with the given fields (and, with the `checksummed` feature, their checksum).
+    pub fn new(value: V, address: I, position: I) -> Self {
         Self {
-            value: V::default(),
-            address: Self::DUMMY_ADDRESS,
-            position: Self::DUMMY_POSITION,
+            value,
+            address,
+            position,
+            #[cfg(feature = "checksummed")]
+            checksum: Checksum::of_block(address.into(), position.into(), &value),
         }
     }
 
+    /// Returns a dummy block, i.e., a vacant slot.
+    pub fn dummy() -> Self {
+        Self::new(V::default(), Self::DUMMY_ADDRESS, Self::DUMMY_POSITION)
+    }
+
+    /// Returns whether this block is a dummy, judging only by its position.
     pub fn ct_is_dummy(&self) -> Choice {
-        self.position.ct_eq(&Self::DUMMY_POSITION)
+        Self::ct_position_is_dummy(&self.position)
     }
 
-    #[cfg(test)]
-    pub fn is_dummy(&self) -> bool {
-        self.position == Self::DUMMY_POSITION
+    /// Returns whether a block with position `position` is a dummy.
+    pub fn ct_position_is_dummy(position: &I) -> Choice {
+        position.ct_eq(&Self::DUMMY_POSITION)
     }
 }
 
-impl<V: OramBlock> std::fmt::Debug for PathOramBlock<V> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.ct_is_dummy().into() {
-            write!(f, "PathOramBlock::Dummy")
-        } else {
-            f.debug_struct("PathOramBlock")
-                .field("value", &self.value)
-                .field("address", &self.address)
-                .field("position", &self.position)
-                .finish()
-        }
+// A block's value, address, and position, and even whether it is a dummy, are secret, so are not printed.
+// `PathOram::debug_dump` prints them.
+impl<V: OramBlock, I: IndexWord> core::fmt::Debug for PathOramBlock<V, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PathOramBlock").finish_non_exhaustive()
     }
 }
 
-impl<V: OramBlock> OramBlock for PathOramBlock<V> {}
+impl<V: OramBlock, I: IndexWord> OramBlock for PathOramBlock<V, I> {
+    fn update_checksum(&self, checksum: &mut Checksum) {
+        checksum.update(&self.address.into().to_le_bytes());
+        checksum.update(&self.position.into().to_le_bytes());
+        self.value.update_checksum(checksum);
+    }
+}
 
-impl<V: ConditionallySelectable> ConditionallySelectable for PathOramBlock<V> {
+impl<V: ConditionallySelectable, I: IndexWord> ConditionallySelectable for PathOramBlock<V, I> {
     fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
         let value = V::conditional_select(&a.value, &b.value, choice);
-        let address = Address::conditional_select(&a.address, &b.address, choice);
-        let position = TreeIndex::conditional_select(&a.position, &b.position, choice);
-        PathOramBlock::<V> {
+        let address = I::conditional_select(&a.address, &b.address, choice);
+        let position = I::conditional_select(&a.position, &b.position, choice);
+        PathOramBlock {
             value,
             address,
             position,
+            #[cfg(feature = "checksummed")]
+            checksum: u32::conditional_select(&a.checksum, &b.checksum, choice),
         }
     }
+
+    // Field by field, so that a value type with in-place exchanges keeps them.
+    fn conditional_assign(&mut self, other: &Self, choice: Choice) {
+        self.value.conditional_assign(&other.value, choice);
+        self.address.conditional_assign(&other.address, choice);
+        self.position.conditional_assign(&other.position, choice);
+        #[cfg(feature = "checksummed")]
+        self.checksum.conditional_assign(&other.checksum, choice);
+    }
+
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+        V::conditional_swap(&mut a.value, &mut b.value, choice);
+        I::conditional_swap(&mut a.address, &mut b.address, choice);
+        I::conditional_swap(&mut a.position, &mut b.position, choice);
+        #[cfg(feature = "checksummed")]
+        u32::conditional_swap(&mut a.checksum, &mut b.checksum, choice);
+    }
 }
 
 #[repr(align(64))]
@@ -134,14 +216,30 @@ impl<const B: BlockSize> Default for PositionBlock<B> {
     }
 }
 
+/// The number of positions in a 64-byte cache line.
+const POSITIONS_PER_CACHE_LINE: usize = 64 / core::mem::size_of::<TreeIndex>();
+
 impl<const B: BlockSize> ConditionallySelectable for PositionBlock<B> {
     fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
-        let mut result = Self::default();
-        for i in 0..B {
-            result.data[i] = TreeIndex::conditional_select(&a.data[i], &b.data[i], choice);
-        }
+        let mut result = *a;
+        result.conditional_assign(b, choice);
         result
     }
+
+    fn conditional_assign(&mut self, other: &Self, choice: Choice) {
+        // Expand `choice` into a mask once, then apply it a cache line at a time,
+        // in a form the compiler can vectorize.
+        let mask = TreeIndex::conditional_select(&0, &TreeIndex::MAX, choice);
+        for (chunk, other_chunk) in self
+            .data
+            .chunks_mut(POSITIONS_PER_CACHE_LINE)
+            .zip(other.data.chunks(POSITIONS_PER_CACHE_LINE))
+        {
+            for (position, other_position) in chunk.iter_mut().zip(other_chunk) {
+                *position ^= mask & (*position ^ *other_position);
+            }
+        }
+    }
 }
 
 impl<const B: BlockSize> Distribution<PositionBlock<B>> for Standard {
@@ -154,52 +252,211 @@ impl<const B: BlockSize> Distribution<PositionBlock<B>> for Standard {
     }
 }
 
-impl<const B: BlockSize> OramBlock for PositionBlock<B> {}
+impl<const B: BlockSize> OramBlock for PositionBlock<B> {
+    fn update_checksum(&self, checksum: &mut Checksum) {
+        for position in &self.data {
+            checksum.update(&position.to_le_bytes());
+        }
+    }
+}
 
+#[repr(align(64))]
 #[derive(Clone, Copy, PartialEq)]
-/// A Path ORAM bucket.
-pub struct Bucket<V: OramBlock, const Z: BucketSize> {
+/// A Path ORAM bucket, whose blocks store their metadata as `I`s.
+///
+/// Buckets are aligned to 64-byte cache lines, so that no two buckets share a cache line
+/// and the reads of a path's buckets start on cache-line boundaries.
+pub struct Bucket<V: OramBlock, const Z: BucketSize, I: IndexWord = TreeIndex> {
     /// The Path ORAM blocks stored by this bucket.
-    pub(crate) blocks: [PathOramBlock<V>; Z],
+    pub(crate) blocks: [PathOramBlock<V, I>; Z],
 }
 
-impl<V: OramBlock, const Z: BucketSize> std::fmt::Debug for Bucket<V, Z> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut self_is_dummy = true;
+// The blocks of a bucket are secret, so only its size is printed.
+impl<V: OramBlock, const Z: BucketSize, I: IndexWord> core::fmt::Debug for Bucket<V, Z, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Bucket")
+            .field("blocks_per_bucket", &Z)
+            .finish_non_exhaustive()
+    }
+}
 
-        for block in self.blocks {
-            if (!block.ct_is_dummy()).into() {
-                self_is_dummy = false;
-            }
+impl<V: OramBlock, const Z: BucketSize, I: IndexWord> Default for Bucket<V, Z, I> {
+    fn default() -> Self {
+        Self {
+            blocks: [PathOramBlock::dummy(); Z],
         }
+    }
+}
 
-        if self_is_dummy {
-            write!(f, "Bucket::Dummy")
-        } else {
-            f.debug_struct("Bucket")
-                .field("blocks", &self.blocks)
-                .finish()
+impl<V: OramBlock, const Z: BucketSize, I: IndexWord> Bucket<V, Z, I> {
+    /// Empties this bucket in place. Unlike assigning `Bucket::default()`, this never holds a whole bucket on the stack,
+    /// which matters for large values (see [`MAX_VALUE_SIZE`](crate::path_oram::MAX_VALUE_SIZE)).
+    pub(crate) fn clear(&mut self) {
+        for block in &mut self.blocks {
+            *block = PathOramBlock::dummy();
         }
     }
-}
 
-impl<V: OramBlock, const Z: BucketSize> Default for Bucket<V, Z> {
-    fn default() -> Self {
-        Self {
-            blocks: [PathOramBlock::<V>::dummy(); Z],
+    /// Appends `n` empty buckets to `buckets`, holding only one on the stack at a time.
+    pub(crate) fn extend_empty(buckets: &mut Vec<Self>, n: usize) {
+        buckets.reserve(n);
+        for _ in 0..n {
+            buckets.push(Self::default());
         }
     }
 }
 
-impl<V: OramBlock, const Z: BucketSize> ConditionallySelectable for Bucket<V, Z> {
+impl<V: OramBlock, const Z: BucketSize, I: IndexWord> ConditionallySelectable for Bucket<V, Z, I> {
     fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
-        let mut result = Self::default();
-        for i in 0..result.blocks.len() {
-            result.blocks[i] =
-                PathOramBlock::<V>::conditional_select(&a.blocks[i], &b.blocks[i], choice)
+        let mut result = *a;
+        result.conditional_assign(b, choice);
+        result
+    }
+
+    fn conditional_assign(&mut self, other: &Self, choice: Choice) {
+        for (block, other_block) in self.blocks.iter_mut().zip(&other.blocks) {
+            block.conditional_assign(other_block, choice);
+        }
+    }
+
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+        for (a_block, b_block) in a.blocks.iter_mut().zip(b.blocks.iter_mut()) {
+            PathOramBlock::conditional_swap(a_block, b_block, choice);
+        }
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, I: IndexWord> OramBlock for Bucket<V, Z, I> {
+    fn update_checksum(&self, checksum: &mut Checksum) {
+        for block in &self.blocks {
+            block.update_checksum(checksum);
+        }
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, I: IndexWord> Bucket<V, Z, I> {
+    /// Returns whether the checksum of every block of this bucket is correct (always, without the `checksummed` feature).
+    #[cfg(feature = "checksummed")]
+    pub(crate) fn ct_is_intact(&self) -> Choice {
+        use subtle::ConstantTimeEq;
+        let mut result = Choice::from(1);
+        for block in &self.blocks {
+            let expected =
+                Checksum::of_block(block.address.into(), block.position.into(), &block.value);
+            result &= block.checksum.ct_eq(&expected);
         }
         result
     }
+
+    /// Returns whether the checksum of every block of this bucket is correct (always, without the `checksummed` feature).
+    #[cfg(not(feature = "checksummed"))]
+    pub(crate) fn ct_is_intact(&self) -> Choice {
+        Choice::from(1)
+    }
+}
+
+/// Verifies the buckets read by an access, remembering the first that fails verification without branching on the result.
+pub(crate) struct IntegrityCheck {
+    failed: Choice,
+    bucket: TreeIndex,
+}
+
+impl IntegrityCheck {
+    pub fn new() -> Self {
+        Self {
+            failed: Choice::from(0),
+            bucket: 0,
+        }
+    }
+
+    /// Verifies `bucket`, the bucket with index `index` in the tree.
+    pub fn check<V: OramBlock, const Z: BucketSize, I: IndexWord>(
+        &mut self,
+        bucket: &Bucket<V, Z, I>,
+        index: TreeIndex,
+    ) {
+        let first_failure = !self.failed & !bucket.ct_is_intact();
+        self.bucket.conditional_assign(&index, first_failure);
+        self.failed |= first_failure;
+    }
+
+    /// Returns a `BucketIntegrityError` if any bucket checked failed verification.
+    pub fn result(&self) -> Result<(), OramError> {
+        if self.failed.into() {
+            return Err(OramError::BucketIntegrityError {
+                bucket: self.bucket,
+            });
+        }
+        Ok(())
+    }
 }
 
-impl<V: OramBlock, const Z: BucketSize> OramBlock for Bucket<V, Z> {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_position_block_conditional_assign<const B: BlockSize>() {
+        let a = PositionBlock::<B> {
+            data: core::array::from_fn(|i| i as TreeIndex),
+        };
+        let b = PositionBlock::<B> {
+            data: core::array::from_fn(|i| TreeIndex::MAX - i as TreeIndex),
+        };
+
+        let mut result = a;
+        result.conditional_assign(&b, 0.into());
+        assert_eq!(result, a);
+        result.conditional_assign(&b, 1.into());
+        assert_eq!(result, b);
+
+        assert_eq!(PositionBlock::conditional_select(&a, &b, 0.into()), a);
+        assert_eq!(PositionBlock::conditional_select(&a, &b, 1.into()), b);
+    }
+
+    // The checksum of the `checksummed` feature enlarges blocks.
+    #[cfg(not(feature = "checksummed"))]
+    #[test]
+    fn index_word_sizes() {
+        use core::mem::size_of;
+
+        assert_eq!(size_of::<PathOramBlock<u32>>(), 24);
+        assert_eq!(size_of::<PathOramBlock<u32, u32>>(), 12);
+        // 48 bytes of blocks, padded to a cache line.
+        assert_eq!(size_of::<Bucket<u32, 4, u32>>(), 64);
+    }
+
+    #[test]
+    fn position_block_conditional_assign() {
+        // Blocks smaller than, equal to, and spanning several cache lines.
+        check_position_block_conditional_assign::<2>();
+        check_position_block_conditional_assign::<8>();
+        check_position_block_conditional_assign::<20>();
+    }
+
+    #[test]
+    fn block_value_conditional_swap() {
+        let a = BlockValue::new(core::array::from_fn(|i| i as u8));
+        let b = BlockValue::new(core::array::from_fn(|i| !(i as u8)));
+        let (mut x, mut y) = (a, b);
+        BlockValue::<100>::conditional_swap(&mut x, &mut y, 0.into());
+        assert_eq!((x, y), (a, b));
+        BlockValue::conditional_swap(&mut x, &mut y, 1.into());
+        assert_eq!((x, y), (b, a));
+
+        x.conditional_assign(&a, 0.into());
+        assert_eq!(x, b);
+        x.conditional_assign(&a, 1.into());
+        assert_eq!(x, a);
+        assert_eq!(BlockValue::conditional_select(&a, &b, 1.into()), b);
+    }
+
+    #[test]
+    fn clear_empties_buckets() {
+        let mut buckets = Vec::new();
+        Bucket::<u64, 4>::extend_empty(&mut buckets, 3);
+        assert_eq!(buckets.len(), 3);
+        buckets[1].blocks[2] = PathOramBlock::new(7, 3, 5);
+        buckets[1].clear();
+        assert!(buckets.iter().all(|bucket| *bucket == Bucket::default()));
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An ORAM of byte blocks whose size is chosen at runtime, shared by the foreign-language bindings.
+
+use crate::{Address, BlockSize, BlockValue, DefaultOram, Oram, OramError};
+use rand::rngs::OsRng;
+
+/// The block sizes, in bytes, supported by the foreign-language bindings.
+pub const SUPPORTED_BLOCK_SIZES: [BlockSize; 3] = [64, 256, 4096];
+
+#[derive(Debug)]
+enum SizedOram {
+    B64(DefaultOram<BlockValue<64>>),
+    B256(DefaultOram<BlockValue<256>>),
+    B4096(DefaultOram<BlockValue<4096>>),
+}
+
+/// A [`DefaultOram`] storing blocks of one of the [`SUPPORTED_BLOCK_SIZES`],
+/// which draws its randomness from an owned `OsRng`.
+#[derive(Debug)]
+pub(crate) struct ByteOram {
+    oram: SizedOram,
+    rng: OsRng,
+}
+
+impl ByteOram {
+    /// Returns a new `ByteOram` storing `capacity` zeroed blocks of `block_size` bytes,
+    /// or `None` if `block_size` is not supported.
+    pub(crate) fn new(capacity: Address, block_size: BlockSize) -> Result<Option<Self>, OramError> {
+        let mut rng = OsRng;
+        let oram = match block_size {
+            64 => SizedOram::B64(DefaultOram::new(capacity, &mut rng)?),
+            256 => SizedOram::B256(DefaultOram::new(capacity, &mut rng)?),
+            4096 => SizedOram::B4096(DefaultOram::new(capacity, &mut rng)?),
+            _ => return Ok(None),
+        };
+        Ok(Some(Self { oram, rng }))
+    }
+
+    pub(crate) fn block_size(&self) -> BlockSize {
+        match &self.oram {
+            SizedOram::B64(_) => 64,
+            SizedOram::B256(_) => 256,
+            SizedOram::B4096(_) => 4096,
+        }
+    }
+
+    /// Obliviously reads the block at `address` into `out`.
+    /// Panics unless `out.len()` is the block size.
+    pub(crate) fn read(&mut self, address: Address, out: &mut [u8]) -> Result<(), OramError> {
+        match &mut self.oram {
+            SizedOram::B64(oram) => out.copy_from_slice(&oram.read(address, &mut self.rng)?.data),
+            SizedOram::B256(oram) => out.copy_from_slice(&oram.read(address, &mut self.rng)?.data),
+            SizedOram::B4096(oram) => out.copy_from_slice(&oram.read(address, &mut self.rng)?.data),
+        }
+        Ok(())
+    }
+
+    /// Obliviously writes `data` to the block at `address`.
+    /// Panics unless `data.len()` is the block size.
+    pub(crate) fn write(&mut self, address: Address, data: &[u8]) -> Result<(), OramError> {
+        match &mut self.oram {
+            SizedOram::B64(oram) => write_block(oram, &mut self.rng, address, data),
+            SizedOram::B256(oram) => write_block(oram, &mut self.rng, address, data),
+            SizedOram::B4096(oram) => write_block(oram, &mut self.rng, address, data),
+        }
+    }
+}
+
+fn write_block<const B: BlockSize>(
+    oram: &mut DefaultOram<BlockValue<B>>,
+    rng: &mut OsRng,
+    address: Address,
+    data: &[u8],
+) -> Result<(), OramError> {
+    let data = <[u8; B]>::try_from(data).expect("data length must equal the block size");
+    oram.write(address, BlockValue::new(data), rng)?;
+    Ok(())
+}
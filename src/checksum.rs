@@ -0,0 +1,162 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Block checksums for detecting memory corruption, e.g., bit flips in large, long-lived enclave memory.
+//!
+//! With the `checksummed` feature, every block in a Path ORAM tree carries a CRC-32 [`Checksum`]
+//! of its address, its position, and its value (as fed by [`OramBlock::update_checksum`](crate::OramBlock::update_checksum)).
+//! The checksums of each bucket are verified whenever the bucket is read into the stash, and recomputed whenever it is written,
+//! and an access reading a bucket that fails verification returns a `BucketIntegrityError` with the index of the bucket
+//! (which poisons the ORAM, as any failed access does).
+//!
+//! A checksum detects accidental corruption, not tampering: it is not keyed, so anyone who can modify memory
+//! can also recompute it. [`EncryptedOram`](crate::encrypted::EncryptedOram) authenticates values against an adversary.
+//!
+//! The integer types and the block types of this crate feed their values to checksums.
+//! Other [`OramBlock`](crate::OramBlock) types feed nothing unless they implement
+//! [`OramBlock::update_checksum`](crate::OramBlock::update_checksum); for those that do not,
+//! only the metadata of their blocks is protected.
+
+/// The reversed CRC-32 (IEEE 802.3) polynomial.
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// A CRC-32 checksum, computed bit by bit without table lookups or branches,
+/// so in time independent of the bytes checksummed.
+#[derive(Clone, Copy, Debug)]
+pub struct Checksum {
+    crc: u32,
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checksum {
+    /// Returns the checksum of no bytes.
+    pub fn new() -> Self {
+        Self { crc: u32::MAX }
+    }
+
+    /// Appends `bytes` to the checksummed bytes.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.crc ^= u32::from(*byte);
+            for _ in 0..8 {
+                let mask = 0u32.wrapping_sub(self.crc & 1);
+                self.crc = (self.crc >> 1) ^ (POLYNOMIAL & mask);
+            }
+        }
+    }
+
+    /// Returns the CRC-32 of the checksummed bytes.
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+
+    /// Returns the checksum of a block with the given metadata and value.
+    #[cfg(any(feature = "checksummed", test))]
+    pub(crate) fn of_block<V: crate::OramBlock>(address: u64, position: u64, value: &V) -> u32 {
+        let mut checksum = Self::new();
+        checksum.update(&address.to_le_bytes());
+        checksum.update(&position.to_le_bytes());
+        value.update_checksum(&mut checksum);
+        checksum.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockValue;
+
+    #[test]
+    fn checksum_matches_crc32() {
+        let mut checksum = Checksum::new();
+        checksum.update(b"123456789");
+        assert_eq!(checksum.finish(), 0xCBF4_3926);
+        assert_eq!(Checksum::new().finish(), 0);
+    }
+
+    #[test]
+    fn every_bit_of_a_block_is_checksummed() {
+        let value = BlockValue::new([0x5a; 4]);
+        let original = Checksum::of_block(3, 9, &value);
+        for bit in 0..64 {
+            assert_ne!(Checksum::of_block(3 ^ (1 << bit), 9, &value), original);
+            assert_ne!(Checksum::of_block(3, 9 ^ (1 << bit), &value), original);
+        }
+        for bit in 0..32 {
+            let mut flipped = value;
+            flipped.data[bit / 8] ^= 1 << (bit % 8);
+            assert_ne!(Checksum::of_block(3, 9, &flipped), original);
+        }
+    }
+
+    #[cfg(feature = "checksummed")]
+    mod checksummed {
+        use crate::{path_oram::PathOram, tree::TreeIndex, Oram, OramError};
+        use rand::{rngs::StdRng, SeedableRng};
+
+        type TestOram = PathOram<u64, 4, 8>;
+
+        fn new_oram(rng: &mut StdRng) -> TestOram {
+            let mut oram = TestOram::new_with_parameters(64, rng, 40, 1).unwrap();
+            for address in 0..64 {
+                oram.write(address, address, rng).unwrap();
+            }
+            oram
+        }
+
+        #[test]
+        fn bit_flip_at_the_root_is_detected_on_the_next_access() {
+            let mut rng = StdRng::seed_from_u64(0);
+            for field in 0..3 {
+                let mut oram = new_oram(&mut rng);
+                let block = &mut oram.physical_memory[1].blocks[0];
+                match field {
+                    0 => block.value ^= 1 << 17,
+                    1 => block.address ^= 1,
+                    _ => block.position ^= 1 << 3,
+                }
+                assert!(matches!(
+                    oram.read(0, &mut rng),
+                    Err(OramError::BucketIntegrityError { bucket: 1 })
+                ));
+                assert!(oram.is_poisoned());
+            }
+        }
+
+        #[test]
+        fn bit_flip_in_a_leaf_is_detected_with_its_bucket_index() {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut oram = new_oram(&mut rng);
+            let leaf: TreeIndex = 1 << oram.height;
+            oram.physical_memory[leaf as usize].blocks[1].value ^= 1;
+
+            // Each access reads a random path, so the corrupted leaf is soon read.
+            let error = (0..10_000)
+                .find_map(|i| oram.read(i % 64, &mut rng).err())
+                .unwrap();
+            assert!(matches!(
+                error,
+                OramError::BucketIntegrityError { bucket } if bucket == leaf
+            ));
+        }
+
+        #[test]
+        fn intact_orams_pass_verification() {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut oram = new_oram(&mut rng);
+            oram.set_max_pending_evictions(2).unwrap();
+            for i in 0..1000 {
+                assert_eq!(oram.read(i % 64, &mut rng).unwrap(), i % 64);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Storage of variable-length byte strings in fixed-size blocks.
+//!
+//! A [`PaddedCodec<B>`](PaddedCodec) encodes a payload of at most `B - 4` bytes as a `BlockValue<B>` holding,
+//! in order, the payload length as a 4-byte little-endian integer, the payload, and zero padding.
+//! A [`CodecOram`] stores such payloads in an ORAM of `BlockValue<B>`s.
+//!
+//! Every block is `B` bytes long whatever the length of its payload, so the access pattern of an ORAM storing encoded payloads
+//! reveals nothing about their lengths. The stored length is revealed only to the caller, as the length of a decoded payload.
+//!
+//! ```
+//! use oram::{codec::CodecOram, DefaultOram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut oram = CodecOram::new(DefaultOram::<oram::BlockValue<64>>::new(16, &mut rng)?);
+//! oram.write_bytes(3, b"a variable-length record", &mut rng)?;
+//! assert_eq!(oram.read_bytes(3, &mut rng)?, b"a variable-length record");
+//! assert_eq!(oram.read_bytes(4, &mut rng)?, b"");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Address, BlockSize, BlockValue, Oram, OramError};
+use alloc::{format, string::ToString, vec::Vec};
+use core::marker::PhantomData;
+use rand::{CryptoRng, RngCore};
+use subtle::{ConditionallySelectable, ConstantTimeLess};
+
+const LENGTH_SIZE: usize = 4;
+
+/// Encodes payloads of at most `B - 4` bytes as length-prefixed, zero-padded `BlockValue<B>`s.
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PaddedCodec<const B: BlockSize>;
+
+impl<const B: BlockSize> PaddedCodec<B> {
+    const VALID_SIZE: () = assert!(
+        B >= LENGTH_SIZE && B - LENGTH_SIZE <= u32::MAX as usize,
+        "the block size B must hold a 4-byte length prefix"
+    );
+
+    /// The largest payload that fits in a block, in bytes.
+    pub const MAX_PAYLOAD_SIZE: usize = B - LENGTH_SIZE;
+
+    /// Returns `payload`, length-prefixed and zero-padded to `B` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `payload` is longer than `B - 4` bytes.
+    pub fn encode(payload: &[u8]) -> Result<BlockValue<B>, OramError> {
+        let () = Self::VALID_SIZE;
+        if payload.len() > Self::MAX_PAYLOAD_SIZE {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("Payload length (with block size {B})"),
+                parameter_value: payload.len().to_string(),
+            });
+        }
+        let mut block = BlockValue::default();
+        block.data[..LENGTH_SIZE].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        block.data[LENGTH_SIZE..LENGTH_SIZE + payload.len()].copy_from_slice(payload);
+        Ok(block)
+    }
+
+    /// Returns the payload encoded in `block`. Every byte of the block is copied whatever the length,
+    /// and a length prefix larger than `B - 4` (which `encode` never writes) is clamped to `B - 4`.
+    pub fn decode(block: &BlockValue<B>) -> Vec<u8> {
+        let () = Self::VALID_SIZE;
+        let max = Self::MAX_PAYLOAD_SIZE as u32;
+        let mut length = u32::from_le_bytes(block.data[..LENGTH_SIZE].try_into().unwrap());
+        length.conditional_assign(&max, max.ct_lt(&length));
+
+        let mut payload = block.data[LENGTH_SIZE..].to_vec();
+        payload.truncate(length as usize);
+        payload
+    }
+}
+
+/// An ORAM of variable-length byte strings, stored encoded by a [`PaddedCodec<B>`](PaddedCodec) in an ORAM `O`.
+/// An address that has never been written holds the empty string.
+#[derive(Debug)]
+pub struct CodecOram<const B: BlockSize, O: Oram<V = BlockValue<B>>> {
+    oram: O,
+    codec: PhantomData<PaddedCodec<B>>,
+}
+
+impl<const B: BlockSize, O: Oram<V = BlockValue<B>>> CodecOram<B, O> {
+    /// Returns a `CodecOram` storing its payloads in `oram`.
+    pub fn new(oram: O) -> Self {
+        Self {
+            oram,
+            codec: PhantomData,
+        }
+    }
+
+    /// Returns the capacity of the ORAM, in payloads.
+    pub fn block_capacity(&self) -> Result<Address, OramError> {
+        self.oram.block_capacity()
+    }
+
+    /// Returns the payload stored at `address`.
+    pub fn read_bytes<R: RngCore + CryptoRng>(
+        &mut self,
+        address: Address,
+        rng: &mut R,
+    ) -> Result<Vec<u8>, OramError> {
+        Ok(PaddedCodec::decode(&self.oram.read(address, rng)?))
+    }
+
+    /// Stores `payload` at `address`, returning the payload previously stored there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `payload` is longer than `B - 4` bytes, without accessing the ORAM.
+    pub fn write_bytes<R: RngCore + CryptoRng>(
+        &mut self,
+        address: Address,
+        payload: &[u8],
+        rng: &mut R,
+    ) -> Result<Vec<u8>, OramError> {
+        let block = PaddedCodec::encode(payload)?;
+        Ok(PaddedCodec::decode(&self.oram.write(address, block, rng)?))
+    }
+
+    /// Returns a mutable reference to the underlying ORAM of encoded payloads.
+    pub fn inner_mut(&mut self) -> &mut O {
+        &mut self.oram
+    }
+
+    /// Returns the underlying ORAM of encoded payloads.
+    pub fn into_inner(self) -> O {
+        self.oram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, PathOram};
+    use alloc::vec;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    type Codec = PaddedCodec<16>;
+
+    #[test]
+    fn payloads_round_trip() {
+        for payload in [vec![], vec![7], vec![0; 12], (1..=12).collect()] {
+            let block = Codec::encode(&payload).unwrap();
+            assert_eq!(Codec::decode(&block), payload);
+        }
+        assert_eq!(Codec::decode(&BlockValue::default()), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn over_length_payloads_are_rejected() {
+        assert!(matches!(
+            Codec::encode(&[1; 13]),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        assert!(PaddedCodec::<4>::encode(&[]).is_ok());
+        assert!(PaddedCodec::<4>::encode(&[1]).is_err());
+    }
+
+    #[test]
+    fn oversized_length_prefixes_are_clamped() {
+        let mut block = Codec::encode(&[5; 3]).unwrap();
+        block.data[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let payload = Codec::decode(&block);
+        assert_eq!(payload.len(), 12);
+        assert_eq!(payload[..3], [5; 3]);
+    }
+
+    #[test]
+    fn codec_oram_matches_shadow_model() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = PathOram::<BlockValue<16>, 4, 8>::new_with_parameters(32, &mut rng, 40, 1);
+        let mut oram = CodecOram::new(oram.unwrap());
+        let mut expected = vec![Vec::new(); 32];
+        for _ in 0..500 {
+            let address = rng.gen_range(0..32);
+            if rng.gen() {
+                assert_eq!(
+                    oram.read_bytes(address, &mut rng).unwrap(),
+                    expected[address as usize]
+                );
+            } else {
+                let payload: Vec<u8> = (0..rng.gen_range(0..=12)).map(|_| rng.gen()).collect();
+                let previous = oram.write_bytes(address, &payload, &mut rng).unwrap();
+                assert_eq!(previous, expected[address as usize]);
+                expected[address as usize] = payload;
+            }
+        }
+
+        // An over-length write fails, leaving the stored payload unchanged.
+        let mut oram = CodecOram::new(LinearTimeOram::<BlockValue<16>>::new(4).unwrap());
+        oram.write_bytes(1, b"kept", &mut rng).unwrap();
+        assert!(oram.write_bytes(1, &[0; 13], &mut rng).is_err());
+        assert_eq!(oram.read_bytes(1, &mut rng).unwrap(), b"kept");
+        assert_eq!(oram.block_capacity().unwrap(), 4);
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Constant-time `u64` arithmetic and array indexing, for use in access callbacks.
+//!
+//! As the [`ct_cmp`](crate::ct_cmp) module explains, a callback must take time independent of the value it is given.
+//! The helpers here cover quantities that callbacks commonly derive from that value, such as a bounded counter:
+//!
+//! ```
+//! use oram::{ct::ct_add_saturating, ct_cmp::ct_min, DefaultOram, Oram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! const CAP: u64 = 10;
+//! let mut oram = DefaultOram::<u64>::new(64, &mut OsRng)?;
+//! oram.write(3, 8, &mut OsRng)?;
+//!
+//! // Add 5 to the value stored at address 3, capping it at `CAP`.
+//! oram.access(3, |value| ct_min(&ct_add_saturating(*value, 5), &CAP), &mut OsRng)?;
+//! assert_eq!(oram.read(3, &mut OsRng)?, CAP);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each helper is built from additions, subtractions, and `subtle`'s selections, which compile to branch-free code,
+//! and never indexes memory by a secret: [`ct_mux_array`] reads every element of its array whatever the index.
+
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// Returns `b` if `choice` is true and `a` otherwise, in constant time.
+///
+/// Equivalent to `u64::conditional_select(&a, &b, choice)`, which selects with a mask rather than a branch.
+pub fn ct_select_u64(a: u64, b: u64, choice: Choice) -> u64 {
+    u64::conditional_select(&a, &b, choice)
+}
+
+/// Returns `a + b`, or `u64::MAX` if the sum overflows, in constant time.
+///
+/// The overflow is detected from the carry of a single wrapping addition, and `u64::MAX` selected with a mask.
+pub fn ct_add_saturating(a: u64, b: u64) -> u64 {
+    let (sum, overflowed) = a.overflowing_add(b);
+    ct_select_u64(sum, u64::MAX, Choice::from(u8::from(overflowed)))
+}
+
+/// Returns `a - b`, or 0 if the difference underflows, in constant time.
+///
+/// The underflow is detected from the borrow of a single wrapping subtraction, and 0 selected with a mask.
+pub fn ct_sub_saturating(a: u64, b: u64) -> u64 {
+    let (difference, underflowed) = a.overflowing_sub(b);
+    ct_select_u64(difference, 0, Choice::from(u8::from(underflowed)))
+}
+
+/// Returns `array[index]`, or `T::default()` if `index >= N`, where `index` is secret.
+///
+/// Reads every element of `array`, selecting the one whose position equals `index` with a mask,
+/// so takes time and touches memory independent of `index`. Costs `N` selections, so suits small arrays,
+/// such as the positions packed in a [`PositionBlock`](crate::PositionBlock).
+pub fn ct_mux_array<T: ConditionallySelectable + Default, const N: usize>(
+    index: usize,
+    array: &[T; N],
+) -> T {
+    let mut result = T::default();
+    for (i, element) in array.iter().enumerate() {
+        result.conditional_assign(element, i.ct_eq(&index));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARIES: [u64; 8] = [
+        0,
+        1,
+        2,
+        u64::MAX / 2,
+        u64::MAX / 2 + 1,
+        u64::MAX - 2,
+        u64::MAX - 1,
+        u64::MAX,
+    ];
+
+    #[test]
+    fn ct_select_u64_selects() {
+        for a in BOUNDARIES {
+            for b in BOUNDARIES {
+                assert_eq!(ct_select_u64(a, b, Choice::from(0)), a);
+                assert_eq!(ct_select_u64(a, b, Choice::from(1)), b);
+            }
+        }
+    }
+
+    #[test]
+    fn saturating_arithmetic_matches_std_at_boundaries() {
+        for a in BOUNDARIES {
+            for b in BOUNDARIES {
+                assert_eq!(ct_add_saturating(a, b), a.saturating_add(b), "{a} + {b}");
+                assert_eq!(ct_sub_saturating(a, b), a.saturating_sub(b), "{a} - {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn saturating_arithmetic_matches_std_exhaustively_near_the_bounds() {
+        // Every pair of operands within 256 of 0 or of `u64::MAX`.
+        let operands: Vec<u64> = (0..256).chain(u64::MAX - 255..=u64::MAX).collect();
+        for &a in &operands {
+            for &b in &operands {
+                assert_eq!(ct_add_saturating(a, b), a.saturating_add(b));
+                assert_eq!(ct_sub_saturating(a, b), a.saturating_sub(b));
+            }
+        }
+    }
+
+    #[test]
+    fn ct_mux_array_indexes_exhaustively() {
+        let array: [u64; 16] = core::array::from_fn(|i| 100 + i as u64);
+        for index in 0..16 {
+            assert_eq!(ct_mux_array(index, &array), array[index]);
+        }
+        for index in [16, 17, usize::MAX] {
+            assert_eq!(ct_mux_array(index, &array), 0);
+        }
+        assert_eq!(ct_mux_array(0, &[7u8]), 7);
+        assert_eq!(ct_mux_array::<u8, 0>(0, &[]), 0);
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Constant-time comparisons for ORAM blocks.
+//!
+//! An ORAM hides which address is accessed, but not what an [`Oram::access`](crate::Oram::access) callback does:
+//! a callback that branches on the value it is given (e.g., `if *value < threshold { ... }`) may leak that value
+//! through its timing or memory access pattern. Callbacks should instead compute a [`Choice`] with [`CtOrd`]
+//! (or [`subtle::ConstantTimeEq`]) and select their result with [`ConditionallySelectable::conditional_select`],
+//! or use [`ct_min`], [`ct_max`], and [`ct_clamp`].
+//!
+//! ```
+//! use oram::{ct_cmp::{ct_min, CtOrd}, DefaultOram, Oram, OramError};
+//! use rand::rngs::OsRng;
+//! use subtle::ConditionallySelectable;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut oram = DefaultOram::<u64>::new(64, &mut OsRng)?;
+//! oram.write(3, 250, &mut OsRng)?;
+//!
+//! // Cap the value stored at address 3 at 100.
+//! oram.access(3, |value| ct_min(value, &100), &mut OsRng)?;
+//! assert_eq!(oram.read(3, &mut OsRng)?, 100);
+//!
+//! // If the value stored at address 3 is less than 200, replace it with 200.
+//! oram.access(3, |value| u64::conditional_select(value, &200, value.ct_lt(&200)), &mut OsRng)?;
+//! assert_eq!(oram.read(3, &mut OsRng)?, 200);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{BlockSize, BlockValue};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+
+/// A total order whose comparisons take time independent of the values compared.
+pub trait CtOrd: ConditionallySelectable {
+    /// Returns true if `self < other`.
+    fn ct_lt(&self, other: &Self) -> Choice;
+
+    /// Returns true if `self > other`.
+    fn ct_gt(&self, other: &Self) -> Choice {
+        other.ct_lt(self)
+    }
+
+    /// Returns true if `self <= other`.
+    fn ct_le(&self, other: &Self) -> Choice {
+        !self.ct_gt(other)
+    }
+
+    /// Returns true if `self >= other`.
+    fn ct_ge(&self, other: &Self) -> Choice {
+        !self.ct_lt(other)
+    }
+}
+
+macro_rules! impl_ct_ord_unsigned {
+    ($($t: ty),*) => {
+        $(
+            impl CtOrd for $t {
+                fn ct_lt(&self, other: &Self) -> Choice {
+                    ConstantTimeLess::ct_lt(self, other)
+                }
+            }
+        )*
+    };
+}
+
+// Signed integers are compared as unsigned integers after flipping their sign bits,
+// which maps `MIN..=MAX` onto `0..=<$u>::MAX` in order.
+macro_rules! impl_ct_ord_signed {
+    ($($t: ty => $u: ty),*) => {
+        $(
+            impl CtOrd for $t {
+                fn ct_lt(&self, other: &Self) -> Choice {
+                    let flip = |x: $t| (x as $u) ^ (1 << (<$u>::BITS - 1));
+                    ConstantTimeLess::ct_lt(&flip(*self), &flip(*other))
+                }
+            }
+        )*
+    };
+}
+
+impl_ct_ord_unsigned!(u8, u16, u32, u64);
+impl_ct_ord_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64);
+
+/// Compares blocks lexicographically by their bytes.
+impl<const B: BlockSize> CtOrd for BlockValue<B> {
+    fn ct_lt(&self, other: &Self) -> Choice {
+        let mut less = Choice::from(0);
+        let mut decided = Choice::from(0);
+        for (a, b) in self.data.iter().zip(other.data.iter()) {
+            less |= !decided & ConstantTimeLess::ct_lt(a, b);
+            decided |= !a.ct_eq(b);
+        }
+        less
+    }
+}
+
+/// Returns the lesser of `a` and `b`, in constant time.
+pub fn ct_min<T: CtOrd>(a: &T, b: &T) -> T {
+    T::conditional_select(a, b, b.ct_lt(a))
+}
+
+/// Returns the greater of `a` and `b`, in constant time.
+pub fn ct_max<T: CtOrd>(a: &T, b: &T) -> T {
+    T::conditional_select(a, b, a.ct_lt(b))
+}
+
+/// Returns `value` restricted to the interval `[min, max]`, in constant time. Assumes that `min <= max`.
+pub fn ct_clamp<T: CtOrd>(value: &T, min: &T, max: &T) -> T {
+    ct_min(&ct_max(value, min), max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cmp::Ordering;
+    use core::fmt::Debug;
+
+    // Checks every comparison and helper on `a` and `b` against `Ord`.
+    fn check_pair<T: CtOrd + Debug + PartialEq>(a: T, b: T, ordering: Ordering) {
+        let actual = [a.ct_lt(&b), a.ct_gt(&b), a.ct_le(&b), a.ct_ge(&b)].map(bool::from);
+        let expected = [
+            ordering.is_lt(),
+            ordering.is_gt(),
+            ordering.is_le(),
+            ordering.is_ge(),
+        ];
+        assert_eq!(actual, expected, "comparing {:?} and {:?}", a, b);
+
+        let (min, max) = if ordering.is_gt() { (&b, &a) } else { (&a, &b) };
+        assert_eq!(ct_min(&a, &b), *min);
+        assert_eq!(ct_max(&a, &b), *max);
+    }
+
+    fn check_ord<T: CtOrd + Debug + Ord>(values: &[T]) {
+        for a in values {
+            for b in values {
+                check_pair(*a, *b, a.cmp(b));
+            }
+        }
+    }
+
+    #[test]
+    fn ct_ord_exhaustive_u8_i8() {
+        check_ord(&(u8::MIN..=u8::MAX).collect::<Vec<_>>());
+        check_ord(&(i8::MIN..=i8::MAX).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ct_ord_boundaries() {
+        check_ord(&[
+            u16::MIN,
+            1,
+            u16::MAX / 2,
+            u16::MAX / 2 + 1,
+            u16::MAX - 1,
+            u16::MAX,
+        ]);
+        check_ord(&[
+            u32::MIN,
+            1,
+            u32::MAX / 2,
+            u32::MAX / 2 + 1,
+            u32::MAX - 1,
+            u32::MAX,
+        ]);
+        check_ord(&[
+            u64::MIN,
+            1,
+            u64::MAX / 2,
+            u64::MAX / 2 + 1,
+            u64::MAX - 1,
+            u64::MAX,
+        ]);
+        check_ord(&[i16::MIN, i16::MIN + 1, -1, 0, 1, i16::MAX - 1, i16::MAX]);
+        check_ord(&[i32::MIN, i32::MIN + 1, -1, 0, 1, i32::MAX - 1, i32::MAX]);
+        check_ord(&[i64::MIN, i64::MIN + 1, -1, 0, 1, i64::MAX - 1, i64::MAX]);
+    }
+
+    #[test]
+    fn ct_ord_block_value_is_lexicographic() {
+        let bytes = [0, 1, 127, 128, 255];
+        let mut blocks = Vec::new();
+        for first in bytes {
+            for second in bytes {
+                for third in bytes {
+                    blocks.push([first, second, third]);
+                }
+            }
+        }
+        for a in &blocks {
+            for b in &blocks {
+                check_pair(BlockValue::new(*a), BlockValue::new(*b), a.cmp(b));
+            }
+        }
+        check_pair(
+            BlockValue::<0>::default(),
+            BlockValue::default(),
+            Ordering::Equal,
+        );
+    }
+
+    #[test]
+    fn ct_clamp_bounds() {
+        for value in i8::MIN..=i8::MAX {
+            assert_eq!(ct_clamp(&value, &-10, &20), value.clamp(-10, 20));
+            assert_eq!(ct_clamp(&value, &5, &5), 5);
+            assert_eq!(ct_clamp(&value, &i8::MIN, &i8::MAX), value);
+        }
+    }
+}
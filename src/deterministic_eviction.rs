@@ -0,0 +1,257 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A Path ORAM variant that evicts along deterministic, reverse-lexicographic paths.
+
+use crate::{
+    eviction::{EvictionOrder, EvictionStrategy, GreedyEviction},
+    path_oram::PathOram,
+    tree::{reverse_lexicographic_leaf, TreeIndex},
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError, RecursionCutoff, StashSize,
+};
+use rand::{CryptoRng, Rng};
+
+/// A Path ORAM that decouples the path it reads from the path it evicts to:
+/// a [`PathOram`] with the eviction order [`EvictionOrder::ReverseLexicographic`].
+///
+/// Each access reads the path determined by the accessed block's position,
+/// obliviously moving only the accessed block from that path into the stash.
+/// It then evicts along the next path in reverse-lexicographic order,
+/// which is independent of the accessed address.
+/// Reverse-lexicographic order evicts every bucket at depth `d` exactly once every `2^d` accesses,
+/// which spreads evictions evenly over the tree and simplifies the analysis of stash occupancy.
+///
+/// Blocks that cannot be placed on the eviction path remain in the stash until a later eviction path admits them.
+/// The parameters, and the security caveats concerning stash overflow, are as for [`PathOram`].
+#[derive(Debug)]
+pub struct DeterministicEvictPathOram<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy = GreedyEviction,
+> {
+    oram: PathOram<V, Z, AB, E>,
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy>
+    DeterministicEvictPathOram<V, Z, AB, E>
+{
+    /// Returns a new `DeterministicEvictPathOram` mapping addresses `0 <= address < block_capacity` to default `V` values.
+    /// See [`PathOram::new_with_parameters`] for a description of the parameters and the possible errors.
+    pub fn new_with_parameters<R: Rng + CryptoRng>(
+        block_capacity: Address,
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<Self, OramError> {
+        let mut oram =
+            PathOram::new_with_parameters(block_capacity, rng, overflow_size, recursion_cutoff)?;
+        oram.set_eviction_order(EvictionOrder::ReverseLexicographic);
+        Ok(Self { oram })
+    }
+
+    /// Returns the number of evictions performed so far, modulo 2^64.
+    /// The next eviction is along the path to `next_eviction_leaf()`.
+    pub fn eviction_count(&self) -> u64 {
+        self.oram.eviction_count()
+    }
+
+    /// Returns the leaf of the path that the next eviction will write to.
+    pub fn next_eviction_leaf(&self) -> TreeIndex {
+        reverse_lexicographic_leaf(self.oram.eviction_count(), self.oram.height())
+    }
+
+    /// Returns the underlying Path ORAM.
+    pub fn into_inner(self) -> PathOram<V, Z, AB, E> {
+        self.oram
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy> Oram
+    for DeterministicEvictPathOram<V, Z, AB, E>
+{
+    type V = V;
+
+    fn access<R: Rng + CryptoRng, F: Fn(&V) -> V>(
+        &mut self,
+        address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<V, OramError> {
+        self.oram.access(address, callback, rng)
+    }
+
+    fn read_into<R: Rng + CryptoRng>(
+        &mut self,
+        address: Address,
+        out: &mut V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        self.oram.read_into(address, out, rng)
+    }
+
+    fn write_from<R: Rng + CryptoRng>(
+        &mut self,
+        address: Address,
+        new_value: &V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        self.oram.write_from(address, new_value, rng)
+    }
+
+    fn block_capacity(&self) -> Result<Address, OramError> {
+        self.oram.block_capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bucket::*, test_utils::*, tree::CompleteBinaryTreeIndex};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{collections::HashMap, vec};
+
+    type DeterministicOram = DeterministicEvictPathOram<BlockValue<1>, 4, 8>;
+
+    create_path_oram_correctness_tests_helper!(
+        DeterministicEvictPathOram,
+        "_deterministic_eviction_",
+        4,
+        8,
+        1,
+        40
+    );
+    create_path_oram_correctness_tests_helper!(
+        DeterministicEvictPathOram,
+        "_deterministic_eviction_",
+        3,
+        2,
+        1,
+        10
+    );
+
+    fn check_stash_size(capacity: Address) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = DeterministicOram::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        random_workload(&mut StashSizeMonitor::new(oram.into_inner()), 3000);
+    }
+
+    #[test]
+    fn deterministic_eviction_stash_size_small() {
+        check_stash_size(16);
+    }
+
+    #[test]
+    fn deterministic_eviction_stash_size_medium() {
+        check_stash_size(256);
+    }
+
+    // This test is #[ignore]'d because it takes about 10 seconds to run.
+    #[test]
+    #[ignore]
+    fn deterministic_eviction_stash_size_large() {
+        check_stash_size(2048);
+    }
+
+    #[test]
+    fn deterministic_eviction_path_coverage() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = 64;
+        let mut oram = DeterministicOram::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        let height = oram.oram.height();
+        let number_of_leaves = 1u64 << height;
+
+        // Over any window of `2^height` consecutive evictions, every leaf is evicted exactly once,
+        // and so every bucket at depth `d` is evicted exactly `2^(height - d)` times.
+        let rounds = 3;
+        let mut leaf_counts = HashMap::new();
+        let mut bucket_counts = HashMap::new();
+        for _ in 0..(rounds * number_of_leaves) {
+            let leaf = oram.next_eviction_leaf();
+            assert!(leaf.is_leaf(height));
+            *leaf_counts.entry(leaf).or_insert(0) += 1;
+            for depth in 0..=height {
+                *bucket_counts
+                    .entry(leaf.ct_node_on_path(depth, height))
+                    .or_insert(0) += 1;
+            }
+
+            let address = rng.gen_range(0..capacity);
+            oram.read(address, &mut rng).unwrap();
+        }
+
+        assert_eq!(oram.eviction_count(), rounds * number_of_leaves);
+        assert_eq!(leaf_counts.len() as u64, number_of_leaves);
+        assert!(leaf_counts.values().all(|count| *count == rounds));
+        for (bucket, count) in bucket_counts {
+            let depth = bucket.ct_depth();
+            assert_eq!(count, rounds << (height - depth));
+        }
+    }
+
+    #[test]
+    fn eviction_count_wraps_without_disturbing_the_order() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = DeterministicOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let height = oram.oram.height();
+        oram.oram.eviction_count = u64::MAX;
+        assert_eq!(oram.next_eviction_leaf(), (2 << height) - 1);
+
+        oram.write(3, BlockValue::new([7]), &mut rng).unwrap();
+        assert_eq!(oram.eviction_count(), 0);
+        assert_eq!(oram.next_eviction_leaf(), 1 << height);
+        assert_eq!(oram.read(3, &mut rng).unwrap(), BlockValue::new([7]));
+    }
+
+    // Reverse-lexicographic eviction is an eviction order of `PathOram`, so it composes with the rest of its access path:
+    // deferred eviction, padding levels, and transactions.
+    #[test]
+    fn reverse_lexicographic_eviction_composes_with_path_oram_features() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = 256;
+        let mut oram =
+            PathOram::<u64, 4, 8>::new_with_parameters(capacity, &mut rng, 40, 2).unwrap();
+        oram.set_eviction_order(EvictionOrder::ReverseLexicographic);
+        oram.set_max_pending_evictions(2).unwrap();
+        let levels = oram.recursion_levels_per_access().unwrap();
+        oram.set_uniform_recursion_depth(levels + 1, &mut rng)
+            .unwrap();
+
+        let mut shadow = vec![0; capacity as usize];
+        let mut accesses = 0;
+        for i in 0..500 {
+            let address = rng.gen_range(0..capacity);
+            assert_eq!(
+                oram.write(address, i, &mut rng).unwrap(),
+                shadow[address as usize]
+            );
+            shadow[address as usize] = i;
+            accesses += 1;
+        }
+        let previous = oram.transact(&[(1, 1000), (2, 2000)], &mut rng).unwrap();
+        assert_eq!(previous, [shadow[1], shadow[2]]);
+        (shadow[1], shadow[2]) = (1000, 2000);
+        accesses += 2;
+
+        // Every access queued exactly one eviction in reverse-lexicographic order, of which two are still pending.
+        assert_eq!(oram.eviction_count(), accesses);
+        assert_eq!(oram.pending_evictions(), 2);
+        while oram.evict_one().unwrap() {}
+        assert!(oram.audit().unwrap().is_consistent());
+        assert!(oram.stash_occupancy() < 10);
+        for (address, expected) in shadow.iter().enumerate() {
+            assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn deterministic_eviction_rejects_out_of_bounds_addresses() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = DeterministicOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+    }
+}
@@ -0,0 +1,581 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Consistency checks for Path ORAMs whose memory may have been corrupted. Enabled by the `diagnostics` feature.
+//!
+//! [`PathOram::audit`] cross-references the blocks stored in the tree and stash against the position map,
+//! and [`PathOram::repair`] rebuilds the position map and tree from the blocks found.
+//! [`PathOram::try_recover`] uses them to recover an ORAM poisoned by a failed access.
+//! [`PathOram::debug_dump`] renders the entire contents of an ORAM, which its `Debug` output omits,
+//! and [`PathOram::leaf_loads`] counts the addresses assigned each leaf.
+//! None of these is oblivious: their memory access patterns reveal the entire contents of the ORAM.
+//! They are intended for recovery and debugging, and must not be used where the access pattern is observable.
+
+use crate::{
+    bucket::{Bucket, PathOramBlock},
+    eviction::EvictionStrategy,
+    path_oram::PathOram,
+    position_map::PositionMap,
+    tree::{CompleteBinaryTreeIndex, TreeIndex},
+    utils::IndexWord,
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError,
+};
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::Write;
+use rand::{CryptoRng, Rng};
+
+/// The inconsistencies found by [`PathOram::audit`]. Each list holds addresses in increasing order, without repetition.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Addresses of blocks whose stored position differs from the position recorded in the position map.
+    pub mismatches: Vec<Address>,
+    /// Addresses of blocks that an access would not find: blocks stored in a bucket off the path to the position
+    /// recorded for them, blocks whose recorded position is not a leaf, and blocks with addresses beyond the capacity.
+    pub orphans: Vec<Address>,
+    /// Addresses for which more than one block is stored.
+    pub duplicates: Vec<Address>,
+    /// Addresses below the capacity for which no block is stored.
+    pub missing: Vec<Address>,
+}
+
+impl AuditReport {
+    /// Returns true if no inconsistencies were found.
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+            && self.orphans.is_empty()
+            && self.duplicates.is_empty()
+            && self.missing.is_empty()
+    }
+}
+
+// The location of a block: a stash slot, or the bucket with the given tree index.
+#[derive(Clone, Copy)]
+enum Location {
+    Stash,
+    Bucket(TreeIndex),
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Checks that every address below the capacity is stored in exactly one block,
+    /// which an access would find using the position recorded in the position map. Not oblivious.
+    pub fn audit(&self) -> Result<AuditReport, OramError> {
+        let capacity = self.block_capacity()?;
+        let positions = self.position_map.positions()?;
+        let mut report = AuditReport::default();
+        let mut block_counts = vec![0u32; usize::try_from(capacity)?];
+
+        for (location, block) in self.real_blocks() {
+            let address: Address = block.address.into();
+            if address >= capacity {
+                report.orphans.push(address);
+                continue;
+            }
+            let index = usize::try_from(address)?;
+            block_counts[index] += 1;
+
+            let recorded_position = positions[index];
+            let stored_position: TreeIndex = block.position.into();
+            if stored_position != recorded_position {
+                report.mismatches.push(address);
+            }
+            let reachable = recorded_position != 0
+                && recorded_position.is_leaf(self.height)
+                && match location {
+                    Location::Stash => true,
+                    Location::Bucket(node) => {
+                        recorded_position.ct_node_on_path(node.ct_depth(), self.height) == node
+                    }
+                };
+            if !reachable {
+                report.orphans.push(address);
+            }
+        }
+
+        for (address, count) in (0..capacity).zip(block_counts) {
+            match count {
+                0 => report.missing.push(address),
+                1 => {}
+                _ => report.duplicates.push(address),
+            }
+        }
+        for addresses in [&mut report.mismatches, &mut report.orphans] {
+            addresses.sort_unstable();
+            addresses.dedup();
+        }
+        Ok(report)
+    }
+
+    /// Rebuilds the ORAM from the blocks stored in it, assigning every address a fresh random position.
+    /// Of several blocks with the same address, the one in the stash or nearest the root is kept,
+    /// since it was written most recently. Addresses with no block are reset to the default value,
+    /// and blocks with addresses beyond the capacity are discarded. Not oblivious.
+    ///
+    /// Afterward, [`PathOram::audit`] reports no inconsistencies.
+    pub fn repair<R: Rng + CryptoRng>(&mut self, rng: &mut R) -> Result<(), OramError> {
+        let capacity = self.block_capacity()?;
+        let mut values: Vec<Option<V>> = vec![None; usize::try_from(capacity)?];
+        for (_, block) in self.real_blocks() {
+            let address: Address = block.address.into();
+            if address < capacity {
+                values[usize::try_from(address)?].get_or_insert(block.value);
+            }
+        }
+
+        self.physical_memory.iter_mut().for_each(Bucket::clear);
+        self.stash.clear();
+        self.pending_evictions.clear();
+
+        let mut positions = Vec::with_capacity(values.len());
+        for (address, value) in (0..capacity).zip(values) {
+            let position = TreeIndex::random_leaf(self.height, rng)?;
+            positions.push(position);
+            let block = PathOramBlock::new(
+                value.unwrap_or_default(),
+                I::from_u64(address)?,
+                I::from_u64(position)?,
+            );
+            self.place_block(&block)?;
+        }
+        Self::write_position_map(&mut self.position_map, &positions, rng)
+    }
+
+    /// Recovers a poisoned ORAM (see [`PathOram::is_poisoned`]), together with its position map recursion,
+    /// by [repairing](PathOram::repair) it and then clearing the poisoned flag.
+    /// Returns the [audit](PathOram::audit) of the ORAM taken before the repair, describing any damage done.
+    /// May also be called on an ORAM that is not poisoned. Not oblivious.
+    ///
+    /// # Errors
+    ///
+    /// If the repair fails, returns its error, and the ORAM stays poisoned.
+    pub fn try_recover<R: Rng + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<AuditReport, OramError> {
+        if let PositionMap::Recursive(block_oram) = &mut self.position_map {
+            block_oram.try_recover(rng)?;
+        }
+        let report = self.audit()?;
+        // A repair that fails partway leaves the ORAM partly rebuilt.
+        self.poisoned = true;
+        self.repair(rng)?;
+        self.poisoned = false;
+        Ok(report)
+    }
+
+    /// Returns a rendering of the entire state of this ORAM: its parameters (as printed by `Debug`),
+    /// then every real block in its tree and stash with its location, address, position, and value,
+    /// its pending evictions, and the contents of its position map, recursively. Not oblivious.
+    ///
+    /// The rendering reveals everything the ORAM stores, so must not be logged where its contents are secret.
+    pub fn debug_dump(&self) -> String {
+        let mut dump = String::new();
+        // Writing to a `String` cannot fail.
+        let _ = self.write_dump(&mut dump);
+        dump
+    }
+
+    fn write_dump(&self, dump: &mut String) -> core::fmt::Result {
+        writeln!(dump, "{self:?}")?;
+        for (node, bucket) in self.physical_memory.iter().enumerate().skip(1) {
+            for (slot, block) in bucket.blocks.iter().enumerate() {
+                if !bool::from(block.ct_is_dummy()) {
+                    writeln!(dump, "bucket {node} slot {slot}: {}", render(block))?;
+                }
+            }
+        }
+        for (slot, block) in self.stash.blocks().iter().enumerate() {
+            if !bool::from(block.ct_is_dummy()) {
+                writeln!(dump, "stash slot {slot}: {}", render(block))?;
+            }
+        }
+        writeln!(dump, "pending evictions: {:?}", self.pending_evictions)?;
+        match &self.position_map {
+            PositionMap::Base(linear_oram) => {
+                for (index, block) in linear_oram.physical_memory.iter().enumerate() {
+                    writeln!(dump, "position block {index}: {:?}", block.data)?;
+                }
+            }
+            PositionMap::Recursive(block_oram) => {
+                writeln!(dump, "position map:")?;
+                block_oram.write_dump(dump)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of addresses assigned each leaf of the tree, from the leftmost leaf to the rightmost. Not oblivious.
+    ///
+    /// The counts depend on which addresses have been accessed, and over a short burst of accesses reveal their leaves,
+    /// so must not be exported where the access pattern is secret. See the [`pool`](crate::pool) module for counts that may be.
+    pub fn leaf_loads(&self) -> Result<Vec<u64>, OramError> {
+        let first_leaf: TreeIndex = 1 << self.height;
+        let mut loads = vec![0; usize::try_from(first_leaf)?];
+        let capacity = usize::try_from(self.block_capacity()?)?;
+        for position in &self.position_map.positions()?[..capacity] {
+            loads[usize::try_from(position - first_leaf)?] += 1;
+        }
+        Ok(loads)
+    }
+
+    // Places `block` in the deepest bucket with a free slot on the path to its position, or in the stash if there is none.
+    fn place_block(&mut self, block: &PathOramBlock<V, I>) -> Result<(), OramError> {
+        let position: TreeIndex = block.position.into();
+        for depth in (0..=self.height).rev() {
+            let node = usize::try_from(position.ct_node_on_path(depth, self.height))?;
+            let free_slot = self.physical_memory[node]
+                .blocks
+                .iter_mut()
+                .find(|slot| bool::from(slot.ct_is_dummy()));
+            if let Some(slot) = free_slot {
+                *slot = *block;
+                return Ok(());
+            }
+        }
+        self.stash.insert_block(block)
+    }
+
+    // Returns every real block in the ORAM, those in the stash first and then those in the tree from the root down.
+    fn real_blocks(&self) -> impl Iterator<Item = (Location, PathOramBlock<V, I>)> + '_ {
+        let stash_blocks = self
+            .stash
+            .overflow_blocks()
+            .map(|block| (Location::Stash, block));
+        let tree_blocks =
+            self.physical_memory
+                .iter()
+                .enumerate()
+                .skip(1)
+                .flat_map(|(node, bucket)| {
+                    let node = Location::Bucket(node as TreeIndex);
+                    bucket.blocks.iter().map(move |block| (node, *block))
+                });
+        stash_blocks
+            .chain(tree_blocks)
+            .filter(|(_, block)| !bool::from(block.ct_is_dummy()))
+    }
+
+    // Returns the value stored at every address, or the default value for addresses with no block.
+    // Of several blocks with the same address, the first returned by `real_blocks` is used. Not oblivious.
+    pub(crate) fn contents(&self) -> Result<Vec<V>, OramError> {
+        let capacity = self.block_capacity()?;
+        let mut values: Vec<Option<V>> = vec![None; usize::try_from(capacity)?];
+        for (_, block) in self.real_blocks() {
+            let address: Address = block.address.into();
+            if address < capacity {
+                values[usize::try_from(address)?].get_or_insert(block.value);
+            }
+        }
+        Ok(values.into_iter().map(Option::unwrap_or_default).collect())
+    }
+}
+
+// Renders the fields of a real block.
+fn render<V: OramBlock, I: IndexWord>(block: &PathOramBlock<V, I>) -> String {
+    let address: Address = block.address.into();
+    let position: TreeIndex = block.position.into();
+    alloc::format!(
+        "address {address}, position {position}, value {:?}",
+        block.value
+    )
+}
+
+impl<const AB: BlockSize, const Z: BucketSize> PositionMap<AB, Z> {
+    // Returns the position recorded for every address, including any padding after the last address. Not oblivious.
+    fn positions(&self) -> Result<Vec<TreeIndex>, OramError> {
+        let blocks = match self {
+            PositionMap::Base(linear_oram) => linear_oram.physical_memory.clone(),
+            PositionMap::Recursive(block_oram) => block_oram.contents()?,
+        };
+        Ok(blocks.iter().flat_map(|block| block.data).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bucket::BlockValue, test_utils::random_workload};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::BTreeMap;
+
+    type TestOram = PathOram<BlockValue<8>, 4, 8>;
+
+    // Returns an ORAM of capacity 64 with a recursive position map, in which address `i` holds the value `[i; 8]`.
+    fn filled_oram(rng: &mut StdRng) -> TestOram {
+        let mut oram = TestOram::new_with_parameters(64, rng, 40, 1).unwrap();
+        for address in 0..64 {
+            oram.write(address, BlockValue::new([address as u8; 8]), rng)
+                .unwrap();
+        }
+        oram
+    }
+
+    // Returns the tree index and slot of some real block stored in a leaf bucket of `oram`.
+    fn some_leaf_block(oram: &TestOram) -> (usize, usize) {
+        let first_leaf = 1 << oram.height;
+        (first_leaf..oram.physical_memory.len())
+            .flat_map(|node| (0..4).map(move |slot| (node, slot)))
+            .find(|(node, slot)| {
+                !bool::from(oram.physical_memory[*node].blocks[*slot].ct_is_dummy())
+            })
+            .unwrap()
+    }
+
+    // Checks that every address of `oram` holds `[i; 8]`, except those in `reset`, which hold the default value.
+    fn check_contents(oram: &mut TestOram, reset: &[Address], rng: &mut StdRng) {
+        for address in 0..64 {
+            let expected = if reset.contains(&address) {
+                BlockValue::default()
+            } else {
+                BlockValue::new([address as u8; 8])
+            };
+            assert_eq!(oram.read(address, rng).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn debug_output_redacts_contents_but_debug_dump_shows_them() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        // Distinctive values, whose renderings cannot be mistaken for a parameter or counter.
+        let values: Vec<u64> = (0..64).map(|i| 0xdead_beef_0000 + i).collect();
+        for (address, value) in values.iter().enumerate() {
+            oram.write(address as Address, *value, &mut rng).unwrap();
+        }
+
+        let debug = format!("{oram:?}");
+        assert!(debug.contains("block_capacity: 64"));
+        let dump = oram.debug_dump();
+        assert!(dump.starts_with(&debug));
+        for value in &values {
+            assert!(!debug.contains(&value.to_string()));
+            assert!(dump.contains(&format!("value {value}")));
+        }
+        for address in 0..64 {
+            assert!(dump.contains(&format!("address {address},")));
+        }
+        assert!(dump.contains("position map:"));
+
+        let bucket = oram.physical_memory[1];
+        assert_eq!(format!("{bucket:?}"), "Bucket { blocks_per_bucket: 4, .. }");
+        assert_eq!(format!("{:?}", bucket.blocks[0]), "PathOramBlock { .. }");
+    }
+
+    #[test]
+    fn audit_consistent_oram() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = filled_oram(&mut rng);
+        assert_eq!(oram.audit().unwrap(), AuditReport::default());
+
+        // With deferred evictions, blocks awaiting eviction are found in the stash.
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_max_pending_evictions(4).unwrap();
+        random_workload(&mut oram, 200);
+        assert_eq!(oram.pending_evictions(), 4);
+        assert!(oram.audit().unwrap().is_consistent());
+    }
+
+    #[test]
+    fn audit_detects_and_repair_fixes_corruption() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = filled_oram(&mut rng);
+
+        // Lose a block.
+        let (node, slot) = some_leaf_block(&oram);
+        let lost = oram.physical_memory[node].blocks[slot];
+        oram.physical_memory[node].blocks[slot] = PathOramBlock::dummy();
+
+        // Move a block to the leaf on the other side of the tree, off the path to its position.
+        let (node, slot) = some_leaf_block(&oram);
+        let moved = oram.physical_memory[node].blocks[slot];
+        let mirrored_node = 3 * (1 << oram.height) - 1 - node;
+        let free_slot = (0..4)
+            .find(|s| bool::from(oram.physical_memory[mirrored_node].blocks[*s].ct_is_dummy()))
+            .unwrap();
+        oram.physical_memory[node].blocks[slot] = PathOramBlock::dummy();
+        oram.physical_memory[mirrored_node].blocks[free_slot] = moved;
+
+        // Copy a block into the stash, and corrupt a block's stored position.
+        let (node, slot) = some_leaf_block(&oram);
+        let copied = oram.physical_memory[node].blocks[slot];
+        oram.stash.insert_block(&copied).unwrap();
+        let (node, slot) = some_leaf_block(&oram);
+        let block = &mut oram.physical_memory[node].blocks[slot];
+        block.position ^= 1;
+        let mismatched = block.address;
+
+        let report = oram.audit().unwrap();
+        assert_eq!(report.missing, vec![lost.address]);
+        assert_eq!(report.orphans, vec![moved.address]);
+        assert_eq!(report.duplicates, vec![copied.address]);
+        assert_eq!(report.mismatches, vec![mismatched]);
+        assert!(!report.is_consistent());
+
+        oram.repair(&mut rng).unwrap();
+        assert_eq!(oram.audit().unwrap(), AuditReport::default());
+        check_contents(&mut oram, &[lost.address], &mut rng);
+        assert!(oram.audit().unwrap().is_consistent());
+    }
+
+    #[test]
+    fn repair_consistent_oram_preserves_contents() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = filled_oram(&mut rng);
+        oram.repair(&mut rng).unwrap();
+        assert!(oram.audit().unwrap().is_consistent());
+        check_contents(&mut oram, &[], &mut rng);
+    }
+
+    #[test]
+    fn failed_eviction_poisons_until_recovery() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 0, 1).unwrap();
+
+        // An out-of-bounds access fails without poisoning the ORAM.
+        assert!(matches!(
+            oram.read(64, &mut rng),
+            Err(OramError::AddressOutOfBoundsError { .. })
+        ));
+        assert!(!oram.is_poisoned());
+
+        // Without an overflow, and with no room in the budget to grow the stash, an eviction soon fails partway.
+        let footprint = oram.memory_footprint().unwrap();
+        oram.set_memory_budget(footprint).unwrap();
+        let mut shadow = [BlockValue::default(); 64];
+        let mut failed_write = None;
+        for i in 0..1000u64 {
+            let address = i % 64;
+            let value = BlockValue::new([i as u8; 8]);
+            match oram.write(address, value, &mut rng) {
+                Ok(_) => shadow[address as usize] = value,
+                Err(error) => {
+                    assert!(matches!(error, OramError::MemoryBudgetExceeded { .. }));
+                    failed_write = Some((address, value));
+                    break;
+                }
+            }
+        }
+        let (failed_address, failed_value) = failed_write.unwrap();
+        assert!(oram.is_poisoned());
+
+        // Every further operation is refused, even one that would otherwise succeed.
+        for _ in 0..2 {
+            assert!(matches!(oram.read(0, &mut rng), Err(OramError::Poisoned)));
+        }
+        assert!(matches!(oram.evict_one(), Err(OramError::Poisoned)));
+        assert!(matches!(oram.freeze(&mut rng), Err(OramError::Poisoned)));
+
+        oram.set_memory_budget(u64::MAX).unwrap();
+        oram.try_recover(&mut rng).unwrap();
+        assert!(!oram.is_poisoned());
+        assert!(oram.audit().unwrap().is_consistent());
+
+        // No other block was lost, and the failed write either took effect or did not.
+        for (address, expected) in (0..64).zip(shadow) {
+            let value = oram.read(address, &mut rng).unwrap();
+            if address == failed_address {
+                assert!(value == expected || value == failed_value);
+            } else {
+                assert_eq!(value, expected);
+            }
+        }
+        oram.write(failed_address, failed_value, &mut rng).unwrap();
+        assert_eq!(oram.read(failed_address, &mut rng).unwrap(), failed_value);
+    }
+
+    // Returns the chi-square statistic of `counts` against the probabilities `expected`.
+    fn chi_square(counts: &[u32], expected: &[f64]) -> f64 {
+        let trials: u32 = counts.iter().sum();
+        counts
+            .iter()
+            .zip(expected)
+            .map(|(count, p)| {
+                let expected_count = f64::from(trials) * p;
+                (f64::from(*count) - expected_count).powi(2) / expected_count
+            })
+            .sum()
+    }
+
+    #[test]
+    fn initial_leaves_are_assigned_in_pairs() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (capacity, recursion_cutoff) in [(16, 16384), (256, 1)] {
+            let oram =
+                TestOram::new_with_parameters(capacity, &mut rng, 40, recursion_cutoff).unwrap();
+            assert!(oram.leaf_loads().unwrap().iter().all(|load| *load == 2));
+        }
+    }
+
+    #[test]
+    fn initial_leaf_assignment_is_uniform() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // With capacity 4, there are 6 ways to assign two of the addresses to the first leaf and two to the second.
+        // The 0.1% critical value of the chi-square distribution with 5 degrees of freedom is 20.515.
+        let mut assignments = BTreeMap::new();
+        for _ in 0..6000 {
+            let oram = TestOram::new_with_parameters(4, &mut rng, 40, 16384).unwrap();
+            let positions = oram.position_map.positions().unwrap()[..4].to_vec();
+            *assignments.entry(positions).or_insert(0) += 1;
+        }
+        assert_eq!(assignments.len(), 6);
+        let counts: Vec<u32> = assignments.into_values().collect();
+        let statistic = chi_square(&counts, &[1.0 / 6.0; 6]);
+        assert!(statistic < 20.515, "chi-square = {statistic}");
+
+        // With capacity 16, the initial leaf of each address is uniform over the 8 leaves.
+        // The 0.1% critical value with 7 degrees of freedom is 24.322.
+        let mut counts = vec![vec![0; 8]; 16];
+        for _ in 0..1000 {
+            let oram = TestOram::new_with_parameters(16, &mut rng, 40, 16384).unwrap();
+            for (address, position) in oram.position_map.positions().unwrap()[..16]
+                .iter()
+                .enumerate()
+            {
+                counts[address][(position - 8) as usize] += 1;
+            }
+        }
+        for address_counts in counts {
+            let statistic = chi_square(&address_counts, &[1.0 / 8.0; 8]);
+            assert!(statistic < 24.322, "chi-square = {statistic}");
+        }
+    }
+
+    #[test]
+    fn leaf_loads_become_binomial_once_every_address_is_accessed() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // Once each of 16 addresses has a fresh leaf, the number assigned each of the 8 leaves is Binomial(16, 1/8).
+        // Count leaves with loads 0, 1, 2, 3, and more than 3. The 0.1% critical value with 4 degrees of freedom is 18.467.
+        let binomial = |k: i32| {
+            let choose: f64 = (0..k)
+                .map(|i| f64::from(16 - i) / f64::from(i + 1))
+                .product();
+            choose * (1.0f64 / 8.0).powi(k) * (7.0f64 / 8.0).powi(16 - k)
+        };
+        let mut expected: Vec<f64> = (0..4).map(binomial).collect();
+        expected.push(1.0 - expected.iter().sum::<f64>());
+
+        let mut counts = [0u32; 5];
+        let mut pairs = 0;
+        for _ in 0..500 {
+            let mut oram = TestOram::new_with_parameters(16, &mut rng, 40, 16384).unwrap();
+            for address in 0..16 {
+                oram.read(address, &mut rng).unwrap();
+            }
+            for load in oram.leaf_loads().unwrap() {
+                counts[load.min(4) as usize] += 1;
+            }
+            pairs += u32::from(oram.leaf_loads().unwrap().iter().all(|load| *load == 2));
+        }
+        let statistic = chi_square(&counts, &expected);
+        assert!(statistic < 18.467, "chi-square = {statistic}");
+        // Every leaf holding exactly two addresses is now rare (with probability 16! / (2^8 8^16), about 3e-7).
+        assert_eq!(pairs, 0);
+    }
+}
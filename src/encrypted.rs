@@ -0,0 +1,314 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An adapter keeping the values of an ORAM encrypted even inside its buckets and stash,
+//! as defense in depth against an adversary who can read (or modify) the ORAM's memory.
+//!
+//! An [`EncryptedOram`] stores each plaintext `BlockValue<P>` in its underlying ORAM as a `BlockValue<N>` holding,
+//! in order, the 8-byte little-endian nonce it was encrypted under, the authentication tag, and the ciphertext,
+//! so `N` must equal `P + 8 + C::TAG_SIZE` for a [`BlockCipher`] `C` (which is checked at compile time).
+//! Values are decrypted only transiently, inside the access callback.
+//!
+//! Nonces are drawn from a counter incremented on every access (each of which rewrites the accessed block),
+//! so a key must never be shared between `EncryptedOram`s, nor reused after one is dropped.
+//! Once the counter reaches `u64::MAX`, every access fails with a `NoncesExhausted` error.
+//!
+//! A block that has never been written is all zeroes, and is read as the default plaintext without authentication.
+//! An adversary who can modify memory can therefore reset any block to the default value undetected.
+
+use crate::{Address, BlockSize, BlockValue, Oram, OramError};
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// An authenticated cipher with which an [`EncryptedOram`] encrypts its blocks.
+/// Both methods must take time independent of the data they are given.
+pub trait BlockCipher {
+    /// The size in bytes of an authentication tag.
+    const TAG_SIZE: usize;
+
+    /// Encrypts `data` in place under `nonce`, and writes an authentication tag for it to `tag`.
+    /// Assumes that `tag.len() == Self::TAG_SIZE`.
+    fn encrypt(&self, nonce: u64, data: &mut [u8], tag: &mut [u8]);
+
+    /// Decrypts `data` in place under `nonce`, and returns whether `tag` authenticates it.
+    /// Assumes that `tag.len() == Self::TAG_SIZE`.
+    fn decrypt(&self, nonce: u64, data: &mut [u8], tag: &[u8]) -> Choice;
+}
+
+const NONCE_SIZE: usize = 8;
+
+/// An ORAM of `BlockValue<P>` plaintexts, stored encrypted with `C` as `BlockValue<N>` ciphertexts in an ORAM `O`.
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct EncryptedOram<const P: BlockSize, const N: BlockSize, O, C>
+where
+    O: Oram<V = BlockValue<N>>,
+    C: BlockCipher,
+{
+    oram: O,
+    cipher: C,
+    access_count: u64,
+}
+
+impl<const P: BlockSize, const N: BlockSize, O, C> EncryptedOram<P, N, O, C>
+where
+    O: Oram<V = BlockValue<N>>,
+    C: BlockCipher,
+{
+    const VALID_SIZES: () = assert!(
+        N == P + NONCE_SIZE + C::TAG_SIZE,
+        "the ciphertext block size N must be the plaintext block size P plus 8 plus the tag size"
+    );
+
+    /// Returns an `EncryptedOram` storing its blocks in `oram`, encrypted with `cipher`.
+    /// `oram` must not have been written to, and `cipher` must use a fresh key.
+    pub fn new(oram: O, cipher: C) -> Self {
+        let () = Self::VALID_SIZES;
+        Self {
+            oram,
+            cipher,
+            access_count: 0,
+        }
+    }
+
+    /// Returns a mutable reference to the underlying ORAM of ciphertexts.
+    pub fn inner_mut(&mut self) -> &mut O {
+        &mut self.oram
+    }
+
+    // Decrypts `stored`, returning the plaintext and whether `stored` was authenticated (or was never written).
+    fn open(cipher: &C, stored: &BlockValue<N>) -> (BlockValue<P>, Choice) {
+        let (nonce, rest) = stored.data.split_at(NONCE_SIZE);
+        let (tag, ciphertext) = rest.split_at(C::TAG_SIZE);
+        let nonce = u64::from_le_bytes(nonce.try_into().unwrap());
+
+        let mut plaintext = BlockValue::<P>::default();
+        plaintext.data.copy_from_slice(ciphertext);
+        let authenticated = cipher.decrypt(nonce, &mut plaintext.data, tag);
+
+        let never_written = stored.data.ct_eq(&[0; N]);
+        plaintext.conditional_assign(&BlockValue::default(), never_written);
+        (plaintext, authenticated | never_written)
+    }
+
+    // Encrypts `plaintext` under `nonce`.
+    fn seal(cipher: &C, nonce: u64, plaintext: &BlockValue<P>) -> BlockValue<N> {
+        let mut stored = BlockValue::<N>::default();
+        let (nonce_bytes, rest) = stored.data.split_at_mut(NONCE_SIZE);
+        let (tag, ciphertext) = rest.split_at_mut(C::TAG_SIZE);
+        nonce_bytes.copy_from_slice(&nonce.to_le_bytes());
+        ciphertext.copy_from_slice(&plaintext.data);
+        cipher.encrypt(nonce, ciphertext, tag);
+        stored
+    }
+}
+
+impl<const P: BlockSize, const N: BlockSize, O, C> Oram for EncryptedOram<P, N, O, C>
+where
+    O: Oram<V = BlockValue<N>>,
+    C: BlockCipher,
+{
+    type V = BlockValue<P>;
+
+    fn block_capacity(&self) -> Result<Address, OramError> {
+        self.oram.block_capacity()
+    }
+
+    /// Performs an access as in [`Oram::access`], decrypting the stored value before passing it to `callback`
+    /// and encrypting the result under a fresh nonce.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `IntegrityError` if the stored value fails authentication, in which case it is left unchanged,
+    /// and a `NoncesExhausted` error, without accessing the underlying ORAM, if every nonce has been used.
+    fn access<R: RngCore + CryptoRng, F: Fn(&Self::V) -> Self::V>(
+        &mut self,
+        index: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<Self::V, OramError> {
+        // Nonces start from 1, so that no written block is all zeroes. They must never repeat, so must not wrap.
+        self.access_count = self
+            .access_count
+            .checked_add(1)
+            .ok_or(OramError::NoncesExhausted)?;
+        let nonce = self.access_count;
+        let cipher = &self.cipher;
+
+        let stored = self.oram.access(
+            index,
+            |stored| {
+                let (plaintext, authenticated) = Self::open(cipher, stored);
+                let sealed = Self::seal(cipher, nonce, &callback(&plaintext));
+                // Never re-encrypt a block that failed authentication, so that tampering stays detectable.
+                BlockValue::conditional_select(stored, &sealed, authenticated)
+            },
+            rng,
+        )?;
+
+        let (plaintext, authenticated) = Self::open(cipher, &stored);
+        if !bool::from(authenticated) {
+            return Err(OramError::IntegrityError { address: index });
+        }
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, PathOram};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // A keyed toy cipher for testing, which is NOT secure: a keystream and a tag derived from SplitMix64.
+    struct ToyCipher {
+        key: u64,
+    }
+
+    fn splitmix(mut x: u64) -> u64 {
+        x = x.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+
+    impl ToyCipher {
+        fn apply_keystream(&self, nonce: u64, data: &mut [u8]) {
+            for (i, byte) in data.iter_mut().enumerate() {
+                *byte ^= splitmix(self.key ^ splitmix(nonce) ^ i as u64) as u8;
+            }
+        }
+
+        fn tag(&self, nonce: u64, ciphertext: &[u8]) -> [u8; 8] {
+            let mut state = splitmix(self.key.wrapping_add(nonce));
+            for byte in ciphertext {
+                state = splitmix(state ^ u64::from(*byte));
+            }
+            state.to_le_bytes()
+        }
+    }
+
+    impl BlockCipher for ToyCipher {
+        const TAG_SIZE: usize = 8;
+
+        fn encrypt(&self, nonce: u64, data: &mut [u8], tag: &mut [u8]) {
+            self.apply_keystream(nonce, data);
+            tag.copy_from_slice(&self.tag(nonce, data));
+        }
+
+        fn decrypt(&self, nonce: u64, data: &mut [u8], tag: &[u8]) -> Choice {
+            let authenticated = self.tag(nonce, data).ct_eq(tag);
+            self.apply_keystream(nonce, data);
+            authenticated
+        }
+    }
+
+    type TestOram = EncryptedOram<16, 32, PathOram<BlockValue<32>, 4, 8>, ToyCipher>;
+
+    fn new_test_oram(rng: &mut StdRng) -> TestOram {
+        let oram = PathOram::new_with_parameters(64, rng, 40, 1).unwrap();
+        EncryptedOram::new(oram, ToyCipher { key: rng.gen() })
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_test_oram(&mut rng);
+        let mut expected = [BlockValue::default(); 64];
+        for _ in 0..500 {
+            let address = rng.gen_range(0..64);
+            if rng.gen() {
+                assert_eq!(
+                    oram.read(address, &mut rng).unwrap(),
+                    expected[address as usize]
+                );
+            } else {
+                let value = rng.gen();
+                assert_eq!(
+                    oram.write(address, value, &mut rng).unwrap(),
+                    expected[address as usize]
+                );
+                expected[address as usize] = value;
+            }
+        }
+
+        // Accesses also work on an underlying linear-time ORAM, which runs the callback on every block.
+        let mut oram = EncryptedOram::<16, 32, _, _>::new(
+            LinearTimeOram::new(4).unwrap(),
+            ToyCipher { key: 1 },
+        );
+        let value = BlockValue::new([7; 16]);
+        oram.write(2, value, &mut rng).unwrap();
+        assert_eq!(oram.read(2, &mut rng).unwrap(), value);
+        assert_eq!(oram.read(3, &mut rng).unwrap(), BlockValue::default());
+    }
+
+    #[test]
+    fn tampered_block_fails_authentication() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_test_oram(&mut rng);
+        oram.write(5, BlockValue::new([3; 16]), &mut rng).unwrap();
+
+        let mut stored = oram.inner_mut().read(5, &mut rng).unwrap();
+        stored.data[31] ^= 1;
+        oram.inner_mut().write(5, stored, &mut rng).unwrap();
+
+        for _ in 0..2 {
+            // The tampered block is left as is, so a second read fails too.
+            assert!(matches!(
+                oram.read(5, &mut rng),
+                Err(OramError::IntegrityError { address: 5 })
+            ));
+        }
+        // Other blocks are unaffected.
+        assert_eq!(oram.read(6, &mut rng).unwrap(), BlockValue::default());
+    }
+
+    #[test]
+    fn equal_plaintexts_encrypt_differently() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_test_oram(&mut rng);
+        let value = BlockValue::new([9; 16]);
+
+        oram.write(1, value, &mut rng).unwrap();
+        let first = oram.inner_mut().read(1, &mut rng).unwrap();
+        oram.write(1, value, &mut rng).unwrap();
+        let second = oram.inner_mut().read(1, &mut rng).unwrap();
+        oram.write(2, value, &mut rng).unwrap();
+        let third = oram.inner_mut().read(2, &mut rng).unwrap();
+
+        assert_ne!(first, second);
+        assert_ne!(first, third);
+        assert_ne!(second, third);
+        assert_eq!(oram.read(1, &mut rng).unwrap(), value);
+    }
+
+    #[test]
+    fn exhausted_nonces_are_an_error() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_test_oram(&mut rng);
+        let value = BlockValue::new([4; 16]);
+        oram.access_count = u64::MAX - 2;
+
+        // The last two nonces are still usable.
+        oram.write(1, value, &mut rng).unwrap();
+        assert_eq!(oram.read(1, &mut rng).unwrap(), value);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                oram.write(1, BlockValue::default(), &mut rng),
+                Err(OramError::NoncesExhausted)
+            ));
+        }
+        // The failed accesses left the stored ciphertext, sealed under the last nonce, untouched.
+        let stored = oram.inner_mut().read(1, &mut rng).unwrap();
+        assert_eq!(stored.data[..NONCE_SIZE], u64::MAX.to_le_bytes());
+        let (plaintext, authenticated) = TestOram::open(&oram.cipher, &stored);
+        assert!(bool::from(authenticated));
+        assert_eq!(plaintext, value);
+    }
+}
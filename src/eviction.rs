@@ -0,0 +1,300 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Eviction strategies for the Path ORAM stash.
+//!
+//! When the stash writes a path back to the tree, it must decide which of its blocks go into which
+//! bucket on that path. An [`EvictionStrategy`] makes this decision obliviously, by assigning
+//! each block either to a level of the path or to the stash overflow.
+//! An [`EvictionOrder`] decides which path the stash writes back to.
+
+use crate::{
+    tree::{CompleteBinaryTreeIndex, TreeIndex},
+    utils::IndexWord,
+    BucketSize, OramError,
+};
+use alloc::{vec, vec::Vec};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// The level assignment marking a block that stays in the stash overflow.
+pub const OVERFLOW_LEVEL: TreeIndex = TreeIndex::MAX - 1;
+
+/// The level assignment marking a dummy block that has not (yet) been assigned to a level.
+pub const UNASSIGNED_LEVEL: TreeIndex = TreeIndex::MAX;
+
+/// The paths to which a Path ORAM evicts, set with [`PathOram::set_eviction_order`](crate::PathOram::set_eviction_order).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionOrder {
+    /// Each access reads the whole path to the accessed block's position and evicts to that same path,
+    /// as in the original Path ORAM paper.
+    #[default]
+    ReadPath,
+    /// Each access moves only the accessed block from the path to its position into the stash,
+    /// and evicts to the next path in reverse-lexicographic order (see [`reverse_lexicographic_leaf`](crate::tree::reverse_lexicographic_leaf)),
+    /// which is independent of the accessed address. Every bucket at depth `d` is evicted to exactly once every `2^d` evictions,
+    /// which spreads evictions evenly over the tree and simplifies the analysis of stash occupancy.
+    /// An access reads and writes two paths rather than one.
+    ReverseLexicographic,
+}
+
+/// A strategy for assigning stash blocks to the buckets of an eviction path.
+pub trait EvictionStrategy: core::fmt::Debug {
+    /// Obliviously computes a level assignment for each block in the stash.
+    ///
+    /// `block_positions` holds the position of each stash block, with position 0 denoting a dummy block.
+    /// `path` is the leaf identifying the eviction path, and `bucket_size` is the number of blocks per bucket.
+    ///
+    /// The returned vector has one entry per block. Each real block must be assigned either
+    /// to a level `l` such that the path to its position and `path` share the node at depth `l`,
+    /// or to `OVERFLOW_LEVEL`. Each dummy block must be assigned `UNASSIGNED_LEVEL`.
+    /// No more than `bucket_size` blocks may be assigned to any level.
+    /// The memory access pattern must not depend on `block_positions`.
+    fn assign_levels<I: IndexWord>(
+        block_positions: &[I],
+        path: TreeIndex,
+        bucket_size: BucketSize,
+    ) -> Result<Vec<TreeIndex>, OramError>;
+}
+
+/// The eviction strategy of the original Path ORAM paper:
+/// each block is assigned greedily to the deepest non-full bucket it can legally occupy.
+#[derive(Debug)]
+pub struct GreedyEviction;
+
+impl EvictionStrategy for GreedyEviction {
+    fn assign_levels<I: IndexWord>(
+        block_positions: &[I],
+        path: TreeIndex,
+        bucket_size: BucketSize,
+    ) -> Result<Vec<TreeIndex>, OramError> {
+        let height = path.ct_depth();
+        let bucket_size = u64::try_from(bucket_size)?;
+        let mut level_assignments = vec![UNASSIGNED_LEVEL; block_positions.len()];
+        let mut level_counts = vec![0; usize::try_from(height)? + 1];
+
+        for (i, position) in block_positions.iter().enumerate() {
+            let position: TreeIndex = (*position).into();
+
+            // If the block is a dummy, the rest of this loop iteration will be a no-op, and the values don't matter.
+            let block_is_dummy = position.ct_eq(&0);
+
+            // Set up valid but meaningless input to the computation in case the block is a dummy.
+            // (In a tree of height 0, this is the root, which is the only leaf.)
+            let an_arbitrary_leaf: TreeIndex = 1 << height;
+            let block_position =
+                TreeIndex::conditional_select(&position, &an_arbitrary_leaf, block_is_dummy);
+
+            // Assign the block to a bucket or to the overflow.
+            let mut assigned = Choice::from(0);
+            // Obliviously scan through the buckets from leaf to root,
+            // assigning the block to the first empty bucket satisfying the invariant.
+            for (level, count) in level_counts.iter_mut().enumerate().rev() {
+                let level_bucket_full: Choice = count.ct_eq(&bucket_size);
+
+                let level_u64 = u64::try_from(level)?;
+                let level_satisfies_invariant = block_position
+                    .ct_node_on_path(level_u64, height)
+                    .ct_eq(&path.ct_node_on_path(level_u64, height));
+
+                let should_assign = level_satisfies_invariant
+                    & (!level_bucket_full)
+                    & (!block_is_dummy)
+                    & (!assigned);
+                assigned |= should_assign;
+
+                let level_count_incremented = *count + 1;
+                count.conditional_assign(&level_count_incremented, should_assign);
+                level_assignments[i].conditional_assign(&level_u64, should_assign);
+            }
+            // If the block was not able to be assigned to any bucket, assign it to the overflow.
+            level_assignments[i].conditional_assign(&OVERFLOW_LEVEL, !assigned & !block_is_dummy);
+        }
+
+        Ok(level_assignments)
+    }
+}
+
+/// An eviction strategy filling the buckets of the path one at a time from the leaf to the root,
+/// each with up to `bucket_size` of the blocks not yet assigned that can legally occupy it.
+///
+/// The levels a block can occupy always form a prefix of the path (from the root down to where the block's path
+/// leaves the eviction path), so this places exactly as many blocks as [`GreedyEviction`], though not necessarily in the same buckets.
+/// It makes one pass over the blocks per level, rather than one pass over the levels per block.
+#[derive(Debug)]
+pub struct LevelByLevelEviction;
+
+impl EvictionStrategy for LevelByLevelEviction {
+    fn assign_levels<I: IndexWord>(
+        block_positions: &[I],
+        path: TreeIndex,
+        bucket_size: BucketSize,
+    ) -> Result<Vec<TreeIndex>, OramError> {
+        let height = path.ct_depth();
+        let bucket_size = u64::try_from(bucket_size)?;
+        let mut level_assignments = vec![UNASSIGNED_LEVEL; block_positions.len()];
+        let mut assigned = vec![Choice::from(0); block_positions.len()];
+
+        // As in `GreedyEviction`, dummy blocks are given a valid but meaningless position, and are never assigned.
+        let an_arbitrary_leaf: TreeIndex = 1 << height;
+        let blocks: Vec<(TreeIndex, Choice)> = block_positions
+            .iter()
+            .map(|position| {
+                let position: TreeIndex = (*position).into();
+                let block_is_dummy = position.ct_eq(&0);
+                (
+                    TreeIndex::conditional_select(&position, &an_arbitrary_leaf, block_is_dummy),
+                    block_is_dummy,
+                )
+            })
+            .collect();
+
+        // Fill the buckets from leaf to root, assigning to each the first blocks (up to `bucket_size`) that are unassigned
+        // and satisfy the invariant for its level.
+        for level in (0..=height).rev() {
+            let path_node = path.ct_node_on_path(level, height);
+            let mut count = 0u64;
+            for (i, &(block_position, block_is_dummy)) in blocks.iter().enumerate() {
+                let level_satisfies_invariant = block_position
+                    .ct_node_on_path(level, height)
+                    .ct_eq(&path_node);
+
+                let should_assign = level_satisfies_invariant
+                    & (!count.ct_eq(&bucket_size))
+                    & (!block_is_dummy)
+                    & (!assigned[i]);
+                assigned[i] |= should_assign;
+
+                let count_incremented = count + 1;
+                count.conditional_assign(&count_incremented, should_assign);
+                level_assignments[i].conditional_assign(&level, should_assign);
+            }
+        }
+
+        // Blocks that fit in no bucket are assigned to the overflow.
+        for (i, &(_, block_is_dummy)) in blocks.iter().enumerate() {
+            level_assignments[i]
+                .conditional_assign(&OVERFLOW_LEVEL, !assigned[i] & !block_is_dummy);
+        }
+
+        Ok(level_assignments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bucket::BlockValue,
+        test_utils::{random_workload, StashSizeMonitor},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Checks that `E` produces a legal assignment for many random stashes.
+    fn check_assignments_are_legal<E: EvictionStrategy>() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let bucket_size = 4;
+
+        for height in 0..6u64 {
+            for _ in 0..100 {
+                let path = TreeIndex::random_leaf(height, &mut rng).unwrap();
+                let positions: Vec<TreeIndex> = (0..40)
+                    .map(|_| {
+                        if rng.gen::<bool>() {
+                            0
+                        } else {
+                            TreeIndex::random_leaf(height, &mut rng).unwrap()
+                        }
+                    })
+                    .collect();
+
+                let assignments = E::assign_levels(&positions, path, bucket_size).unwrap();
+                assert_eq!(assignments.len(), positions.len());
+
+                let mut level_counts = vec![0; usize::try_from(height).unwrap() + 1];
+                for (position, level) in positions.iter().zip(assignments.iter()) {
+                    if *position == 0 {
+                        assert_eq!(*level, UNASSIGNED_LEVEL);
+                    } else if *level != OVERFLOW_LEVEL {
+                        assert!(*level <= height);
+                        assert_eq!(
+                            position.ct_node_on_path(*level, height),
+                            path.ct_node_on_path(*level, height)
+                        );
+                        level_counts[usize::try_from(*level).unwrap()] += 1;
+                    }
+                }
+                assert!(level_counts.iter().all(|count| *count <= bucket_size));
+            }
+        }
+    }
+
+    // Checks that an ORAM evicting with `E` is correct and keeps its stash small.
+    fn check_stash_size<E: EvictionStrategy>() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for capacity in [2, 16, 64] {
+            let mut oram = StashSizeMonitor::<BlockValue<1>, 4, 8, E>::new_with_parameters(
+                capacity, &mut rng, 40, 1,
+            )
+            .unwrap();
+            random_workload(&mut oram, 1000);
+        }
+    }
+
+    #[test]
+    fn greedy_eviction_assignments_are_legal() {
+        check_assignments_are_legal::<GreedyEviction>();
+    }
+
+    #[test]
+    fn greedy_eviction_stash_size() {
+        check_stash_size::<GreedyEviction>();
+    }
+
+    #[test]
+    fn level_by_level_eviction_assignments_are_legal() {
+        check_assignments_are_legal::<LevelByLevelEviction>();
+    }
+
+    #[test]
+    fn level_by_level_eviction_stash_size() {
+        check_stash_size::<LevelByLevelEviction>();
+    }
+
+    #[test]
+    fn strategies_place_the_same_number_of_blocks() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let placed = |assignments: Vec<TreeIndex>| {
+            assignments
+                .iter()
+                .filter(|level| **level != OVERFLOW_LEVEL && **level != UNASSIGNED_LEVEL)
+                .count()
+        };
+
+        for height in 0..6u64 {
+            for _ in 0..100 {
+                let path = TreeIndex::random_leaf(height, &mut rng).unwrap();
+                // Many more blocks than the path can hold, so that some are left in the overflow.
+                let positions: Vec<TreeIndex> = (0..60)
+                    .map(|_| TreeIndex::random_leaf(height, &mut rng).unwrap())
+                    .collect();
+                assert_eq!(
+                    placed(GreedyEviction::assign_levels(&positions, path, 3).unwrap()),
+                    placed(LevelByLevelEviction::assign_levels(&positions, path, 3).unwrap())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn greedy_eviction_fills_deepest_bucket_first() {
+        // Two blocks destined for the eviction leaf itself should land in the leaf bucket.
+        let height = 3;
+        let path: TreeIndex = 1 << height;
+        let assignments = GreedyEviction::assign_levels(&[path, 0, path], path, 4).unwrap();
+        assert_eq!(assignments, vec![height, UNASSIGNED_LEVEL, height]);
+    }
+}
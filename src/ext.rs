@@ -0,0 +1,190 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Atomic-style read-modify-write operations on ORAMs of integers, such as per-key counters.
+//!
+//! Each operation is a single [`Oram::access`] whose callback computes in constant time,
+//! so it reveals neither the address accessed nor the value stored there.
+//!
+//! For example, a rate limiter admitting at most three requests per client:
+//!
+//! ```
+//! use oram::{ct_cmp::CtOrd, ext::{IntegerOram, Overflow}, DefaultOram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! const LIMIT: u32 = 3;
+//! let mut request_counts = DefaultOram::<u32>::new(64, &mut OsRng)?;
+//!
+//! let client = 5;
+//! let mut admitted = Vec::new();
+//! for _ in 0..5 {
+//!     // Saturating, so that a client cannot wrap its count around to zero.
+//!     let previous_count = request_counts.fetch_add(client, 1, Overflow::Saturating, &mut OsRng)?;
+//!     admitted.push(bool::from(previous_count.ct_lt(&LIMIT)));
+//! }
+//! assert_eq!(admitted, [true, true, true, false, false]);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    ct_cmp::{ct_max, ct_min, CtOrd},
+    Address, Oram, OramBlock, OramError,
+};
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConditionallySelectable};
+
+/// The behavior of [`IntegerOram::fetch_add`] on overflow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wrap around at the boundary of the type.
+    Wrapping,
+    /// Saturate at the numeric bounds of the type.
+    Saturating,
+}
+
+/// An integer `OramBlock` supporting constant-time addition.
+pub trait CtInteger: OramBlock + CtOrd {
+    /// Returns `self + other`, with overflow handled as specified by `overflow`.
+    /// Takes time independent of `self` and `other` (but not of `overflow`).
+    fn ct_add(&self, other: &Self, overflow: Overflow) -> Self;
+}
+
+macro_rules! impl_ct_integer {
+    ($($t: ty),*) => {
+        $(
+            impl CtInteger for $t {
+                fn ct_add(&self, other: &Self, overflow: Overflow) -> Self {
+                    let (sum, overflowed) = self.overflowing_add(*other);
+                    match overflow {
+                        Overflow::Wrapping => sum,
+                        Overflow::Saturating => {
+                            // Adding a negative number can only overflow below `MIN`,
+                            // and adding a nonnegative one only above `MAX`.
+                            let bound =
+                                <$t>::conditional_select(&<$t>::MAX, &<$t>::MIN, other.ct_lt(&0));
+                            let overflowed = Choice::from(u8::from(overflowed));
+                            <$t>::conditional_select(&sum, &bound, overflowed)
+                        }
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_ct_integer!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Read-modify-write operations on ORAMs storing integers. Implemented for every such [`Oram`].
+pub trait IntegerOram: Oram
+where
+    Self::V: CtInteger,
+{
+    /// Obliviously adds `delta` to the value stored at `index`, with overflow handled as specified by `overflow`.
+    /// Returns the previous value.
+    fn fetch_add<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        delta: Self::V,
+        overflow: Overflow,
+        rng: &mut R,
+    ) -> Result<Self::V, OramError> {
+        self.access(index, |value| value.ct_add(&delta, overflow), rng)
+    }
+
+    /// Obliviously replaces the value stored at `index` with its maximum with `value`. Returns the previous value.
+    fn fetch_max<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        value: Self::V,
+        rng: &mut R,
+    ) -> Result<Self::V, OramError> {
+        self.access(index, |stored| ct_max(stored, &value), rng)
+    }
+
+    /// Obliviously replaces the value stored at `index` with its minimum with `value`. Returns the previous value.
+    fn fetch_min<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        value: Self::V,
+        rng: &mut R,
+    ) -> Result<Self::V, OramError> {
+        self.access(index, |stored| ct_min(stored, &value), rng)
+    }
+}
+
+impl<O: Oram> IntegerOram for O where O::V: CtInteger {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, PathOram};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn ct_add_exhaustive_u8_i8() {
+        for a in u8::MIN..=u8::MAX {
+            for b in u8::MIN..=u8::MAX {
+                assert_eq!(a.ct_add(&b, Overflow::Wrapping), a.wrapping_add(b));
+                assert_eq!(a.ct_add(&b, Overflow::Saturating), a.saturating_add(b));
+            }
+        }
+        for a in i8::MIN..=i8::MAX {
+            for b in i8::MIN..=i8::MAX {
+                assert_eq!(a.ct_add(&b, Overflow::Wrapping), a.wrapping_add(b));
+                assert_eq!(a.ct_add(&b, Overflow::Saturating), a.saturating_add(b));
+            }
+        }
+    }
+
+    #[test]
+    fn ct_add_boundaries() {
+        assert_eq!(u64::MAX.ct_add(&1, Overflow::Wrapping), 0);
+        assert_eq!(u64::MAX.ct_add(&1, Overflow::Saturating), u64::MAX);
+        assert_eq!(u64::MAX.ct_add(&0, Overflow::Saturating), u64::MAX);
+        assert_eq!(i64::MAX.ct_add(&1, Overflow::Wrapping), i64::MIN);
+        assert_eq!(i64::MAX.ct_add(&1, Overflow::Saturating), i64::MAX);
+        assert_eq!(i64::MIN.ct_add(&-1, Overflow::Wrapping), i64::MAX);
+        assert_eq!(i64::MIN.ct_add(&-1, Overflow::Saturating), i64::MIN);
+        assert_eq!(i64::MIN.ct_add(&i64::MAX, Overflow::Saturating), -1);
+        assert_eq!(u16::MAX.ct_add(&u16::MAX, Overflow::Saturating), u16::MAX);
+        assert_eq!(i32::MAX.ct_add(&i32::MAX, Overflow::Wrapping), -2);
+    }
+
+    fn check_fetch_operations<O: IntegerOram<V = i32>>(oram: &mut O) {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            oram.fetch_add(3, 5, Overflow::Wrapping, &mut rng).unwrap(),
+            0
+        );
+        assert_eq!(oram.fetch_max(3, 2, &mut rng).unwrap(), 5);
+        assert_eq!(oram.fetch_max(3, 9, &mut rng).unwrap(), 5);
+        assert_eq!(oram.fetch_min(3, -4, &mut rng).unwrap(), 9);
+        assert_eq!(oram.fetch_min(3, 0, &mut rng).unwrap(), -4);
+        assert_eq!(
+            oram.fetch_add(3, i32::MIN, Overflow::Saturating, &mut rng)
+                .unwrap(),
+            -4
+        );
+        assert_eq!(
+            oram.fetch_add(3, -1, Overflow::Wrapping, &mut rng).unwrap(),
+            i32::MIN
+        );
+        assert_eq!(oram.read(3, &mut rng).unwrap(), i32::MAX);
+        assert_eq!(oram.read(4, &mut rng).unwrap(), 0);
+    }
+
+    #[test]
+    fn fetch_operations() {
+        let mut rng = StdRng::seed_from_u64(0);
+        check_fetch_operations(&mut LinearTimeOram::<i32>::new(16).unwrap());
+        check_fetch_operations(
+            &mut PathOram::<i32, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap(),
+        );
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A C ABI for embedding a [`DefaultOram`](crate::DefaultOram) in non-Rust applications. Enabled by the `ffi` feature.
+//!
+//! A C header declaring these functions is generated by the build script as `oram.h` in the build output directory.
+//! Each ORAM stores blocks of one of the sizes in [`SUPPORTED_BLOCK_SIZES`],
+//! and owns an `OsRng` from which it draws all of its randomness.
+//! Every function catches panics, which never unwind across the C boundary.
+
+use crate::{byte_oram::ByteOram, Address, OramError};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+pub use crate::byte_oram::SUPPORTED_BLOCK_SIZES;
+
+/// The status codes returned by the C ABI. The error codes mirror the variants of [`OramError`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OramStatus {
+    /// The operation succeeded.
+    Ok = 0,
+    /// See [`OramError::IntegerConversionError`].
+    IntegerConversionError = 1,
+    /// See [`OramError::AddressOutOfBoundsError`].
+    AddressOutOfBoundsError = 2,
+    /// See [`OramError::InvalidConfigurationError`].
+    InvalidConfigurationError = 3,
+    /// A pointer argument was null, or a buffer length did not match the ORAM's block size.
+    InvalidArgument = 4,
+    /// The ORAM panicked. The handle should not be used again, except to free it.
+    Panic = 5,
+    /// See [`OramError::IntegrityError`] and [`OramError::BucketIntegrityError`].
+    IntegrityError = 6,
+    /// See [`OramError::MemoryBudgetExceeded`].
+    MemoryBudgetExceeded = 7,
+    /// See [`OramError::Poisoned`] and [`OramError::ReseedRequired`].
+    Poisoned = 8,
+    /// See [`OramError::AddressCollisionError`].
+    AddressCollisionError = 9,
+    /// See [`OramError::QueueFull`].
+    QueueFull = 10,
+    /// See [`OramError::ProbeSequenceFull`].
+    ProbeSequenceFull = 11,
+    /// See [`OramError::NoncesExhausted`].
+    NoncesExhausted = 12,
+    /// See [`OramError::RngError`].
+    RngError = 13,
+    /// See [`OramError::KeyLimitReached`].
+    KeyLimitReached = 14,
+}
+
+impl From<&OramError> for OramStatus {
+    fn from(error: &OramError) -> Self {
+        match error {
+            OramError::IntegerConversionError(_) => Self::IntegerConversionError,
+            OramError::AddressOutOfBoundsError { .. }
+            | OramError::PartitionAddressOutOfBoundsError { .. } => Self::AddressOutOfBoundsError,
+            OramError::AddressCollisionError { .. } => Self::AddressCollisionError,
+            OramError::InvalidConfigurationError { .. } => Self::InvalidConfigurationError,
+            OramError::IntegrityError { .. } | OramError::BucketIntegrityError { .. } => {
+                Self::IntegrityError
+            }
+            OramError::MemoryBudgetExceeded { .. } => Self::MemoryBudgetExceeded,
+            OramError::Poisoned | OramError::ReseedRequired => Self::Poisoned,
+            OramError::QueueFull { .. } => Self::QueueFull,
+            OramError::ProbeSequenceFull { .. } => Self::ProbeSequenceFull,
+            OramError::NoncesExhausted => Self::NoncesExhausted,
+            OramError::RngError(_) => Self::RngError,
+            OramError::KeyLimitReached { .. } => Self::KeyLimitReached,
+        }
+    }
+}
+
+/// An ORAM created by [`oram_new`], opaque to C callers.
+#[derive(Debug)]
+pub struct OramHandle(ByteOram);
+
+impl OramHandle {
+    fn read(&mut self, address: Address, out: &mut [u8]) -> Result<OramStatus, OramError> {
+        if out.len() != self.0.block_size() {
+            return Ok(OramStatus::InvalidArgument);
+        }
+        self.0.read(address, out)?;
+        Ok(OramStatus::Ok)
+    }
+
+    fn write(&mut self, address: Address, data: &[u8]) -> Result<OramStatus, OramError> {
+        if data.len() != self.0.block_size() {
+            return Ok(OramStatus::InvalidArgument);
+        }
+        self.0.write(address, data)?;
+        Ok(OramStatus::Ok)
+    }
+}
+
+// Runs `operation`, converting errors and panics into status codes.
+fn to_status<F: FnOnce() -> Result<OramStatus, OramError>>(operation: F) -> OramStatus {
+    match catch_unwind(AssertUnwindSafe(operation)) {
+        Ok(Ok(status)) => status,
+        Ok(Err(error)) => OramStatus::from(&error),
+        Err(_) => OramStatus::Panic,
+    }
+}
+
+/// Returns a new ORAM storing `capacity` blocks of `block_size` bytes, initialized to zero,
+/// or null if the parameters are invalid (see [`DefaultOram::new`](crate::DefaultOram::new) and [`SUPPORTED_BLOCK_SIZES`]).
+/// The ORAM must be freed with [`oram_free`].
+#[no_mangle]
+pub extern "C" fn oram_new(capacity: u64, block_size: usize) -> *mut OramHandle {
+    match catch_unwind(|| ByteOram::new(capacity, block_size)) {
+        Ok(Ok(Some(oram))) => Box::into_raw(Box::new(OramHandle(oram))),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Obliviously reads the block at `address` into `out_buf`, which must have length `len` equal to the ORAM's block size.
+///
+/// # Safety
+///
+/// `handle` must be null or a live pointer returned by [`oram_new`],
+/// and `out_buf` must be null or valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn oram_read(
+    handle: *mut OramHandle,
+    address: u64,
+    out_buf: *mut u8,
+    len: usize,
+) -> OramStatus {
+    if handle.is_null() || out_buf.is_null() {
+        return OramStatus::InvalidArgument;
+    }
+    let handle = &mut *handle;
+    let out = std::slice::from_raw_parts_mut(out_buf, len);
+    to_status(|| handle.read(address, out))
+}
+
+/// Obliviously writes the `len` bytes at `buf` to the block at `address`. `len` must equal the ORAM's block size.
+///
+/// # Safety
+///
+/// `handle` must be null or a live pointer returned by [`oram_new`],
+/// and `buf` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn oram_write(
+    handle: *mut OramHandle,
+    address: u64,
+    buf: *const u8,
+    len: usize,
+) -> OramStatus {
+    if handle.is_null() || buf.is_null() {
+        return OramStatus::InvalidArgument;
+    }
+    let handle = &mut *handle;
+    let data = std::slice::from_raw_parts(buf, len);
+    to_status(|| handle.write(address, data))
+}
+
+/// Frees an ORAM returned by [`oram_new`]. Does nothing if `handle` is null.
+///
+/// # Safety
+///
+/// `handle` must be null or a live pointer returned by [`oram_new`], which must not be used afterward.
+#[no_mangle]
+pub unsafe extern "C" fn oram_free(handle: *mut OramHandle) {
+    if !handle.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(handle))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffi_round_trip() {
+        for block_size in SUPPORTED_BLOCK_SIZES {
+            let handle = oram_new(64, block_size);
+            assert!(!handle.is_null());
+
+            let data: Vec<u8> = (0..block_size).map(|i| i as u8).collect();
+            let mut out = vec![0xff; block_size];
+            unsafe {
+                assert_eq!(
+                    oram_read(handle, 3, out.as_mut_ptr(), block_size),
+                    OramStatus::Ok
+                );
+                assert!(out.iter().all(|byte| *byte == 0));
+
+                assert_eq!(
+                    oram_write(handle, 3, data.as_ptr(), block_size),
+                    OramStatus::Ok
+                );
+                assert_eq!(
+                    oram_read(handle, 3, out.as_mut_ptr(), block_size),
+                    OramStatus::Ok
+                );
+                assert_eq!(out, data);
+
+                assert_eq!(
+                    oram_read(handle, 64, out.as_mut_ptr(), block_size),
+                    OramStatus::AddressOutOfBoundsError
+                );
+                assert_eq!(
+                    oram_write(handle, 3, data.as_ptr(), block_size - 1),
+                    OramStatus::InvalidArgument
+                );
+                assert_eq!(
+                    oram_read(std::ptr::null_mut(), 3, out.as_mut_ptr(), block_size),
+                    OramStatus::InvalidArgument
+                );
+                oram_free(handle);
+            }
+        }
+    }
+
+    #[test]
+    fn ffi_rejects_invalid_parameters() {
+        assert!(oram_new(64, 128).is_null());
+        assert!(oram_new(3000, 64).is_null());
+    }
+}
@@ -0,0 +1,543 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An ORAM keyed by sparse 64-bit keys, hashed to the dense addresses of an underlying ORAM.
+//!
+//! A [`HashedAddressOram`] stores each key, with its value, in a [`HashedEntry`] of an underlying ORAM.
+//! The key is mapped by a keyed PRF (by default [`SipHashPrf`]) to a starting address, and may be stored at that address
+//! or any of the `D - 1` addresses after it (wrapping around), for a probe depth `D` fixed at construction.
+//! Every [`get`](HashedAddressOram::get), [`insert`](HashedAddressOram::insert), and [`remove`](HashedAddressOram::remove),
+//! whether or not it finds its key, makes exactly `D` accesses to the underlying ORAM, one to each of those addresses,
+//! and selects the matching entry with `subtle`'s `Choice`, so the operations are indistinguishable from one another.
+//! The PRF key hides the starting addresses, and so which keys collide, from anyone who does not hold it.
+//!
+//! An insert of a new key fails, with a `KeyLimitReached` error and without changing the ORAM,
+//! if the map already holds the maximum number of keys it was constructed with.
+//! It also fails, with a `ProbeSequenceFull` error and without changing the ORAM, if all `D` of its addresses
+//! hold other keys. The error itself reveals that they did, so the capacity should be chosen large enough that it is rare:
+//! with linear probing, the chance that an insert finds its `D` addresses full falls roughly as `(a e^(1 - a))^D`
+//! at a load factor `a` (keys stored over capacity). At a load factor of 1/2, a probe depth of 64 makes it about 1 in 250,000;
+//! at 1/4, a probe depth of 32 makes it about 1 in a billion.
+//!
+//! ```
+//! use oram::{hashed::{HashedAddressOram, HashedEntry, SipHashPrf}, DefaultOram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let oram = DefaultOram::<HashedEntry<u64>>::new(64, &mut rng)?;
+//! let mut map = HashedAddressOram::new(oram, SipHashPrf::random(&mut rng), 16, 16)?;
+//! assert_eq!(map.insert(0x9e37_79b9_7f4a_7c15, 42, &mut rng)?, None);
+//! assert_eq!(map.get(0x9e37_79b9_7f4a_7c15, &mut rng)?, Some(42));
+//! assert_eq!(map.remove(0x9e37_79b9_7f4a_7c15, &mut rng)?, Some(42));
+//! assert_eq!(map.get(0x9e37_79b9_7f4a_7c15, &mut rng)?, None);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{checksum::Checksum, Address, Oram, OramBlock, OramError};
+use alloc::{format, string::ToString};
+use core::fmt;
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+
+/// A keyed pseudorandom function from keys to hashes, with which a [`HashedAddressOram`] derives the starting address of a key.
+pub trait KeyedPrf {
+    /// Returns the hash of `key`. Must take time independent of `key`.
+    fn evaluate(&self, key: u64) -> u64;
+}
+
+/// SipHash-2-4 under a 128-bit key, as a [`KeyedPrf`]. Built from additions, rotations, and XORs, so takes constant time.
+#[derive(Clone)]
+pub struct SipHashPrf {
+    k0: u64,
+    k1: u64,
+}
+
+impl SipHashPrf {
+    /// Returns SipHash-2-4 under `key`.
+    pub fn new(key: [u8; 16]) -> Self {
+        let (k0, k1) = key.split_at(8);
+        let word = |bytes: &[u8]| {
+            let mut word = [0; 8];
+            word.copy_from_slice(bytes);
+            u64::from_le_bytes(word)
+        };
+        Self {
+            k0: word(k0),
+            k1: word(k1),
+        }
+    }
+
+    /// Returns SipHash-2-4 under a key drawn from `rng`.
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut key = [0; 16];
+        rng.fill_bytes(&mut key);
+        Self::new(key)
+    }
+}
+
+// The key is secret, so is not printed.
+impl fmt::Debug for SipHashPrf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SipHashPrf").finish_non_exhaustive()
+    }
+}
+
+impl KeyedPrf for SipHashPrf {
+    fn evaluate(&self, key: u64) -> u64 {
+        let mut v = [
+            self.k0 ^ 0x736f_6d65_7073_6575,
+            self.k1 ^ 0x646f_7261_6e64_6f6d,
+            self.k0 ^ 0x6c79_6765_6e65_7261,
+            self.k1 ^ 0x7465_6462_7974_6573,
+        ];
+        // The message is the 8 bytes of `key`, followed by the final block holding only its length.
+        for word in [key, 8 << 56] {
+            v[3] ^= word;
+            sip_rounds(&mut v, 2);
+            v[0] ^= word;
+        }
+        v[2] ^= 0xff;
+        sip_rounds(&mut v, 4);
+        v[0] ^ v[1] ^ v[2] ^ v[3]
+    }
+}
+
+fn sip_rounds(v: &mut [u64; 4], rounds: usize) {
+    for _ in 0..rounds {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+}
+
+/// An entry of the ORAM underlying a [`HashedAddressOram`]: a key and its value, or nothing.
+/// The default entry holds nothing, so a newly constructed ORAM holds no keys.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct HashedEntry<V> {
+    key: u64,
+    value: V,
+    // 1 if the entry holds a key, and 0 otherwise.
+    occupied: u8,
+}
+
+impl<V> HashedEntry<V> {
+    fn ct_holds(&self, key: u64) -> Choice {
+        self.ct_is_occupied() & self.key.ct_eq(&key)
+    }
+
+    fn ct_is_occupied(&self) -> Choice {
+        self.occupied.ct_eq(&1)
+    }
+}
+
+// An entry's key and value, and even whether it holds them, are secret, so are not printed.
+impl<V> fmt::Debug for HashedEntry<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashedEntry").finish_non_exhaustive()
+    }
+}
+
+impl<V: OramBlock> ConditionallySelectable for HashedEntry<V> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self {
+            key: u64::conditional_select(&a.key, &b.key, choice),
+            value: V::conditional_select(&a.value, &b.value, choice),
+            occupied: u8::conditional_select(&a.occupied, &b.occupied, choice),
+        }
+    }
+}
+
+impl<V: OramBlock> OramBlock for HashedEntry<V> {
+    fn update_checksum(&self, checksum: &mut Checksum) {
+        checksum.update(&self.key.to_le_bytes());
+        checksum.update(&[self.occupied]);
+        self.value.update_checksum(checksum);
+    }
+}
+
+/// A map from 64-bit keys to values of type `V`, stored in an ORAM `O` of [`HashedEntry`]s at addresses derived from the keys
+/// by the PRF `P`. See the [module documentation](self).
+#[derive(Debug)]
+pub struct HashedAddressOram<V: OramBlock, O: Oram<V = HashedEntry<V>>, P: KeyedPrf = SipHashPrf> {
+    oram: O,
+    prf: P,
+    probe_depth: usize,
+    max_keys: Address,
+    capacity: Address,
+    // The number of keys stored, which is at most `max_keys`.
+    key_count: Address,
+}
+
+impl<V: OramBlock, O: Oram<V = HashedEntry<V>>, P: KeyedPrf> HashedAddressOram<V, O, P> {
+    /// Returns a `HashedAddressOram` storing its keys in `oram`, which must hold only default entries (e.g., be newly constructed),
+    /// at addresses derived by `prf`, probing `probe_depth` addresses per operation.
+    /// `max_keys` is the largest number of keys the map will store, beyond which inserts of new keys fail;
+    /// see the [module documentation](self) for the load factors and probe depths that make a failed insert unlikely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `probe_depth` is 0 or exceeds the capacity of `oram`,
+    /// or if `max_keys` exceeds the capacity.
+    pub fn new(oram: O, prf: P, probe_depth: usize, max_keys: Address) -> Result<Self, OramError> {
+        let capacity = oram.block_capacity()?;
+        if probe_depth == 0 || Address::try_from(probe_depth)? > capacity {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("Probe depth (with capacity {capacity})"),
+                parameter_value: probe_depth.to_string(),
+            });
+        }
+        if max_keys > capacity {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("Maximum number of keys (with capacity {capacity})"),
+                parameter_value: max_keys.to_string(),
+            });
+        }
+        Ok(Self {
+            oram,
+            prf,
+            probe_depth,
+            max_keys,
+            capacity,
+            key_count: 0,
+        })
+    }
+
+    /// Returns the number of addresses probed by every operation.
+    pub fn probe_depth(&self) -> usize {
+        self.probe_depth
+    }
+
+    /// Returns the largest number of keys this map was configured to store.
+    pub fn max_keys(&self) -> Address {
+        self.max_keys
+    }
+
+    /// Returns the number of keys stored.
+    pub fn key_count(&self) -> Address {
+        self.key_count
+    }
+
+    /// Returns the capacity of the underlying ORAM, in entries.
+    pub fn block_capacity(&self) -> Address {
+        self.capacity
+    }
+
+    /// Returns the value stored for `key`, or `None` if there is none.
+    pub fn get<R: RngCore + CryptoRng>(
+        &mut self,
+        key: u64,
+        rng: &mut R,
+    ) -> Result<Option<V>, OramError> {
+        self.probe(key, &V::default(), Operation::Get, rng)
+    }
+
+    /// Stores `value` for `key`, returning the value previously stored for it, or `None` if there was none.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `KeyLimitReached` error, leaving the ORAM unchanged, if `key` has no value stored
+    /// and the map already holds [`max_keys`](HashedAddressOram::max_keys) keys,
+    /// and a `ProbeSequenceFull` error, leaving the ORAM unchanged, if `key` has no value stored
+    /// and every address it may be stored at holds another key.
+    pub fn insert<R: RngCore + CryptoRng>(
+        &mut self,
+        key: u64,
+        value: V,
+        rng: &mut R,
+    ) -> Result<Option<V>, OramError> {
+        self.probe(key, &value, Operation::Insert, rng)
+    }
+
+    /// Removes the value stored for `key`, returning it, or `None` if there was none.
+    pub fn remove<R: RngCore + CryptoRng>(
+        &mut self,
+        key: u64,
+        rng: &mut R,
+    ) -> Result<Option<V>, OramError> {
+        self.probe(key, &V::default(), Operation::Remove, rng)
+    }
+
+    /// Returns a reference to the underlying ORAM of entries.
+    pub fn inner(&self) -> &O {
+        &self.oram
+    }
+
+    /// Returns the underlying ORAM of entries.
+    pub fn into_inner(self) -> O {
+        self.oram
+    }
+
+    // Accesses each of the `probe_depth` addresses at which `key` may be stored, in order, and returns the value stored for it.
+    //
+    // An insert stores the new entry at the first address that holds either `key` or nothing, and empties any later address
+    // holding `key`, so that a key is never stored twice; a remove empties every address holding `key`.
+    // If the map holds `max_keys` keys, an insert may only store the entry at an address holding `key`.
+    fn probe<R: RngCore + CryptoRng>(
+        &mut self,
+        key: u64,
+        value: &V,
+        operation: Operation,
+        rng: &mut R,
+    ) -> Result<Option<V>, OramError> {
+        let insert = Choice::from(u8::from(operation == Operation::Insert));
+        let remove = Choice::from(u8::from(operation == Operation::Remove));
+        let new_entry = HashedEntry {
+            key,
+            value: *value,
+            occupied: 1,
+        };
+
+        // Map the hash to `0..capacity` with a multiplication, rather than a division, whose time may depend on the hash.
+        let hash = self.prf.evaluate(key);
+        let start = Address::try_from((u128::from(hash) * u128::from(self.capacity)) >> 64)?;
+
+        let at_limit = self.key_count.ct_eq(&self.max_keys);
+
+        let mut found = Choice::from(0);
+        let mut placed = Choice::from(0);
+        let mut previous = V::default();
+        for offset in 0..Address::try_from(self.probe_depth)? {
+            let unwrapped = start + offset;
+            let address = Address::conditional_select(
+                &unwrapped,
+                &unwrapped.wrapping_sub(self.capacity),
+                !unwrapped.ct_lt(&self.capacity),
+            );
+            let entry = self.oram.access(
+                address,
+                |entry| {
+                    let matches = entry.ct_holds(key);
+                    let place_here =
+                        insert & !placed & (matches | (!entry.ct_is_occupied() & !at_limit));
+                    let empty_here = matches & (remove | (insert & placed));
+                    let mut result = *entry;
+                    result.conditional_assign(&new_entry, place_here);
+                    result.conditional_assign(&HashedEntry::default(), empty_here);
+                    result
+                },
+                rng,
+            )?;
+            let matches = entry.ct_holds(key);
+            previous.conditional_assign(&entry.value, matches);
+            found |= matches;
+            placed |= insert & (matches | (!entry.ct_is_occupied() & !at_limit));
+        }
+
+        if bool::from(insert & !placed) {
+            if bool::from(at_limit) {
+                return Err(OramError::KeyLimitReached {
+                    max_keys: self.max_keys,
+                });
+            }
+            return Err(OramError::ProbeSequenceFull {
+                key,
+                probe_depth: self.probe_depth,
+            });
+        }
+
+        // Whether the operation added or removed a key is secret, so the count is updated without branching.
+        let added = self.key_count.wrapping_add(1);
+        let removed = self.key_count.wrapping_sub(1);
+        self.key_count.conditional_assign(&added, insert & !found);
+        self.key_count.conditional_assign(&removed, remove & found);
+        Ok(bool::from(found).then_some(previous))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Get,
+    Insert,
+    Remove,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        linear_time_oram::LinearTimeOram, path_oram::DefaultOram, test_utils::CountingOram,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    // A PRF sending every key to the hash `self.0`, so that every key starts at the same address.
+    struct ConstantPrf(u64);
+
+    impl KeyedPrf for ConstantPrf {
+        fn evaluate(&self, _: u64) -> u64 {
+            self.0
+        }
+    }
+
+    // Makes `ops` random operations on `map`, on keys drawn from `keys`, checking each against a `HashMap`.
+    fn check_against_model<O: Oram<V = HashedEntry<u64>>, P: KeyedPrf>(
+        map: &mut HashedAddressOram<u64, O, P>,
+        keys: &[u64],
+        ops: usize,
+        rng: &mut StdRng,
+    ) -> usize {
+        let mut model = HashMap::new();
+        let mut full_inserts = 0;
+        for _ in 0..ops {
+            let key = keys[rng.gen_range(0..keys.len())];
+            match rng.gen_range(0..3) {
+                0 => assert_eq!(map.get(key, rng).unwrap(), model.get(&key).copied()),
+                1 => {
+                    let value = rng.gen();
+                    match map.insert(key, value, rng) {
+                        Ok(previous) => assert_eq!(previous, model.insert(key, value)),
+                        Err(OramError::ProbeSequenceFull {
+                            key: full_key,
+                            probe_depth,
+                        }) => {
+                            // Only new keys can fail to find room.
+                            assert_eq!((full_key, probe_depth), (key, map.probe_depth()));
+                            assert!(!model.contains_key(&key));
+                            full_inserts += 1;
+                        }
+                        Err(error) => panic!("unexpected error {error}"),
+                    }
+                }
+                _ => assert_eq!(map.remove(key, rng).unwrap(), model.remove(&key)),
+            }
+            assert_eq!(map.key_count(), Address::try_from(model.len()).unwrap());
+        }
+        for key in keys {
+            assert_eq!(map.get(*key, rng).unwrap(), model.get(key).copied());
+        }
+        full_inserts
+    }
+
+    #[test]
+    fn matches_hash_map_model() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // Sparse keys, spread over the whole 64-bit range.
+        let keys: Vec<u64> = (0..48).map(|_| rng.gen()).collect();
+        let oram = DefaultOram::<HashedEntry<u64>>::new(128, &mut rng).unwrap();
+        let prf = SipHashPrf::random(&mut rng);
+        let mut map = HashedAddressOram::new(oram, prf, 32, 48).unwrap();
+        assert_eq!(check_against_model(&mut map, &keys, 1500, &mut rng), 0);
+    }
+
+    #[test]
+    fn matches_hash_map_model_under_forced_collisions() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // Every key starts at the last address, so probe sequences wrap around, and only 6 keys fit at a time.
+        let keys: Vec<u64> = (0..10).map(|_| rng.gen()).collect();
+        let oram = LinearTimeOram::<HashedEntry<u64>>::new(16).unwrap();
+        let mut map = HashedAddressOram::new(oram, ConstantPrf(u64::MAX), 6, 16).unwrap();
+        assert!(check_against_model(&mut map, &keys, 2000, &mut rng) > 0);
+    }
+
+    #[test]
+    fn full_probe_sequences_reject_only_new_keys() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let oram = CountingOram::new(LinearTimeOram::<HashedEntry<u64>>::new(16).unwrap());
+        let mut map = HashedAddressOram::new(oram, ConstantPrf(0), 4, 16).unwrap();
+        for key in 10..14 {
+            assert_eq!(map.insert(key, key * 100, &mut rng).unwrap(), None);
+        }
+        assert!(matches!(
+            map.insert(14, 1400, &mut rng),
+            Err(OramError::ProbeSequenceFull {
+                key: 14,
+                probe_depth: 4
+            })
+        ));
+        // Keys already stored can still be updated.
+        assert_eq!(map.insert(13, 1301, &mut rng).unwrap(), Some(1300));
+
+        // Removing the key at the first address frees it, and an update of the key at the last address moves it there.
+        assert_eq!(map.remove(10, &mut rng).unwrap(), Some(1000));
+        assert_eq!(map.insert(13, 1302, &mut rng).unwrap(), Some(1301));
+        assert_eq!(map.insert(14, 1400, &mut rng).unwrap(), None);
+        for (key, value) in [(11, 1100), (12, 1200), (13, 1302), (14, 1400)] {
+            assert_eq!(map.get(key, &mut rng).unwrap(), Some(value));
+        }
+        assert_eq!(map.get(10, &mut rng).unwrap(), None);
+        assert_eq!(map.remove(10, &mut rng).unwrap(), None);
+
+        // Every operation, successful or not, made exactly one access per probe.
+        assert_eq!(map.inner().accesses, 15 * 4);
+        assert!(matches!(
+            HashedAddressOram::new(
+                LinearTimeOram::<HashedEntry<u64>>::new(4).unwrap(),
+                ConstantPrf(0),
+                5,
+                4
+            ),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+    }
+
+    #[test]
+    fn inserts_beyond_max_keys_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let oram = CountingOram::new(LinearTimeOram::<HashedEntry<u64>>::new(16).unwrap());
+        let mut map = HashedAddressOram::new(oram, ConstantPrf(0), 8, 3).unwrap();
+        for key in 10..13 {
+            assert_eq!(map.insert(key, key * 100, &mut rng).unwrap(), None);
+        }
+        assert_eq!(map.key_count(), 3);
+
+        // A new key is rejected, even though its probe sequence has room, but stored keys can still be updated.
+        assert!(matches!(
+            map.insert(13, 1300, &mut rng),
+            Err(OramError::KeyLimitReached { max_keys: 3 })
+        ));
+        assert_eq!(map.get(13, &mut rng).unwrap(), None);
+        assert_eq!(map.insert(12, 1201, &mut rng).unwrap(), Some(1200));
+        assert_eq!(map.key_count(), 3);
+
+        // Removing a key makes room for another, but removing an absent key does not.
+        assert_eq!(map.remove(99, &mut rng).unwrap(), None);
+        assert_eq!(map.key_count(), 3);
+        assert_eq!(map.remove(10, &mut rng).unwrap(), Some(1000));
+        assert_eq!(map.key_count(), 2);
+        assert_eq!(map.insert(13, 1300, &mut rng).unwrap(), None);
+        for (key, value) in [(11, 1100), (12, 1201), (13, 1300)] {
+            assert_eq!(map.get(key, &mut rng).unwrap(), Some(value));
+        }
+
+        // The rejected insert made the same accesses as every other operation.
+        assert_eq!(map.inner().accesses, 12 * 8);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn sip_hash_prf_matches_sip_hasher() {
+        use core::hash::{Hasher, SipHasher};
+
+        let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let prf = SipHashPrf::new(key);
+        // The reference test vector for the 8-byte message 00 01 .. 07.
+        assert_eq!(
+            prf.evaluate(u64::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7])),
+            0x93f5_f579_9a93_2462
+        );
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let (k0, k1, message) = (rng.gen(), rng.gen(), rng.gen::<u64>());
+            let mut key = [0; 16];
+            key[..8].copy_from_slice(&u64::to_le_bytes(k0));
+            key[8..].copy_from_slice(&u64::to_le_bytes(k1));
+            let mut hasher = SipHasher::new_with_keys(k0, k1);
+            hasher.write(&message.to_le_bytes());
+            assert_eq!(SipHashPrf::new(key).evaluate(message), hasher.finish());
+        }
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Streaming import of initial contents into a Path ORAM.
+//!
+//! [`PathOram::new_with_initial_values`] places every value in one oblivious sort, so needs all of the values in memory
+//! alongside the ORAM's tree. [`PathOram::ingest_iter`] and [`PathOram::ingest`] instead take the values one at a time,
+//! in address order, from an iterator or a reader, and store each with an ordinary ORAM write, buffering only one record.
+//! Each record therefore costs an access (`O(log^2 n)` blocks for a capacity of `n`), against `O(log^2 n)` compare-exchanges
+//! per value for the sort, but the memory needed beyond the ORAM itself no longer grows with the dataset.
+//! Like any sequence of writes, ingestion reveals only the number of records.
+//!
+//! ```
+//! use oram::{BlockValue, Oram, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut oram = PathOram::<BlockValue<4>, 4, 8>::new_with_parameters(16, &mut rng, 40, 1)?;
+//! // Records of 4 bytes each, for addresses 0, 1, and 2.
+//! let data: &[u8] = b"zeroone_two_";
+//! let mut ingested = 0;
+//! oram.ingest(data, &mut rng, Some(&mut |count| ingested = count))?;
+//! assert_eq!(ingested, 3);
+//! assert_eq!(oram.read(1, &mut rng)?, BlockValue::new(*b"one_"));
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "std")]
+use crate::BlockValue;
+use crate::{
+    eviction::EvictionStrategy, path_oram::PathOram, utils::IndexWord, Address, BlockSize,
+    BucketSize, Oram, OramBlock, OramError,
+};
+#[cfg(feature = "std")]
+use alloc::{format, string::ToString};
+use rand::{CryptoRng, Rng};
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Writes the values yielded by `records` to addresses 0, 1, 2, and so on, one access per value,
+    /// calling `progress` (if given) with the number of values written after each. Returns the number of values written.
+    /// Addresses beyond the last value keep their contents. See the [`ingest`](crate::ingest) module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError` if `records` yields more values than the capacity,
+    /// after writing as many as fit; and any error from the writes themselves.
+    pub fn ingest_iter<It: IntoIterator<Item = V>, R: Rng + CryptoRng>(
+        &mut self,
+        records: It,
+        rng: &mut R,
+        mut progress: Option<&mut dyn FnMut(Address)>,
+    ) -> Result<Address, OramError> {
+        let mut count: Address = 0;
+        for value in records {
+            self.write_from(count, &value, rng)?;
+            count += 1;
+            if let Some(progress) = progress.as_mut() {
+                progress(count);
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<
+        const B: BlockSize,
+        const Z: BucketSize,
+        const AB: BlockSize,
+        E: EvictionStrategy,
+        I: IndexWord,
+    > PathOram<BlockValue<B>, Z, AB, E, I>
+{
+    /// As [`PathOram::ingest_iter`], but reading the values from `reader` as consecutive records of `B` bytes each,
+    /// up to the end of its data, with at most one record buffered at a time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if reading from `reader` fails, or if its data ends partway through a record,
+    /// after writing every complete record before that point. Otherwise, as [`PathOram::ingest_iter`].
+    pub fn ingest<Rd: std::io::Read, R: Rng + CryptoRng>(
+        &mut self,
+        mut reader: Rd,
+        rng: &mut R,
+        mut progress: Option<&mut dyn FnMut(Address)>,
+    ) -> Result<Address, OramError> {
+        let mut record = BlockValue::<B>::default();
+        let mut count: Address = 0;
+        loop {
+            let mut filled = 0;
+            while filled < B {
+                match reader.read(&mut record.data[filled..]) {
+                    Ok(0) => break,
+                    Ok(read) => filled += read,
+                    Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(error) => {
+                        return Err(OramError::InvalidConfigurationError {
+                            parameter_name: format!("Ingested record {count}"),
+                            parameter_value: error.to_string(),
+                        })
+                    }
+                }
+            }
+            if filled == 0 {
+                return Ok(count);
+            }
+            if filled < B {
+                return Err(OramError::InvalidConfigurationError {
+                    parameter_name: format!(
+                        "Length of ingested record {count} (with block size {B})"
+                    ),
+                    parameter_value: filled.to_string(),
+                });
+            }
+            self.write_from(count, &record, rng)?;
+            count += 1;
+            if let Some(progress) = progress.as_mut() {
+                progress(count);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockValue, PathOram};
+    use rand::{rngs::StdRng, SeedableRng};
+    #[cfg(feature = "std")]
+    use std::io::Read;
+
+    type TestOram = PathOram<BlockValue<8>, 4, 8>;
+
+    // A reader of `data` that records the largest read it was asked for.
+    #[cfg(feature = "std")]
+    struct TrackingReader<'a> {
+        data: &'a [u8],
+        largest_request: usize,
+    }
+
+    #[cfg(feature = "std")]
+    impl Read for TrackingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.largest_request = self.largest_request.max(buf.len());
+            // Return short reads, to check that records are reassembled.
+            let read = buf.len().min(self.data.len()).min(5);
+            buf[..read].copy_from_slice(&self.data[..read]);
+            self.data = &self.data[read..];
+            Ok(read)
+        }
+    }
+
+    fn record(address: Address) -> BlockValue<8> {
+        BlockValue::new((address * 7 + 1).to_le_bytes())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ingest_matches_new_with_initial_values() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = 256;
+        let values: Vec<BlockValue<8>> = (0..capacity).map(record).collect();
+        let data: Vec<u8> = values.iter().flat_map(|value| value.data).collect();
+
+        let mut oram = TestOram::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        let footprint = oram.memory_footprint().unwrap();
+        let mut reader = TrackingReader {
+            data: &data,
+            largest_request: 0,
+        };
+        let mut reported = Vec::new();
+        let count = oram
+            .ingest(
+                &mut reader,
+                &mut rng,
+                Some(&mut |count| reported.push(count)),
+            )
+            .unwrap();
+        assert_eq!(count, capacity);
+        assert_eq!(reported, (1..=capacity).collect::<Vec<_>>());
+        // Only one record is buffered at a time, and the ORAM holds no more memory than before.
+        assert_eq!(reader.largest_request, 8);
+        assert_eq!(oram.memory_footprint().unwrap(), footprint);
+
+        let mut expected = TestOram::new_with_initial_values(&values, &mut rng, 40, 1).unwrap();
+        for address in 0..capacity {
+            assert_eq!(
+                oram.read(address, &mut rng).unwrap(),
+                expected.read(address, &mut rng).unwrap()
+            );
+        }
+        assert!(oram.audit().unwrap().is_consistent());
+    }
+
+    #[test]
+    fn ingest_iter_writes_a_prefix() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.write(50, record(1000), &mut rng).unwrap();
+        let count = oram
+            .ingest_iter((0..40).map(record), &mut rng, None)
+            .unwrap();
+        assert_eq!(count, 40);
+        for address in 0..40 {
+            assert_eq!(oram.read(address, &mut rng).unwrap(), record(address));
+        }
+        assert_eq!(oram.read(50, &mut rng).unwrap(), record(1000));
+        assert_eq!(oram.read(40, &mut rng).unwrap(), BlockValue::default());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ingest_failures_keep_the_records_before_them() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut oram = TestOram::new_with_parameters(4, &mut rng, 40, 1).unwrap();
+
+        // Too many records.
+        assert!(matches!(
+            oram.ingest_iter((0..5).map(record), &mut rng, None),
+            Err(OramError::AddressOutOfBoundsError { .. })
+        ));
+        assert!(!oram.is_poisoned());
+        for address in 0..4 {
+            assert_eq!(oram.read(address, &mut rng).unwrap(), record(address));
+        }
+
+        // A truncated last record.
+        let data: Vec<u8> = (10..12).flat_map(|address| record(address).data).collect();
+        assert!(matches!(
+            oram.ingest(&data[..12], &mut rng, None),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        assert_eq!(oram.read(0, &mut rng).unwrap(), record(10));
+        assert_eq!(oram.read(1, &mut rng).unwrap(), record(1));
+        assert_eq!(oram.ingest(&data[..0], &mut rng, None).unwrap(), 0);
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Pluggable sources of the random choices that a Path ORAM makes about where to place its blocks.
+//!
+//! By default, a [`PathOram`](crate::PathOram) draws the leaf assigned to each accessed block
+//! from the RNG passed to the access. A Path ORAM constructed with
+//! [`PathOram::new_with_leaf_sampler`](crate::PathOram::new_with_leaf_sampler) instead draws both these leaves
+//! and the permutation of blocks at initialization from a [`LeafSampler`],
+//! giving a single point through which all of its placement randomness flows (e.g., to use a mandated DRBG).
+//! The per-call RNG is then used only by a recursive position map, which is a separate Path ORAM.
+
+use crate::{
+    oblivious::random_permutation_of_0_through_n_exclusive,
+    tree::{CompleteBinaryTreeIndex, TreeHeight, TreeIndex},
+    OramError,
+};
+use alloc::{collections::VecDeque, vec::Vec};
+use core::fmt;
+use rand::{CryptoRng, RngCore};
+
+/// A source of the leaves and permutations from which a Path ORAM places its blocks.
+///
+/// For the ORAM to be oblivious, leaves must be uniformly random and independent,
+/// and permutations uniformly random, from the point of view of an adversary.
+pub trait LeafSampler: fmt::Debug + Send + Sync {
+    /// Returns a leaf of a tree of height `height`, i.e., a value in `2^height..2^(height + 1)`.
+    fn sample_leaf(&mut self, height: TreeHeight) -> Result<TreeIndex, OramError>;
+
+    /// Returns a permutation of `0..n`.
+    fn sample_permutation(&mut self, n: u64) -> Vec<u64>;
+}
+
+/// A [`LeafSampler`] drawing from a cryptographically secure RNG `R`.
+#[derive(Debug)]
+pub struct RngLeafSampler<R: RngCore + CryptoRng>(pub R);
+
+impl<R: RngCore + CryptoRng + fmt::Debug + Send + Sync> LeafSampler for RngLeafSampler<R> {
+    fn sample_leaf(&mut self, height: TreeHeight) -> Result<TreeIndex, OramError> {
+        Ok(TreeIndex::random_leaf(height, &mut self.0)?)
+    }
+
+    fn sample_permutation(&mut self, n: u64) -> Vec<u64> {
+        random_permutation_of_0_through_n_exclusive(n, &mut self.0)
+    }
+}
+
+/// A [`LeafSampler`] drawing from a [`ChaCha20Rng`](rand_chacha::ChaCha20Rng). Requires the `seeded` feature.
+#[cfg(feature = "seeded")]
+pub type ChaChaLeafSampler = RngLeafSampler<rand_chacha::ChaCha20Rng>;
+
+#[cfg(feature = "seeded")]
+impl ChaChaLeafSampler {
+    /// Returns a sampler seeded with `seed`, which must be secret and uniformly random for the ORAM to be oblivious.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        use rand::SeedableRng;
+        Self(rand_chacha::ChaCha20Rng::from_seed(seed))
+    }
+}
+
+/// A [`LeafSampler`] returning a fixed script of leaves, and the identity permutation,
+/// for writing deterministic tests of eviction behavior. Not random, and so not oblivious.
+#[derive(Debug, Default)]
+pub struct ScriptedLeafSampler {
+    leaves: VecDeque<TreeIndex>,
+}
+
+impl ScriptedLeafSampler {
+    /// Returns a sampler that will return `leaves` in order.
+    pub fn new<I: IntoIterator<Item = TreeIndex>>(leaves: I) -> Self {
+        Self {
+            leaves: leaves.into_iter().collect(),
+        }
+    }
+
+    /// Appends `leaf` to the script.
+    pub fn push(&mut self, leaf: TreeIndex) {
+        self.leaves.push_back(leaf);
+    }
+}
+
+impl LeafSampler for ScriptedLeafSampler {
+    /// Returns the next leaf in the script.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the script is exhausted, or if its next leaf is not a leaf of a tree of height `height`.
+    fn sample_leaf(&mut self, height: TreeHeight) -> Result<TreeIndex, OramError> {
+        let leaf = self
+            .leaves
+            .pop_front()
+            .expect("the script of leaves is exhausted");
+        assert!(leaf != 0 && leaf.is_leaf(height), "{} is not a leaf", leaf);
+        Ok(leaf)
+    }
+
+    fn sample_permutation(&mut self, n: u64) -> Vec<u64> {
+        (0..n).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bucket::BlockValue, Address, Oram, PathOram};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::vec;
+
+    type TestOram = PathOram<BlockValue<1>, 2, 8>;
+
+    // Returns the (address, position) pairs of the real blocks in bucket `node`.
+    fn bucket_contents(oram: &TestOram, node: usize) -> Vec<(Address, TreeIndex)> {
+        oram.physical_memory[node]
+            .blocks
+            .iter()
+            .filter(|block| !bool::from(block.ct_is_dummy()))
+            .map(|block| (block.address, block.position))
+            .collect()
+    }
+
+    // Returns a Path ORAM of capacity 8, with a tree of height 2 and two blocks per bucket.
+    // With the identity permutation, addresses `2i` and `2i + 1` start in leaf `4 + i`.
+    fn scripted_oram(leaves: Vec<TreeIndex>) -> TestOram {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram =
+            TestOram::new_with_leaf_sampler(8, ScriptedLeafSampler::new(leaves), &mut rng, 40, 1)
+                .unwrap();
+        for leaf in 4..8 {
+            let first_address = 2 * (leaf - 4);
+            assert_eq!(
+                bucket_contents(&oram, leaf as usize),
+                [(first_address, leaf), (first_address + 1, leaf)]
+            );
+        }
+        oram
+    }
+
+    #[test]
+    fn blocks_remapped_across_the_root_collide_there() {
+        // Addresses 0, 2, and 1 are all remapped to leaves sharing only the root with the paths they were read from.
+        let mut oram = scripted_oram(vec![7, 6, 7]);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        oram.read(0, &mut rng).unwrap();
+        assert_eq!(bucket_contents(&oram, 1), [(0, 7)]);
+        oram.read(2, &mut rng).unwrap();
+        let mut root = bucket_contents(&oram, 1);
+        root.sort();
+        assert_eq!(root, [(0, 7), (2, 6)]);
+        assert_eq!(oram.stash_occupancy(), 0);
+
+        // The root is full, so the third block can only stay in the stash.
+        oram.read(1, &mut rng).unwrap();
+        assert_eq!(bucket_contents(&oram, 1).len(), 2);
+        assert_eq!(oram.stash_occupancy(), 1);
+        assert_eq!(bucket_contents(&oram, 4), []);
+    }
+
+    #[test]
+    fn blocks_sink_when_a_path_toward_their_leaves_is_evicted() {
+        // Addresses 0 and 2 collide at the root, and then an access to address 4 evicts the path to leaf 6.
+        let mut oram = scripted_oram(vec![7, 6, 7]);
+        let mut rng = StdRng::seed_from_u64(1);
+        oram.read(0, &mut rng).unwrap();
+        oram.read(2, &mut rng).unwrap();
+
+        oram.read(4, &mut rng).unwrap();
+        // Blocks mapped to leaf 6 sink all the way; blocks mapped to leaf 7 sink to the common ancestor 3.
+        let mut leaf_bucket = bucket_contents(&oram, 6);
+        leaf_bucket.sort();
+        assert_eq!(leaf_bucket, [(2, 6), (5, 6)]);
+        let mut middle_bucket = bucket_contents(&oram, 3);
+        middle_bucket.sort();
+        assert_eq!(middle_bucket, [(0, 7), (4, 7)]);
+        assert_eq!(bucket_contents(&oram, 1), []);
+        assert_eq!(oram.stash_occupancy(), 0);
+    }
+
+    #[test]
+    fn rng_sampler_draws_leaves() {
+        let mut sampler = RngLeafSampler(StdRng::seed_from_u64(0));
+        for _ in 0..100 {
+            assert!(sampler.sample_leaf(3).unwrap().is_leaf(3));
+        }
+        let mut permutation = sampler.sample_permutation(10);
+        permutation.sort();
+        assert_eq!(permutation, (0..10).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "seeded")]
+    #[test]
+    fn chacha_sampler_is_deterministic() {
+        let mut a = ChaChaLeafSampler::from_seed([3; 32]);
+        let mut b = ChaChaLeafSampler::from_seed([3; 32]);
+        for _ in 0..10 {
+            assert_eq!(a.sample_leaf(10).unwrap(), b.sample_leaf(10).unwrap());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "exhausted")]
+    fn exhausted_script_panics() {
+        let mut oram = scripted_oram(vec![4]);
+        let mut rng = StdRng::seed_from_u64(0);
+        oram.read(0, &mut rng).unwrap();
+        oram.read(0, &mut rng).unwrap();
+    }
+}
@@ -55,7 +55,7 @@
 //!
 //! ORAMs can store arbitrary structs implementing `OramBlock`.
 //! We provide implementations of `OramBlock` for `u8`, `u16`, `u32`, `u64`, `i8`, `i16`, `i32`, `i64`,
-//! and `BlockValue<const B: BlockSize>`.
+//! `BlockValue<const B: BlockSize>`, and the zero-sized `Unit`.
 //!
 //! The `DefaultOram` used in the above example should have good performance in most use cases.
 //! But the underlying algorithms have several tunable parameters that impact performance.
@@ -65,8 +65,8 @@
 //! ```
 //! use oram::{Address, BlockSize, BlockValue, BucketSize,
 //!             Oram, PathOram, StashSize, RecursionCutoff};
-//! use oram::path_oram::{DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_RECURSION_CUTOFF,
-//!             DEFAULT_POSITIONS_PER_BLOCK, DEFAULT_STASH_OVERFLOW_SIZE};
+//! use oram::path_oram::{default_overflow_size, DEFAULT_BLOCKS_PER_BUCKET,
+//!             DEFAULT_RECURSION_CUTOFF, DEFAULT_POSITIONS_PER_BLOCK};
 //! # use oram::OramError;
 //! # let mut rng = rand::rngs::OsRng;
 //! # const BLOCK_SIZE: BlockSize = 64;
@@ -75,7 +75,7 @@
 //! const RECURSION_CUTOFF: RecursionCutoff = DEFAULT_RECURSION_CUTOFF;
 //! const BUCKET_SIZE: BucketSize = DEFAULT_BLOCKS_PER_BUCKET;
 //! const POSITIONS_PER_BLOCK: BlockSize = DEFAULT_POSITIONS_PER_BLOCK;
-//! const INITIAL_STASH_OVERFLOW_SIZE: StashSize = DEFAULT_STASH_OVERFLOW_SIZE;
+//! const INITIAL_STASH_OVERFLOW_SIZE: StashSize = default_overflow_size(DB_SIZE, BUCKET_SIZE);
 //!
 //! let mut oram = PathOram::<
 //!     BlockValue<BLOCK_SIZE>,
@@ -86,27 +86,135 @@
 //! ```
 //!
 //! See [`PathOram`] for an explanation of these parameters and their possible settings.
+//!
+//! # Features
+//!
+//! - `std` (enabled by default): use the standard library. Without it, the crate is `no_std` and requires only `alloc`,
+//!   and callers must supply their own cryptographically secure RNG. Reseeding against replayed RNG state
+//!   (see the `reseed` module) requires it, as does the conformance suite of the `testing` module.
+//! - `logging` (enabled by default): log ORAM construction and stash overflows through the `log` crate.
+//!   Without it (or any other non-default feature), the crate depends only on `rand` and `subtle`.
+//! - `ffi`: expose a C ABI (see the `ffi` module).
+//! - `wasm`: expose JavaScript bindings (see the `wasm` module).
+//! - `diagnostics`: check Path ORAMs for, and repair, inconsistencies between their position maps and their trees
+//!   (see the `diagnostics` module), dump their entire contents, which their `Debug` output omits,
+//!   count the addresses assigned each leaf, and read the per-ORAM operation counts of an `OramPool` without rate limiting.
+//!   These checks and dumps are not oblivious.
+//! - `hugepages`: on Linux, back the physical memory of each Path ORAM with transparent huge pages where available.
+//! - `seeded`: construct ORAMs that draw all of their randomness from a seed, to reproduce bugs deterministically
+//!   (see the `seeded` module). Not for production use.
+//! - `soak`: run long seeded workloads against a Path ORAM, checking it periodically (see the `soak` module).
+//! - `checksummed`: store a checksum with every block of a Path ORAM tree, verified whenever its bucket is read,
+//!   to detect memory corruption (see the `checksum` module).
+//! - `timing`: enable a test of the constant-time tree index functions in the `tree` module,
+//!   comparing their running times on extreme and random positions. Affects only tests.
+//! - `serde-values`: store `serde`-serializable records in ORAMs of fixed-size blocks (see the `serde_values` module).
+//! - `z3-default`: use 3 rather than 4 blocks per bucket in `DefaultOram` (and `path_oram::DEFAULT_BLOCKS_PER_BUCKET`),
+//!   reading and writing a quarter fewer tree blocks per access but reserving a quarter more stash overflow blocks.
+//! - `simd` (nightly only): compare stash addresses several at a time using portable SIMD.
+//!   Without it, the comparison is scalar (but still branch-free).
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
 #![warn(clippy::cargo, clippy::doc_markdown, missing_docs, rustdoc::all)]
 
-use std::num::TryFromIntError;
+extern crate alloc;
+
+// Logs through the `log` crate if the `logging` feature is enabled, and otherwise does nothing.
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::info!($($arg)*);
+    };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::warn!($($arg)*);
+    };
+}
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, num::TryFromIntError};
 
+use checksum::Checksum;
 use rand::{CryptoRng, RngCore};
+use range::AddressRange;
 use subtle::ConditionallySelectable;
-use thiserror::Error;
 
 pub(crate) mod bucket;
+#[cfg(any(feature = "ffi", feature = "wasm"))]
+pub(crate) mod byte_oram;
+pub mod checksum;
+pub mod codec;
+pub mod ct;
+pub mod ct_cmp;
+pub mod deterministic_eviction;
+#[cfg(any(feature = "diagnostics", test))]
+pub mod diagnostics;
+pub mod encrypted;
+pub mod eviction;
+pub mod ext;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod hashed;
+pub mod ingest;
+pub mod leaf_sampler;
 pub mod linear_time_oram;
+pub mod oblivious;
+pub mod ops;
+pub mod partitioned;
 pub mod path_oram;
+pub mod pool;
 pub(crate) mod position_map;
+pub mod range;
+pub mod rebuild;
+pub mod remapped;
+#[cfg(feature = "std")]
+pub mod reseed;
+pub mod ring_oram;
+pub mod scheduler;
+#[cfg(any(feature = "seeded", test))]
+pub mod seeded;
+#[cfg(feature = "std")]
+pub mod self_test;
+#[cfg(any(feature = "serde-values", test))]
+pub mod serde_values;
+#[cfg(feature = "std")]
+pub mod shared_oram;
+pub mod size_classed;
+pub mod snapshot;
+#[cfg(any(feature = "soak", test))]
+pub mod soak;
 pub(crate) mod stash;
+pub mod stepwise;
 #[cfg(test)]
 mod test_utils;
+#[cfg(any(feature = "std", test))]
+pub mod testing;
+pub mod tiered;
+pub mod tracked;
+pub mod transaction;
+pub mod tree;
+pub mod uniform_depth;
 pub(crate) mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub use crate::bucket::BlockValue;
+pub use crate::bucket::Bucket;
+pub use crate::bucket::PositionBlock;
+pub use crate::bucket::Unit;
 pub use crate::path_oram::DefaultOram;
 pub use crate::path_oram::PathOram;
+#[cfg(feature = "std")]
+pub use crate::self_test::self_test;
+pub use crate::utils::IndexWord;
 
 /// The numeric type used to specify the size of an ORAM block in bytes.
 pub type BlockSize = usize;
@@ -116,47 +224,231 @@ pub type Address = u64;
 pub type BucketSize = usize;
 /// The numeric type used to specify the cutoff size
 /// below which `PathOram` uses a linear position map instead of a recursive one.
+///
+/// The cutoff trades memory traffic for latency. Every access scans the whole linear base case,
+/// reading and writing 128 bytes per position block (with the default 8 positions per block),
+/// while each Path ORAM level of the recursion costs only a path of buckets and a pass over its stash.
+/// So a higher cutoff moves many more bytes per access, but the scan is sequential and cheap per byte.
+/// In the `recursion_cutoffs` benchmark (see `benches/README.md`), on ORAMs of 8-byte blocks,
+/// raising the cutoff from 2^8 to 2^14 multiplied the bytes per access of a capacity-2^16 ORAM by 24,
+/// to about 1 MB, but cut its latency by almost half; at capacity 2^20, a cutoff of 2^16 was about a fifth faster
+/// than one of 2^14. Where memory bandwidth is scarce (e.g., under enclave memory encryption, or with many concurrent ORAMs),
+/// a cutoff of 2^8 to 2^10 minimizes traffic; otherwise the default, [`path_oram::DEFAULT_RECURSION_CUTOFF`],
+/// or a cutoff as large as the position map of the largest capacity in use, tends to minimize latency.
+/// Measure with your own block sizes before departing from the default.
 pub type RecursionCutoff = u64;
 /// Numeric type used to represent the size of a Path ORAM stash in blocks.
 pub type StashSize = u64;
 
 /// A "trait alias" for ORAM blocks: the values read and written by ORAMs.
 pub trait OramBlock:
-    Copy + Clone + std::fmt::Debug + Default + PartialEq + ConditionallySelectable
+    Copy + Clone + fmt::Debug + Default + PartialEq + ConditionallySelectable
 {
+    /// Appends the contents of this value to `checksum`, in time independent of the value.
+    /// Used only with the `checksummed` feature (see the [`checksum`] module).
+    /// The default implementation appends nothing, so that only the metadata of blocks holding this value is checksummed.
+    fn update_checksum(&self, checksum: &mut Checksum) {
+        let _ = checksum;
+    }
 }
 
-impl OramBlock for u8 {}
-impl OramBlock for u16 {}
-impl OramBlock for u32 {}
-impl OramBlock for u64 {}
-impl OramBlock for i8 {}
-impl OramBlock for i16 {}
-impl OramBlock for i32 {}
-impl OramBlock for i64 {}
+macro_rules! impl_oram_block_for_integers {
+    ($($t:ty),*) => {
+        $(
+            impl OramBlock for $t {
+                fn update_checksum(&self, checksum: &mut Checksum) {
+                    checksum.update(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_oram_block_for_integers!(u8, u16, u32, u64, i8, i16, i32, i64);
 
 /// A list of error types which are produced during ORAM protocol execution.
-#[derive(Error, Debug)]
+#[derive(Debug)]
 pub enum OramError {
     /// Errors arising from conversions between integer types.
-    #[error("Arithmetic error encountered.")]
-    IntegerConversionError(#[from] TryFromIntError),
+    IntegerConversionError(TryFromIntError),
     /// Errors arising from attempting to make an ORAM access to an invalid address.
-    #[error("Attempted to access ORAM address {attempted}, which is larger than ORAM capacity {capacity}.")]
     AddressOutOfBoundsError {
         /// The invalid address that was accessed.
         attempted: Address,
         /// The capacity of the ORAM that was accessed.
         capacity: Address,
     },
+    /// Errors arising from accessing an invalid address of a named partition of a
+    /// [`PartitionedOram`](partitioned::PartitionedOram).
+    PartitionAddressOutOfBoundsError {
+        /// The name of the partition.
+        partition: String,
+        /// The invalid address within the partition that was accessed.
+        attempted: Address,
+        /// The capacity of the partition.
+        capacity: Address,
+    },
+    /// Errors arising from mapping a virtual address of a [`RemappedOram`](remapped::RemappedOram)
+    /// to a physical address that holds the record of another virtual address.
+    AddressCollisionError {
+        /// The virtual address being remapped.
+        virtual_address: Address,
+        /// The physical address it was to be mapped to.
+        physical_address: Address,
+    },
     /// Errors arising from invalid parameters or configuration.
-    #[error("Invalid configuration. {parameter_name} cannot have value {parameter_value}.")]
     InvalidConfigurationError {
         /// The misconfigured parameter.
         parameter_name: String,
         /// Its invalid value.
         parameter_value: String,
     },
+    /// Errors arising from reading a block that fails authentication, i.e., that has been tampered with.
+    IntegrityError {
+        /// The address of the block.
+        address: Address,
+    },
+    /// Errors arising from reading a bucket of a Path ORAM tree whose block checksums fail verification,
+    /// i.e., whose memory has been corrupted. Returned only with the `checksummed` feature.
+    BucketIntegrityError {
+        /// The index of the bucket in the tree.
+        bucket: u64,
+    },
+    /// Errors arising from accessing a Path ORAM restored from a snapshot with a reseed interval
+    /// before giving it fresh entropy (see the [`reseed`] module).
+    ReseedRequired,
+    /// Errors arising from constructing or growing an ORAM beyond its memory budget.
+    MemoryBudgetExceeded {
+        /// The number of bytes of memory the ORAM would have held.
+        required: u64,
+        /// The memory budget in bytes.
+        budget: u64,
+    },
+    /// Errors arising from using an ORAM after an earlier operation on it failed partway,
+    /// which may have left it inconsistent.
+    Poisoned,
+    /// Errors arising from queueing an operation for an ORAM of an
+    /// [`InterleavedScheduler`](scheduler::InterleavedScheduler) whose queue is full.
+    QueueFull {
+        /// The index of the ORAM in the scheduler.
+        oram: usize,
+        /// The capacity of each queue.
+        capacity: usize,
+    },
+    /// Errors arising from inserting a new key into a [`HashedAddressOram`](hashed::HashedAddressOram)
+    /// when every address it may be stored at holds another key.
+    ProbeSequenceFull {
+        /// The key being inserted.
+        key: u64,
+        /// The number of addresses at which the key may be stored.
+        probe_depth: usize,
+    },
+    /// Errors arising from accessing an [`EncryptedOram`](encrypted::EncryptedOram) that has used every nonce,
+    /// which must never be reused.
+    NoncesExhausted,
+    /// Errors arising from inserting a new key into a [`HashedAddressOram`](hashed::HashedAddressOram)
+    /// that already holds as many keys as it was configured to.
+    KeyLimitReached {
+        /// The maximum number of keys.
+        max_keys: Address,
+    },
+    /// Errors arising from failing to draw entropy from the operating system's RNG.
+    RngError(rand::Error),
+}
+
+impl fmt::Display for OramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OramError::IntegerConversionError(_) => write!(f, "Arithmetic error encountered."),
+            OramError::AddressOutOfBoundsError {
+                attempted,
+                capacity,
+            } => write!(
+                f,
+                "Attempted to access ORAM address {attempted}, which is larger than ORAM capacity {capacity}."
+            ),
+            OramError::PartitionAddressOutOfBoundsError {
+                partition,
+                attempted,
+                capacity,
+            } => write!(
+                f,
+                "Attempted to access address {attempted} of partition {partition}, which is larger than its capacity {capacity}."
+            ),
+            OramError::AddressCollisionError {
+                virtual_address,
+                physical_address,
+            } => write!(
+                f,
+                "Cannot map virtual address {virtual_address} to physical address {physical_address}, which holds the record of another virtual address."
+            ),
+            OramError::InvalidConfigurationError {
+                parameter_name,
+                parameter_value,
+            } => write!(
+                f,
+                "Invalid configuration. {parameter_name} cannot have value {parameter_value}."
+            ),
+            OramError::IntegrityError { address } => {
+                write!(f, "The block at ORAM address {address} failed authentication.")
+            }
+            OramError::BucketIntegrityError { bucket } => {
+                write!(f, "A block in bucket {bucket} of the ORAM tree failed its checksum.")
+            }
+            OramError::ReseedRequired => write!(
+                f,
+                "The ORAM was restored from a snapshot, and must be reseeded before it is accessed."
+            ),
+            OramError::MemoryBudgetExceeded { required, budget } => write!(
+                f,
+                "The ORAM would hold {required} bytes of memory, exceeding its budget of {budget} bytes."
+            ),
+            OramError::Poisoned => write!(
+                f,
+                "The ORAM is poisoned, since an earlier operation on it failed partway."
+            ),
+            OramError::QueueFull { oram, capacity } => write!(
+                f,
+                "The queue of ORAM {oram} already holds {capacity} operations, its capacity."
+            ),
+            OramError::ProbeSequenceFull { key, probe_depth } => write!(
+                f,
+                "All {probe_depth} addresses at which key {key} may be stored hold other keys."
+            ),
+            OramError::NoncesExhausted => write!(
+                f,
+                "The ORAM has encrypted under every nonce, so cannot encrypt another block."
+            ),
+            OramError::KeyLimitReached { max_keys } => write!(
+                f,
+                "The map already holds {max_keys} keys, its maximum."
+            ),
+            OramError::RngError(error) => write!(f, "The operating system's RNG failed: {error}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OramError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OramError::IntegerConversionError(error) => Some(error),
+            OramError::RngError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<TryFromIntError> for OramError {
+    fn from(error: TryFromIntError) -> Self {
+        OramError::IntegerConversionError(error)
+    }
+}
+
+impl From<rand::Error> for OramError {
+    fn from(error: rand::Error) -> Self {
+        OramError::RngError(error)
+    }
 }
 
 /// Represents an oblivious RAM (ORAM) mapping addresses of type `Address` to values of type `V: OramBlock`.
@@ -175,6 +467,16 @@ where
     ///
     /// For updating a block in place, using `access` is expected to be about
     /// twice as fast as performing a `read` followed by a `write`.
+    ///
+    /// The physical memory accesses of an access, and the randomness it consumes, must not depend on `callback`.
+    /// In particular, reads and writes (including those made by `read_into` and `write_from`) are physically
+    /// indistinguishable: given the same RNG, they read and write the same locations, and leave the same metadata there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError` if `index` is not less than the capacity.
+    /// Implementations may use addresses at or beyond their capacity (such as `Address::MAX`) to mark dummy blocks internally,
+    /// so must reject such addresses before touching any block.
     fn access<R: RngCore + CryptoRng, F: Fn(&Self::V) -> Self::V>(
         &mut self,
         index: Address,
@@ -192,7 +494,8 @@ where
         self.access(index, callback, rng)
     }
 
-    /// Obliviously writes the value stored at `index`. Returns the value previously stored at `index`.
+    /// Obliviously writes the value stored at `index`. Returns the value previously stored at `index`,
+    /// which is `V::default()` if `index` has never been written (see the [`tracked`] module to tell the two apart).
     fn write<R: RngCore + CryptoRng>(
         &mut self,
         index: Address,
@@ -202,4 +505,90 @@ where
         let callback = |_: &Self::V| new_value;
         self.access(index, callback, rng)
     }
+
+    /// Obliviously reads the value stored at `index` into `out`.
+    /// If an error is returned, `out` is unchanged.
+    ///
+    /// For large blocks, implementations may override this method to avoid the copies made by `read`,
+    /// which returns the value by move.
+    fn read_into<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        out: &mut Self::V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        *out = self.read(index, rng)?;
+        Ok(())
+    }
+
+    /// Obliviously writes `new_value` to `index`. Unlike `write`, does not return the value previously stored there.
+    ///
+    /// For large blocks, implementations may override this method to avoid the copies made by `write`,
+    /// which takes the value by move and returns the previous value.
+    fn write_from<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        new_value: &Self::V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        self.write(index, *new_value, rng)?;
+        Ok(())
+    }
+
+    /// Obliviously reads the values stored at the addresses of `range`, in order.
+    ///
+    /// The range itself is public: this reads each of its addresses in turn, hiding only the values read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError`, before making any access, if `range` extends past the capacity.
+    fn read_range<R: RngCore + CryptoRng>(
+        &mut self,
+        range: AddressRange,
+        rng: &mut R,
+    ) -> Result<Vec<Self::V>, OramError> {
+        check_range(range, self.block_capacity()?)?;
+        range
+            .iter()
+            .map(|address| self.read(address, rng))
+            .collect()
+    }
+
+    /// Obliviously writes `values` to the addresses of `range`, in order.
+    ///
+    /// The range itself is public: this writes each of its addresses in turn, hiding only the values written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError` if `range` extends past the capacity,
+    /// and an `InvalidConfigurationError` if `values` is not as long as `range`, in either case before making any access.
+    fn write_range<R: RngCore + CryptoRng>(
+        &mut self,
+        range: AddressRange,
+        values: &[Self::V],
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        check_range(range, self.block_capacity()?)?;
+        if u64::try_from(values.len())? != range.len() {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("Number of values (with range length {})", range.len()),
+                parameter_value: values.len().to_string(),
+            });
+        }
+        for (address, value) in range.iter().zip(values) {
+            self.write_from(address, value, rng)?;
+        }
+        Ok(())
+    }
+}
+
+// A range may have been checked against the capacity of another ORAM.
+fn check_range(range: AddressRange, capacity: Address) -> Result<(), OramError> {
+    if range.end() > capacity {
+        return Err(OramError::AddressOutOfBoundsError {
+            attempted: range.end() - 1,
+            capacity,
+        });
+    }
+    Ok(())
 }
@@ -8,25 +8,76 @@
 //! A simple linear-time implementation of Oblivious RAM.
 
 use crate::{Address, Oram, OramBlock, OramError};
+use alloc::vec::Vec;
 use rand::{CryptoRng, RngCore};
 use subtle::{ConstantTimeEq, ConstantTimeLess};
 
 /// A simple ORAM that, for each access, ensures obliviousness by making a complete pass over the database,
 /// reading and writing each memory location.
-#[derive(Debug)]
 pub struct LinearTimeOram<V: OramBlock> {
     /// The memory of the ORAM (public for benchmarking).
     pub physical_memory: Vec<V>,
+    // The number of blocks read and written, to check `PathOram::expected_physical_accesses_per_op`.
+    #[cfg(test)]
+    pub(crate) physical_reads: u64,
+    #[cfg(test)]
+    pub(crate) physical_writes: u64,
+}
+
+// The stored values are secret, so only the capacity is printed.
+impl<V: OramBlock> core::fmt::Debug for LinearTimeOram<V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LinearTimeOram")
+            .field("block_capacity", &self.physical_memory.len())
+            .finish_non_exhaustive()
+    }
 }
 
 impl<V: OramBlock> LinearTimeOram<V> {
     /// Returns a new `LinearTimeOram` mapping addresses `0 <= address < block_capacity` to default `V` values.
     pub fn new(block_capacity: Address) -> Result<Self, OramError> {
-        log::info!("LinearTimeOram::new(capacity = {})", block_capacity,);
+        log_info!("LinearTimeOram::new(capacity = {})", block_capacity,);
 
         let mut physical_memory = Vec::new();
         physical_memory.resize(usize::try_from(block_capacity)?, V::default());
-        Ok(Self { physical_memory })
+        Ok(Self {
+            physical_memory,
+            #[cfg(test)]
+            physical_reads: 0,
+            #[cfg(test)]
+            physical_writes: 0,
+        })
+    }
+
+    /// Returns a new `LinearTimeOram` storing `values[i]` at each address `i`, in the buffer `values`
+    /// supplied by the caller (e.g., allocated from an arena) rather than in one it allocates.
+    pub fn from_vec(values: Vec<V>) -> Result<Self, OramError> {
+        log_info!("LinearTimeOram::from_vec(capacity = {})", values.len());
+
+        Ok(Self {
+            physical_memory: values,
+            #[cfg(test)]
+            physical_reads: 0,
+            #[cfg(test)]
+            physical_writes: 0,
+        })
+    }
+
+    /// Consumes the ORAM and returns the buffer holding its memory, i.e., the value stored at each address, in address order.
+    pub fn into_inner(self) -> Vec<V> {
+        self.physical_memory
+    }
+
+    /// Returns the number of bytes of physical memory that each access reads and writes (counting both).
+    pub fn physical_bytes_per_access(&self) -> Result<u64, OramError> {
+        Ok(2 * self.memory_footprint()?)
+    }
+
+    /// Returns the number of bytes of memory that the ORAM's blocks occupy.
+    pub fn memory_footprint(&self) -> Result<u64, OramError> {
+        Ok(u64::try_from(core::mem::size_of_val(
+            self.physical_memory.as_slice(),
+        ))?)
     }
 }
 
@@ -63,6 +114,11 @@ impl<V: OramBlock> Oram for LinearTimeOram<V> {
 
             self.physical_memory[i].conditional_assign(&potential_new_value, is_requested_index);
         }
+        #[cfg(test)]
+        {
+            self.physical_reads += self.block_capacity()?;
+            self.physical_writes += self.block_capacity()?;
+        }
         Ok(result)
     }
 
@@ -87,4 +143,42 @@ mod tests {
         let mut oram = LinearTimeOram::<BlockValue<1>>::new(64).unwrap();
         linear_workload(&mut oram, 1000);
     }
+
+    #[test]
+    fn linear_time_oram_correctness_read_into_workload() {
+        let mut oram = LinearTimeOram::<BlockValue<1>>::new(64).unwrap();
+        read_into_workload(&mut oram, 1000);
+    }
+
+    #[test]
+    fn linear_time_oram_rejects_out_of_bounds_addresses() {
+        let mut oram = LinearTimeOram::<BlockValue<1>>::new(64).unwrap();
+        out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+    }
+
+    #[test]
+    fn linear_time_oram_conforms_to_the_oram_contract() {
+        let report = crate::testing::conformance(
+            |capacity, _| LinearTimeOram::<BlockValue<4>>::new(capacity),
+            &[1, 2, 3, 64, 100],
+            500,
+            0,
+        );
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn linear_time_oram_in_caller_buffer() {
+        let mut rng = rand::thread_rng();
+        let values: Vec<u64> = (0..16).collect();
+        let pointer = values.as_ptr();
+        let mut oram = LinearTimeOram::from_vec(values).unwrap();
+        assert_eq!(oram.block_capacity().unwrap(), 16);
+        assert_eq!(oram.write(3, 42, &mut rng).unwrap(), 3);
+        assert_eq!(oram.read(15, &mut rng).unwrap(), 15);
+
+        let values = oram.into_inner();
+        assert_eq!(values.as_ptr(), pointer);
+        assert_eq!(values[3], 42);
+    }
 }
@@ -0,0 +1,349 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Oblivious sorting, compaction, and permutation.
+//!
+//! Unless noted otherwise, the functions in this module are oblivious: the sequence of memory locations they touch,
+//! and the time they take, depend only on the lengths of their inputs and never on the values in them.
+//! Comparisons and exchanges are made with [`CtOrd`] and [`ConditionallySelectable`] rather than by branching.
+//!
+//! ```
+//! use oram::oblivious::{bitonic_sort_by_keys, oblivious_shuffle};
+//! use rand::rngs::OsRng;
+//!
+//! let mut items = [10u64, 20, 30, 40];
+//! let mut keys = [3u32, 1, 4, 2];
+//! bitonic_sort_by_keys(&mut items, &mut keys);
+//! assert_eq!(items, [20, 40, 10, 30]);
+//!
+//! oblivious_shuffle(&mut items, &mut OsRng);
+//! ```
+
+use crate::{ct_cmp::CtOrd, OramError};
+use alloc::{borrow::ToOwned, vec, vec::Vec};
+use rand::seq::SliceRandom;
+use rand::{CryptoRng, Rng, RngCore};
+use subtle::{Choice, ConditionallySelectable};
+
+/// Sorts `items` in ascending order of `keys`, obliviously and in constant time.
+/// The sort is not stable. Assumes that `keys.len() == items.len()`.
+/// The algorithm is bitonic sort, based on code written by Hans Werner Lang
+/// and available [here](https://hwlang.de/algorithmen/sortieren/bitonic/oddn.htm).
+pub fn bitonic_sort_by_keys<T: ConditionallySelectable, K: CtOrd>(items: &mut [T], keys: &mut [K]) {
+    bitonic_sort_by_keys_with(keys, &mut |i, j, do_swap| {
+        conditional_swap_at(items, i, j, do_swap)
+    });
+}
+
+/// As [`bitonic_sort_by_keys`], but also returns the permutation applied:
+/// the item now at index `i` was at index `permutation[i]` before sorting.
+pub fn bitonic_sort_by_keys_with_permutation<T: ConditionallySelectable, K: CtOrd>(
+    items: &mut [T],
+    keys: &mut [K],
+) -> Result<Vec<u64>, OramError> {
+    let n: u64 = items.len().try_into()?;
+    let mut permutation = Vec::from_iter(0..n);
+    bitonic_sort_by_keys_with(keys, &mut |i, j, do_swap| {
+        conditional_swap_at(items, i, j, do_swap);
+        conditional_swap_at(&mut permutation, i, j, do_swap);
+    });
+    Ok(permutation)
+}
+
+/// Sorts `keys` in ascending order, obliviously and in constant time,
+/// calling `swap(i, j, do_swap)` for every compare-exchange of `keys[i]` and `keys[j]` (with `i < j`).
+/// This allows the same sequence of exchanges to be applied to any number of parallel arrays,
+/// without the comparisons ever touching them.
+pub(crate) fn bitonic_sort_by_keys_with<K: CtOrd, F: FnMut(usize, usize, Choice)>(
+    keys: &mut [K],
+    swap: &mut F,
+) {
+    let ascending: Choice = 1.into();
+    helper_bitonic_sort_by_keys(0, keys.len(), keys, swap, ascending);
+}
+
+/// Swaps `items[i]` and `items[j]` if `do_swap` is set, obliviously and in constant time.
+/// Assumes that `i < j`.
+pub(crate) fn conditional_swap_at<T: ConditionallySelectable>(
+    items: &mut [T],
+    i: usize,
+    j: usize,
+    do_swap: Choice,
+) {
+    let (items_i, items_j) = items.split_at_mut(i + 1);
+    T::conditional_swap(&mut items_i[i], &mut items_j[j - (i + 1)], do_swap);
+}
+
+fn helper_bitonic_sort_by_keys<K: CtOrd, F: FnMut(usize, usize, Choice)>(
+    lo: usize,
+    n: usize,
+    keys: &mut [K],
+    swap: &mut F,
+    direction: Choice,
+) {
+    if n > 1 {
+        let m = n / 2;
+        helper_bitonic_sort_by_keys(lo, m, keys, swap, !direction);
+        helper_bitonic_sort_by_keys(lo + m, n - m, keys, swap, direction);
+        helper_bitonic_merge_by_keys(lo, n, keys, swap, direction);
+    }
+}
+
+fn helper_bitonic_merge_by_keys<K: CtOrd, F: FnMut(usize, usize, Choice)>(
+    lo: usize,
+    n: usize,
+    keys: &mut [K],
+    swap: &mut F,
+    direction: Choice,
+) {
+    if n > 1 {
+        let m = n.next_power_of_two() >> 1;
+        for i in lo..(lo + n - m) {
+            let j = i + m;
+            let jlti = keys[j].ct_lt(&keys[i]);
+            let do_swap = !(jlti ^ direction);
+            swap(i, j, do_swap);
+            conditional_swap_at(keys, i, j, do_swap);
+        }
+
+        helper_bitonic_merge_by_keys(lo, m, keys, swap, direction);
+        helper_bitonic_merge_by_keys(lo + m, n - m, keys, swap, direction);
+    }
+}
+
+/// Returns a uniformly random permutation of 0 through n.
+///
+/// Not oblivious: the memory access pattern of the shuffle reveals the permutation.
+/// Use [`oblivious_shuffle`] to permute secret data.
+pub fn random_permutation_of_0_through_n_exclusive<R: RngCore + CryptoRng>(
+    n: u64,
+    rng: &mut R,
+) -> Vec<u64> {
+    let permuted_addresses = 0..n;
+    let mut permuted_addresses = Vec::from_iter(permuted_addresses);
+    let permuted_addresses = permuted_addresses.as_mut_slice();
+    permuted_addresses.shuffle(rng);
+    Vec::from(permuted_addresses)
+}
+
+/// Given a permutation, inverts it using oblivious (data-independent) operations.
+pub fn invert_permutation_oblivious(permutation: &[u64]) -> Result<Vec<u64>, OramError> {
+    let n: u64 = permutation.len().try_into()?;
+    let mut copied = permutation.to_owned();
+    let mut result = Vec::from_iter(0u64..n);
+    bitonic_sort_by_keys(&mut result, &mut copied);
+    Ok(result)
+}
+
+/// Randomly permutes `items`, obliviously, by sorting them by fresh random keys.
+/// The keys are 64 bits, so the permutation is uniform except with probability about `items.len()^2 / 2^65`
+/// (that two keys collide).
+pub fn oblivious_shuffle<T: ConditionallySelectable, R: RngCore + CryptoRng>(
+    items: &mut [T],
+    rng: &mut R,
+) {
+    let mut keys: Vec<u64> = (0..items.len()).map(|_| rng.gen()).collect();
+    bitonic_sort_by_keys(items, &mut keys);
+}
+
+/// Moves the items of `items` whose entries in `marks` are set to the front of `items`, obliviously and in constant time.
+/// The marked items keep their relative order; the order of the unmarked items afterward is unspecified.
+/// Assumes that `marks.len() == items.len()`.
+///
+/// Takes `O(n log n)` time for `n` items. Each marked item is shifted toward the front by its distance to its destination,
+/// one power of two at a time from the smallest; shifting in this order never moves two marked items to the same index.
+pub fn compact<T: ConditionallySelectable>(items: &mut [T], marks: &[Choice]) {
+    assert_eq!(items.len(), marks.len());
+    let mut marks = marks.to_vec();
+
+    // `distances[i]` is the number of unmarked items before item `i` if it is marked, and 0 otherwise.
+    let mut distances = vec![0u64; items.len()];
+    let mut unmarked_so_far = 0u64;
+    for (distance, mark) in distances.iter_mut().zip(&marks) {
+        distance.conditional_assign(&unmarked_so_far, *mark);
+        unmarked_so_far += u64::from((!*mark).unwrap_u8());
+    }
+
+    let mut shift = 1;
+    let mut bit = 0;
+    while shift < items.len() {
+        for i in shift..items.len() {
+            let do_swap = marks[i] & Choice::from(((distances[i] >> bit) & 1) as u8);
+            conditional_swap_at(items, i - shift, i, do_swap);
+            conditional_swap_at(&mut marks, i - shift, i, do_swap);
+            conditional_swap_at(&mut distances, i - shift, i, do_swap);
+        }
+        shift <<= 1;
+        bit += 1;
+    }
+}
+
+/// As [`compact`], marking the items for which `is_marked` returns true.
+pub fn compact_by_key<T: ConditionallySelectable, F: Fn(&T) -> Choice>(
+    items: &mut [T],
+    is_marked: F,
+) {
+    let marks: Vec<Choice> = items.iter().map(is_marked).collect();
+    compact(items, &marks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::BTreeMap;
+    use subtle::ConstantTimeEq;
+
+    #[test]
+    fn test_invert_permutation_oblivious() {
+        let n = 16;
+        let mut rng = StdRng::seed_from_u64(0);
+        let permutation = random_permutation_of_0_through_n_exclusive(n, &mut rng);
+        let inverse = invert_permutation_oblivious(&permutation).unwrap();
+        for i in 0..n {
+            assert_eq!(i, inverse[permutation[i as usize] as usize]);
+        }
+    }
+
+    #[test]
+    fn test_bitonic_sort() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut items: Vec<u64> = Vec::new();
+        let mut keys: Vec<u64> = Vec::new();
+        let n = 128;
+        for e in random_permutation_of_0_through_n_exclusive(n, &mut rng) {
+            items.push(e);
+            keys.push(e + (2 * n));
+        }
+
+        bitonic_sort_by_keys(&mut items, &mut keys);
+        for i in 0..(items.len() - 1) {
+            assert!(keys[i] <= keys[i + 1]);
+            assert_eq!(keys[i], items[i] + (2 * n));
+        }
+    }
+
+    #[test]
+    fn test_bitonic_sort_with_parallel_arrays() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let n = 100;
+        let mut keys = random_permutation_of_0_through_n_exclusive(n, &mut rng);
+        let mut doubled: Vec<u64> = keys.iter().map(|k| 2 * k).collect();
+        let mut tripled: Vec<u64> = keys.iter().map(|k| 3 * k).collect();
+
+        bitonic_sort_by_keys_with(&mut keys, &mut |i, j, do_swap| {
+            conditional_swap_at(&mut doubled, i, j, do_swap);
+            conditional_swap_at(&mut tripled, i, j, do_swap);
+        });
+        for i in 0..n {
+            assert_eq!(keys[i as usize], i);
+            assert_eq!(doubled[i as usize], 2 * i);
+            assert_eq!(tripled[i as usize], 3 * i);
+        }
+    }
+
+    // Sorts random signed keys, with many duplicates, and checks the result against the permutation applied.
+    #[test]
+    fn bitonic_sort_with_permutation_sorts_and_permutes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for n in 0..70 {
+            let original_keys: Vec<i16> = (0..n).map(|_| rng.gen_range(-8..8)).collect();
+            let original_items: Vec<u32> = (0..n).map(|_| rng.gen()).collect();
+            let mut keys = original_keys.clone();
+            let mut items = original_items.clone();
+            let permutation = bitonic_sort_by_keys_with_permutation(&mut items, &mut keys).unwrap();
+
+            assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+            let mut sorted_permutation = permutation.clone();
+            sorted_permutation.sort();
+            assert_eq!(sorted_permutation, Vec::from_iter(0..n));
+            for (i, original_index) in permutation.iter().enumerate() {
+                let original_index = *original_index as usize;
+                assert_eq!(keys[i], original_keys[original_index]);
+                assert_eq!(items[i], original_items[original_index]);
+            }
+        }
+    }
+
+    // Checks that every permutation of `n` items is about equally likely, with a chi-square test.
+    // `critical_value` is the 0.1% critical value of the chi-square distribution with `n! - 1` degrees of freedom.
+    fn check_shuffle_is_uniform(n: u8, critical_value: f64) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let number_of_permutations: u32 = (1..=u32::from(n)).product();
+        let trials = 1000 * number_of_permutations;
+
+        let mut counts = BTreeMap::new();
+        for _ in 0..trials {
+            let mut items = Vec::from_iter(0..n);
+            oblivious_shuffle(&mut items, &mut rng);
+            *counts.entry(items).or_insert(0u32) += 1;
+        }
+
+        assert_eq!(counts.len(), number_of_permutations as usize);
+        let expected = f64::from(trials) / f64::from(number_of_permutations);
+        let chi_square: f64 = counts
+            .values()
+            .map(|count| (f64::from(*count) - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_square < critical_value, "chi-square = {}", chi_square);
+    }
+
+    #[test]
+    fn oblivious_shuffle_is_uniform() {
+        check_shuffle_is_uniform(3, 20.515);
+        check_shuffle_is_uniform(4, 49.728);
+    }
+
+    // Checks that `compact` moves exactly the marked items to the front, in their original order, and loses no item.
+    fn check_compact(marks: &[bool]) {
+        let mut items = Vec::from_iter(0..marks.len() as u32);
+        let choices: Vec<Choice> = marks
+            .iter()
+            .map(|mark| Choice::from(u8::from(*mark)))
+            .collect();
+        compact(&mut items, &choices);
+
+        let marked: Vec<u32> = (0..marks.len() as u32)
+            .filter(|i| marks[*i as usize])
+            .collect();
+        assert_eq!(items[..marked.len()], marked);
+        let mut unmarked: Vec<u32> = items[marked.len()..].to_vec();
+        unmarked.sort();
+        let expected_unmarked: Vec<u32> = (0..marks.len() as u32)
+            .filter(|i| !marks[*i as usize])
+            .collect();
+        assert_eq!(unmarked, expected_unmarked);
+    }
+
+    #[test]
+    fn compact_exhaustive_small_lengths() {
+        for n in 0..=10 {
+            for pattern in 0u32..(1 << n) {
+                let marks: Vec<bool> = (0..n).map(|i| (pattern >> i) & 1 == 1).collect();
+                check_compact(&marks);
+            }
+        }
+    }
+
+    #[test]
+    fn compact_random_marks() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for n in [100, 127, 128, 129, 1000, 1023] {
+            for density in [0.0, 0.1, 0.5, 0.9, 1.0] {
+                let marks: Vec<bool> = (0..n).map(|_| rng.gen_bool(density)).collect();
+                check_compact(&marks);
+            }
+        }
+    }
+
+    #[test]
+    fn compact_by_key_keeps_even_items_first() {
+        let mut items = [5u64, 2, 7, 4, 4, 9, 0];
+        compact_by_key(&mut items, |item| (item % 2).ct_eq(&0));
+        assert_eq!(items[..4], [2, 4, 4, 0]);
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Oblivious operations spanning more than one ORAM.
+//!
+//! [`conditional_exchange`] swaps the values at an address of one ORAM and an address of another if a secret [`Choice`] is set,
+//! e.g., to move a record from an "active" table to an "archived" one without revealing whether it moved.
+//! It makes the same accesses, to the same addresses, whether or not the values are swapped.
+//!
+//! ```
+//! use oram::{ops::conditional_exchange, DefaultOram, Oram, OramError};
+//! use rand::rngs::OsRng;
+//! use subtle::Choice;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut active = DefaultOram::<u64>::new(64, &mut OsRng)?;
+//! let mut archived = DefaultOram::<u64>::new(128, &mut OsRng)?;
+//! active.write(3, 7, &mut OsRng)?;
+//! conditional_exchange(&mut active, 3, &mut archived, 100, Choice::from(1), &mut OsRng)?;
+//! assert_eq!(active.read(3, &mut OsRng)?, 0);
+//! assert_eq!(archived.read(100, &mut OsRng)?, 7);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Address, Oram, OramError};
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConditionallySelectable};
+
+/// Obliviously swaps the value at `address_a` of `a` with the value at `address_b` of `b` if `condition` is set,
+/// and leaves both unchanged otherwise.
+///
+/// The exchange reads `a`, accesses `b` (writing the value read from `a` if `condition` is set),
+/// and writes `a` (the value previously at `address_b` if `condition` is set, and its own value otherwise).
+/// So it makes two accesses to `a` and one to `b` whatever `condition` is, each with a callback taking constant time.
+/// Two accesses to `a` are needed because an access writes back the value it reads before it returns,
+/// so whichever ORAM is accessed first cannot be written the other's value in the same access.
+/// The ORAMs may be of different types and capacities, but not the same ORAM.
+///
+/// # Errors
+///
+/// Returns an `AddressOutOfBoundsError` if either address is out of bounds, before accessing either ORAM,
+/// and otherwise any error of the accesses themselves.
+pub fn conditional_exchange<A: Oram, B: Oram<V = A::V>, R: RngCore + CryptoRng>(
+    a: &mut A,
+    address_a: Address,
+    b: &mut B,
+    address_b: Address,
+    condition: Choice,
+    rng: &mut R,
+) -> Result<(), OramError> {
+    for (address, capacity) in [
+        (address_a, a.block_capacity()?),
+        (address_b, b.block_capacity()?),
+    ] {
+        if address >= capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity,
+            });
+        }
+    }
+
+    let value_a = a.read(address_a, rng)?;
+    let value_b = b.access(
+        address_b,
+        |value_b| A::V::conditional_select(value_b, &value_a, condition),
+        rng,
+    )?;
+    a.write(
+        address_a,
+        A::V::conditional_select(&value_a, &value_b, condition),
+        rng,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, tree::TreeIndex, PathOram};
+    use alloc::vec::Vec;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    type BucketTrace = Vec<(TreeIndex, bool)>;
+
+    // Exchanges address 3 of a Path ORAM of capacity 64 with address 200 of one of capacity 256 if `condition` is set,
+    // from the same initial state and with the same randomness whatever `condition` is.
+    // Returns the buckets each ORAM read and wrote during the exchange, and the values at both addresses afterwards.
+    fn exchange(condition: u8) -> (BucketTrace, BucketTrace, u64, u64) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut a = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let mut b = PathOram::<u64, 4, 8>::new_with_parameters(256, &mut rng, 40, 1).unwrap();
+        a.write(3, 30, &mut rng).unwrap();
+        b.write(200, 2000, &mut rng).unwrap();
+
+        a.stash.bucket_trace = Some(Vec::new());
+        b.stash.bucket_trace = Some(Vec::new());
+        conditional_exchange(&mut a, 3, &mut b, 200, condition.into(), &mut rng).unwrap();
+        let trace_a = a.stash.bucket_trace.take().unwrap();
+        let trace_b = b.stash.bucket_trace.take().unwrap();
+
+        let value_a = a.read(3, &mut rng).unwrap();
+        let value_b = b.read(200, &mut rng).unwrap();
+        (trace_a, trace_b, value_a, value_b)
+    }
+
+    #[test]
+    fn exchanges_are_indistinguishable() {
+        let (kept_trace_a, kept_trace_b, kept_a, kept_b) = exchange(0);
+        let (swapped_trace_a, swapped_trace_b, swapped_a, swapped_b) = exchange(1);
+        assert_eq!((kept_a, kept_b), (30, 2000));
+        assert_eq!((swapped_a, swapped_b), (2000, 30));
+
+        assert_eq!(kept_trace_a, swapped_trace_a);
+        assert_eq!(kept_trace_b, swapped_trace_b);
+        // Two accesses to the tree of `a` (of height 5), and one to that of `b` (of height 7), each reading and writing a path.
+        assert_eq!(kept_trace_a.len(), 2 * 2 * 6);
+        assert_eq!(kept_trace_b.len(), 2 * 8);
+    }
+
+    #[test]
+    fn exchanges_between_oram_types() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut a = LinearTimeOram::<u64>::new(16).unwrap();
+        let mut b = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        a.write(1, 10, &mut rng).unwrap();
+        b.write(60, 600, &mut rng).unwrap();
+
+        conditional_exchange(&mut a, 1, &mut b, 60, 0.into(), &mut rng).unwrap();
+        assert_eq!(a.physical_reads, 3 * 16);
+        assert_eq!(
+            (a.read(1, &mut rng).unwrap(), b.read(60, &mut rng).unwrap()),
+            (10, 600)
+        );
+
+        conditional_exchange(&mut a, 1, &mut b, 60, 1.into(), &mut rng).unwrap();
+        assert_eq!(
+            (a.read(1, &mut rng).unwrap(), b.read(60, &mut rng).unwrap()),
+            (600, 10)
+        );
+        // Exchanging back restores both.
+        conditional_exchange(&mut b, 60, &mut a, 1, 1.into(), &mut rng).unwrap();
+        assert_eq!(
+            (a.read(1, &mut rng).unwrap(), b.read(60, &mut rng).unwrap()),
+            (10, 600)
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_addresses_access_neither_oram() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut a = LinearTimeOram::<u64>::new(16).unwrap();
+        let mut b = LinearTimeOram::<u64>::new(64).unwrap();
+        assert!(matches!(
+            conditional_exchange(&mut a, 1, &mut b, 64, 1.into(), &mut rng),
+            Err(OramError::AddressOutOfBoundsError {
+                attempted: 64,
+                capacity: 64
+            })
+        ));
+        assert!(conditional_exchange(&mut a, 16, &mut b, 1, 1.into(), &mut rng).is_err());
+        assert_eq!((a.physical_reads, b.physical_reads), (0, 0));
+    }
+}
@@ -0,0 +1,396 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Several logical tables multiplexed into one ORAM, each occupying a named range of its addresses.
+//!
+//! A [`PartitionTable`] lists named partitions and their capacities, which are laid out consecutively from address 0.
+//! A [`PartitionedOram`] accesses each partition by a local address, checking it against the capacity of that partition
+//! (not of the whole ORAM) before computing the global address.
+//!
+//! The partition table is not secret: which partition an access is to is revealed by the time taken to look it up.
+//! The local address within the partition is hidden, as with any ORAM access.
+//!
+//! To persist a `PartitionedOram`, save [`PartitionTable::to_bytes`] alongside the state of its ORAM,
+//! and reassemble them with [`PartitionedOram::restore`], which checks that the saved table is the expected one.
+//!
+//! ```
+//! use oram::{partitioned::{PartitionTable, PartitionedOram}, DefaultOram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let table = PartitionTable::new([("users", 48), ("sessions", 16)])?;
+//! let mut oram = PartitionedOram::new(DefaultOram::<u64>::new(64, &mut OsRng)?, table)?;
+//! oram.write("sessions", 0, 7, &mut OsRng)?;
+//! assert_eq!(oram.read("users", 0, &mut OsRng)?, 0);
+//! assert_eq!(oram.read("sessions", 0, &mut OsRng)?, 7);
+//! assert!(oram.read("sessions", 16, &mut OsRng).is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Address, Oram, OramError};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use rand::{CryptoRng, RngCore};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Partition {
+    name: String,
+    offset: Address,
+    capacity: Address,
+}
+
+/// The names and capacities of the partitions of a [`PartitionedOram`], in address order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartitionTable {
+    partitions: Vec<Partition>,
+}
+
+impl PartitionTable {
+    /// Returns a table of partitions with the given names and capacities,
+    /// the first starting at address 0 and each following directly after the previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if two partitions have the same name,
+    /// or if the total capacity overflows an `Address`.
+    pub fn new<'a, P: IntoIterator<Item = (&'a str, Address)>>(
+        partitions: P,
+    ) -> Result<Self, OramError> {
+        let mut table = Self {
+            partitions: Vec::new(),
+        };
+        for (name, capacity) in partitions {
+            if table.lookup(name).is_ok() {
+                return Err(OramError::InvalidConfigurationError {
+                    parameter_name: "Partition name".to_string(),
+                    parameter_value: format!("{name} (which is duplicated)"),
+                });
+            }
+            let offset = table.total_capacity();
+            if offset.checked_add(capacity).is_none() {
+                return Err(OramError::InvalidConfigurationError {
+                    parameter_name: "Total partition capacity".to_string(),
+                    parameter_value: format!("{offset} + {capacity}"),
+                });
+            }
+            table.partitions.push(Partition {
+                name: name.to_string(),
+                offset,
+                capacity,
+            });
+        }
+        Ok(table)
+    }
+
+    /// Returns the sum of the capacities of the partitions.
+    pub fn total_capacity(&self) -> Address {
+        self.partitions
+            .last()
+            .map_or(0, |partition| partition.offset + partition.capacity)
+    }
+
+    /// Returns the capacity of the partition named `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if there is no such partition.
+    pub fn capacity(&self, name: &str) -> Result<Address, OramError> {
+        Ok(self.lookup(name)?.capacity)
+    }
+
+    /// Returns the names of the partitions, in address order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.partitions
+            .iter()
+            .map(|partition| partition.name.as_str())
+    }
+
+    /// Returns the address in the whole ORAM of address `local_address` of the partition named `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if there is no such partition,
+    /// and a `PartitionAddressOutOfBoundsError` if `local_address` is not less than its capacity.
+    pub fn global_address(&self, name: &str, local_address: Address) -> Result<Address, OramError> {
+        let partition = self.lookup(name)?;
+        if local_address >= partition.capacity {
+            return Err(OramError::PartitionAddressOutOfBoundsError {
+                partition: partition.name.clone(),
+                attempted: local_address,
+                capacity: partition.capacity,
+            });
+        }
+        Ok(partition.offset + local_address)
+    }
+
+    /// Serializes the table: the number of partitions, followed by the length of the name, the name,
+    /// and the capacity of each, with integers as 8-byte little-endian values and names in UTF-8.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.partitions.len() as u64).to_le_bytes());
+        for partition in &self.partitions {
+            bytes.extend_from_slice(&(partition.name.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(partition.name.as_bytes());
+            bytes.extend_from_slice(&partition.capacity.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserializes a table serialized by [`PartitionTable::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `bytes` is not a valid serialized table.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OramError> {
+        let malformed = || OramError::InvalidConfigurationError {
+            parameter_name: "Serialized partition table".to_string(),
+            parameter_value: format!("{bytes:?}"),
+        };
+        let mut rest = bytes;
+        let mut partitions = Vec::new();
+        let count = take_u64(&mut rest).ok_or_else(malformed)?;
+        for _ in 0..count {
+            let name_length = usize::try_from(take_u64(&mut rest).ok_or_else(malformed)?)?;
+            let name = take(&mut rest, name_length).ok_or_else(malformed)?;
+            let name = String::from_utf8(name.to_vec()).map_err(|_| malformed())?;
+            let capacity = take_u64(&mut rest).ok_or_else(malformed)?;
+            partitions.push((name, capacity));
+        }
+        if !rest.is_empty() {
+            return Err(malformed());
+        }
+        Self::new(
+            partitions
+                .iter()
+                .map(|(name, capacity)| (name.as_str(), *capacity)),
+        )
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Partition, OramError> {
+        self.partitions
+            .iter()
+            .find(|partition| partition.name == name)
+            .ok_or_else(|| OramError::InvalidConfigurationError {
+                parameter_name: "Partition name".to_string(),
+                parameter_value: name.to_string(),
+            })
+    }
+}
+
+// Removes and returns the first `n` bytes of `bytes`, if there are that many.
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if bytes.len() < n {
+        return None;
+    }
+    let (taken, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Some(taken)
+}
+
+// Removes and returns the little-endian `u64` at the start of `bytes`, if there is one.
+fn take_u64(bytes: &mut &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap()))
+}
+
+/// An ORAM divided into named partitions, each accessed by addresses local to it. See the [module documentation](self).
+#[derive(Debug)]
+pub struct PartitionedOram<O: Oram> {
+    oram: O,
+    table: PartitionTable,
+}
+
+impl<O: Oram> PartitionedOram<O> {
+    /// Returns a `PartitionedOram` laying out the partitions of `table` in `oram`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if the partitions do not fit in `oram`.
+    pub fn new(oram: O, table: PartitionTable) -> Result<Self, OramError> {
+        let total_capacity = table.total_capacity();
+        if total_capacity > oram.block_capacity()? {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Total partition capacity".to_string(),
+                parameter_value: total_capacity.to_string(),
+            });
+        }
+        Ok(Self { oram, table })
+    }
+
+    /// As [`PartitionedOram::new`], for an ORAM restored from a snapshot alongside the partition table `saved_table`
+    /// (as serialized by [`PartitionTable::to_bytes`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `saved_table` is malformed or differs from `table`,
+    /// in which case the addresses of the snapshot would be misinterpreted.
+    pub fn restore(oram: O, table: PartitionTable, saved_table: &[u8]) -> Result<Self, OramError> {
+        if PartitionTable::from_bytes(saved_table)? != table {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Partition table".to_string(),
+                parameter_value: format!("{:?}, which differs from the saved table", table),
+            });
+        }
+        Self::new(oram, table)
+    }
+
+    /// Returns the partition table.
+    pub fn table(&self) -> &PartitionTable {
+        &self.table
+    }
+
+    /// Returns the capacity of the partition named `name`. See [`PartitionTable::capacity`].
+    pub fn partition_capacity(&self, name: &str) -> Result<Address, OramError> {
+        self.table.capacity(name)
+    }
+
+    /// As [`Oram::access`], for address `local_address` of the partition named `partition`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if there is no such partition,
+    /// and a `PartitionAddressOutOfBoundsError` if `local_address` is not less than its capacity.
+    pub fn access<R: RngCore + CryptoRng, F: Fn(&O::V) -> O::V>(
+        &mut self,
+        partition: &str,
+        local_address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<O::V, OramError> {
+        let address = self.table.global_address(partition, local_address)?;
+        self.oram.access(address, callback, rng)
+    }
+
+    /// As [`Oram::read`], for address `local_address` of the partition named `partition`.
+    pub fn read<R: RngCore + CryptoRng>(
+        &mut self,
+        partition: &str,
+        local_address: Address,
+        rng: &mut R,
+    ) -> Result<O::V, OramError> {
+        let address = self.table.global_address(partition, local_address)?;
+        self.oram.read(address, rng)
+    }
+
+    /// As [`Oram::write`], for address `local_address` of the partition named `partition`.
+    pub fn write<R: RngCore + CryptoRng>(
+        &mut self,
+        partition: &str,
+        local_address: Address,
+        new_value: O::V,
+        rng: &mut R,
+    ) -> Result<O::V, OramError> {
+        let address = self.table.global_address(partition, local_address)?;
+        self.oram.write(address, new_value, rng)
+    }
+
+    /// Returns the ORAM and the partition table.
+    pub fn into_parts(self) -> (O, PartitionTable) {
+        (self.oram, self.table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, PathOram};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn test_table() -> PartitionTable {
+        PartitionTable::new([("a", 10), ("b", 1), ("c", 0), ("d", 21)]).unwrap()
+    }
+
+    #[test]
+    fn adjacent_partitions_do_not_overlap() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(32, &mut rng, 40, 1).unwrap();
+        let mut oram = PartitionedOram::new(oram, test_table()).unwrap();
+        let capacities = [("a", 10), ("b", 1), ("c", 0), ("d", 21)];
+        for (name, capacity) in capacities {
+            assert_eq!(oram.partition_capacity(name).unwrap(), capacity);
+        }
+
+        // Write a distinct value to the first and last address of each partition.
+        for (i, (name, capacity)) in capacities.into_iter().enumerate() {
+            if capacity > 0 {
+                oram.write(name, 0, 100 * i as u64, &mut rng).unwrap();
+                oram.write(name, capacity - 1, 100 * i as u64 + 1, &mut rng)
+                    .unwrap();
+            }
+        }
+        let (mut inner, _) = oram.into_parts();
+        assert_eq!(inner.read(9, &mut rng).unwrap(), 1);
+        assert_eq!(inner.read(10, &mut rng).unwrap(), 101);
+        assert_eq!(inner.read(11, &mut rng).unwrap(), 300);
+        assert_eq!(inner.read(31, &mut rng).unwrap(), 301);
+
+        let mut oram = PartitionedOram::new(inner, test_table()).unwrap();
+        assert_eq!(oram.read("a", 9, &mut rng).unwrap(), 1);
+        assert_eq!(oram.read("b", 0, &mut rng).unwrap(), 101);
+        assert_eq!(oram.read("d", 0, &mut rng).unwrap(), 300);
+
+        // The first address past each partition is rejected, naming the partition, rather than reaching the next one.
+        for (name, capacity) in capacities {
+            match oram.read(name, capacity, &mut rng) {
+                Err(OramError::PartitionAddressOutOfBoundsError {
+                    partition,
+                    attempted,
+                    capacity: reported_capacity,
+                }) => {
+                    assert_eq!(partition, name);
+                    assert_eq!(attempted, capacity);
+                    assert_eq!(reported_capacity, capacity);
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+        assert!(matches!(
+            oram.read("e", 0, &mut rng),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        assert!(PartitionTable::new([("a", 1), ("a", 1)]).is_err());
+        assert!(PartitionTable::new([("a", Address::MAX), ("b", 1)]).is_err());
+        assert!(
+            PartitionedOram::new(LinearTimeOram::<u8>::new(31).unwrap(), test_table()).is_err()
+        );
+        assert!(PartitionedOram::new(LinearTimeOram::<u8>::new(32).unwrap(), test_table()).is_ok());
+    }
+
+    #[test]
+    fn restore_detects_a_different_table() {
+        let saved = test_table().to_bytes();
+        assert_eq!(PartitionTable::from_bytes(&saved).unwrap(), test_table());
+
+        let oram = || LinearTimeOram::<u8>::new(32).unwrap();
+        assert!(PartitionedOram::restore(oram(), test_table(), &saved).is_ok());
+        let different_tables = [
+            PartitionTable::new([("a", 10), ("b", 1), ("c", 0), ("d", 20)]).unwrap(),
+            PartitionTable::new([("a", 10), ("b", 1), ("d", 21)]).unwrap(),
+            PartitionTable::new([("b", 1), ("a", 10), ("c", 0), ("d", 21)]).unwrap(),
+            PartitionTable::new([("a", 10), ("b", 1), ("e", 0), ("d", 21)]).unwrap(),
+        ];
+        for table in different_tables {
+            assert!(matches!(
+                PartitionedOram::restore(oram(), table, &saved),
+                Err(OramError::InvalidConfigurationError { .. })
+            ));
+        }
+
+        // Truncated or extended serializations are rejected too.
+        assert!(PartitionedOram::restore(oram(), test_table(), &saved[..saved.len() - 1]).is_err());
+        let mut extended = saved.clone();
+        extended.push(0);
+        assert!(PartitionedOram::restore(oram(), test_table(), &extended).is_err());
+    }
+}
@@ -8,31 +8,97 @@
 //! An implementation of Path ORAM.
 
 use super::{position_map::PositionMap, stash::ObliviousStash};
+#[cfg(feature = "std")]
+use crate::reseed::Reseeder;
+#[cfg(feature = "hugepages")]
+use crate::utils::advise_huge_pages;
 use crate::{
     bucket::{Bucket, PathOramBlock, PositionBlock},
+    eviction::{EvictionOrder, EvictionStrategy, GreedyEviction},
+    leaf_sampler::LeafSampler,
     linear_time_oram::LinearTimeOram,
-    utils::{
-        invert_permutation_oblivious, random_permutation_of_0_through_n_exclusive, to_usize_vec,
-        CompleteBinaryTreeIndex, TreeHeight,
+    oblivious::{
+        bitonic_sort_by_keys, invert_permutation_oblivious,
+        random_permutation_of_0_through_n_exclusive,
     },
+    rebuild::RebuildSchedule,
+    transaction::UndoLog,
+    tree::{reverse_lexicographic_leaf, CompleteBinaryTreeIndex, TreeHeight, TreeIndex},
+    uniform_depth::PaddingOram,
+    utils::{to_usize_vec, IndexWord},
     Address, BlockSize, BucketSize, Oram, OramBlock, OramError, RecursionCutoff, StashSize,
 };
+use alloc::{boxed::Box, collections::VecDeque, format, string::ToString, vec, vec::Vec};
 use rand::{CryptoRng, Rng};
+use subtle::{ConditionallySelectable, ConstantTimeLess};
 
-/// The default cutoff size in blocks
-/// below which `PathOram` uses a linear position map instead of a recursive one.
+/// The default recursion cutoff: the largest number of position blocks
+/// for which `PathOram` uses a linear position map instead of a recursive one.
 pub const DEFAULT_RECURSION_CUTOFF: RecursionCutoff = 1 << 14;
 
 /// The parameter "Z" from the Path ORAM literature that sets the number of blocks per bucket; typical values are 3 or 4.
-/// Here we adopt the more conservative setting of 4.
-pub const DEFAULT_BLOCKS_PER_BUCKET: BucketSize = 4;
+/// Here we adopt the more conservative setting of 4, unless the `z3-default` feature selects 3.
+///
+/// With `Z = 3`, an access reads and writes 3 rather than 4 blocks per bucket on its path, a quarter fewer tree blocks.
+/// In exchange, the stash fills faster: in the simulations described at [`default_overflow_size`],
+/// each additional block of stash occupancy was about 0.52 times as likely as the last for `Z = 3`, against 0.42 for `Z = 4`.
+/// So for the same bound on the probability of stash overflow, `default_overflow_size` reserves a quarter more overflow blocks
+/// (125 rather than 100 for a capacity of 2^10, and 150 rather than 120 for a capacity of 2^20), all of which every access
+/// scans and obliviously sorts. Keeping the 100 overflow blocks of `Z = 4` at a capacity of 2^10 would instead
+/// multiply the bound by about 2^10.
+pub const DEFAULT_BLOCKS_PER_BUCKET: BucketSize = if cfg!(feature = "z3-default") { 3 } else { 4 };
 
 /// The default number of positions stored per position block.
 pub const DEFAULT_POSITIONS_PER_BLOCK: BlockSize = 8;
 
-/// The default number of overflow blocks that the Path ORAM stash (and recursive stashes) can store.
+/// The smallest overflow size returned by [`default_overflow_size`], which was formerly the default for every capacity.
 pub const DEFAULT_STASH_OVERFLOW_SIZE: StashSize = 40;
 
+/// Returns the default number of overflow blocks for the stash of a Path ORAM of capacity `capacity`
+/// with `z` blocks per bucket, chosen so that the stash overflows (and has to grow, revealing that it has)
+/// with probability at most about 2^-20 over 2^20 accesses per block, i.e., over `2^20 * capacity` accesses.
+/// Never less than [`DEFAULT_STASH_OVERFLOW_SIZE`]. Used by [`DefaultOram`];
+/// an overflow size passed explicitly, e.g., to [`PathOram::new_with_parameters`], takes precedence.
+///
+/// The Path ORAM stash bound (Stefanov et al., Theorem 1) shows that the probability that the stash holds more than
+/// `R` blocks after an access decays geometrically in `R`, at a rate `q` that depends on `Z` but not on the capacity.
+/// A union bound over `T` accesses then calls for `R = log2(T / p) / log2(1 / q)` blocks for a failure probability `p`:
+/// a constant number of blocks for every doubling of the capacity, and so of `T`.
+/// The rates used here come from simulations of 500,000 accesses to Path ORAMs of capacity 2^10
+/// (and, for `Z = 4`, 2^14), in which the probability of each additional block of occupancy fell by a factor of about
+/// 0.70 for `Z = 2`, 0.52 for `Z = 3`, 0.42 for `Z = 4`, and 0.37 for `Z = 5`, needing about 1.94, 1.06, 0.80,
+/// and 0.70 blocks per bit of `T / p`. In the sparser tail, simulated for `Z = 4` at capacity 2^14, the factor rose to 0.68,
+/// needing 1.80 blocks per bit, 2.25 times as many. Since the tail sets the overflow probability, every rate is multiplied
+/// by 2.25 and rounded up, giving 9/2, 5/2, 2, and 8/5 blocks per bit.
+pub const fn default_overflow_size(capacity: Address, z: BucketSize) -> StashSize {
+    // log2(2^20 accesses per block) + log2(1 / p), plus log2 of the capacity (rounded up).
+    let capacity_bits = match capacity.checked_next_power_of_two() {
+        Some(power) => power.trailing_zeros() as u64,
+        None => Address::BITS as u64,
+    };
+    let bits = 20 + 20 + capacity_bits;
+    let (numerator, denominator) = match z {
+        0..=2 => (9, 2),
+        3 => (5, 2),
+        4 => (2, 1),
+        _ => (8, 5),
+    };
+    let overflow_size = (bits * numerator).div_ceil(denominator);
+    if overflow_size < DEFAULT_STASH_OVERFLOW_SIZE {
+        DEFAULT_STASH_OVERFLOW_SIZE
+    } else {
+        overflow_size
+    }
+}
+
+/// The largest value size, `size_of::<V>()` in bytes, of a `PathOram`.
+///
+/// Every access obliviously sorts its stash of about `Z * height + overflow_size` blocks, exchanging values
+/// `O(n log^2 n)` times, so its cost grows with the value size even though no larger value is read or written.
+/// The exchanges are done in place, but a larger value is still copied whole where a single value is returned.
+/// Larger values should be split into blocks of at most this size, stored at separate addresses.
+pub const MAX_VALUE_SIZE: usize = 1 << 16;
+
 const LINEAR_TIME_ORAM_CUTOFF: RecursionCutoff = 1 << 10;
 
 /// A doubly oblivious Path ORAM.
@@ -49,13 +115,19 @@ const LINEAR_TIME_ORAM_CUTOFF: RecursionCutoff = 1 << 10;
 ///     Must be a power of two and must be at least 2 (otherwise the recursion will not terminate).
 ///     Otherwise, can be freely tuned for performance.
 ///     Larger `AB` means fewer levels of recursion but higher costs for accessing each level.
-/// - Recursion threshold: the maximum number of position blocks that will be stored in a recursive Path ORAM.
-///     Below this value, the position map will be a linear scanning ORAM.
-///     Can be freely tuned for performance.
+/// - Recursion cutoff: the maximum number of position blocks that will be stored in a linear scanning ORAM.
+///     A position map of at most this many blocks (inclusive) is a linear scanning ORAM;
+///     a larger one is itself a Path ORAM, with a position map chosen in the same way (see [`PathOram::recursion_profile`]).
+///     Can be freely tuned for performance (see [`RecursionCutoff`] for guidance).
 ///     A larger values means fewer levels of recursion, but a more expensive base position map.
 /// - Overflow size: The number of blocks that the stash can store between ORAM accesses without overflowing.
 ///     Along with the bucket size, this value affects the probability of stash overflow (see below)
 ///     and should be set with care.
+/// - Eviction strategy `E`: how stash blocks are assigned to buckets when a path is written back.
+///     Defaults to [`GreedyEviction`], the strategy of the original Path ORAM paper.
+/// - Index word `I`: the integer type in which each block stores its address and position (see [`IndexWord`]).
+///     Defaults to `u64`. Choosing `u32` halves the metadata stored with each block, which matters for small block types,
+///     but limits the capacity to 2^31 blocks.
 ///
 /// ## Security
 ///
@@ -72,16 +144,174 @@ const LINEAR_TIME_ORAM_CUTOFF: RecursionCutoff = 1 << 10;
 /// and that setting SO = 40 is enough to reduce this probability to below 2^{-50} (Figure 3).
 /// The authors conservatively estimate that setting SO = 89 suffices for 2^{-80} overflow probability.
 /// The choice Z = 3 is also popular, although the probability of overflow is less well understood.
-#[derive(Debug)]
-pub struct PathOram<V: OramBlock, const Z: BucketSize, const AB: BlockSize> {
+///
+/// ## Deferred eviction
+///
+/// By default, each access evicts to the path it read before returning.
+/// After [`PathOram::set_max_pending_evictions`] is called with a nonzero bound,
+/// an access instead moves only the requested block from its path into the stash,
+/// and queues the path for eviction by a later call to [`PathOram::evict_one`].
+/// Paths are evicted in the order they were read, so every path read is eventually evicted.
+/// If an access finds the queue full, it first evicts the oldest pending path itself.
+///
+/// Each pending eviction holds one accessed block in the stash, and the stash grows by one slot per pending eviction
+/// (so that its size reveals nothing beyond the public number of pending evictions).
+/// The stash therefore holds up to `max_pending_evictions` more blocks than it would without deferral,
+/// and the overflow size should be increased by that amount to keep the same overflow probability.
+///
+/// An address may be accessed again while its block is still in the stash, before the path it was taken from is evicted.
+/// Such an access is performed exactly as any other: the position map is updated to a fresh leaf,
+/// the path to the block's previous leaf (which no longer holds it) is read and a dummy block taken from it,
+/// and the scan of the stash finds the block and moves it to the fresh leaf. Nothing observable distinguishes the two cases.
+/// The block is never duplicated, so [`PathOram::stash_occupancy`] counts it once however often it is accessed.
+///
+/// The `Debug` output of a `PathOram` shows only its parameters and counters, never the blocks it stores
+/// (their values, addresses, or positions). With the `diagnostics` feature, `PathOram::debug_dump` shows everything.
+#[allow(clippy::doc_overindented_list_items)]
+pub struct PathOram<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy = GreedyEviction,
+    I: IndexWord = TreeIndex,
+> {
     /// The underlying untrusted memory that the ORAM is obliviously accessing on behalf of its client.
-    physical_memory: Vec<Bucket<V, Z>>,
-    /// The Path ORAM stash.
-    stash: ObliviousStash<V>,
+    pub(crate) physical_memory: Vec<Bucket<V, Z, I>>,
+    /// The Path ORAM stash. Unlike `physical_memory`, it is part of the trusted client state.
+    pub(crate) stash: ObliviousStash<V, E, I>,
     /// The Path ORAM position map.
-    position_map: PositionMap<AB, Z>,
+    pub(crate) position_map: PositionMap<AB, Z>,
     /// The height of the Path ORAM tree data structure.
-    height: TreeHeight,
+    pub(crate) height: TreeHeight,
+    /// The paths read by accesses but not yet evicted, oldest first.
+    pub(crate) pending_evictions: VecDeque<TreeIndex>,
+    /// The maximum length of `pending_evictions`. If 0, accesses evict synchronously.
+    pub(crate) max_pending_evictions: usize,
+    /// The paths to which accesses evict.
+    pub(crate) eviction_order: EvictionOrder,
+    /// The number of evictions made in reverse-lexicographic order, modulo 2^64.
+    pub(crate) eviction_count: u64,
+    /// The source of new leaves for accessed blocks, or `None` to draw them from the RNG passed to each access.
+    pub(crate) leaf_sampler: Option<Box<dyn LeafSampler>>,
+    /// The parameters this ORAM was constructed with, kept for constructing copies of it.
+    pub(crate) overflow_size: StashSize,
+    pub(crate) recursion_cutoff: RecursionCutoff,
+    /// True if an operation failed partway, after which every operation fails until the ORAM is recovered.
+    pub(crate) poisoned: bool,
+    /// The internal RNG masking new leaves, if a reseed interval is set.
+    #[cfg(feature = "std")]
+    pub(crate) reseeder: Option<Reseeder>,
+    /// The state to restore if the transaction in progress fails, if there is one.
+    pub(crate) undo_log: Option<UndoLog<V, Z, AB, I>>,
+    /// The padding levels to which every access makes a dummy access (see the `uniform_depth` module).
+    pub(crate) recursion_padding: Vec<PaddingOram<Z, AB>>,
+    /// When this ORAM rebuilds itself (see the `rebuild` module).
+    pub(crate) rebuild_schedule: RebuildSchedule,
+}
+
+// The contents of the tree, stash, and position map are secret, so only the ORAM's parameters and counters are printed.
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    core::fmt::Debug for PathOram<V, Z, AB, E, I>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("PathOram");
+        debug
+            .field("block_capacity", &self.physical_memory.len())
+            .field("height", &self.height)
+            .field("blocks_per_bucket", &Z)
+            .field("positions_per_block", &AB)
+            .field("stash", &self.stash)
+            .field("position_map", &self.position_map)
+            .field("pending_evictions", &self.pending_evictions.len())
+            .field("max_pending_evictions", &self.max_pending_evictions)
+            .field("eviction_order", &self.eviction_order)
+            .field("eviction_count", &self.eviction_count)
+            .field("overflow_size", &self.overflow_size)
+            .field("recursion_cutoff", &self.recursion_cutoff)
+            .field("padding_levels", &self.recursion_padding.len())
+            .field("poisoned", &self.poisoned)
+            .field("rebuild_schedule", &self.rebuild_schedule);
+        #[cfg(feature = "std")]
+        debug.field("reseeder", &self.reseeder);
+        debug.finish_non_exhaustive()
+    }
+}
+
+/// One level of the position map recursion of a [`PathOram`], as reported by [`PathOram::recursion_profile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PositionMapLevel {
+    /// The number of position blocks at this level.
+    pub block_capacity: Address,
+    /// True if this level is a linear scanning ORAM (the base case), and false if it is a Path ORAM.
+    pub is_linear: bool,
+    /// The number of bytes of physical memory read and written by an access to this level alone.
+    pub physical_bytes_per_access: u64,
+}
+
+/// The geometry of a [`PathOram`], as returned by [`PathOram::validate_parameters_with`] without constructing it.
+/// It describes exactly the ORAM that [`PathOram::new_with_parameters`] would construct with the same arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OramPlan {
+    /// The number of addresses, which is also the number of buckets in the tree.
+    pub block_capacity: Address,
+    /// The height of the tree, which has `block_capacity / 2` leaves.
+    pub height: u64,
+    /// The number of blocks the stash holds beyond one path of the tree.
+    pub overflow_size: StashSize,
+    /// The largest number of position blocks for which the position map is a linear scan.
+    pub recursion_cutoff: RecursionCutoff,
+    /// The levels of the position map recursion, as [`PathOram::recursion_profile`] would report them.
+    pub position_map_levels: Vec<PositionMapLevel>,
+    /// The number of bytes of memory, as [`PathOram::memory_footprint`] would report it, saturating at `u64::MAX`.
+    pub memory_bytes: u64,
+}
+
+/// The number of blocks of physical memory that an access to a [`PathOram`] reads and writes,
+/// as returned by [`PathOram::expected_physical_accesses_per_op`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhysicalCost {
+    /// The number of blocks read from the ORAM's own tree.
+    pub data_reads: u64,
+    /// The number of blocks written to the ORAM's own tree.
+    pub data_writes: u64,
+    /// The number of position blocks read from the levels of the position map recursion.
+    pub position_map_reads: u64,
+    /// The number of position blocks written to the levels of the position map recursion.
+    pub position_map_writes: u64,
+}
+
+/// The leaves whose paths [`PathOram::warmup`] touches: a prefix of the leaves in reverse-lexicographic order
+/// (see [`reverse_lexicographic_leaf`]), so that the paths touched are spread evenly over the tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WarmupPlan {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl WarmupPlan {
+    /// Touches every leaf exactly once.
+    pub fn full() -> Self {
+        Self::fraction(1, 1)
+    }
+
+    /// Touches `numerator / denominator` of the leaves (rounded up, and at most all of them).
+    /// A `denominator` of 0 touches no leaves.
+    pub fn fraction(numerator: u64, denominator: u64) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    // Returns the number of leaves to touch in a tree with `number_of_leaves` leaves.
+    fn leaves_touched(&self, number_of_leaves: u64) -> u64 {
+        if self.denominator == 0 {
+            return 0;
+        }
+        let leaves = (u128::from(number_of_leaves) * u128::from(self.numerator))
+            .div_ceil(u128::from(self.denominator));
+        leaves.min(number_of_leaves.into()) as u64
+    }
 }
 
 /// An `Oram` suitable for most use cases, with reasonable default choices of parameters.
@@ -90,7 +320,7 @@ pub struct DefaultOram<V: OramBlock>(DefaultOramBackend<V>);
 
 #[derive(Debug)]
 enum DefaultOramBackend<V: OramBlock> {
-    Path(PathOram<V, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK>),
+    Path(Box<PathOram<V, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK>>),
     Linear(LinearTimeOram<V>),
 }
 
@@ -115,6 +345,30 @@ impl<V: OramBlock> Oram for DefaultOram<V> {
             DefaultOramBackend::Linear(l) => l.access(index, callback, rng),
         }
     }
+
+    fn read_into<R: rand::RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        out: &mut V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        match &mut self.0 {
+            DefaultOramBackend::Path(p) => p.read_into(index, out, rng),
+            DefaultOramBackend::Linear(l) => l.read_into(index, out, rng),
+        }
+    }
+
+    fn write_from<R: rand::RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        new_value: &V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        match &mut self.0 {
+            DefaultOramBackend::Path(p) => p.write_from(index, new_value, rng),
+            DefaultOramBackend::Linear(l) => l.write_from(index, new_value, rng),
+        }
+    }
 }
 
 impl<V: OramBlock> DefaultOram<V> {
@@ -132,67 +386,295 @@ impl<V: OramBlock> DefaultOram<V> {
                 block_capacity,
             )?)))
         } else {
-            Ok(Self(DefaultOramBackend::Path(PathOram::<
-                V,
-                DEFAULT_BLOCKS_PER_BUCKET,
-                DEFAULT_POSITIONS_PER_BLOCK,
-            >::new_with_parameters(
-                block_capacity,
-                rng,
-                DEFAULT_STASH_OVERFLOW_SIZE,
-                DEFAULT_RECURSION_CUTOFF,
-            )?)))
+            Ok(Self(DefaultOramBackend::Path(
+                Box::new(PathOram::<
+                    V,
+                    DEFAULT_BLOCKS_PER_BUCKET,
+                    DEFAULT_POSITIONS_PER_BLOCK,
+                >::new_with_parameters(
+                    block_capacity,
+                    rng,
+                    default_overflow_size(block_capacity, DEFAULT_BLOCKS_PER_BUCKET),
+                    DEFAULT_RECURSION_CUTOFF,
+                )?),
+            )))
+        }
+    }
+
+    /// Returns the number of real blocks currently in the stash overflow, or 0 if the ORAM has no stash
+    /// (which is the case for small capacities). See [`PathOram::stash_occupancy`].
+    pub fn stash_occupancy(&self) -> StashSize {
+        match &self.0 {
+            DefaultOramBackend::Path(p) => p.stash_occupancy(),
+            DefaultOramBackend::Linear(_) => 0,
         }
     }
 }
 
-impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> PathOram<V, Z, AB> {
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    // Rejects the parameters that are invalid whatever the capacity. This is evaluated when `new_with_parameters`
+    // is instantiated, so that constructing an ORAM with invalid parameters fails to compile.
+    const VALID_PARAMETERS: () = {
+        assert!(Z >= 2, "the bucket size Z must be at least 2");
+        assert!(
+            AB >= 2 && AB.is_power_of_two(),
+            "the position block size AB must be a power of two and at least 2"
+        );
+    };
+
+    /// As [`PathOram::validate_parameters_with`], with the overflow size and recursion cutoff that [`DefaultOram`] uses:
+    /// [`default_overflow_size`] and [`DEFAULT_RECURSION_CUTOFF`].
+    pub fn validate_parameters(block_capacity: Address) -> Result<OramPlan, OramError> {
+        Self::validate_parameters_with(
+            block_capacity,
+            default_overflow_size(block_capacity, Z),
+            DEFAULT_RECURSION_CUTOFF,
+        )
+    }
+
+    /// Makes every check that [`PathOram::new_with_parameters`] makes of its arguments, without constructing anything,
+    /// and returns the geometry of the ORAM it would construct, e.g., to validate a configuration file up front.
+    /// The constructors build their ORAMs from this plan, so a plan always matches the ORAM.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`PathOram::new_with_parameters`], except those of allocation.
+    pub fn validate_parameters_with(
+        block_capacity: Address,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<OramPlan, OramError> {
+        let () = Self::VALID_PARAMETERS;
+
+        if !block_capacity.is_power_of_two() | (block_capacity <= 1) {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "ORAM capacity".to_string(),
+                parameter_value: block_capacity.to_string(),
+            });
+        }
+
+        // Every address and tree index must fit in an `I`, with `I::MAX` left free to mark dummy blocks.
+        if block_capacity > I::MAX.into() {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("ORAM capacity (with {}-bit indices)", I::BITS),
+                parameter_value: block_capacity.to_string(),
+            });
+        }
+
+        if recursion_cutoff == 0 {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Recursion cutoff".to_string(),
+                parameter_value: recursion_cutoff.to_string(),
+            });
+        }
+
+        if core::mem::size_of::<V>() > MAX_VALUE_SIZE {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("Value size (at most {MAX_VALUE_SIZE} bytes)"),
+                parameter_value: core::mem::size_of::<V>().to_string(),
+            });
+        }
+
+        let height: u64 = (block_capacity.ilog2() - 1).into();
+        let path_size = u64::try_from(Z)? * (height + 1);
+        let bucket_bytes = u64::try_from(core::mem::size_of::<Bucket<V, Z, I>>())?;
+        let tree_bytes = block_capacity.saturating_mul(bucket_bytes);
+        let stash_bytes =
+            ObliviousStash::<V, E, I>::bytes_for_len(path_size.saturating_add(overflow_size))?;
+        let (position_map_levels, position_map_bytes) =
+            PositionMap::<AB, Z>::plan(block_capacity, overflow_size, recursion_cutoff)?;
+
+        Ok(OramPlan {
+            block_capacity,
+            height,
+            overflow_size,
+            recursion_cutoff,
+            position_map_levels,
+            memory_bytes: tree_bytes
+                .saturating_add(stash_bytes)
+                .saturating_add(position_map_bytes),
+        })
+    }
+
     /// Returns a new `PathOram` mapping addresses `0 <= address < block_capacity` to default `V` values,
     /// with a stash overflow size of `overflow_size` blocks, and a recursion cutoff of `recursion_cutoff`.
     /// (See [`PathOram`]) for a description of these parameters).
     ///
+    /// Initially, every block is stored in a leaf bucket, two to a leaf: the addresses are randomly permuted,
+    /// and the `i`th pair of addresses in the permutation is assigned the `i`th leaf.
+    /// So the initial leaf of each address is uniformly random, but the leaves of different addresses are not independent,
+    /// since each leaf is assigned exactly two addresses. Each access assigns the accessed address a fresh,
+    /// independent leaf, so once every address has been accessed, the leaves are independent and uniformly random.
+    ///
+    /// The smallest capacity is 2, for a tree of height 0: a single bucket, which every access reads and rewrites in full.
+    /// Trees this small (and of height 1, capacity 4) are supported, and arise as the last levels of a recursive position map
+    /// when `recursion_cutoff` is small. But an access to one reads and writes `Z` blocks per bucket of its path,
+    /// plus its position map, where a linear scan would read and write only `capacity` blocks,
+    /// so a larger `recursion_cutoff` (such as [`DEFAULT_RECURSION_CUTOFF`]) is cheaper.
+    ///
     /// # Errors
     ///
     /// Returns an `InvalidConfigurationError` in the following cases.
     ///
     /// - `block_capacity` is 0, 1, or is not a power of two.
-    /// - `AB` is 0, 1, or is not a power of two.
-    /// - `Z` is 0 or 1.
+    /// - `block_capacity` does not fit in an `I`.
     /// - `recursion_cutoff` is 0.
+    /// - `V` is larger than [`MAX_VALUE_SIZE`] bytes.
     ///
-    /// If `block_capacity` is not a power of two, returns an `InvalidConfigurationError`.
+    /// Calls with a `Z` of 0 or 1, or with an `AB` that is 0, 1, or not a power of two, fail to compile.
     pub fn new_with_parameters<R: Rng + CryptoRng>(
         block_capacity: Address,
         rng: &mut R,
         overflow_size: StashSize,
         recursion_cutoff: RecursionCutoff,
     ) -> Result<Self, OramError> {
-        log::info!("PathOram::new(capacity = {})", block_capacity,);
+        Self::new_internal(
+            block_capacity,
+            None,
+            None,
+            None,
+            rng,
+            overflow_size,
+            recursion_cutoff,
+        )
+    }
 
-        if !block_capacity.is_power_of_two() | (block_capacity <= 1) {
-            return Err(OramError::InvalidConfigurationError {
-                parameter_name: "ORAM capacity".to_string(),
-                parameter_value: block_capacity.to_string(),
-            });
-        }
+    /// As [`PathOram::new_with_parameters`], but drawing the initial placement of blocks, and the new leaf of every accessed block,
+    /// from `leaf_sampler` rather than from `rng` (see the [`leaf_sampler`](crate::leaf_sampler) module).
+    /// `rng` and the RNG passed to each access are still used by a recursive position map.
+    pub fn new_with_leaf_sampler<S: LeafSampler + 'static, R: Rng + CryptoRng>(
+        block_capacity: Address,
+        leaf_sampler: S,
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<Self, OramError> {
+        Self::new_internal(
+            block_capacity,
+            Some(Box::new(leaf_sampler)),
+            None,
+            None,
+            rng,
+            overflow_size,
+            recursion_cutoff,
+        )
+    }
+
+    /// As [`PathOram::new_with_parameters`], but storing `initial_values[i]` at each address `i` rather than a default value,
+    /// and with capacity `initial_values.len()`. Initialization obliviously sorts the values into place,
+    /// so reveals nothing about them, and is faster than writing them one by one.
+    pub fn new_with_initial_values<R: Rng + CryptoRng>(
+        initial_values: &[V],
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<Self, OramError> {
+        Self::new_internal(
+            initial_values.len().try_into()?,
+            None,
+            Some(initial_values),
+            None,
+            rng,
+            overflow_size,
+            recursion_cutoff,
+        )
+    }
 
-        if Z <= 1 {
+    /// As [`PathOram::new_with_parameters`], but storing the tree in `buckets`, a buffer supplied by the caller
+    /// (e.g., allocated from an arena), rather than allocating it. The buffer must hold exactly `block_capacity` buckets,
+    /// whose contents are overwritten; [`PathOram::into_inner`] recovers it.
+    ///
+    /// The tree is by far the largest allocation of a Path ORAM. The stash, the position map
+    /// (about `1 / AB` of the size of the tree, for the default recursion cutoff), and the scratch space of initialization
+    /// are still allocated internally, and no access allocates more than a path's worth of memory,
+    /// except to grow the stash on overflow.
+    ///
+    /// # Errors
+    ///
+    /// As [`PathOram::new_with_parameters`], and returns an `InvalidConfigurationError` if `buckets.len()`
+    /// is not `block_capacity`.
+    pub fn new_in_buffer<R: Rng + CryptoRng>(
+        block_capacity: Address,
+        buckets: Vec<Bucket<V, Z, I>>,
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<Self, OramError> {
+        if u64::try_from(buckets.len())? != block_capacity {
             return Err(OramError::InvalidConfigurationError {
-                parameter_name: "Bucket size Z".to_string(),
-                parameter_value: Z.to_string(),
+                parameter_name: format!("Tree buffer length (with capacity {block_capacity})"),
+                parameter_value: buckets.len().to_string(),
             });
         }
+        Self::new_internal(
+            block_capacity,
+            None,
+            None,
+            Some(buckets),
+            rng,
+            overflow_size,
+            recursion_cutoff,
+        )
+    }
 
-        if recursion_cutoff == 0 {
-            return Err(OramError::InvalidConfigurationError {
-                parameter_name: "Recursion cutoff".to_string(),
-                parameter_value: recursion_cutoff.to_string(),
+    /// Consumes the ORAM and returns the buffer holding its tree, e.g., to return a buffer passed to
+    /// [`PathOram::new_in_buffer`] to its arena. Every bucket is reset to empty first, so the buffer holds none of the ORAM's contents.
+    /// If the ORAM has rebuilt itself (see the [`rebuild`](crate::rebuild) module), the buffer is one it allocated.
+    pub fn into_inner(self) -> Vec<Bucket<V, Z, I>> {
+        let mut buckets = self.physical_memory;
+        buckets.iter_mut().for_each(Bucket::clear);
+        buckets
+    }
+
+    /// As [`PathOram::new_with_parameters`], but limiting the ORAM to `memory_budget` bytes of memory
+    /// (see [`PathOram::set_memory_budget`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MemoryBudgetExceeded` error, without allocating, if the ORAM would exceed its budget when constructed
+    /// (see [`PathOram::projected_memory_footprint`]).
+    pub fn new_with_memory_budget<R: Rng + CryptoRng>(
+        block_capacity: Address,
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+        memory_budget: u64,
+    ) -> Result<Self, OramError> {
+        let required =
+            Self::validate_parameters_with(block_capacity, overflow_size, recursion_cutoff)?
+                .memory_bytes;
+        if required > memory_budget {
+            return Err(OramError::MemoryBudgetExceeded {
+                required,
+                budget: memory_budget,
             });
         }
+        let mut result =
+            Self::new_with_parameters(block_capacity, rng, overflow_size, recursion_cutoff)?;
+        result.set_memory_budget(memory_budget)?;
+        Ok(result)
+    }
+
+    // Constructs a Path ORAM, drawing its placement randomness from `leaf_sampler` if there is one,
+    // storing `initial_values` (which must have length `block_capacity`) if there are any,
+    // and storing the tree in `buffer` (which must have length `block_capacity`) if there is one.
+    fn new_internal<R: Rng + CryptoRng>(
+        block_capacity: Address,
+        mut leaf_sampler: Option<Box<dyn LeafSampler>>,
+        initial_values: Option<&[V]>,
+        buffer: Option<Vec<Bucket<V, Z, I>>>,
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<Self, OramError> {
+        log_info!("PathOram::new(capacity = {})", block_capacity,);
+        let plan = Self::validate_parameters_with(block_capacity, overflow_size, recursion_cutoff)?;
 
         let number_of_nodes = block_capacity;
 
-        let height: u64 = (block_capacity.ilog2() - 1).into();
+        let height = plan.height;
 
         let path_size = u64::try_from(Z)? * (height + 1);
         let stash = ObliviousStash::new(path_size, overflow_size)?;
@@ -200,8 +682,20 @@ impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> PathOram<V, Z, AB>
         // physical_memory holds `block_capacity` buckets, each storing up to Z blocks.
         // The number of leaves is `block_capacity` / 2, which the original Path ORAM paper's experiments
         // found was sufficient to keep the stash size small with high probability.
-        let mut physical_memory = Vec::new();
-        physical_memory.resize(usize::try_from(number_of_nodes)?, Bucket::<V, Z>::default());
+        // A buffer supplied by the caller is the caller's to advise.
+        let mut physical_memory = match buffer {
+            Some(mut buffer) => {
+                buffer.iter_mut().for_each(Bucket::clear);
+                buffer
+            }
+            None => {
+                let mut physical_memory = Vec::with_capacity(usize::try_from(number_of_nodes)?);
+                #[cfg(feature = "hugepages")]
+                advise_huge_pages(physical_memory.spare_capacity_mut());
+                Bucket::extend_empty(&mut physical_memory, usize::try_from(number_of_nodes)?);
+                physical_memory
+            }
+        };
 
         // The rest of this function initializes the logical memory to contain default values at every address.
         // This is done by (1) initializing the position map with fresh random leaf identifiers,
@@ -209,11 +703,19 @@ impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> PathOram<V, Z, AB>
         let mut position_map =
             PositionMap::new(block_capacity, rng, overflow_size, recursion_cutoff)?;
 
-        let slot_indices_to_addresses =
-            random_permutation_of_0_through_n_exclusive(block_capacity, rng);
+        let slot_indices_to_addresses = match &mut leaf_sampler {
+            Some(sampler) => sampler.sample_permutation(block_capacity),
+            None => random_permutation_of_0_through_n_exclusive(block_capacity, rng),
+        };
         let addresses_to_slot_indices = invert_permutation_oblivious(&slot_indices_to_addresses)?;
+        // Sort the initial values (if any) into slot order, so that `values_by_slot[i]` is the value of the block in slot `i`.
+        let values_by_slot = initial_values.map(|initial_values| {
+            let mut values = initial_values.to_vec();
+            bitonic_sort_by_keys(&mut values, &mut addresses_to_slot_indices.clone());
+            values
+        });
         let slot_indices_to_addresses = to_usize_vec(slot_indices_to_addresses)?;
-        let mut addresses_to_slot_indices = to_usize_vec(addresses_to_slot_indices)?;
+        let addresses_to_slot_indices = to_usize_vec(addresses_to_slot_indices)?;
 
         let first_leaf_index: usize = 2u64.pow(height.try_into()?).try_into()?;
         let last_leaf_index = (2 * first_leaf_index) - 1;
@@ -228,79 +730,267 @@ impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> PathOram<V, Z, AB>
         {
             for slot_index in 0..addresses_per_leaf {
                 let address_index = (leaf_index - first_leaf_index) * 2 + slot_index;
-                tree_bucket.blocks[slot_index] = PathOramBlock::<V> {
-                    value: V::default(),
-                    address: slot_indices_to_addresses[address_index].try_into()?,
-                    position: leaf_index.try_into()?,
-                };
+                tree_bucket.blocks[slot_index] = PathOramBlock::new(
+                    values_by_slot
+                        .as_ref()
+                        .map_or_else(V::default, |values| values[address_index]),
+                    I::from_u64(slot_indices_to_addresses[address_index].try_into()?)?,
+                    I::from_u64(leaf_index.try_into()?)?,
+                );
             }
         }
 
-        // The address block size might not divide the block capacity.
-        // If it doesn't, we will have one block that contains dummy values.
-        let ab_address: Address = AB.try_into()?;
-        let mut num_blocks = block_capacity / ab_address;
-        if block_capacity % ab_address > 0 {
-            num_blocks += 1;
-            addresses_to_slot_indices.resize((block_capacity + ab_address).try_into()?, 0);
-        }
-
-        for block_index in 0..num_blocks {
-            let mut data = [0; AB];
-            for i in 0..AB {
-                let offset: usize = (block_index * ab_address).try_into()?;
-                data[i] =
-                    (first_leaf_index + addresses_to_slot_indices[offset + i] / 2).try_into()?;
-            }
-            let block = PositionBlock::<AB> { data };
-            position_map.write_position_block(block_index * ab_address, block, rng)?;
-        }
+        let positions = addresses_to_slot_indices
+            .iter()
+            .map(|slot_index| TreeIndex::try_from(first_leaf_index + slot_index / 2))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::write_position_map(&mut position_map, &positions, rng)?;
 
         Ok(Self {
             physical_memory,
             stash,
             position_map,
             height,
+            pending_evictions: VecDeque::new(),
+            max_pending_evictions: 0,
+            eviction_order: EvictionOrder::default(),
+            eviction_count: 0,
+            leaf_sampler,
+            overflow_size,
+            recursion_cutoff,
+            poisoned: false,
+            #[cfg(feature = "std")]
+            reseeder: None,
+            undo_log: None,
+            recursion_padding: Vec::new(),
+            rebuild_schedule: RebuildSchedule::default(),
         })
     }
 
-    #[cfg(test)]
-    pub(crate) fn stash_occupancy(&self) -> StashSize {
-        self.stash.occupancy()
+    // Writes `positions[address]` to the position map for every address.
+    // The address block size might not divide the number of addresses (if it exceeds the number of addresses).
+    // If it doesn't, the last position block is padded with entries for addresses beyond the capacity,
+    // which are never written and are left at the dummy position 0.
+    pub(crate) fn write_position_map<R: Rng + CryptoRng>(
+        position_map: &mut PositionMap<AB, Z>,
+        positions: &[TreeIndex],
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        let mut updates = Vec::with_capacity(positions.len());
+        for (address, position) in positions.iter().enumerate() {
+            updates.push((Address::try_from(address)?, *position));
+        }
+        position_map.write_many(&updates, rng)
     }
-}
 
-impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> Oram for PathOram<V, Z, AB> {
-    type V = V;
+    /// Sets the maximum number of read paths that may await eviction, evicting synchronously any paths beyond the new bound.
+    /// A bound of 0 (the default) makes every access evict synchronously. See the [`PathOram`] documentation on deferred eviction.
+    pub fn set_max_pending_evictions(&mut self, bound: usize) -> Result<(), OramError> {
+        self.max_pending_evictions = bound;
+        while self.pending_evictions.len() > bound {
+            self.evict_one()?;
+        }
+        Ok(())
+    }
 
-    // REVIEW NOTE: This function has not been modified.
-    fn access<R: Rng + CryptoRng, F: Fn(&V) -> V>(
+    /// Sets the paths to which accesses evict (see [`EvictionOrder`]). May be changed at any time:
+    /// blocks that an eviction path cannot hold stay in the stash until a later eviction places them.
+    /// With deferred eviction, the paths queued are those chosen by the order.
+    pub fn set_eviction_order(&mut self, order: EvictionOrder) {
+        self.eviction_order = order;
+    }
+
+    /// Returns the number of evictions made so far in [`EvictionOrder::ReverseLexicographic`] order, modulo 2^64.
+    /// The next such eviction is to the path to `reverse_lexicographic_leaf(eviction_count, height)`.
+    /// The order repeats with a period (the number of leaves) dividing 2^64, so the count wrapping is harmless.
+    pub fn eviction_count(&self) -> u64 {
+        self.eviction_count
+    }
+
+    /// Registers `observer` to be called after each eviction with the number of real blocks left in the stash overflow,
+    /// e.g., to monitor how close the stash comes to overflowing.
+    /// The observer sees only this ORAM's stash, not the stashes of a recursive position map.
+    pub fn set_occupancy_observer(&mut self, observer: fn(StashSize)) {
+        self.stash.set_occupancy_observer(observer);
+    }
+
+    /// Returns the number of read paths awaiting eviction.
+    pub fn pending_evictions(&self) -> usize {
+        self.pending_evictions.len()
+    }
+
+    /// Evicts the oldest read path awaiting eviction, if there is one.
+    /// Returns whether a path was evicted.
+    pub fn evict_one(&mut self) -> Result<bool, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        match self.pending_evictions.pop_front() {
+            Some(position) => {
+                let result = self
+                    .log_path(position)
+                    .and_then(|()| self.stash.evict_path(&mut self.physical_memory, position));
+                self.poisoned = result.is_err();
+                result.map(|()| true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Reads and rewrites the paths to the leaves chosen by `plan`, without changing the value stored at any address,
+    /// e.g., to prime the caches of a backing store so that the first real accesses do not hit cold storage.
+    /// Each path is read into the stash and evicted back to, as by an access, so a warmup may move blocks nearer the leaves,
+    /// but never changes their positions. The paths touched depend only on `plan`, so reveal nothing.
+    /// Returns the number of leaves touched. Touching a leaf reads and writes `Z * (height + 1)` blocks.
+    ///
+    /// Does not touch a recursive position map. Takes no RNG, since no leaf is drawn: the paths are fixed by `plan`,
+    /// and no block is given a new position.
+    pub fn warmup(&mut self, plan: WarmupPlan) -> Result<u64, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        let leaves = plan.leaves_touched(1 << self.height);
+        for counter in 0..leaves {
+            let leaf = reverse_lexicographic_leaf(counter, self.height);
+            let result = self
+                .stash
+                .read_from_path(&mut self.physical_memory, leaf)
+                .and_then(|()| self.stash.write_to_path(&mut self.physical_memory, leaf))
+                .and_then(|()| self.stash.release_slots(0));
+            if result.is_err() {
+                self.poisoned = true;
+                return result.map(|()| counter);
+            }
+        }
+        Ok(leaves)
+    }
+
+    /// Returns true if the ORAM is poisoned: an access or eviction failed partway, possibly leaving the ORAM inconsistent,
+    /// so every subsequent access or eviction fails with a `Poisoned` error. Any error from an access to an address
+    /// within the capacity, or from an eviction, poisons the ORAM (such as a `MemoryBudgetExceeded` error on stash growth,
+    /// or an error from a recursive position map); an `AddressOutOfBoundsError` does not.
+    /// With the `diagnostics` feature, `PathOram::try_recover` rebuilds a poisoned ORAM.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Performs an access to `address` as [`Oram::access`] does, but passes `f` a mutable reference to the value
+    /// while its block is in the stash, so that `f` can read and overwrite it any number of times,
+    /// and returns whatever `f` returns. `f` is called exactly once, on a copy of the value that is written back afterwards,
+    /// so a read-modify-write-read sequence costs one access rather than several.
+    /// As with the callback of `Oram::access`, `f` must take time independent of the value for the access to be oblivious.
+    ///
+    /// # Errors
+    ///
+    /// As [`Oram::access`].
+    pub fn with_block<T, F: FnOnce(&mut V) -> T, R: Rng + CryptoRng>(
         &mut self,
         address: Address,
-        callback: F,
+        f: F,
         rng: &mut R,
-    ) -> Result<V, OramError> {
+    ) -> Result<T, OramError> {
+        self.access_with(
+            address,
+            |stash, address, new_position| stash.modify(address, new_position, f),
+            rng,
+        )
+    }
+
+    // Performs an access to `address`, in which `stash_operation` reads and/or updates the target block
+    // once it is stash-resident, given the stash, `address`, and the block's new position.
+    fn access_with<
+        T,
+        R: Rng + CryptoRng,
+        F: FnOnce(&mut ObliviousStash<V, E, I>, Address, TreeIndex) -> Result<T, OramError>,
+    >(
+        &mut self,
+        address: Address,
+        stash_operation: F,
+        rng: &mut R,
+    ) -> Result<T, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        self.check_reseeded()?;
+
         // This operation is not constant-time, but only leaks whether the ORAM index is well-formed or not.
-        if address > self.block_capacity()? {
+        if address >= self.block_capacity()? {
             return Err(OramError::AddressOutOfBoundsError {
                 attempted: address,
                 capacity: self.block_capacity()?,
             });
         }
 
+        self.rebuild_if_due(rng)?;
+        let result = self.access_in_bounds(address, stash_operation, rng);
+        self.poisoned = result.is_err();
+        if result.is_ok() {
+            self.count_access();
+        }
+        result
+    }
+
+    // Returns a `ReseedRequired` error if this ORAM was restored from a snapshot and has not since been reseeded.
+    pub(crate) fn check_reseeded(&self) -> Result<(), OramError> {
+        #[cfg(feature = "std")]
+        if self.is_awaiting_reseed() {
+            return Err(OramError::ReseedRequired);
+        }
+        Ok(())
+    }
+
+    // Draws a new leaf for an accessed block, from the leaf sampler if there is one and otherwise from `rng`,
+    // masked by the internal RNG if a reseed interval is set.
+    pub(crate) fn sample_new_position<R: Rng + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<TreeIndex, OramError> {
+        let leaf = match &mut self.leaf_sampler {
+            Some(sampler) => sampler.sample_leaf(self.height)?,
+            None => CompleteBinaryTreeIndex::random_leaf(self.height, rng)?,
+        };
+        #[cfg(feature = "std")]
+        if let Some(reseeder) = &mut self.reseeder {
+            return reseeder.mask_leaf(leaf, self.height);
+        }
+        Ok(leaf)
+    }
+
+    // Performs an access as `access_with`, for an address known to be within the capacity.
+    pub(crate) fn access_in_bounds<
+        T,
+        R: Rng + CryptoRng,
+        F: FnOnce(&mut ObliviousStash<V, E, I>, Address, TreeIndex) -> Result<T, OramError>,
+    >(
+        &mut self,
+        address: Address,
+        stash_operation: F,
+        rng: &mut R,
+    ) -> Result<T, OramError> {
         // Get the position of the target block (with address `address`),
         // and update that block's position map entry to a fresh random position
-        let new_position = CompleteBinaryTreeIndex::random_leaf(self.height, rng)?;
+        let new_position = self.sample_new_position(rng)?;
         let position = self.position_map.write(address, new_position, rng)?;
 
         assert!(position.is_leaf(self.height));
+        self.access_recursion_padding(rng)?;
+        self.log_path(position)?;
+
+        if self.evicts_separately() {
+            return self.access_deferring_eviction(
+                address,
+                position,
+                new_position,
+                stash_operation,
+            );
+        }
 
         self.stash
             .read_from_path(&mut self.physical_memory, position)?;
 
-        // Scan the stash for the target block, read its value into `result`,
+        // Scan the stash for the target block, read its value,
         // and overwrite its position (and possibly its value).
-        let result = self.stash.access(address, new_position, callback);
+        let result = stash_operation(&mut self.stash, address, new_position);
 
         // Evict blocks from the stash into the path that was just read,
         // replacing them with dummy blocks.
@@ -310,45 +1000,1235 @@ impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> Oram for PathOram<V
         result
     }
 
-    fn block_capacity(&self) -> Result<Address, OramError> {
-        Ok(u64::try_from(self.physical_memory.len())?)
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // The foreground part of an access that does not evict to the path it reads (with deferred eviction or a
+    // reverse-lexicographic eviction order): moves the target block from the path to `position` into the stash,
+    // accesses it there, and queues an eviction.
+    fn access_deferring_eviction<
+        T,
+        F: FnOnce(&mut ObliviousStash<V, E, I>, Address, TreeIndex) -> Result<T, OramError>,
+    >(
+        &mut self,
+        address: Address,
+        position: TreeIndex,
+        new_position: TreeIndex,
+        stash_operation: F,
+    ) -> Result<T, OramError> {
+        self.stash
+            .take_block(&mut self.physical_memory, position, address)?;
 
-    use crate::{bucket::*, test_utils::*};
+        let result = stash_operation(&mut self.stash, address, new_position);
 
-    use rand::{rngs::StdRng, SeedableRng};
+        self.queue_eviction(position)?;
 
-    // Test default parameters. For the small capacity used in the tests, this means a linear position map.
-    create_path_oram_correctness_tests!(4, 8, 16384, 40);
+        result
+    }
 
-    // The remaining tests have RECURSION_CUTOFF = 1 in order to test the recursive position map.
+    // Returns whether accesses move only their target block from the path they read,
+    // and evict (now or later) to a path queued by `queue_eviction`, rather than rewriting the path they read.
+    pub(crate) fn evicts_separately(&self) -> bool {
+        self.max_pending_evictions > 0 || self.eviction_order == EvictionOrder::ReverseLexicographic
+    }
 
-    // Default parameters, but with RECURSION_CUTOFF = 1.
-    create_path_oram_correctness_tests!(4, 8, 1, 40);
+    // Queues the eviction owed by an access that took its target block from the path to `position`,
+    // to that path or to the next in reverse-lexicographic order, as the eviction order requires.
+    // Then bounds the stash growth by evicting the oldest queued paths synchronously while the queue is too long,
+    // so that with no deferral the eviction is made immediately.
+    pub(crate) fn queue_eviction(&mut self, position: TreeIndex) -> Result<(), OramError> {
+        let eviction_path = match self.eviction_order {
+            EvictionOrder::ReadPath => position,
+            EvictionOrder::ReverseLexicographic => {
+                let leaf = reverse_lexicographic_leaf(self.eviction_count, self.height);
+                self.eviction_count = self.eviction_count.wrapping_add(1);
+                leaf
+            }
+        };
+        self.pending_evictions.push_back(eviction_path);
+        while self.pending_evictions.len() > self.max_pending_evictions {
+            if let Some(oldest) = self.pending_evictions.pop_front() {
+                self.log_path(oldest)?;
+                self.stash.evict_path(&mut self.physical_memory, oldest)?;
+            }
+        }
+        Ok(())
+    }
 
-    // Test small initial stash sizes and correct resizing of stash on overflow.
-    create_path_oram_correctness_tests!(4, 8, 1, 10);
-    create_path_oram_correctness_tests!(4, 8, 1, 0);
+    /// Returns the height of the tree, which has `block_capacity / 2` leaves.
+    pub fn height(&self) -> TreeHeight {
+        self.height
+    }
 
-    // Test small and large bucket sizes.
-    create_path_oram_correctness_tests!(3, 8, 1, 40);
-    create_path_oram_correctness_tests!(5, 8, 1, 40);
+    /// Returns the stash overflow size this ORAM was constructed with.
+    /// The stash may since have grown beyond it (see [`PathOram::stash_occupancy`]).
+    pub fn overflow_size(&self) -> StashSize {
+        self.overflow_size
+    }
 
-    // Test small and large position map blocks.
-    create_path_oram_correctness_tests!(4, 2, 1, 40);
-    create_path_oram_correctness_tests!(4, 64, 1, 40);
+    /// Returns the recursion cutoff this ORAM was constructed with.
+    pub fn recursion_cutoff(&self) -> RecursionCutoff {
+        self.recursion_cutoff
+    }
 
-    // "Running sanity checks" for the default parameters.
+    /// Returns the number of real blocks currently in the stash overflow,
+    /// not counting the stashes of a recursive position map. See also [`PathOram::set_occupancy_observer`].
+    pub fn stash_occupancy(&self) -> StashSize {
+        self.stash.occupancy()
+    }
 
-    // Check that the stash size stays reasonably small over the test runs.
-    create_path_oram_stash_size_tests!(4, 8, 16384, 40);
+    /// Returns the number of bytes of physical memory that each access reads and writes (counting both),
+    /// including the accesses made to a recursive position map and to any padding levels.
+    /// An access whose eviction is deferred is charged for that eviction, as by [`PathOram::expected_physical_accesses_per_op`].
+    pub fn physical_bytes_per_access(&self) -> Result<u64, OramError> {
+        let bucket_size = u64::try_from(core::mem::size_of::<Bucket<V, Z, I>>())?;
+        let paths_per_access = if self.evicts_separately() { 2 } else { 1 };
+        let path_bytes = (self.height + 1) * bucket_size;
+        let mut padding_bytes = 0;
+        for padding_oram in &self.recursion_padding {
+            padding_bytes += 2 * (padding_oram.height + 1) * bucket_size;
+        }
+        Ok(2 * paths_per_access * path_bytes
+            + self.position_map.physical_bytes_per_access()?
+            + padding_bytes)
+    }
+
+    /// Returns the levels of this ORAM's position map recursion, from its own position map down to the linear base case.
+    /// The recursion depth, i.e., the number of position maps that are Path ORAMs, is one less than the number of levels.
+    pub fn recursion_profile(&self) -> Result<Vec<PositionMapLevel>, OramError> {
+        match &self.position_map {
+            PositionMap::Base(linear_oram) => Ok(vec![PositionMapLevel {
+                block_capacity: linear_oram.block_capacity()?,
+                is_linear: true,
+                physical_bytes_per_access: linear_oram.physical_bytes_per_access()?,
+            }]),
+            PositionMap::Recursive(block_oram) => {
+                let bucket_size =
+                    u64::try_from(core::mem::size_of::<Bucket<PositionBlock<AB>, Z>>())?;
+                let mut levels = vec![PositionMapLevel {
+                    block_capacity: block_oram.block_capacity()?,
+                    is_linear: false,
+                    physical_bytes_per_access: 2 * (block_oram.height + 1) * bucket_size,
+                }];
+                levels.extend(block_oram.recursion_profile()?);
+                Ok(levels)
+            }
+        }
+    }
+
+    /// Returns the number of blocks of physical memory that each access reads and writes, computed from `Z`,
+    /// the height of the tree, and the [`PathOram::recursion_profile`].
+    ///
+    /// An access reads and writes every block on one path of the tree, and accesses each level of the position map once:
+    /// one path of each level that is a Path ORAM, and every block of the linear base case.
+    /// With deferred eviction (see [`PathOram::set_max_pending_evictions`]), an access also reads and writes the path it evicts,
+    /// so once the queue of pending evictions is full, each access reads and writes two paths of the tree.
+    /// So does every access with a reverse-lexicographic eviction order (see [`PathOram::set_eviction_order`]).
+    /// An access whose eviction is deferred is charged for that eviction.
+    /// The padding levels set by [`PathOram::set_uniform_recursion_depth`] are counted as levels of the position map.
+    pub fn expected_physical_accesses_per_op(&self) -> Result<PhysicalCost, OramError> {
+        let bucket_size = u64::try_from(Z)?;
+        let paths_per_access = if self.evicts_separately() { 2 } else { 1 };
+        let data_blocks = paths_per_access * bucket_size * (self.height + 1);
+
+        let mut position_map_blocks = 0;
+        for level in self.recursion_profile()? {
+            position_map_blocks += if level.is_linear {
+                level.block_capacity
+            } else {
+                // A Path ORAM of capacity `c` has a tree of height `log2(c) - 1`.
+                bucket_size * u64::from(level.block_capacity.ilog2())
+            };
+        }
+        for padding_oram in &self.recursion_padding {
+            position_map_blocks += bucket_size * (padding_oram.height + 1);
+        }
+
+        Ok(PhysicalCost {
+            data_reads: data_blocks,
+            data_writes: data_blocks,
+            position_map_reads: position_map_blocks,
+            position_map_writes: position_map_blocks,
+        })
+    }
+
+    /// Returns the number of bytes of memory that this ORAM holds in its tree, its stash, its position map recursion,
+    /// and any padding levels.
+    /// This excludes the small, fixed-size bookkeeping of each level, and any allocator overhead.
+    pub fn memory_footprint(&self) -> Result<u64, OramError> {
+        let tree_bytes = u64::try_from(core::mem::size_of_val(self.physical_memory.as_slice()))?;
+        let mut padding_bytes = 0;
+        for padding_oram in &self.recursion_padding {
+            padding_bytes += padding_oram.memory_footprint()?;
+        }
+        Ok(tree_bytes
+            + self.stash.memory_footprint()?
+            + self.position_map.memory_footprint()?
+            + padding_bytes)
+    }
+
+    /// Returns the [`PathOram::memory_footprint`] that a `PathOram` constructed with these arguments would have,
+    /// saturating at `u64::MAX`, without constructing it. See [`PathOram::validate_parameters_with`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`PathOram::validate_parameters_with`].
+    pub fn projected_memory_footprint(
+        block_capacity: Address,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<u64, OramError> {
+        Ok(
+            Self::validate_parameters_with(block_capacity, overflow_size, recursion_cutoff)?
+                .memory_bytes,
+        )
+    }
+
+    /// Limits this ORAM to `memory_budget` bytes of [`PathOram::memory_footprint`], e.g., to stay within an enclave's memory limit.
+    /// An access that would grow the stash beyond the budget (on stash overflow, or to defer an eviction)
+    /// instead fails with a `MemoryBudgetExceeded` error, which poisons the ORAM (see [`PathOram::is_poisoned`]).
+    ///
+    /// The budget limits only the growth of this ORAM's own stash. The stashes of a recursive position map hold
+    /// small position blocks and grow only on their own overflows, so are counted at their size when this is called.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MemoryBudgetExceeded` error if the ORAM already exceeds `memory_budget`.
+    pub fn set_memory_budget(&mut self, memory_budget: u64) -> Result<(), OramError> {
+        let required = self.memory_footprint()?;
+        if required > memory_budget {
+            return Err(OramError::MemoryBudgetExceeded {
+                required,
+                budget: memory_budget,
+            });
+        }
+        let other_bytes = required - self.stash.memory_footprint()?;
+        self.stash.set_memory_budget(memory_budget, other_bytes);
+        Ok(())
+    }
+
+    /// Returns an independent copy of this ORAM, holding the same value at every address,
+    /// e.g., to serve a read-heavy query against a consistent view on another thread while this ORAM continues to be written.
+    ///
+    /// The copy is built afresh with randomness from `rng` (as by [`PathOram::new_with_initial_values`]),
+    /// so its block placement is independent of this ORAM's, and accesses to one reveal nothing about accesses to the other.
+    /// Extracting the values takes an oblivious sort of every slot of the tree and stash, so the copy costs `O(N log^2 N)` time
+    /// and as much memory as this ORAM. It has the same parameters as this ORAM, and draws its leaves from the RNG
+    /// passed to each access even if this ORAM was constructed with a [`LeafSampler`].
+    pub fn freeze<R: Rng + CryptoRng>(&self, rng: &mut R) -> Result<Self, OramError> {
+        let mut copy = Self::new_with_initial_values(
+            &self.values_by_address()?,
+            rng,
+            self.overflow_size,
+            self.recursion_cutoff,
+        )?;
+        copy.max_pending_evictions = self.max_pending_evictions;
+        copy.eviction_order = self.eviction_order;
+        copy.set_uniform_recursion_depth(self.recursion_levels_per_access()?, rng)?;
+        Ok(copy)
+    }
+
+    /// Consumes this ORAM and returns a new one of the same capacity and parameters, storing `f(value)`
+    /// at each address at which this ORAM stores `value`, e.g., to migrate records to a new schema.
+    /// Every address is mapped, including those never written, at which `f` is applied to `V::default()`.
+    ///
+    /// As with [`PathOram::freeze`], the values are extracted by an oblivious sort of the tree and stash,
+    /// and the new ORAM is built afresh with randomness from `rng`.
+    pub fn migrate<NewV: OramBlock, F: Fn(&V) -> NewV, R: Rng + CryptoRng>(
+        self,
+        f: F,
+        rng: &mut R,
+    ) -> Result<PathOram<NewV, Z, AB, E, I>, OramError> {
+        let values: Vec<NewV> = self.values_by_address()?.iter().map(f).collect();
+        let mut migrated = PathOram::new_with_initial_values(
+            &values,
+            rng,
+            self.overflow_size,
+            self.recursion_cutoff,
+        )?;
+        migrated.max_pending_evictions = self.max_pending_evictions;
+        migrated.eviction_order = self.eviction_order;
+        migrated.set_uniform_recursion_depth(self.recursion_levels_per_access()?, rng)?;
+        Ok(migrated)
+    }
+
+    // Returns the value stored at each address, in address order, by obliviously sorting every block in the tree and stash.
+    pub(crate) fn values_by_address(&self) -> Result<Vec<V>, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        let capacity = usize::try_from(self.block_capacity()?)?;
+        let blocks: Vec<PathOramBlock<V, I>> = self
+            .physical_memory
+            .iter()
+            .flat_map(|bucket| bucket.blocks)
+            .chain(self.stash.overflow_blocks())
+            .collect();
+
+        // Sort the values of the real blocks into address order, followed by the dummy blocks.
+        let mut keys: Vec<u64> = blocks
+            .iter()
+            .map(|block| {
+                u64::conditional_select(&block.address.into(), &u64::MAX, block.ct_is_dummy())
+            })
+            .collect();
+        let mut values: Vec<V> = blocks.iter().map(|block| block.value).collect();
+        bitonic_sort_by_keys(&mut values, &mut keys);
+        values.truncate(capacity);
+        Ok(values)
+    }
+
+    /// Obliviously applies `f` to the values stored at the `len` addresses `start..start + len`,
+    /// where `start` and `len` are secret, but `len_bound` is public.
+    ///
+    /// Performs exactly `len_bound` accesses, to addresses `start..start + len_bound`, applying `f` in the first `len`
+    /// and leaving the values unchanged in the rest, so the physical access pattern depends only on `len_bound`.
+    /// Addresses past the end of the ORAM wrap around to its start.
+    /// Assumes that `len <= len_bound`; any further addresses are left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `len_bound` exceeds the capacity of the ORAM.
+    pub fn range_update<R: Rng + CryptoRng, F: Fn(&V) -> V>(
+        &mut self,
+        start: Address,
+        len: Address,
+        len_bound: Address,
+        f: F,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        let capacity = self.block_capacity()?;
+        if len_bound > capacity {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Range length bound".to_string(),
+                parameter_value: len_bound.to_string(),
+            });
+        }
+
+        for offset in 0..len_bound {
+            // The capacity is a power of two, so this is reduction modulo the capacity.
+            let address = start.wrapping_add(offset) & (capacity - 1);
+            let in_range = offset.ct_lt(&len);
+            self.access(
+                address,
+                |value| V::conditional_select(value, &f(value), in_range),
+                rng,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Folds `f` over the values stored at every address, in address order, starting from `init`,
+    /// e.g., to compute a sum or a count of the values matching a predicate.
+    ///
+    /// Performs one access to each address `0..capacity` in turn, so the physical access pattern is fixed and public.
+    /// Only the result reveals anything about the values, provided that `f` takes time independent of them
+    /// (see the [`ct_cmp`](crate::ct_cmp) module).
+    ///
+    /// # Errors
+    ///
+    /// As [`Oram::read`], in which case the fold stops at the failed access.
+    pub fn fold_all<A, F: Fn(A, &V) -> A, R: Rng + CryptoRng>(
+        &mut self,
+        init: A,
+        f: F,
+        rng: &mut R,
+    ) -> Result<A, OramError> {
+        let mut accumulator = init;
+        for address in 0..self.block_capacity()? {
+            let value = self.read(address, rng)?;
+            accumulator = f(accumulator, &value);
+        }
+        Ok(accumulator)
+    }
+
+    /// As [`PathOram::fold_all`], but without making any accesses: folds `f` over the blocks of the tree
+    /// and of the stash overflow where they lie, so it costs one pass over the tree and changes no block's position.
+    /// Reads every slot, so leaks no more than `fold_all` does.
+    ///
+    /// The values are folded in the order their blocks happen to lie in, not in address order,
+    /// so `f` should not depend on the order (as a sum or a count does not).
+    /// `f` is called on every slot, dummies included, and its result kept only for real blocks,
+    /// so it must take time independent of its arguments, and `A` must be [`ConditionallySelectable`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Poisoned` error if the ORAM is poisoned.
+    pub fn fold_all_in_place<A: ConditionallySelectable, F: Fn(A, &V) -> A>(
+        &self,
+        init: A,
+        f: F,
+    ) -> Result<A, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        let blocks = self
+            .physical_memory
+            .iter()
+            .flat_map(|bucket| bucket.blocks)
+            .chain(self.stash.overflow_blocks());
+        let mut accumulator = init;
+        for block in blocks {
+            let folded = f(accumulator, &block.value);
+            accumulator.conditional_assign(&folded, !block.ct_is_dummy());
+        }
+        Ok(accumulator)
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord> Oram
+    for PathOram<V, Z, AB, E, I>
+{
+    type V = V;
+
+    fn access<R: Rng + CryptoRng, F: Fn(&V) -> V>(
+        &mut self,
+        address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<V, OramError> {
+        self.access_with(
+            address,
+            |stash, address, new_position| stash.access(address, new_position, callback),
+            rng,
+        )
+    }
+
+    /// Reads the target block's value directly from the stash into `out`,
+    /// avoiding the copies made by returning it.
+    fn read_into<R: Rng + CryptoRng>(
+        &mut self,
+        address: Address,
+        out: &mut V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        self.access_with(
+            address,
+            |stash, address, new_position| stash.read_into(address, new_position, out),
+            rng,
+        )
+    }
+
+    /// Writes `new_value` directly into the target block in the stash, without reading its previous value.
+    fn write_from<R: Rng + CryptoRng>(
+        &mut self,
+        address: Address,
+        new_value: &V,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        self.access_with(
+            address,
+            |stash, address, new_position| stash.write_from(address, new_position, new_value),
+            rng,
+        )
+    }
+
+    fn block_capacity(&self) -> Result<Address, OramError> {
+        Ok(u64::try_from(self.physical_memory.len())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        bucket::*, deterministic_eviction::DeterministicEvictPathOram,
+        leaf_sampler::ScriptedLeafSampler, test_utils::*,
+    };
+
+    use rand::{rngs::StdRng, SeedableRng};
+    use subtle::Choice;
+
+    // Test default parameters. For the small capacity used in the tests, this means a linear position map.
+    create_path_oram_correctness_tests!(4, 8, 16384, 40);
+
+    // The remaining tests have RECURSION_CUTOFF = 1 in order to test the recursive position map.
+
+    // Default parameters, but with RECURSION_CUTOFF = 1.
+    create_path_oram_correctness_tests!(4, 8, 1, 40);
+
+    // Test small initial stash sizes and correct resizing of stash on overflow.
+    create_path_oram_correctness_tests!(4, 8, 1, 10);
+    create_path_oram_correctness_tests!(4, 8, 1, 0);
+
+    // Test small and large bucket sizes.
+    create_path_oram_correctness_tests!(3, 8, 1, 40);
+    create_path_oram_correctness_tests!(5, 8, 1, 40);
+
+    // Test small and large position map blocks.
+    create_path_oram_correctness_tests!(4, 2, 1, 40);
+    create_path_oram_correctness_tests!(4, 64, 1, 40);
+
+    // Test storing block metadata in 32-bit words, with both linear and recursive position maps.
+    type PathOramU32<V, const Z: BucketSize, const AB: BlockSize> =
+        PathOram<V, Z, AB, GreedyEviction, u32>;
+    create_path_oram_correctness_tests_helper!(PathOramU32, "_u32_indices_", 4, 8, 16384, 40);
+    create_path_oram_correctness_tests_helper!(PathOramU32, "_u32_indices_", 4, 8, 1, 40);
+
+    #[test]
+    fn index_word_limits_capacity() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = PathOramU32::<u8, 4, 8>::new_with_parameters(1 << 32, &mut rng, 40, 1);
+        assert!(matches!(
+            result,
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+    }
+
+    // Checks that a Path ORAM of `B`-byte values is correct, on the default (2 MiB) stack of a test thread.
+    // The values are built and checked in place, so that the stack holds only the few values an access returns.
+    fn check_large_values<const B: BlockSize>() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram =
+            PathOram::<BlockValue<B>, 4, 8>::new_with_parameters(8, &mut rng, 4, 1).unwrap();
+        let byte = |i: usize, address: Address, round: u8| (i as u8) ^ (address as u8) ^ round;
+        for round in 0..3 {
+            for address in 0..8 {
+                let mut value = BlockValue::<B>::default();
+                for (i, data) in value.data.iter_mut().enumerate() {
+                    *data = byte(i, address, round);
+                }
+                oram.write(address, value, &mut rng).unwrap();
+            }
+            for address in (0..8).rev() {
+                let value = oram.read(address, &mut rng).unwrap();
+                assert!(value
+                    .data
+                    .iter()
+                    .enumerate()
+                    .all(|(i, data)| *data == byte(i, address, round)));
+            }
+        }
+    }
+
+    #[test]
+    fn values_of_16_kib() {
+        check_large_values::<{ 1 << 14 }>();
+    }
+
+    #[test]
+    fn values_of_64_kib() {
+        check_large_values::<MAX_VALUE_SIZE>();
+    }
+
+    #[test]
+    fn values_larger_than_the_maximum_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = PathOram::<BlockValue<{ MAX_VALUE_SIZE + 1 }>, 4, 8>::new_with_parameters(
+            8, &mut rng, 4, 1,
+        );
+        assert!(matches!(
+            result,
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+    }
+
+    // Checks that every bucket of a Path ORAM with block type `V` and bucket size `Z` starts on a cache line.
+    fn check_physical_memory_alignment<V: OramBlock, const Z: BucketSize>() {
+        assert_eq!(core::mem::align_of::<Bucket<V, Z>>(), 64);
+        assert_eq!(core::mem::size_of::<Bucket<V, Z>>() % 64, 0);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = PathOram::<V, Z, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        for bucket in &oram.physical_memory {
+            assert_eq!(bucket as *const Bucket<V, Z> as usize % 64, 0);
+        }
+    }
+
+    #[test]
+    fn physical_memory_is_cache_line_aligned() {
+        check_physical_memory_alignment::<u8, 2>();
+        check_physical_memory_alignment::<u64, 3>();
+        check_physical_memory_alignment::<BlockValue<1>, 4>();
+        check_physical_memory_alignment::<BlockValue<64>, 5>();
+        check_physical_memory_alignment::<BlockValue<100>, 4>();
+        check_physical_memory_alignment::<BlockValue<4096>, 4>();
+    }
+
+    // Checks that the initial position map of an ORAM of capacity `capacity` holds a leaf for every address,
+    // and the dummy position 0 for the padding after the last address.
+    fn check_initial_positions<const AB: BlockSize>(capacity: Address) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram =
+            PathOram::<u8, 4, AB>::new_with_parameters(capacity, &mut rng, 40, 16384).unwrap();
+        let first_leaf_index = 1 << oram.height;
+        let last_leaf_index = 2 * first_leaf_index - 1;
+
+        let PositionMap::Base(linear_oram) = &oram.position_map else {
+            panic!("Expected a linear position map");
+        };
+        let positions: Vec<TreeIndex> = linear_oram
+            .physical_memory
+            .iter()
+            .flat_map(|block| block.data)
+            .collect();
+        let capacity = usize::try_from(capacity).unwrap();
+        assert_eq!(positions.len(), capacity.max(AB));
+        assert!(positions[..capacity]
+            .iter()
+            .all(|position| (first_leaf_index..=last_leaf_index).contains(position)));
+        assert!(positions[capacity..].iter().all(|position| *position == 0));
+    }
+
+    #[test]
+    fn position_block_size_not_dividing_capacity() {
+        check_initial_positions::<8>(2);
+        check_initial_positions::<8>(4);
+        check_initial_positions::<8>(8);
+        check_initial_positions::<8>(32);
+        check_initial_positions::<64>(32);
+    }
+
+    #[test]
+    fn physical_bytes_per_access() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // A tree of height 5, whose 128-byte buckets are read and written once each,
+        // plus a linear position map of 8 blocks of 64 bytes, each read and written once.
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 16384).unwrap();
+        assert_eq!(
+            oram.physical_bytes_per_access().unwrap(),
+            2 * 6 * 128 + 2 * 8 * 64
+        );
+
+        // With a recursive position map, the position map's own tree (of height 2, with 512-byte buckets)
+        // and its single-block linear position map are accessed too.
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        assert_eq!(
+            oram.physical_bytes_per_access().unwrap(),
+            2 * 6 * 128 + 2 * 3 * 512 + 2 * 64
+        );
+    }
+
+    #[test]
+    fn default_overflow_size_grows_with_capacity() {
+        for (capacity, expected) in [
+            (0, 80),
+            (64, 92),
+            (1000, 100),
+            (1 << 10, 100),
+            (1 << 16, 112),
+            (1 << 20, 120),
+            (1 << 26, 132),
+            (Address::MAX, 208),
+        ] {
+            assert_eq!(default_overflow_size(capacity, 4), expected);
+        }
+        // Smaller buckets need more overflow, and larger ones less.
+        assert_eq!(default_overflow_size(1 << 10, 2), 225);
+        assert_eq!(default_overflow_size(1 << 10, 3), 125);
+        assert_eq!(default_overflow_size(1 << 20, 3), 150);
+        assert_eq!(default_overflow_size(1 << 6, 5), 74);
+        assert_eq!(default_overflow_size(1 << 20, 5), 96);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = DefaultOram::<u64>::new(1 << 10, &mut rng).unwrap();
+        match oram.0 {
+            DefaultOramBackend::Path(path_oram) => {
+                let expected = if cfg!(feature = "z3-default") {
+                    125
+                } else {
+                    100
+                };
+                assert_eq!(path_oram.overflow_size, expected);
+            }
+            DefaultOramBackend::Linear(_) => panic!("expected a Path ORAM backend"),
+        }
+        // An explicit overflow size takes precedence.
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(1 << 10, &mut rng, 10, 1).unwrap();
+        assert_eq!(oram.overflow_size, 10);
+    }
+
+    #[test]
+    fn with_block_is_equivalent_to_access() {
+        let new_oram = || {
+            PathOram::<u64, 4, 8>::new_with_parameters(256, &mut StdRng::seed_from_u64(0), 40, 1)
+                .unwrap()
+        };
+        let mut expected_oram = new_oram();
+        let mut oram = new_oram();
+        let mut expected_rng = StdRng::seed_from_u64(1);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut workload_rng = StdRng::seed_from_u64(2);
+        for _ in 0..200 {
+            let address = workload_rng.gen_range(0..256);
+            let increment = workload_rng.gen_range(0..10);
+            let expected = expected_oram
+                .access(address, |value| value + increment, &mut expected_rng)
+                .unwrap();
+            let previous = oram
+                .with_block(
+                    address,
+                    |value| {
+                        let previous = *value;
+                        *value += increment;
+                        previous
+                    },
+                    &mut rng,
+                )
+                .unwrap();
+            assert_eq!(previous, expected);
+            assert_eq!(oram.debug_dump(), expected_oram.debug_dump());
+        }
+
+        assert!(matches!(
+            oram.with_block(256, |_| (), &mut rng),
+            Err(OramError::AddressOutOfBoundsError { .. })
+        ));
+    }
+
+    #[test]
+    fn with_block_returns_computed_values() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.write(5, 20, &mut rng).unwrap();
+
+        // Read, modify, and read again within one access.
+        let (before, after) = oram
+            .with_block(
+                5,
+                |value| {
+                    let before = *value;
+                    *value = 2 * before + 1;
+                    (before, *value)
+                },
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!((before, after), (20, 41));
+        assert_eq!(oram.read(5, &mut rng).unwrap(), 41);
+
+        // A callback that only inspects the value leaves it unchanged.
+        let is_odd = oram
+            .with_block(5, |value| *value % 2 == 1, &mut rng)
+            .unwrap();
+        assert!(is_odd);
+        assert_eq!(oram.read(5, &mut rng).unwrap(), 41);
+        assert_eq!(oram.read(6, &mut rng).unwrap(), 0);
+    }
+
+    #[test]
+    fn memory_budget() {
+        let mut rng = StdRng::seed_from_u64(0);
+        type TestOram = PathOram<u64, 4, 8>;
+
+        // 64 buckets of 128 bytes, 64 stash slots of 25 bytes, and a linear position map of 8 blocks of 64 bytes.
+        let footprint = 64 * 128 + 64 * 25 + 8 * 64;
+        assert_eq!(
+            TestOram::projected_memory_footprint(64, 40, 16384).unwrap(),
+            footprint
+        );
+        assert!(matches!(
+            TestOram::new_with_memory_budget(64, &mut rng, 40, 16384, footprint - 1),
+            Err(OramError::MemoryBudgetExceeded { required, budget })
+                if required == footprint && budget == footprint - 1
+        ));
+        let mut oram =
+            TestOram::new_with_memory_budget(64, &mut rng, 40, 16384, footprint).unwrap();
+        assert_eq!(oram.memory_footprint().unwrap(), footprint);
+        assert!(matches!(
+            oram.set_memory_budget(footprint - 1),
+            Err(OramError::MemoryBudgetExceeded { required, budget })
+                if required == footprint && budget == footprint - 1
+        ));
+
+        // Deferring an eviction grows the stash by one slot, which the budget has no room for.
+        oram.set_max_pending_evictions(1).unwrap();
+        assert!(matches!(
+            oram.read(0, &mut rng),
+            Err(OramError::MemoryBudgetExceeded { required, budget })
+                if required == footprint + 25 && budget == footprint
+        ));
+
+        // Without an overflow, the stash grows by 10 slots as soon as a block is left in it.
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 0, 16384).unwrap();
+        let footprint = oram.memory_footprint().unwrap();
+        oram.set_memory_budget(footprint + 9 * 25).unwrap();
+        let error = (0..1000)
+            .find_map(|i| oram.read(i % 64, &mut rng).err())
+            .unwrap();
+        assert!(matches!(
+            error,
+            OramError::MemoryBudgetExceeded { required, budget }
+                if required == footprint + 10 * 25 && budget == footprint + 9 * 25
+        ));
+
+        // The projection matches the footprint of a freshly constructed ORAM, with a recursive position map too.
+        for (capacity, overflow_size, recursion_cutoff) in [(64, 40, 1), (1024, 10, 4), (2, 0, 1)] {
+            let oram =
+                TestOram::new_with_parameters(capacity, &mut rng, overflow_size, recursion_cutoff)
+                    .unwrap();
+            assert_eq!(
+                oram.memory_footprint().unwrap(),
+                TestOram::projected_memory_footprint(capacity, overflow_size, recursion_cutoff)
+                    .unwrap()
+            );
+        }
+        assert!(TestOram::projected_memory_footprint(1 << 62, 40, 1).unwrap() > 1 << 62);
+    }
+
+    // Checks that the plan for each set of parameters describes the ORAM constructed with them.
+    fn check_plans_match<V: OramBlock, I: IndexWord>() {
+        let mut rng = StdRng::seed_from_u64(0);
+        type TestOram<V, I> = PathOram<V, 4, 8, GreedyEviction, I>;
+        for (capacity, overflow_size, recursion_cutoff) in [
+            (2, 0, 1),
+            (64, 40, 1),
+            (64, 40, 16384),
+            (1024, 10, 4),
+            (4096, 60, 8),
+        ] {
+            let plan = TestOram::<V, I>::validate_parameters_with(
+                capacity,
+                overflow_size,
+                recursion_cutoff,
+            )
+            .unwrap();
+            let oram = TestOram::<V, I>::new_with_parameters(
+                capacity,
+                &mut rng,
+                overflow_size,
+                recursion_cutoff,
+            )
+            .unwrap();
+            assert_eq!(plan.block_capacity, oram.block_capacity().unwrap());
+            assert_eq!(plan.height, oram.height());
+            assert_eq!(plan.overflow_size, oram.overflow_size());
+            assert_eq!(plan.recursion_cutoff, oram.recursion_cutoff());
+            assert_eq!(plan.position_map_levels, oram.recursion_profile().unwrap());
+            assert_eq!(plan.memory_bytes, oram.memory_footprint().unwrap());
+        }
+
+        let plan = TestOram::<V, I>::validate_parameters(1 << 12).unwrap();
+        assert_eq!(plan.overflow_size, default_overflow_size(1 << 12, 4));
+        assert_eq!(plan.recursion_cutoff, DEFAULT_RECURSION_CUTOFF);
+    }
+
+    #[test]
+    fn plans_match_constructed_orams() {
+        check_plans_match::<u64, TreeIndex>();
+        check_plans_match::<BlockValue<100>, u32>();
+        check_plans_match::<Unit, TreeIndex>();
+    }
+
+    #[test]
+    fn plans_reject_what_construction_rejects() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let is_invalid = |result: Result<OramPlan, OramError>| {
+            matches!(result, Err(OramError::InvalidConfigurationError { .. }))
+        };
+        for (capacity, recursion_cutoff) in [(0, 1), (1, 1), (48, 1), (64, 0)] {
+            assert!(is_invalid(PathOram::<u64, 4, 8>::validate_parameters_with(
+                capacity,
+                40,
+                recursion_cutoff
+            )));
+            assert!(PathOram::<u64, 4, 8>::new_with_parameters(
+                capacity,
+                &mut rng,
+                40,
+                recursion_cutoff
+            )
+            .is_err());
+        }
+        assert!(is_invalid(PathOramU32::<u64, 4, 8>::validate_parameters(
+            1 << 32
+        )));
+        assert!(is_invalid(PathOram::<
+            BlockValue<{ MAX_VALUE_SIZE + 1 }>,
+            4,
+            8,
+        >::validate_parameters(64)));
+        // Capacities too large to construct are still planned, saturating.
+        assert_eq!(
+            PathOram::<u64, 4, 8>::validate_parameters_with(1 << 62, 40, 1)
+                .unwrap()
+                .height,
+            61
+        );
+    }
+
+    #[test]
+    fn read_into_and_write_from() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (recursion_cutoff, max_pending_evictions) in [(1, 0), (16384, 0), (1, 4)] {
+            let mut oram = PathOram::<BlockValue<64>, 4, 8>::new_with_parameters(
+                64,
+                &mut rng,
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            oram.set_max_pending_evictions(max_pending_evictions)
+                .unwrap();
+            read_into_workload(&mut oram, 500);
+        }
+
+        for capacity in [64, 2048] {
+            let mut oram = DefaultOram::<BlockValue<64>>::new(capacity, &mut rng).unwrap();
+            read_into_workload(&mut oram, 500);
+        }
+    }
+
+    // Runs workloads against Path ORAMs of capacity `capacity`, small enough that the tree has height 0 or 1,
+    // with linear and recursive position maps and with deferred eviction, checking them after each.
+    fn check_degenerate_tree<const Z: BucketSize, const AB: BlockSize>(capacity: Address) {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (recursion_cutoff, overflow_size, max_pending_evictions) in
+            [(16384, 40, 0), (1, 40, 0), (1, 0, 0), (1, 40, 2)]
+        {
+            let mut new_oram = || {
+                let mut oram = PathOram::<u64, Z, AB>::new_with_parameters(
+                    capacity,
+                    &mut rng,
+                    overflow_size,
+                    recursion_cutoff,
+                )
+                .unwrap();
+                oram.set_max_pending_evictions(max_pending_evictions)
+                    .unwrap();
+                assert_eq!(1 << oram.height, capacity / 2);
+                assert!(oram.audit().unwrap().is_consistent());
+                oram
+            };
+            let mut oram = new_oram();
+            random_workload(&mut oram, 500);
+            assert!(oram.audit().unwrap().is_consistent());
+            linear_workload(&mut new_oram(), 100);
+            read_into_workload(&mut new_oram(), 100);
+
+            // Every leaf is touched, and the stash stays small.
+            let mut oram = new_oram();
+            out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+            assert_eq!(oram.warmup(WarmupPlan::full()).unwrap(), capacity / 2);
+            assert!(oram.stash_occupancy() <= capacity);
+            assert!(oram.audit().unwrap().is_consistent());
+
+            let snapshot = new_oram().export_snapshot().unwrap();
+            let mut restored = PathOram::<u64, Z, AB>::import_snapshot(snapshot).unwrap();
+            random_workload(&mut restored, 100);
+        }
+
+        let mut oram =
+            StashSizeMonitor::<u64, Z, AB>::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        random_workload(&mut oram, 1000);
+        let mut oram = DeterministicEvictPathOram::<u64, Z, AB>::new_with_parameters(
+            capacity, &mut rng, 40, 1,
+        )
+        .unwrap();
+        random_workload(&mut oram, 1000);
+    }
+
+    #[test]
+    fn degenerate_trees() {
+        // Height 0: a single bucket, which every access reads and rewrites.
+        check_degenerate_tree::<4, 8>(2);
+        check_degenerate_tree::<2, 2>(2);
+        // Height 1. With 2 addresses per position block, the position map recurses into a Path ORAM of capacity 2.
+        check_degenerate_tree::<4, 8>(4);
+        check_degenerate_tree::<4, 2>(4);
+        check_degenerate_tree::<2, 2>(4);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = PathOram::<u64, 4, 2>::new_with_parameters(4, &mut rng, 40, 1).unwrap();
+        let levels = oram.recursion_profile().unwrap();
+        assert_eq!(
+            levels
+                .iter()
+                .map(|level| (level.block_capacity, level.is_linear))
+                .collect::<Vec<_>>(),
+            [(2, false), (1, true)]
+        );
+        assert_eq!(
+            oram.expected_physical_accesses_per_op().unwrap(),
+            PhysicalCost {
+                data_reads: 8,
+                data_writes: 8,
+                position_map_reads: 4 + 1,
+                position_map_writes: 4 + 1,
+            }
+        );
+    }
+
+    #[test]
+    fn zero_sized_blocks() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // Linear and recursive position maps, an empty initial stash, and deferred eviction.
+        for (recursion_cutoff, overflow_size, max_pending_evictions) in
+            [(16384, 40, 0), (1, 40, 0), (1, 0, 0), (1, 40, 4)]
+        {
+            let mut oram = PathOram::<Unit, 4, 8>::new_with_parameters(
+                64,
+                &mut rng,
+                overflow_size,
+                recursion_cutoff,
+            )
+            .unwrap();
+            oram.set_max_pending_evictions(max_pending_evictions)
+                .unwrap();
+            random_workload(&mut oram, 1000);
+            linear_workload(&mut oram, 128);
+            read_into_workload(&mut oram, 500);
+            out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+
+            let snapshot = oram.export_snapshot().unwrap();
+            let mut restored = PathOram::<Unit, 4, 8>::import_snapshot(snapshot).unwrap();
+            random_workload(&mut restored, 100);
+        }
+
+        let mut oram = PathOramU32::<Unit, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        random_workload(&mut oram, 1000);
+        let mut oram = DefaultOram::<Unit>::new(64, &mut rng).unwrap();
+        random_workload(&mut oram, 1000);
+        let mut oram =
+            PathOram::<BlockValue<0>, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        random_workload(&mut oram, 1000);
+        let mut oram =
+            StashSizeMonitor::<Unit, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        random_workload(&mut oram, 1000);
+
+        // The blocks hold only their metadata.
+        assert_eq!(
+            core::mem::size_of::<PathOramBlock<Unit>>(),
+            core::mem::size_of::<PathOramBlock<BlockValue<0>>>()
+        );
+        assert!(
+            core::mem::size_of::<PathOramBlock<Unit>>()
+                < core::mem::size_of::<PathOramBlock<u64>>()
+        );
+    }
+
+    // "Running sanity checks" for the default parameters.
+
+    // Check that the stash size stays reasonably small over the test runs.
+    create_path_oram_stash_size_tests!(4, 8, 16384, 40);
+
+    // Runs random accesses with evictions deferred up to `max_pending_evictions`,
+    // performing pending evictions at random points in between, and checks the stash occupancy after every step.
+    fn deferred_eviction_workload(capacity: Address, max_pending_evictions: usize) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram =
+            PathOram::<BlockValue<1>, 4, 8>::new_with_parameters(capacity, &mut rng, 40, 1)
+                .unwrap();
+        oram.set_max_pending_evictions(max_pending_evictions)
+            .unwrap();
+
+        let check_occupancy = |oram: &PathOram<BlockValue<1>, 4, 8>| {
+            assert!(oram.pending_evictions() <= max_pending_evictions);
+            let pending = StashSize::try_from(oram.pending_evictions()).unwrap();
+            assert!(oram.stash_occupancy() < 10 + pending);
+        };
+
+        let mut mirror_array = vec![BlockValue::<1>::default(); usize::try_from(capacity).unwrap()];
+        for _ in 0..1000 {
+            let index = rng.gen_range(0..capacity);
+            let value = rng.gen::<BlockValue<1>>();
+            let previous = oram.write(index, value, &mut rng).unwrap();
+            assert_eq!(previous, mirror_array[usize::try_from(index).unwrap()]);
+            mirror_array[usize::try_from(index).unwrap()] = value;
+            check_occupancy(&oram);
+
+            while rng.gen_bool(0.4) {
+                oram.evict_one().unwrap();
+                check_occupancy(&oram);
+            }
+        }
+
+        // Draining the queue returns the ORAM to synchronous eviction.
+        oram.set_max_pending_evictions(0).unwrap();
+        assert_eq!(oram.pending_evictions(), 0);
+        assert!(!oram.evict_one().unwrap());
+        for index in 0..capacity {
+            assert_eq!(
+                oram.read(index, &mut rng).unwrap(),
+                mirror_array[usize::try_from(index).unwrap()]
+            );
+            check_occupancy(&oram);
+        }
+    }
+
+    #[test]
+    fn deferred_eviction_small_queue() {
+        deferred_eviction_workload(64, 1);
+        deferred_eviction_workload(64, 4);
+    }
+
+    #[test]
+    fn deferred_eviction_large_queue() {
+        deferred_eviction_workload(256, 32);
+    }
+
+    #[test]
+    fn deferred_eviction_forces_eviction_when_full() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram =
+            PathOram::<BlockValue<1>, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_max_pending_evictions(3).unwrap();
+
+        for expected_pending in [1, 2, 3, 3, 3] {
+            oram.read(0, &mut rng).unwrap();
+            assert_eq!(oram.pending_evictions(), expected_pending);
+        }
+
+        oram.set_max_pending_evictions(1).unwrap();
+        assert_eq!(oram.pending_evictions(), 1);
+        assert!(oram.evict_one().unwrap());
+        assert_eq!(oram.pending_evictions(), 0);
+    }
+
+    thread_local! {
+        static OBSERVED_OCCUPANCIES: std::cell::RefCell<Vec<StashSize>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    fn record_occupancy(occupancy: StashSize) {
+        OBSERVED_OCCUPANCIES.with(|observed| observed.borrow_mut().push(occupancy));
+    }
+
+    // Returns the positions of the real copies of the block with address `address` in the tree and in the stash overflow.
+    fn copies_of(oram: &PathOram<u64, 4, 8>, address: Address) -> Vec<TreeIndex> {
+        oram.physical_memory
+            .iter()
+            .flat_map(|bucket| bucket.blocks)
+            .chain(oram.stash.overflow_blocks())
+            .filter(|block| !bool::from(block.ct_is_dummy()) && block.address == address)
+            .map(|block| block.position)
+            .collect()
+    }
+
+    // Accesses address 3 three times before any of the accesses' paths is evicted, so that the second and third accesses
+    // find its block already in the stash, and checks that the block is neither lost nor duplicated, that its position
+    // follows the position map, and that it is counted once in the stash occupancy.
+    fn check_repeated_stash_resident_accesses(synchronous_access_first: bool) {
+        let mut rng = StdRng::seed_from_u64(0);
+        // With capacity 16 and the identity permutation, address 3 starts in leaf 9 (of leaves 8 to 15).
+        // The script chooses new leaves on alternating sides of the tree.
+        let mut sampler = ScriptedLeafSampler::new([]);
+        if synchronous_access_first {
+            sampler.push(10);
+        }
+        for leaf in [15, 8, 12, 14] {
+            sampler.push(leaf);
+        }
+        let mut oram =
+            PathOram::<u64, 4, 8>::new_with_leaf_sampler(16, sampler, &mut rng, 40, 16384).unwrap();
+        oram.set_occupancy_observer(record_occupancy);
+        let mut previous = 0;
+        if synchronous_access_first {
+            // Leaves stale copies of the blocks on the path to leaf 9, including address 3, in the path region of the stash.
+            oram.write(3, 10, &mut rng).unwrap();
+            previous = 10;
+            OBSERVED_OCCUPANCIES.with(|observed| observed.borrow_mut().clear());
+        }
+
+        oram.set_max_pending_evictions(4).unwrap();
+        for (value, new_leaf) in [(11, 15), (12, 8), (13, 12)] {
+            assert_eq!(oram.write(3, value, &mut rng).unwrap(), previous);
+            previous = value;
+            assert_eq!(copies_of(&oram, 3), [new_leaf]);
+            assert_eq!(oram.position_map.read(3, &mut rng).unwrap(), new_leaf);
+            assert_eq!(oram.stash_occupancy(), 1);
+        }
+        assert_eq!(oram.pending_evictions(), 3);
+        // No eviction has run, so the observer has not been called.
+        OBSERVED_OCCUPANCIES.with(|observed| assert!(observed.borrow().is_empty()));
+
+        // The first eviction, of the path the first of these accesses read (to leaf 9 or 10),
+        // moves the block (now bound for leaf 12) to the root, the only node those paths share.
+        // The second, of the path to leaf 15, moves it down to node 3, the ancestor at depth 1 of leaves 12 to 15.
+        while oram.evict_one().unwrap() {}
+        OBSERVED_OCCUPANCIES.with(|observed| assert_eq!(*observed.borrow(), [0, 0, 0]));
+        assert_eq!(copies_of(&oram, 3), [12]);
+        assert!(oram.physical_memory[3]
+            .blocks
+            .iter()
+            .any(|block| !bool::from(block.ct_is_dummy()) && block.address == 3));
+        assert_eq!(oram.read(3, &mut rng).unwrap(), 13);
+    }
+
+    #[test]
+    fn repeated_accesses_to_a_stash_resident_block() {
+        check_repeated_stash_resident_accesses(false);
+        check_repeated_stash_resident_accesses(true);
+    }
+
+    #[test]
+    fn occupancy_observer_fires_once_per_access() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram =
+            PathOram::<BlockValue<1>, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_occupancy_observer(record_occupancy);
+
+        for number_of_accesses in 1..=500 {
+            let address = rng.gen_range(0..64);
+            oram.write(address, rng.gen(), &mut rng).unwrap();
+
+            OBSERVED_OCCUPANCIES.with(|observed| {
+                let observed = observed.borrow();
+                assert_eq!(observed.len(), number_of_accesses);
+                assert_eq!(*observed.last().unwrap(), oram.stash_occupancy());
+            });
+        }
+    }
+
+    #[test]
+    fn warmup_touches_planned_leaves_without_changing_contents() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = 64;
+        let mut oram =
+            PathOram::<u64, 4, 8>::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        let mut shadow = vec![0u64; capacity as usize];
+        for _ in 0..200 {
+            let address = rng.gen_range(0..capacity);
+            let value = rng.gen();
+            oram.write(address, value, &mut rng).unwrap();
+            shadow[address as usize] = value;
+        }
+        oram.set_occupancy_observer(record_occupancy);
+
+        // The tree has height 5, so 32 leaves. The observer fires once per path touched.
+        for (plan, expected_leaves) in [
+            (WarmupPlan::full(), 32),
+            (WarmupPlan::fraction(1, 4), 8),
+            (WarmupPlan::fraction(1, 3), 11),
+            (WarmupPlan::fraction(5, 1), 32),
+            (WarmupPlan::fraction(1, 0), 0),
+        ] {
+            OBSERVED_OCCUPANCIES.with(|observed| observed.borrow_mut().clear());
+            let occupancy_before = oram.stash_occupancy();
+            let cost_before = measured_physical_cost(&oram);
+            assert_eq!(oram.warmup(plan).unwrap(), expected_leaves);
+            // Each leaf touched reads and writes the Z * (height + 1) blocks of its path, and no position map block.
+            let cost = measured_physical_cost(&oram);
+            assert_eq!(
+                cost.data_reads - cost_before.data_reads,
+                expected_leaves * 4 * 6
+            );
+            assert_eq!(
+                cost.data_writes - cost_before.data_writes,
+                expected_leaves * 4 * 6
+            );
+            assert_eq!(cost.position_map_reads, cost_before.position_map_reads);
+            assert_eq!(cost.position_map_writes, cost_before.position_map_writes);
+            OBSERVED_OCCUPANCIES.with(|observed| {
+                assert_eq!(observed.borrow().len() as u64, expected_leaves);
+            });
+            // Evicting along more paths never leaves more blocks in the stash.
+            assert!(oram.stash_occupancy() <= occupancy_before);
+            assert!(oram.audit().unwrap().is_consistent());
+        }
+
+        // A quarter of the leaves, in reverse-lexicographic order, hit each of the 8 subtrees at depth 3 exactly once.
+        let mut subtrees: Vec<TreeIndex> = (0..8)
+            .map(|counter| reverse_lexicographic_leaf(counter, 5) >> 2)
+            .collect();
+        subtrees.sort();
+        assert_eq!(subtrees, (8..16).collect::<Vec<_>>());
+
+        for (address, expected) in shadow.iter().enumerate() {
+            assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *expected);
+        }
+    }
 
     // Sanity checks on the `DefaultOram` convenience wrapper.
+    #[allow(clippy::assertions_on_constants)]
     #[test]
     fn default_oram_linear_correctness() {
         let mut rng = StdRng::seed_from_u64(0);
@@ -361,6 +2241,7 @@ mod tests {
     }
 
     // This test is #[ignore]'d because it takes about 1 second to run.
+    #[allow(clippy::assertions_on_constants)]
     #[test]
     #[ignore]
     fn default_oram_path_correctness() {
@@ -374,4 +2255,641 @@ mod tests {
         }
         random_workload(&mut oram, 1000);
     }
+
+    #[test]
+    fn range_update_matches_shadow_model() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = 64;
+        let len_bound = 8;
+        let mut oram =
+            PathOram::<u64, 4, 8>::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        oram.set_occupancy_observer(record_occupancy);
+        let mut shadow = vec![0u64; capacity as usize];
+
+        // The last start wraps around the end of the ORAM.
+        for (start, len) in [(3, 0), (10, 1), (20, len_bound), (0, 5), (60, len_bound)] {
+            OBSERVED_OCCUPANCIES.with(|observed| observed.borrow_mut().clear());
+            oram.range_update(start, len, len_bound, |value| value + 100, &mut rng)
+                .unwrap();
+            // Every call makes `len_bound` accesses, whatever the secret length.
+            OBSERVED_OCCUPANCIES.with(|observed| assert_eq!(observed.borrow().len(), 8));
+
+            for offset in 0..len {
+                shadow[((start + offset) % capacity) as usize] += 100;
+            }
+            for (address, expected) in shadow.iter().enumerate() {
+                assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *expected);
+            }
+        }
+
+        assert!(matches!(
+            oram.range_update(0, 0, capacity + 1, |value| *value, &mut rng),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+    }
+
+    #[test]
+    fn initial_values_are_stored() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let initial: Vec<u64> = (0..256).map(|_| rng.gen()).collect();
+        for recursion_cutoff in [1, 1 << 14] {
+            let mut oram = PathOram::<u64, 4, 8>::new_with_initial_values(
+                &initial,
+                &mut rng,
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            assert_eq!(oram.block_capacity().unwrap(), 256);
+            for (address, value) in initial.iter().enumerate() {
+                assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *value);
+            }
+        }
+    }
+
+    #[test]
+    fn recursion_cutoff_is_inclusive() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // 256 addresses fill 32 position blocks of 8 positions each.
+        let capacity = 256;
+        let position_blocks = 32;
+        let mut profile = |recursion_cutoff| {
+            let oram = PathOram::<u64, 4, 8>::new_with_parameters(
+                capacity,
+                &mut rng,
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            (
+                oram.recursion_profile().unwrap(),
+                oram.physical_bytes_per_access().unwrap(),
+            )
+        };
+
+        // A position map of exactly `recursion_cutoff` blocks is linear, as is a smaller one.
+        let (at_cutoff, at_cutoff_bytes) = profile(position_blocks);
+        let (above_cutoff, above_cutoff_bytes) = profile(position_blocks + 1);
+        assert_eq!(at_cutoff, above_cutoff);
+        assert_eq!(at_cutoff_bytes, above_cutoff_bytes);
+        assert_eq!(at_cutoff.len(), 1);
+        assert!(at_cutoff[0].is_linear);
+        assert_eq!(at_cutoff[0].block_capacity, position_blocks);
+        assert_eq!(
+            at_cutoff[0].physical_bytes_per_access,
+            2 * position_blocks * core::mem::size_of::<PositionBlock<8>>() as u64
+        );
+
+        // One block more than the cutoff recurses once, into 4 position blocks.
+        let (below_cutoff, below_cutoff_bytes) = profile(position_blocks - 1);
+        assert_eq!(below_cutoff.len(), 2);
+        assert_eq!(
+            (below_cutoff[0].block_capacity, below_cutoff[0].is_linear),
+            (position_blocks, false)
+        );
+        assert_eq!(
+            (below_cutoff[1].block_capacity, below_cutoff[1].is_linear),
+            (4, true)
+        );
+        assert_ne!(below_cutoff_bytes, at_cutoff_bytes);
+
+        // The levels account for all of the physical traffic of an access beyond the data ORAM's own path.
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        let path_bytes = 2 * (oram.height + 1) * core::mem::size_of::<Bucket<u64, 4>>() as u64;
+        let levels = oram.recursion_profile().unwrap();
+        assert_eq!(
+            levels
+                .iter()
+                .map(|level| level.block_capacity)
+                .collect::<Vec<_>>(),
+            [32, 4, 1]
+        );
+        assert_eq!(
+            path_bytes
+                + levels
+                    .iter()
+                    .map(|level| level.physical_bytes_per_access)
+                    .sum::<u64>(),
+            oram.physical_bytes_per_access().unwrap()
+        );
+    }
+
+    // Checks that each of 100 accesses to `oram` (after enough to fill its queue of pending evictions)
+    // reads and writes exactly the blocks that `expected_physical_accesses_per_op` reports.
+    fn check_physical_cost<const Z: BucketSize, const AB: BlockSize>(
+        mut oram: PathOram<u64, Z, AB>,
+        rng: &mut StdRng,
+    ) -> PhysicalCost {
+        let capacity = oram.block_capacity().unwrap();
+        for _ in 0..oram.max_pending_evictions {
+            oram.write(rng.gen_range(0..capacity), rng.gen(), rng)
+                .unwrap();
+        }
+        let expected = oram.expected_physical_accesses_per_op().unwrap();
+        for _ in 0..100 {
+            let before = measured_physical_cost(&oram);
+            oram.read(rng.gen_range(0..capacity), rng).unwrap();
+            let after = measured_physical_cost(&oram);
+            let measured = PhysicalCost {
+                data_reads: after.data_reads - before.data_reads,
+                data_writes: after.data_writes - before.data_writes,
+                position_map_reads: after.position_map_reads - before.position_map_reads,
+                position_map_writes: after.position_map_writes - before.position_map_writes,
+            };
+            assert_eq!(measured, expected);
+        }
+        expected
+    }
+
+    #[test]
+    fn expected_physical_accesses_match_measured_counts() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // A linear position map of 8 blocks.
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 16384).unwrap();
+        let cost = check_physical_cost(oram, &mut rng);
+        assert_eq!(
+            cost,
+            PhysicalCost {
+                data_reads: 4 * 6,
+                data_writes: 4 * 6,
+                position_map_reads: 8,
+                position_map_writes: 8,
+            }
+        );
+
+        // Recursive position maps of 32 and 4 position blocks, over a linear map of 1.
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(256, &mut rng, 40, 1).unwrap();
+        let cost = check_physical_cost(oram, &mut rng);
+        assert_eq!(cost.data_reads, 4 * 8);
+        assert_eq!(cost.position_map_reads, 4 * 5 + 4 * 2 + 1);
+
+        // Other bucket sizes, position block sizes, and recursion cutoffs.
+        let oram = PathOram::<u64, 3, 8>::new_with_parameters(128, &mut rng, 40, 4).unwrap();
+        check_physical_cost(oram, &mut rng);
+        let oram = PathOram::<u64, 5, 2>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        check_physical_cost(oram, &mut rng);
+        let oram = PathOram::<u64, 4, 64>::new_with_parameters(1024, &mut rng, 40, 8).unwrap();
+        check_physical_cost(oram, &mut rng);
+
+        // Deferred eviction reads and writes a second path of the tree.
+        let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(256, &mut rng, 40, 1).unwrap();
+        oram.set_max_pending_evictions(3).unwrap();
+        let deferred_cost = check_physical_cost(oram, &mut rng);
+        assert_eq!(deferred_cost.data_reads, 2 * cost.data_reads);
+        assert_eq!(deferred_cost.position_map_reads, cost.position_map_reads);
+    }
+
+    #[test]
+    fn folds_match_shadow_model() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for max_pending_evictions in [0, 3] {
+            let mut oram =
+                PathOram::<u64, 4, 8>::new_with_parameters(128, &mut rng, 40, 1).unwrap();
+            oram.set_max_pending_evictions(max_pending_evictions)
+                .unwrap();
+            let mut mirror = vec![0u64; 128];
+            for _ in 0..300 {
+                let address = rng.gen_range(0..128);
+                let value = rng.gen_range(0..1000);
+                oram.write(address, value, &mut rng).unwrap();
+                mirror[address as usize] = value;
+            }
+
+            let sum = |total: u64, value: &u64| total.wrapping_add(*value);
+            let expected_sum: u64 = mirror.iter().sum();
+            // A count of the values below 500, computed without branching on the value.
+            let count_small =
+                |count: u64, value: &u64| count + u64::from(value.ct_lt(&500).unwrap_u8());
+            let expected_count = mirror.iter().filter(|value| **value < 500).count() as u64;
+
+            assert_eq!(oram.fold_all_in_place(0, sum).unwrap(), expected_sum);
+            assert_eq!(
+                oram.fold_all_in_place(0, count_small).unwrap(),
+                expected_count
+            );
+            assert_eq!(oram.fold_all(0, sum, &mut rng).unwrap(), expected_sum);
+            assert_eq!(
+                oram.fold_all(0, count_small, &mut rng).unwrap(),
+                expected_count
+            );
+
+            // `fold_all` visits the addresses in order.
+            let addresses_in_order = oram
+                .fold_all(
+                    Vec::new(),
+                    |mut values, value| {
+                        values.push(*value);
+                        values
+                    },
+                    &mut rng,
+                )
+                .unwrap();
+            assert_eq!(addresses_in_order, mirror);
+        }
+    }
+
+    #[test]
+    fn frozen_copy_diverges_independently() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = 256;
+        for (recursion_cutoff, max_pending_evictions) in [(1, 0), (1 << 14, 3)] {
+            let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(
+                capacity,
+                &mut rng,
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            oram.set_max_pending_evictions(max_pending_evictions)
+                .unwrap();
+            let mut shadow = vec![0; capacity as usize];
+            for _ in 0..500 {
+                let address = rng.gen_range(0..capacity);
+                let value = rng.gen();
+                oram.write(address, value, &mut rng).unwrap();
+                shadow[address as usize] = value;
+            }
+
+            let mut frozen = oram.freeze(&mut rng).unwrap();
+            assert_eq!(frozen.pending_evictions(), 0);
+            let frozen_shadow = shadow.clone();
+
+            // Run different workloads on the two copies concurrently, each checked against its own shadow model.
+            let run = |oram: &mut PathOram<u64, 4, 8>, mut shadow: Vec<u64>, seed: u64| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                for _ in 0..1000 {
+                    let address = rng.gen_range(0..capacity);
+                    if rng.gen() {
+                        assert_eq!(
+                            oram.read(address, &mut rng).unwrap(),
+                            shadow[address as usize]
+                        );
+                    } else {
+                        let value = rng.gen();
+                        assert_eq!(
+                            oram.write(address, value, &mut rng).unwrap(),
+                            shadow[address as usize]
+                        );
+                        shadow[address as usize] = value;
+                    }
+                }
+                shadow
+            };
+            let (shadow, frozen_shadow) = std::thread::scope(|scope| {
+                let frozen_thread = scope.spawn(|| run(&mut frozen, frozen_shadow, 1));
+                let shadow = run(&mut oram, shadow, 2);
+                (shadow, frozen_thread.join().unwrap())
+            });
+            assert_ne!(shadow, frozen_shadow);
+
+            for address in 0..capacity {
+                assert_eq!(
+                    oram.read(address, &mut rng).unwrap(),
+                    shadow[address as usize]
+                );
+                assert_eq!(
+                    frozen.read(address, &mut rng).unwrap(),
+                    frozen_shadow[address as usize]
+                );
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    struct Record {
+        id: u32,
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    struct RecordWithFlags {
+        id: u32,
+        flags: u16,
+    }
+
+    impl ConditionallySelectable for Record {
+        fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+            Self {
+                id: u32::conditional_select(&a.id, &b.id, choice),
+            }
+        }
+    }
+
+    impl ConditionallySelectable for RecordWithFlags {
+        fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+            Self {
+                id: u32::conditional_select(&a.id, &b.id, choice),
+                flags: u16::conditional_select(&a.flags, &b.flags, choice),
+            }
+        }
+    }
+
+    impl OramBlock for Record {}
+    impl OramBlock for RecordWithFlags {}
+
+    #[test]
+    fn migrate_maps_every_value() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = 128;
+        for (recursion_cutoff, max_pending_evictions) in [(1, 0), (16384, 2)] {
+            let mut oram = PathOram::<u32, 4, 8>::new_with_parameters(
+                capacity,
+                &mut rng,
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            oram.set_max_pending_evictions(max_pending_evictions)
+                .unwrap();
+            let mut shadow = vec![0u32; capacity as usize];
+            for _ in 0..300 {
+                let address = rng.gen_range(0..capacity);
+                let value = rng.gen();
+                oram.write(address, value, &mut rng).unwrap();
+                shadow[address as usize] = value;
+            }
+
+            let mut widened = oram
+                .migrate(|value| u64::from(*value) << 32, &mut rng)
+                .unwrap();
+            assert_eq!(widened.pending_evictions(), 0);
+            for (address, value) in shadow.iter().enumerate() {
+                assert_eq!(
+                    widened.read(address as Address, &mut rng).unwrap(),
+                    u64::from(*value) << 32
+                );
+            }
+        }
+
+        // Migrate records to a schema with an added field, computed from the old ones.
+        let mut oram = PathOram::<Record, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        for address in (0..64).step_by(3) {
+            let id = u32::try_from(address).unwrap() + 1000;
+            oram.write(address, Record { id }, &mut rng).unwrap();
+        }
+        let mut migrated = oram
+            .migrate(
+                |record| RecordWithFlags {
+                    id: record.id,
+                    flags: u16::from(record.id != 0),
+                },
+                &mut rng,
+            )
+            .unwrap();
+        for address in 0..64 {
+            let expected = if address % 3 == 0 {
+                RecordWithFlags {
+                    id: u32::try_from(address).unwrap() + 1000,
+                    flags: 1,
+                }
+            } else {
+                RecordWithFlags::default()
+            };
+            assert_eq!(migrated.read(address, &mut rng).unwrap(), expected);
+        }
+    }
+
+    type TraceOram = PathOram<BlockValue<8>, 4, 8>;
+
+    // Performs 300 operations at a fixed sequence of addresses, writing the `i`th value `[i; 8]` if `operation` writes,
+    // and returns the metadata (address and position) of every slot of the tree and stash after each one:
+    // everything that the physical access pattern could reveal about the ORAM, given a fixed RNG.
+    fn physical_trace<O: Fn(&mut TraceOram, Address, BlockValue<8>, &mut StdRng)>(
+        max_pending_evictions: usize,
+        operation: O,
+    ) -> Vec<Vec<(Address, TreeIndex)>> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TraceOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_max_pending_evictions(max_pending_evictions)
+            .unwrap();
+        let mut address_rng = StdRng::seed_from_u64(1);
+        let mut trace = Vec::new();
+        for i in 0..300 {
+            operation(
+                &mut oram,
+                address_rng.gen_range(0..64),
+                BlockValue::new([i as u8; 8]),
+                &mut rng,
+            );
+            trace.push(
+                oram.physical_memory
+                    .iter()
+                    .flat_map(|bucket| bucket.blocks)
+                    .chain(oram.stash.overflow_blocks())
+                    .map(|block| (block.address, block.position))
+                    .collect(),
+            );
+        }
+        trace
+    }
+
+    #[test]
+    fn reads_and_writes_are_physically_indistinguishable() {
+        for max_pending_evictions in [0, 3] {
+            let reads = physical_trace(max_pending_evictions, |oram, address, _, rng| {
+                oram.read(address, rng).unwrap();
+            });
+            let out = core::cell::RefCell::new(BlockValue::default());
+            let traces = [
+                physical_trace(max_pending_evictions, |oram, address, value, rng| {
+                    oram.write(address, value, rng).unwrap();
+                }),
+                physical_trace(max_pending_evictions, |oram, address, value, rng| {
+                    if address % 2 == 0 {
+                        oram.read(address, rng).unwrap();
+                    } else {
+                        oram.write(address, value, rng).unwrap();
+                    }
+                }),
+                physical_trace(max_pending_evictions, |oram, address, value, rng| {
+                    oram.access(
+                        address,
+                        |old| BlockValue::new([old.data[0] ^ value.data[0]; 8]),
+                        rng,
+                    )
+                    .unwrap();
+                }),
+                physical_trace(max_pending_evictions, |oram, address, _, rng| {
+                    oram.read_into(address, &mut out.borrow_mut(), rng).unwrap();
+                }),
+                physical_trace(max_pending_evictions, |oram, address, value, rng| {
+                    oram.write_from(address, &value, rng).unwrap();
+                }),
+                physical_trace(max_pending_evictions, |oram, address, value, rng| {
+                    let len = u64::from(value.data[0] % 2);
+                    oram.range_update(address, len, 1, |_| value, rng).unwrap();
+                }),
+            ];
+            for trace in traces {
+                assert!(trace == reads);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_and_dummy_addresses_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for recursion_cutoff in [1, 16384] {
+            let mut oram = PathOram::<BlockValue<1>, 4, 8>::new_with_parameters(
+                64,
+                &mut rng,
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+            assert!(!oram.is_poisoned());
+
+            // With 32-bit indices, dummy blocks have address `u32::MAX`.
+            let mut oram = PathOramU32::<BlockValue<1>, 4, 8>::new_with_parameters(
+                64,
+                &mut rng,
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            out_of_bounds_addresses_are_rejected(&mut oram, &[u32::MAX.into()]);
+        }
+        let mut oram = DefaultOram::<BlockValue<1>>::new(64, &mut rng).unwrap();
+        out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+    }
+
+    #[test]
+    fn path_oram_conforms_to_the_oram_contract() {
+        // Path ORAM capacities are powers of two, at least 2.
+        let capacities = [2, 4, 64, 256];
+        for recursion_cutoff in [1, 16384] {
+            let report = crate::testing::conformance(
+                |capacity, rng| {
+                    PathOram::<BlockValue<4>, 4, 8>::new_with_parameters(
+                        capacity,
+                        rng,
+                        40,
+                        recursion_cutoff,
+                    )
+                },
+                &capacities,
+                500,
+                0,
+            );
+            assert!(report.passed(), "{report}");
+        }
+        let report = crate::testing::conformance(
+            |capacity, rng| PathOramU32::<u64, 3, 4>::new_with_parameters(capacity, rng, 40, 1),
+            &capacities,
+            500,
+            1,
+        );
+        assert!(report.passed(), "{report}");
+        let report = crate::testing::conformance(DefaultOram::<u64>::new, &capacities, 500, 2);
+        assert!(report.passed(), "{report}");
+    }
+
+    // Runs a fixed script of 100 reads, writes, and accesses against a Path ORAM of capacity 2^8 with `Z` blocks per bucket
+    // and otherwise the parameters `DefaultOram` would choose (were it not to use a linear ORAM below capacity 2^10),
+    // with every random choice drawn from seeded ChaCha RNGs, which, unlike `StdRng`, are stable across versions of `rand`.
+    // Returns the buckets of the tree each operation reads (`r`) and writes (`w`), one line per operation,
+    // followed by the total numbers of tree blocks read and written.
+    fn golden_trace<const Z: BucketSize>() -> String {
+        use rand_chacha::ChaCha20Rng;
+        use std::fmt::Write;
+
+        let capacity = 1 << 8;
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let mut oram = PathOram::<u64, Z, DEFAULT_POSITIONS_PER_BLOCK>::new_with_parameters(
+            capacity,
+            &mut rng,
+            default_overflow_size(capacity, Z),
+            DEFAULT_RECURSION_CUTOFF,
+        )
+        .unwrap();
+        oram.stash.bucket_trace = Some(Vec::new());
+
+        let mut script_rng = ChaCha20Rng::seed_from_u64(1);
+        let mut trace = String::new();
+        for i in 0..100 {
+            let address = script_rng.gen_range(0..capacity);
+            match script_rng.gen_range(0..3) {
+                0 => {
+                    oram.read(address, &mut rng).unwrap();
+                }
+                1 => {
+                    oram.write(address, i, &mut rng).unwrap();
+                }
+                _ => {
+                    oram.access(address, |value| value + i, &mut rng).unwrap();
+                }
+            }
+            let buckets = oram.stash.bucket_trace.replace(Vec::new()).unwrap();
+            write!(trace, "{i}:").unwrap();
+            for (node, is_write) in buckets {
+                write!(trace, " {}{node}", if is_write { 'w' } else { 'r' }).unwrap();
+            }
+            trace.push('\n');
+        }
+        writeln!(
+            trace,
+            "blocks read: {}, blocks written: {}",
+            oram.stash.physical_reads, oram.stash.physical_writes
+        )
+        .unwrap();
+        trace
+    }
+
+    // Compares `golden_trace::<Z>()` to the fixture `src/testdata/golden_trace_z{Z}.txt`.
+    // A change to the stash, eviction, or access logic that changes which buckets are read or written,
+    // or how many, fails this test. If the change is intended, regenerate the fixtures with
+    // `ORAM_REGENERATE_GOLDEN_TRACES=1 cargo test golden_trace`, and review the diff of the fixtures with the change.
+    fn check_golden_trace<const Z: BucketSize>() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join(format!("src/testdata/golden_trace_z{Z}.txt"));
+        let trace = golden_trace::<Z>();
+        if std::env::var_os("ORAM_REGENERATE_GOLDEN_TRACES").is_some() {
+            std::fs::write(&path, &trace).unwrap();
+        }
+        let expected = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            trace == expected,
+            "the physical access pattern differs from {}; \
+             if this is intended, rerun with ORAM_REGENERATE_GOLDEN_TRACES=1",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn golden_trace_default_bucket_size() {
+        check_golden_trace::<DEFAULT_BLOCKS_PER_BUCKET>();
+    }
+
+    #[test]
+    fn golden_trace_z3() {
+        check_golden_trace::<3>();
+    }
+
+    #[test]
+    fn path_oram_in_caller_buffer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for len in [0, 32, 65, 128] {
+            let buffer = vec![Bucket::<u64, 4>::default(); len];
+            assert!(matches!(
+                PathOram::<u64, 4, 8>::new_in_buffer(64, buffer, &mut rng, 40, 1),
+                Err(OramError::InvalidConfigurationError { .. })
+            ));
+        }
+
+        // The buffer's contents are overwritten, and the same allocation is returned, emptied.
+        let mut buffer = Vec::with_capacity(64);
+        let mut garbage = Bucket::<u64, 4>::default();
+        garbage.blocks[0] = PathOramBlock::new(7, 3, 40);
+        buffer.resize(64, garbage);
+        let pointer = buffer.as_ptr();
+        let mut oram = PathOram::<u64, 4, 8>::new_in_buffer(64, buffer, &mut rng, 40, 1).unwrap();
+        random_workload(&mut oram, 500);
+        assert_eq!(oram.block_capacity().unwrap(), 64);
+
+        let buffer = oram.into_inner();
+        assert_eq!(buffer.as_ptr(), pointer);
+        assert_eq!(buffer.len(), 64);
+        assert!(buffer.iter().all(|bucket| *bucket == Bucket::default()));
+    }
 }
@@ -0,0 +1,549 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A collection of named ORAMs sharing one RNG, for applications that maintain many ORAMs (e.g., one per table).
+//!
+//! ```
+//! use oram::pool::OramPool;
+//! use rand::rngs::OsRng;
+//!
+//! let mut pool = OramPool::<u64, _>::new(OsRng);
+//! pool.create("users", 64)?;
+//! pool.create("orders", 256)?;
+//! pool.write("users", 3, 42)?;
+//! assert_eq!(pool.read("users", 3)?, 42);
+//! assert_eq!(pool.read("orders", 3)?, 0);
+//! // Fewer operations than the export interval have been made, so the counts may not be exported yet.
+//! assert_eq!(pool.export_stats(), None);
+//! # Ok::<(), oram::pool::PoolError>(())
+//! ```
+//!
+//! Each access is exactly as oblivious as an access to the underlying ORAM; in particular, it reveals which ORAM is accessed.
+//!
+//! The pool's counts are read with [`OramPool::export_stats`], which returns only the combined totals of the pool,
+//! and at most once per [`export interval`](OramPool::with_export_interval) of operations
+//! ([`DEFAULT_EXPORT_INTERVAL`] for a pool constructed by [`OramPool::new`]),
+//! so that an export never describes a short burst of accesses.
+//! The per-ORAM counts, and reads of the totals that ignore the interval, are available only with the `diagnostics` feature.
+//! The totals determine the number of buckets read at each depth of every tree, since each access reads one bucket per depth,
+//! so no finer counts are kept. Counts of the addresses assigned each leaf, which do depend on the accesses made,
+//! are available only through `PathOram::leaf_loads`, with the `diagnostics` feature.
+
+use crate::{path_oram::DefaultOram, Address, Oram, OramBlock, OramError};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+use core::{fmt, ops::AddAssign};
+use rand::{CryptoRng, RngCore};
+
+/// The export interval of an [`OramPool`] constructed by [`OramPool::new`]: the number of successful operations between exports of its counts.
+pub const DEFAULT_EXPORT_INTERVAL: u64 = 1000;
+
+/// Counts of the operations performed on an ORAM, or on all the ORAMs of a pool.
+///
+/// The counts saturate at `u64::MAX` rather than wrapping, and record that they have done so in `saturated`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OramStats {
+    /// The number of successful reads.
+    pub reads: u64,
+    /// The number of successful writes and accesses.
+    pub writes: u64,
+    /// The capacity in blocks.
+    pub block_capacity: Address,
+    /// True if any of the above has saturated, in which case it is a lower bound.
+    pub saturated: bool,
+}
+
+impl OramStats {
+    // Adds `amount` to `counter`, saturating and setting `saturated` on overflow.
+    fn add(counter: &mut u64, amount: u64, saturated: &mut bool) {
+        match counter.checked_add(amount) {
+            Some(sum) => *counter = sum,
+            None => {
+                *counter = u64::MAX;
+                *saturated = true;
+            }
+        }
+    }
+}
+
+impl AddAssign for OramStats {
+    fn add_assign(&mut self, other: Self) {
+        self.saturated = self.saturated || other.saturated;
+        Self::add(&mut self.reads, other.reads, &mut self.saturated);
+        Self::add(&mut self.writes, other.writes, &mut self.saturated);
+        Self::add(
+            &mut self.block_capacity,
+            other.block_capacity,
+            &mut self.saturated,
+        );
+    }
+}
+
+/// An error from an operation on the ORAM named `name` in an [`OramPool`].
+#[derive(Debug)]
+pub struct PoolError {
+    /// The name of the ORAM.
+    pub name: String,
+    /// The underlying error.
+    pub error: OramError,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ORAM \"{}\": {}", self.name, self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PoolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[derive(Debug)]
+struct PoolEntry<V: OramBlock> {
+    oram: DefaultOram<V>,
+    stats: OramStats,
+}
+
+/// A collection of named [`DefaultOram`]s storing values of type `V`, which draw randomness from a shared RNG `R`
+/// and keep counts of the operations performed on them.
+#[derive(Debug)]
+pub struct OramPool<V: OramBlock, R: RngCore + CryptoRng> {
+    orams: BTreeMap<String, PoolEntry<V>>,
+    rng: R,
+    export_interval: u64,
+    operations_since_export: u64,
+}
+
+impl<V: OramBlock, R: RngCore + CryptoRng> OramPool<V, R> {
+    /// Returns an empty pool whose ORAMs will draw randomness from `rng`,
+    /// with an export interval of [`DEFAULT_EXPORT_INTERVAL`] operations.
+    pub fn new(rng: R) -> Self {
+        Self::with_export_interval(rng, DEFAULT_EXPORT_INTERVAL)
+    }
+
+    /// Returns an empty pool whose ORAMs will draw randomness from `rng`, and whose counts may be exported
+    /// by [`OramPool::export_stats`] only once at least `export_interval` operations have succeeded since the last export.
+    /// An `export_interval` of 0 places no limit on exports. The interval cannot be changed afterwards.
+    pub fn with_export_interval(rng: R, export_interval: u64) -> Self {
+        Self {
+            orams: BTreeMap::new(),
+            rng,
+            export_interval,
+            operations_since_export: 0,
+        }
+    }
+
+    /// Adds a new ORAM named `name`, mapping addresses `0 <= address < block_capacity` to default `V` values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if the pool already contains an ORAM named `name`,
+    /// or if `block_capacity` is invalid (see [`DefaultOram::new`]).
+    pub fn create(&mut self, name: &str, block_capacity: Address) -> Result<(), PoolError> {
+        if self.orams.contains_key(name) {
+            return Err(PoolError {
+                name: name.to_string(),
+                error: OramError::InvalidConfigurationError {
+                    parameter_name: "ORAM name (already in use)".to_string(),
+                    parameter_value: name.to_string(),
+                },
+            });
+        }
+        let oram = DefaultOram::new(block_capacity, &mut self.rng).map_err(|error| PoolError {
+            name: name.to_string(),
+            error,
+        })?;
+        let stats = OramStats {
+            block_capacity,
+            ..OramStats::default()
+        };
+        self.orams
+            .insert(name.to_string(), PoolEntry { oram, stats });
+        Ok(())
+    }
+
+    /// Returns the names of the ORAMs in the pool, in lexicographic order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.orams.keys().map(String::as_str)
+    }
+
+    /// Performs an access as in [`Oram::access`] on the ORAM named `name`.
+    pub fn access<F: Fn(&V) -> V>(
+        &mut self,
+        name: &str,
+        index: Address,
+        callback: F,
+    ) -> Result<V, PoolError> {
+        let (entry, rng) = self.entry(name)?;
+        let result = entry.oram.access(index, callback, rng);
+        let result = Self::record(name, result, &mut entry.stats, false);
+        self.count_export(result)
+    }
+
+    /// Reads the value stored at `index` in the ORAM named `name`, as in [`Oram::read`].
+    pub fn read(&mut self, name: &str, index: Address) -> Result<V, PoolError> {
+        let (entry, rng) = self.entry(name)?;
+        let result = entry.oram.read(index, rng);
+        let result = Self::record(name, result, &mut entry.stats, true);
+        self.count_export(result)
+    }
+
+    /// Writes `new_value` to `index` in the ORAM named `name`, as in [`Oram::write`].
+    /// Returns the value previously stored at `index`.
+    pub fn write(&mut self, name: &str, index: Address, new_value: V) -> Result<V, PoolError> {
+        let (entry, rng) = self.entry(name)?;
+        let result = entry.oram.write(index, new_value, rng);
+        let result = Self::record(name, result, &mut entry.stats, false);
+        self.count_export(result)
+    }
+
+    /// Returns the operation counts of the ORAM named `name`, regardless of the export interval.
+    /// Requires the `diagnostics` feature.
+    #[cfg(any(feature = "diagnostics", test))]
+    pub fn oram_stats(&self, name: &str) -> Result<OramStats, PoolError> {
+        self.orams
+            .get(name)
+            .map(|entry| entry.stats)
+            .ok_or_else(|| Self::unknown_name(name))
+    }
+
+    /// Returns the operation counts of all the ORAMs in the pool combined, regardless of the export interval.
+    /// Requires the `diagnostics` feature.
+    #[cfg(any(feature = "diagnostics", test))]
+    pub fn stats(&self) -> OramStats {
+        self.totals()
+    }
+
+    /// Resets the operation counts of every ORAM in the pool to zero,
+    /// and returns the combined counts from before the reset, regardless of the export interval.
+    /// Requires the `diagnostics` feature.
+    #[cfg(any(feature = "diagnostics", test))]
+    #[must_use = "the counts from before the reset are discarded"]
+    pub fn reset_stats(&mut self) -> OramStats {
+        let total = self.totals();
+        for entry in self.orams.values_mut() {
+            entry.stats = OramStats {
+                block_capacity: entry.stats.block_capacity,
+                ..OramStats::default()
+            };
+        }
+        total
+    }
+
+    /// Returns the operation counts of all the ORAMs in the pool combined,
+    /// if at least the export interval of operations has succeeded since the last export, and `None` otherwise.
+    /// Only the totals are exported, never the counts of individual ORAMs, addresses, or leaves.
+    /// Resetting the counts with `OramPool::reset_stats` does not restart the interval.
+    pub fn export_stats(&mut self) -> Option<OramStats> {
+        if self.operations_since_export < self.export_interval {
+            return None;
+        }
+        self.operations_since_export = 0;
+        Some(self.totals())
+    }
+
+    // Returns the operation counts of all the ORAMs in the pool combined.
+    fn totals(&self) -> OramStats {
+        let mut total = OramStats::default();
+        for entry in self.orams.values() {
+            total += entry.stats;
+        }
+        total
+    }
+
+    fn entry(&mut self, name: &str) -> Result<(&mut PoolEntry<V>, &mut R), PoolError> {
+        match self.orams.get_mut(name) {
+            Some(entry) => Ok((entry, &mut self.rng)),
+            None => Err(Self::unknown_name(name)),
+        }
+    }
+
+    // Counts `result` in `stats` as a read or a write if it is a success, and attributes it to `name` if it is an error.
+    fn record(
+        name: &str,
+        result: Result<V, OramError>,
+        stats: &mut OramStats,
+        is_read: bool,
+    ) -> Result<V, PoolError> {
+        match result {
+            Ok(value) => {
+                let counter = if is_read {
+                    &mut stats.reads
+                } else {
+                    &mut stats.writes
+                };
+                OramStats::add(counter, 1, &mut stats.saturated);
+                Ok(value)
+            }
+            Err(error) => Err(PoolError {
+                name: name.to_string(),
+                error,
+            }),
+        }
+    }
+
+    // Counts a successful operation towards the export interval.
+    fn count_export(&mut self, result: Result<V, PoolError>) -> Result<V, PoolError> {
+        if result.is_ok() {
+            self.operations_since_export = self.operations_since_export.saturating_add(1);
+        }
+        result
+    }
+
+    fn unknown_name(name: &str) -> PoolError {
+        PoolError {
+            name: name.to_string(),
+            error: OramError::InvalidConfigurationError {
+                parameter_name: "ORAM name (not in pool)".to_string(),
+                parameter_value: name.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, vec::Vec};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    #[test]
+    fn interleaved_pools_are_isolated() {
+        // Small enough for linear-time ORAMs, and large enough for a Path ORAM.
+        let capacities = [64, 256, 2048];
+        let mut pools: Vec<OramPool<u32, StdRng>> = (0..3)
+            .map(|seed| OramPool::new(StdRng::seed_from_u64(seed)))
+            .collect();
+        // The same names in every pool, so that isolation between pools is tested as well as within them.
+        let names: Vec<_> = (0..capacities.len())
+            .map(|i| format!("table{}", i))
+            .collect();
+        for pool in &mut pools {
+            for (name, capacity) in names.iter().zip(capacities) {
+                pool.create(name, capacity).unwrap();
+            }
+        }
+
+        let mut expected: HashMap<(usize, usize, Address), u32> = HashMap::new();
+        let mut expected_stats = [[(0, 0); 3]; 3];
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..2000 {
+            let pool = rng.gen_range(0..pools.len());
+            let oram = rng.gen_range(0..names.len());
+            let address = rng.gen_range(0..capacities[oram]);
+            let stored = expected.get(&(pool, oram, address)).copied().unwrap_or(0);
+            if rng.gen() {
+                assert_eq!(pools[pool].read(&names[oram], address).unwrap(), stored);
+                expected_stats[pool][oram].0 += 1;
+            } else {
+                let value = rng.gen();
+                assert_eq!(
+                    pools[pool].write(&names[oram], address, value).unwrap(),
+                    stored
+                );
+                expected.insert((pool, oram, address), value);
+                expected_stats[pool][oram].1 += 1;
+            }
+        }
+
+        for (pool, pool_expected_stats) in pools.iter().zip(expected_stats) {
+            let mut total = OramStats::default();
+            for ((name, capacity), (reads, writes)) in
+                names.iter().zip(capacities).zip(pool_expected_stats)
+            {
+                let stats = OramStats {
+                    reads,
+                    writes,
+                    block_capacity: capacity,
+                    saturated: false,
+                };
+                assert_eq!(pool.oram_stats(name).unwrap(), stats);
+                total += stats;
+            }
+            assert_eq!(pool.stats(), total);
+            assert_eq!(pool.stats().block_capacity, 2368);
+        }
+    }
+
+    #[test]
+    fn errors_identify_the_oram() {
+        let mut pool = OramPool::<u8, _>::new(StdRng::seed_from_u64(0));
+        pool.create("small", 64).unwrap();
+
+        let error = pool.read("missing", 0).unwrap_err();
+        assert_eq!(error.name, "missing");
+
+        let error = pool.write("small", 64, 1).unwrap_err();
+        assert_eq!(error.name, "small");
+        assert!(matches!(
+            error.error,
+            OramError::AddressOutOfBoundsError {
+                attempted: 64,
+                capacity: 64
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "ORAM \"small\": Attempted to access ORAM address 64, which is larger than ORAM capacity 64."
+        );
+
+        assert_eq!(pool.create("small", 128).unwrap_err().name, "small");
+        assert_eq!(pool.create("odd", 1025).unwrap_err().name, "odd");
+        assert_eq!(pool.names().collect::<Vec<_>>(), ["small"]);
+        // Failed operations are not counted.
+        assert_eq!(
+            pool.stats(),
+            OramStats {
+                reads: 0,
+                writes: 0,
+                block_capacity: 64,
+                saturated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn access_counts_as_write() {
+        let mut pool = OramPool::<u64, _>::new(StdRng::seed_from_u64(0));
+        pool.create("counters", 64).unwrap();
+        pool.access("counters", 1, |value| value + 5).unwrap();
+        assert_eq!(pool.access("counters", 1, |value| value * 2).unwrap(), 5);
+        assert_eq!(pool.read("counters", 1).unwrap(), 10);
+        assert_eq!(
+            pool.oram_stats("counters").unwrap(),
+            OramStats {
+                reads: 1,
+                writes: 2,
+                block_capacity: 64,
+                saturated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn counts_saturate_instead_of_wrapping() {
+        let mut pool = OramPool::<u8, _>::new(StdRng::seed_from_u64(0));
+        pool.create("small", 64).unwrap();
+        pool.create("other", 64).unwrap();
+        pool.orams.get_mut("small").unwrap().stats.reads = u64::MAX - 1;
+
+        pool.read("small", 0).unwrap();
+        assert_eq!(pool.oram_stats("small").unwrap().reads, u64::MAX);
+        assert!(!pool.oram_stats("small").unwrap().saturated);
+
+        pool.read("small", 0).unwrap();
+        pool.write("small", 0, 1).unwrap();
+        let stats = pool.oram_stats("small").unwrap();
+        assert_eq!((stats.reads, stats.writes), (u64::MAX, 1));
+        assert!(stats.saturated);
+
+        // Combining counts saturates too, and a saturated count taints the total.
+        pool.read("other", 0).unwrap();
+        let total = pool.stats();
+        assert_eq!((total.reads, total.writes), (u64::MAX, 1));
+        assert!(total.saturated);
+        let mut sum = OramStats {
+            reads: u64::MAX,
+            ..OramStats::default()
+        };
+        sum += OramStats {
+            reads: 1,
+            ..OramStats::default()
+        };
+        assert_eq!(sum.reads, u64::MAX);
+        assert!(sum.saturated);
+
+        assert_eq!(pool.reset_stats(), total);
+        assert_eq!(
+            pool.stats(),
+            OramStats {
+                block_capacity: 128,
+                ..OramStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn exports_are_rate_limited() {
+        let mut pool = OramPool::<u64, _>::with_export_interval(StdRng::seed_from_u64(0), 10);
+        pool.create("a", 64).unwrap();
+        pool.create("b", 64).unwrap();
+        for i in 0..9 {
+            pool.write("a", i, i).unwrap();
+            assert_eq!(pool.export_stats(), None);
+        }
+        // Failed operations do not count towards the interval, and neither do resets restart it.
+        pool.read("a", 64).unwrap_err();
+        pool.read("missing", 0).unwrap_err();
+        assert_eq!(pool.export_stats(), None);
+        let _ = pool.reset_stats();
+        pool.read("b", 0).unwrap();
+        assert_eq!(
+            pool.export_stats(),
+            Some(OramStats {
+                reads: 1,
+                writes: 0,
+                block_capacity: 128,
+                saturated: false,
+            })
+        );
+        // An export starts a new interval.
+        assert_eq!(pool.export_stats(), None);
+        for _ in 0..25 {
+            pool.read("b", 1).unwrap();
+        }
+        assert_eq!(pool.export_stats().unwrap().reads, 26);
+        assert_eq!(pool.export_stats(), None);
+
+        // With an interval of 0, every export succeeds.
+        let mut pool = OramPool::<u64, _>::with_export_interval(StdRng::seed_from_u64(0), 0);
+        assert_eq!(pool.export_stats(), Some(OramStats::default()));
+        assert_eq!(pool.export_stats(), Some(OramStats::default()));
+
+        // A pool constructed by `new` has the default interval.
+        let mut pool = OramPool::<u64, _>::new(StdRng::seed_from_u64(0));
+        pool.create("a", 64).unwrap();
+        for _ in 1..DEFAULT_EXPORT_INTERVAL {
+            pool.read("a", 0).unwrap();
+        }
+        assert_eq!(pool.export_stats(), None);
+        pool.read("a", 0).unwrap();
+        assert_eq!(pool.export_stats().unwrap().reads, DEFAULT_EXPORT_INTERVAL);
+    }
+
+    #[test]
+    fn exports_do_not_depend_on_the_addresses_accessed() {
+        // One pool hammers a single address of one ORAM; the other spreads the same operations over both ORAMs.
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut skewed = OramPool::<u64, _>::with_export_interval(StdRng::seed_from_u64(2), 100);
+        let mut spread = OramPool::<u64, _>::with_export_interval(StdRng::seed_from_u64(3), 100);
+        for pool in [&mut skewed, &mut spread] {
+            pool.create("a", 64).unwrap();
+            pool.create("b", 64).unwrap();
+        }
+        for i in 0..100 {
+            let name = if rng.gen() { "a" } else { "b" };
+            let address = rng.gen_range(0..64);
+            if i % 2 == 0 {
+                skewed.read("a", 0).unwrap();
+                spread.read(name, address).unwrap();
+            } else {
+                skewed.write("a", 0, i).unwrap();
+                spread.write(name, address, i).unwrap();
+            }
+        }
+        let exported = skewed.export_stats().unwrap();
+        assert_eq!(spread.export_stats().unwrap(), exported);
+        assert_eq!((exported.reads, exported.writes), (50, 50));
+    }
+}
@@ -7,15 +7,17 @@
 
 //! A recursive Path ORAM position map data structure.
 
-use super::path_oram::PathOram;
-use crate::bucket::PositionBlock;
+use super::path_oram::{PathOram, PositionMapLevel};
+use crate::bucket::{Bucket, PositionBlock};
+use crate::ct::ct_mux_array;
 use crate::StashSize;
 use crate::{
-    linear_time_oram::LinearTimeOram, utils::TreeIndex, Address, BlockSize, BucketSize, Oram,
+    linear_time_oram::LinearTimeOram, tree::TreeIndex, Address, BlockSize, BucketSize, Oram,
 };
 use crate::{OramError, RecursionCutoff};
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
 use rand::{CryptoRng, RngCore};
-use subtle::{ConditionallySelectable, ConstantTimeEq};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
 /// A recursive Path ORAM position map data structure. `AB` is the number of addresses stored in each ORAM block.
 #[derive(Debug)]
@@ -26,12 +28,12 @@ pub enum PositionMap<const AB: BlockSize, const Z: BucketSize> {
     Recursive(Box<PathOram<PositionBlock<AB>, Z, AB>>),
 }
 impl<const AB: BlockSize, const Z: BucketSize> PositionMap<AB, Z> {
-    fn address_of_block(address: Address) -> Address {
+    pub(crate) fn address_of_block(address: Address) -> Address {
         let block_address_bits = AB.ilog2();
         address >> block_address_bits
     }
 
-    fn address_within_block(address: Address) -> Result<usize, OramError> {
+    pub(crate) fn address_within_block(address: Address) -> Result<usize, OramError> {
         let block_address_bits = AB.ilog2();
         let shift: usize = (Address::BITS - block_address_bits).try_into()?;
         Ok(((address << shift) >> shift).try_into()?)
@@ -39,24 +41,104 @@ impl<const AB: BlockSize, const Z: BucketSize> PositionMap<AB, Z> {
 }
 
 impl<const AB: BlockSize, const Z: BucketSize> PositionMap<AB, Z> {
-    pub fn write_position_block<R: RngCore + CryptoRng>(
+    /// Returns the number of bytes of physical memory that each access reads and writes.
+    pub fn physical_bytes_per_access(&self) -> Result<u64, OramError> {
+        match self {
+            PositionMap::Base(linear_oram) => linear_oram.physical_bytes_per_access(),
+            PositionMap::Recursive(block_oram) => block_oram.physical_bytes_per_access(),
+        }
+    }
+
+    /// Returns the number of bytes of memory that the position map holds, including its own position map.
+    pub fn memory_footprint(&self) -> Result<u64, OramError> {
+        match self {
+            PositionMap::Base(linear_oram) => linear_oram.memory_footprint(),
+            PositionMap::Recursive(block_oram) => block_oram.memory_footprint(),
+        }
+    }
+
+    /// Returns the levels of the position map that `PositionMap::new` would construct with these arguments
+    /// (as [`PathOram::recursion_profile`] reports them), and the number of bytes of memory it would allocate,
+    /// saturating at `u64::MAX`.
+    pub fn plan(
+        number_of_addresses: Address,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<(Vec<PositionMapLevel>, u64), OramError> {
+        let ab_address: Address = AB.try_into()?;
+        if number_of_addresses / ab_address <= recursion_cutoff {
+            let block_capacity = number_of_addresses.div_ceil(ab_address);
+            let block_bytes = u64::try_from(core::mem::size_of::<PositionBlock<AB>>())?;
+            let memory_bytes = block_capacity.saturating_mul(block_bytes);
+            let level = PositionMapLevel {
+                block_capacity,
+                is_linear: true,
+                physical_bytes_per_access: memory_bytes.saturating_mul(2),
+            };
+            Ok((vec![level], memory_bytes))
+        } else {
+            let plan = PathOram::<PositionBlock<AB>, Z, AB>::validate_parameters_with(
+                number_of_addresses / ab_address,
+                overflow_size,
+                recursion_cutoff,
+            )?;
+            let bucket_bytes = u64::try_from(core::mem::size_of::<Bucket<PositionBlock<AB>, Z>>())?;
+            let mut levels = vec![PositionMapLevel {
+                block_capacity: plan.block_capacity,
+                is_linear: false,
+                physical_bytes_per_access: (2 * (plan.height + 1)).saturating_mul(bucket_bytes),
+            }];
+            levels.extend(plan.position_map_levels);
+            Ok((levels, plan.memory_bytes))
+        }
+    }
+
+    /// Writes `position` to the position map at `address` for each `(address, position)` in `updates`,
+    /// with one recursive access per position block touched rather than one per update.
+    /// If `updates` contains an address more than once, the last update to it wins.
+    ///
+    /// The number of accesses, and the blocks they access, reveal which updates share a position block,
+    /// so this is only for bulk updates whose addresses are public, such as initialization.
+    pub fn write_many<R: RngCore + CryptoRng>(
         &mut self,
-        address: Address,
-        position_block: PositionBlock<AB>,
+        updates: &[(Address, TreeIndex)],
         rng: &mut R,
     ) -> Result<(), OramError> {
-        let address_of_block = PositionMap::<AB, Z>::address_of_block(address);
+        let mut updates = updates.to_vec();
+        // A stable sort, so that later updates to an address are still applied after earlier ones.
+        updates.sort_by_key(|(address, _)| Self::address_of_block(*address));
 
-        match self {
-            PositionMap::Base(linear_oram) => {
-                linear_oram.write(address_of_block, position_block, rng)?;
+        let mut start = 0;
+        while start < updates.len() {
+            let address_of_block = Self::address_of_block(updates[start].0);
+            let mut end = start + 1;
+            while end < updates.len() && Self::address_of_block(updates[end].0) == address_of_block
+            {
+                end += 1;
             }
 
-            PositionMap::Recursive(block_oram) => {
-                block_oram.write(address_of_block, position_block, rng)?;
+            let mut offsets = Vec::with_capacity(end - start);
+            for (address, position) in &updates[start..end] {
+                offsets.push((Self::address_within_block(*address)?, *position));
             }
-        }
+            let block_callback = |block: &PositionBlock<AB>| {
+                let mut result = *block;
+                for (offset, position) in &offsets {
+                    result.data[*offset] = *position;
+                }
+                result
+            };
 
+            match self {
+                PositionMap::Base(linear_oram) => {
+                    linear_oram.access(address_of_block, block_callback, rng)?;
+                }
+                PositionMap::Recursive(block_oram) => {
+                    block_oram.access(address_of_block, block_callback, rng)?;
+                }
+            }
+            start = end;
+        }
         Ok(())
     }
 }
@@ -68,7 +150,7 @@ impl<const AB: BlockSize, const Z: BucketSize> PositionMap<AB, Z> {
         overflow_size: StashSize,
         recursion_cutoff: RecursionCutoff,
     ) -> Result<Self, OramError> {
-        log::info!(
+        log_info!(
             "PositionMap::new(number_of_addresses = {})",
             number_of_addresses
         );
@@ -104,7 +186,10 @@ impl<const AB: BlockSize, const Z: BucketSize> Oram for PositionMap<AB, Z> {
 
     fn block_capacity(&self) -> Result<Address, OramError> {
         match self {
-            PositionMap::Base(linear_oram) => linear_oram.block_capacity(),
+            PositionMap::Base(linear_oram) => {
+                let ab_address: Address = AB.try_into()?;
+                Ok(linear_oram.block_capacity()? * ab_address)
+            }
             PositionMap::Recursive(block_oram) => {
                 let ab_address: Address = AB.try_into()?;
                 Ok(block_oram.block_capacity()? * ab_address)
@@ -118,15 +203,31 @@ impl<const AB: BlockSize, const Z: BucketSize> Oram for PositionMap<AB, Z> {
         callback: F,
         rng: &mut R,
     ) -> Result<TreeIndex, OramError> {
+        // The block ORAM would reject the address of a block past its last, but checking here reports the address itself.
+        let capacity = self.block_capacity()?;
+        if address >= capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity,
+            });
+        }
+
         let address_of_block = PositionMap::<AB, Z>::address_of_block(address);
         let address_within_block = PositionMap::<AB, Z>::address_within_block(address)?;
 
+        // Compare the offset within the block against each index once, rather than once per scanned block.
+        let index_matches: [Choice; AB] = core::array::from_fn(|i| i.ct_eq(&address_within_block));
+
         let block_callback = |block: &PositionBlock<AB>| {
+            let mut position = TreeIndex::default();
+            for (entry, matches) in block.data.iter().zip(index_matches) {
+                position.conditional_assign(entry, matches);
+            }
+            let position_to_write = callback(&position);
+
             let mut result: PositionBlock<AB> = *block;
-            for i in 0..block.data.len() {
-                let index_matches = i.ct_eq(&address_within_block);
-                let position_to_write = callback(&block.data[i]);
-                result.data[i].conditional_assign(&position_to_write, index_matches);
+            for (entry, matches) in result.data.iter_mut().zip(index_matches) {
+                entry.conditional_assign(&position_to_write, matches);
             }
             result
         };
@@ -144,15 +245,115 @@ impl<const AB: BlockSize, const Z: BucketSize> Oram for PositionMap<AB, Z> {
             // (3) Return the address of interest from the block.
             PositionMap::Recursive(block_oram) => {
                 let block = block_oram.access(address_of_block, block_callback, rng)?;
+                Ok(ct_mux_array(address_within_block, &block.data))
+            }
+        }
+    }
+}
 
-                let mut result = u64::default();
-                for i in 0..block.data.len() {
-                    let index_matches = i.ct_eq(&address_within_block);
-                    result.conditional_assign(&block.data[i], index_matches);
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bucket::*, path_oram::PathOram, test_utils::*};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // With 8 positions per block and a recursion cutoff of 16 position blocks,
+    // a capacity of 128 uses a linear base position map and a capacity of 256 uses a recursive one.
+    create_path_oram_correctness_tests_all_parameters!(
+        PathOram,
+        "_position_map_cutoff_",
+        128,
+        1,
+        4,
+        8,
+        40,
+        16,
+        300
+    );
+    create_path_oram_correctness_tests_all_parameters!(
+        PathOram,
+        "_position_map_cutoff_",
+        256,
+        1,
+        4,
+        8,
+        40,
+        16,
+        300
+    );
+
+    thread_local! {
+        static ACCESS_COUNT: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+    }
 
-                Ok(result)
+    // Registered as an occupancy observer, which is called once per access.
+    fn count_access(_: StashSize) {
+        ACCESS_COUNT.with(|count| count.set(count.get() + 1));
+    }
+
+    fn random_updates(
+        capacity: Address,
+        count: usize,
+        rng: &mut StdRng,
+    ) -> Vec<(Address, TreeIndex)> {
+        (0..count)
+            .map(|_| (rng.gen_range(0..capacity), rng.gen_range(1..1000)))
+            .collect()
+    }
+
+    #[test]
+    fn write_many_matches_per_entry_writes() {
+        // Capacities with a linear and a recursive position map, respectively.
+        for capacity in [128, 256] {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut batched = PositionMap::<8, 4>::new(capacity, &mut rng, 40, 16).unwrap();
+            let mut naive = PositionMap::<8, 4>::new(capacity, &mut rng, 40, 16).unwrap();
+
+            // Enough updates that many addresses are updated more than once.
+            let updates = random_updates(capacity, 400, &mut rng);
+            batched.write_many(&updates, &mut rng).unwrap();
+            for (address, position) in &updates {
+                naive.write(*address, *position, &mut rng).unwrap();
+            }
+
+            for address in 0..capacity {
+                assert_eq!(
+                    batched.read(address, &mut rng).unwrap(),
+                    naive.read(address, &mut rng).unwrap()
+                );
             }
         }
     }
+
+    #[test]
+    fn write_many_accesses_each_block_once() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut position_map = PositionMap::<8, 4>::new(256, &mut rng, 40, 16).unwrap();
+        let PositionMap::Recursive(block_oram) = &mut position_map else {
+            panic!("expected a recursive position map");
+        };
+        block_oram.set_occupancy_observer(count_access);
+
+        // Every address of the first four blocks, in an order interleaving the blocks.
+        let updates: Vec<_> = (0..32).map(|i| ((i % 4) * 8 + i / 4, i + 1)).collect();
+
+        position_map.write_many(&updates, &mut rng).unwrap();
+        assert_eq!(ACCESS_COUNT.with(|count| count.replace(0)), 4);
+
+        for (address, position) in &updates {
+            position_map.write(*address, *position, &mut rng).unwrap();
+        }
+        assert_eq!(ACCESS_COUNT.with(|count| count.get()), 32);
+    }
+
+    #[test]
+    fn position_map_rejects_out_of_bounds_addresses() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // A linear position map of 13 blocks, padded past the last of its 100 addresses, and a recursive one.
+        let mut position_map = PositionMap::<8, 4>::new(100, &mut rng, 40, 16).unwrap();
+        assert_eq!(position_map.block_capacity().unwrap(), 104);
+        out_of_bounds_addresses_are_rejected(&mut position_map, &[]);
+        let mut position_map = PositionMap::<8, 4>::new(256, &mut rng, 40, 16).unwrap();
+        out_of_bounds_addresses_are_rejected(&mut position_map, &[]);
+    }
 }
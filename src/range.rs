@@ -0,0 +1,219 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Ranges of consecutive ORAM addresses, with bounds checked once at construction.
+//!
+//! Address arithmetic such as `base + offset * stride` wraps silently in release builds,
+//! producing addresses that are wrong but may still be in bounds. An [`AddressRange`] can only be constructed
+//! if all of its addresses exist in an ORAM of a given capacity, and [`Oram::read_range`](crate::Oram::read_range)
+//! and [`Oram::write_range`](crate::Oram::write_range) access each of them in turn.
+//!
+//! The start and length of a range are public: accessing a range accesses exactly its addresses, in order,
+//! so they protect only the values read and written. Use [`PathOram::range_update`](crate::PathOram::range_update)
+//! to access a range whose start and length are secret.
+//!
+//! ```
+//! use oram::{range::AddressRange, DefaultOram, Oram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut oram = DefaultOram::<u32>::new(64, &mut OsRng)?;
+//! let range = AddressRange::new(8, 3, oram.block_capacity()?)?;
+//! oram.write_range(range, &[1, 2, 3], &mut OsRng)?;
+//! assert_eq!(oram.read_range(range, &mut OsRng)?, [1, 2, 3]);
+//! assert!(AddressRange::new(62, 3, 64).is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Address, OramError};
+use alloc::{format, string::ToString};
+use core::ops::Range;
+
+/// A range of `len` consecutive addresses starting at `start`, all less than the capacity it was checked against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AddressRange {
+    start: Address,
+    len: Address,
+}
+
+impl AddressRange {
+    /// Returns the range of the `len` addresses `start..start + len`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `start + len` overflows an `Address`,
+    /// and an `AddressOutOfBoundsError` if the range extends past `capacity`.
+    pub fn new(start: Address, len: Address, capacity: Address) -> Result<Self, OramError> {
+        let end = start
+            .checked_add(len)
+            .ok_or_else(|| OramError::InvalidConfigurationError {
+                parameter_name: format!("Address range length (with start {start})"),
+                parameter_value: len.to_string(),
+            })?;
+        if end > capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: end - 1,
+                capacity,
+            });
+        }
+        Ok(Self { start, len })
+    }
+
+    /// Returns the range of every address of an ORAM of capacity `capacity`.
+    pub fn full(capacity: Address) -> Self {
+        Self {
+            start: 0,
+            len: capacity,
+        }
+    }
+
+    /// Returns the first address of the range.
+    pub fn start(&self) -> Address {
+        self.start
+    }
+
+    /// Returns the number of addresses in the range.
+    pub fn len(&self) -> Address {
+        self.len
+    }
+
+    /// Returns whether the range contains no addresses.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the address one past the end of the range.
+    pub fn end(&self) -> Address {
+        self.start + self.len
+    }
+
+    /// Returns the `offset`th address of the range, or `None` if `offset` is not less than its length.
+    pub fn get(&self, offset: Address) -> Option<Address> {
+        (offset < self.len).then(|| self.start + offset)
+    }
+
+    /// Returns an iterator over the addresses of the range, in increasing order.
+    pub fn iter(&self) -> Range<Address> {
+        self.start..self.end()
+    }
+}
+
+impl IntoIterator for AddressRange {
+    type Item = Address;
+    type IntoIter = Range<Address>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl From<AddressRange> for Range<Address> {
+    fn from(range: AddressRange) -> Self {
+        range.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, Oram, PathOram};
+    use alloc::vec::Vec;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn construction_checks_overflow_and_bounds() {
+        assert!(matches!(
+            AddressRange::new(Address::MAX, 1, Address::MAX),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        assert!(matches!(
+            AddressRange::new(1, Address::MAX, Address::MAX),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        assert!(matches!(
+            AddressRange::new(60, 5, 64),
+            Err(OramError::AddressOutOfBoundsError {
+                attempted: 64,
+                capacity: 64
+            })
+        ));
+        assert!(AddressRange::new(Address::MAX - 1, 1, Address::MAX).is_ok());
+        assert_eq!(
+            AddressRange::new(0, Address::MAX, Address::MAX)
+                .unwrap()
+                .end(),
+            Address::MAX
+        );
+    }
+
+    #[test]
+    fn empty_ranges() {
+        let range = AddressRange::new(64, 0, 64).unwrap();
+        assert!(range.is_empty());
+        assert_eq!(range.iter().count(), 0);
+        assert_eq!(range.get(0), None);
+        assert!(AddressRange::new(Address::MAX, 0, Address::MAX).is_ok());
+        assert!(AddressRange::new(65, 0, 64).is_err());
+    }
+
+    #[test]
+    fn iteration() {
+        let range = AddressRange::new(5, 3, 16).unwrap();
+        assert_eq!(range.into_iter().collect::<Vec<_>>(), [5, 6, 7]);
+        assert_eq!(range.get(2), Some(7));
+        assert_eq!(range.get(3), None);
+        assert_eq!(Range::from(range), 5..8);
+        assert_eq!(
+            AddressRange::full(4).iter().collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+    }
+
+    fn check_range_accesses<O: Oram<V = u32>>(oram: &mut O) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let capacity = oram.block_capacity().unwrap();
+
+        let empty = AddressRange::new(capacity, 0, capacity).unwrap();
+        oram.write_range(empty, &[], &mut rng).unwrap();
+        assert!(oram.read_range(empty, &mut rng).unwrap().is_empty());
+
+        let full = AddressRange::full(capacity);
+        let values: Vec<u32> = (0..capacity as u32).map(|i| i * 3).collect();
+        oram.write_range(full, &values, &mut rng).unwrap();
+        assert_eq!(oram.read_range(full, &mut rng).unwrap(), values);
+
+        let middle = AddressRange::new(2, 3, capacity).unwrap();
+        oram.write_range(middle, &[7, 8, 9], &mut rng).unwrap();
+        assert_eq!(oram.read_range(middle, &mut rng).unwrap(), [7, 8, 9]);
+        assert_eq!(oram.read(1, &mut rng).unwrap(), 3);
+        assert_eq!(oram.read(5, &mut rng).unwrap(), 15);
+
+        // Ranges and value slices of different lengths are rejected before any access.
+        assert!(matches!(
+            oram.write_range(middle, &[1, 2], &mut rng),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        assert_eq!(oram.read(4, &mut rng).unwrap(), 9);
+
+        // So are ranges checked against a larger capacity.
+        let too_long = AddressRange::full(capacity + 1);
+        assert!(matches!(
+            oram.read_range(too_long, &mut rng),
+            Err(OramError::AddressOutOfBoundsError { .. })
+        ));
+    }
+
+    #[test]
+    fn range_accesses() {
+        let mut rng = StdRng::seed_from_u64(0);
+        check_range_accesses(&mut LinearTimeOram::<u32>::new(16).unwrap());
+        check_range_accesses(
+            &mut PathOram::<u32, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap(),
+        );
+    }
+}
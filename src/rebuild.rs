@@ -0,0 +1,302 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Periodic rebuilding of a Path ORAM.
+//!
+//! [`PathOram::rebuild`] replaces the tree, stash, and position map of an ORAM with those of a fresh ORAM
+//! holding the same values (built as by [`PathOram::freeze`]), emptying the stash overflow and shrinking any stash growth.
+//! [`PathOram::set_rebuild_policy`] makes an ORAM rebuild itself automatically before an access,
+//! every so many accesses or whenever its stash overflow holds too many blocks, and reports each rebuild
+//! to the observer registered with [`PathOram::set_rebuild_observer`]. Since the rebuild precedes the access,
+//! an access either fails before it is made (if the rebuild fails) or returns its value; a completed access is never discarded.
+//!
+//! Whether a rebuild is triggered depends only on the number of accesses and on the stash occupancy,
+//! both of which are treated as public: the number of accesses is visible to anyone observing them,
+//! and the stash occupancy is already revealed by stash growth and reported by [`PathOram::stash_occupancy`].
+//!
+//! ```
+//! use oram::{rebuild::RebuildPolicy, Oram, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1)?;
+//! oram.set_rebuild_policy(RebuildPolicy::EveryNAccesses(100));
+//! for i in 0..250 {
+//!     oram.write(i % 64, i, &mut rng)?;
+//! }
+//! assert_eq!(oram.accesses_since_rebuild(), 50);
+//! assert_eq!(oram.read(3, &mut rng)?, 195);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    eviction::EvictionStrategy, path_oram::PathOram, utils::IndexWord, BlockSize, BucketSize,
+    OramBlock, OramError, StashSize,
+};
+use rand::{CryptoRng, Rng};
+
+/// When a Path ORAM rebuilds itself (see the [`rebuild`](crate::rebuild) module).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RebuildPolicy {
+    /// Never automatically; only when [`PathOram::rebuild`] is called. The default.
+    #[default]
+    Manual,
+    /// Before any access once `n` accesses have been made since the last rebuild. A value of 0 is treated as `Manual`.
+    EveryNAccesses(u64),
+    /// Before any access made while more than `k` real blocks are in the stash overflow.
+    WhenStashExceeds(StashSize),
+}
+
+/// A report of a rebuild, passed to the observer registered with [`PathOram::set_rebuild_observer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RebuildEvent {
+    /// The number of accesses since the previous rebuild (or since the ORAM was constructed).
+    pub accesses_since_rebuild: u64,
+    /// The number of real blocks in the stash overflow just before the rebuild.
+    pub stash_occupancy: StashSize,
+}
+
+// The rebuild policy of a Path ORAM, and the state needed to apply it.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RebuildSchedule {
+    policy: RebuildPolicy,
+    accesses_since_rebuild: u64,
+    observer: Option<fn(RebuildEvent)>,
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Sets when this ORAM rebuilds itself. The policy is checked before each access made through the [`Oram`](crate::Oram)
+    /// trait, [`PathOram::with_block`], or [stepwise](crate::stepwise), and once before each [transaction](crate::transaction),
+    /// each of whose accesses counts toward it. The count of accesses since the last rebuild carries over from the previous policy.
+    pub fn set_rebuild_policy(&mut self, policy: RebuildPolicy) {
+        self.rebuild_schedule.policy = policy;
+    }
+
+    /// Registers `observer` to be called after each rebuild, automatic or manual, with a report of it.
+    pub fn set_rebuild_observer(&mut self, observer: fn(RebuildEvent)) {
+        self.rebuild_schedule.observer = Some(observer);
+    }
+
+    /// Returns the number of accesses made since the last rebuild, or since this ORAM was constructed.
+    pub fn accesses_since_rebuild(&self) -> u64 {
+        self.rebuild_schedule.accesses_since_rebuild
+    }
+
+    /// Rebuilds this ORAM: evicts every pending path, then replaces the tree, stash, and position map
+    /// with those of a fresh ORAM holding the same values, built with randomness from `rng`.
+    /// Keeps the other settings of this ORAM, such as its eviction deferral, memory budget, reseed interval, and observers.
+    ///
+    /// Like [`PathOram::freeze`], takes `O(N log^2 N)` time, and while it runs holds a second copy of the ORAM,
+    /// which is not counted against the memory budget.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Poisoned` error if this ORAM is poisoned, and any error from the pending evictions (which poisons it).
+    /// If building the fresh ORAM fails, returns its error, leaving this ORAM unchanged.
+    pub fn rebuild<R: Rng + CryptoRng>(&mut self, rng: &mut R) -> Result<(), OramError> {
+        while self.evict_one()? {}
+        let stash_occupancy = self.stash_occupancy();
+        #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+        let mut fresh = Self::new_with_initial_values(
+            &self.values_by_address()?,
+            rng,
+            self.overflow_size,
+            self.recursion_cutoff,
+        )?;
+        // The fresh position map masks the leaves it draws, as this ORAM's did.
+        #[cfg(feature = "std")]
+        if let (Some(interval), crate::position_map::PositionMap::Recursive(block_oram)) =
+            (self.reseed_interval(), &mut fresh.position_map)
+        {
+            block_oram.set_reseed_interval(interval)?;
+        }
+        self.physical_memory = fresh.physical_memory;
+        self.position_map = fresh.position_map;
+        // Keep this stash, with its observer and memory budget, but give it the fresh stash's (empty) contents.
+        self.stash.restore_blocks(&fresh.stash.blocks());
+
+        let event = RebuildEvent {
+            accesses_since_rebuild: self.rebuild_schedule.accesses_since_rebuild,
+            stash_occupancy,
+        };
+        self.rebuild_schedule.accesses_since_rebuild = 0;
+        if let Some(observer) = self.rebuild_schedule.observer {
+            observer(event);
+        }
+        Ok(())
+    }
+
+    // Rebuilds this ORAM, before an access, if its policy calls for it.
+    pub(crate) fn rebuild_if_due<R: Rng + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        let due = match self.rebuild_schedule.policy {
+            RebuildPolicy::Manual => false,
+            RebuildPolicy::EveryNAccesses(n) => {
+                n > 0 && self.rebuild_schedule.accesses_since_rebuild >= n
+            }
+            RebuildPolicy::WhenStashExceeds(k) => self.stash_occupancy() > k,
+        };
+        if due {
+            self.rebuild(rng)?;
+        }
+        Ok(())
+    }
+
+    // Counts a completed access toward the rebuild policy.
+    pub(crate) fn count_access(&mut self) {
+        self.rebuild_schedule.accesses_since_rebuild += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::*, Address, Oram};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{cell::RefCell, vec::Vec};
+
+    type TestOram = PathOram<u64, 4, 8>;
+
+    thread_local! {
+        static EVENTS: RefCell<Vec<RebuildEvent>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_event(event: RebuildEvent) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+
+    fn take_events() -> Vec<RebuildEvent> {
+        EVENTS.with(|events| events.take())
+    }
+
+    fn observed_oram(capacity: Address, overflow_size: StashSize, rng: &mut StdRng) -> TestOram {
+        let mut oram = TestOram::new_with_parameters(capacity, rng, overflow_size, 1).unwrap();
+        oram.set_rebuild_observer(record_event);
+        take_events();
+        oram
+    }
+
+    #[test]
+    fn rebuilds_every_n_accesses() {
+        for max_pending_evictions in [0, 3] {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut oram = observed_oram(64, 40, &mut rng);
+            oram.set_max_pending_evictions(max_pending_evictions)
+                .unwrap();
+            oram.set_rebuild_policy(RebuildPolicy::EveryNAccesses(10));
+            // The workload makes 101 random accesses and then reads each of the 64 addresses.
+            random_workload(&mut oram, 101);
+
+            let events = take_events();
+            assert_eq!(events.len(), 16);
+            assert!(events
+                .iter()
+                .all(|event| event.accesses_since_rebuild == 10));
+            assert_eq!(oram.accesses_since_rebuild(), 5);
+            assert_eq!(oram.pending_evictions(), max_pending_evictions.min(5));
+            assert!(oram.audit().unwrap().is_consistent());
+        }
+    }
+
+    #[test]
+    fn rebuilds_when_the_stash_exceeds_a_threshold() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // With two blocks per bucket, blocks often overflow the tree.
+        let mut oram = PathOram::<u64, 2, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_rebuild_observer(record_event);
+        take_events();
+        oram.set_rebuild_policy(RebuildPolicy::WhenStashExceeds(0));
+        let mut shadow = [0; 64];
+        let mut rebuilds = 0;
+        for i in 0..2000 {
+            let address = i % 64;
+            // Any access made while a block is in the overflow is preceded by a rebuild.
+            if oram.stash_occupancy() > 0 {
+                rebuilds += 1;
+            }
+            assert_eq!(
+                oram.write(address, i, &mut rng).unwrap(),
+                shadow[address as usize]
+            );
+            shadow[address as usize] = i;
+        }
+        let events = take_events();
+        assert!(rebuilds > 0);
+        assert_eq!(events.len(), rebuilds);
+        assert!(events.iter().all(|event| event.stash_occupancy > 0));
+        let total: u64 = events
+            .iter()
+            .map(|event| event.accesses_since_rebuild)
+            .sum();
+        assert_eq!(total + oram.accesses_since_rebuild(), 2000);
+        assert!(oram.audit().unwrap().is_consistent());
+    }
+
+    #[test]
+    fn rebuilds_precede_the_access_that_is_due() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut oram = observed_oram(64, 40, &mut rng);
+        oram.set_rebuild_policy(RebuildPolicy::EveryNAccesses(10));
+        for i in 0..10 {
+            oram.write(i, i, &mut rng).unwrap();
+        }
+        assert!(take_events().is_empty());
+        assert_eq!(oram.accesses_since_rebuild(), 10);
+        assert_eq!(oram.read(3, &mut rng).unwrap(), 3);
+        assert_eq!(take_events()[0].accesses_since_rebuild, 10);
+        assert_eq!(oram.accesses_since_rebuild(), 1);
+
+        // Transactions and stepwise accesses count toward the policy too.
+        oram.transact(&[(0, 100), (1, 101), (2, 102)], &mut rng)
+            .unwrap();
+        assert_eq!(oram.accesses_since_rebuild(), 4);
+        for _ in 0..6 {
+            oram.access_stepwise(5, |value| value + 1, &mut rng)
+                .unwrap()
+                .finish()
+                .unwrap();
+        }
+        assert_eq!(oram.accesses_since_rebuild(), 10);
+        assert!(take_events().is_empty());
+        oram.transact(&[(0, 200)], &mut rng).unwrap();
+        assert_eq!(take_events()[0].accesses_since_rebuild, 10);
+        assert_eq!(oram.accesses_since_rebuild(), 1);
+        assert_eq!(oram.read(5, &mut rng).unwrap(), 11);
+    }
+
+    #[test]
+    fn manual_rebuilds_keep_contents_and_settings() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut oram = observed_oram(64, 40, &mut rng);
+        random_workload(&mut oram, 200);
+        assert!(take_events().is_empty());
+
+        let values: Vec<u64> = (0..64).map(|i| i * 3).collect();
+        for (address, value) in values.iter().enumerate() {
+            oram.write(address as Address, *value, &mut rng).unwrap();
+        }
+        oram.set_max_pending_evictions(4).unwrap();
+        oram.read(0, &mut rng).unwrap();
+        oram.rebuild(&mut rng).unwrap();
+        let events = take_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].accesses_since_rebuild, 264 + 64 + 1);
+        assert_eq!(oram.accesses_since_rebuild(), 0);
+        assert_eq!(oram.pending_evictions(), 0);
+        assert_eq!(oram.stash_occupancy(), 0);
+        assert_eq!(oram.max_pending_evictions, 4);
+        for (address, value) in values.iter().enumerate() {
+            assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *value);
+        }
+    }
+}
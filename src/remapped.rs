@@ -0,0 +1,392 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An ORAM whose (virtual) addresses are translated to physical addresses of a data ORAM through a translation table,
+//! itself an ORAM, so that records can be moved, e.g., to defragment the data ORAM, without changing the addresses
+//! that an application holds.
+//!
+//! Every access to a [`RemappedOram`] makes exactly one access to the translation table, then exactly one access to the data ORAM,
+//! so reveals neither the virtual nor the physical address accessed. Initially every virtual address is mapped
+//! to the physical address equal to it. [`RemappedOram::remap`] moves the record at a virtual address to a given free
+//! physical address, and [`RemappedOram::compact`] moves every record back to the physical address equal to its virtual address,
+//! leaving the physical addresses beyond the virtual capacity free.
+//!
+//! The translation table holds a forward entry for each virtual address and a reverse entry for each physical address,
+//! each stored as the XOR of its value and its own address, so that a table of zeros (e.g., a new ORAM) holds the identity mapping.
+//!
+//! ```
+//! use oram::{remapped::RemappedOram, DefaultOram, Oram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let data = DefaultOram::<u64>::new(64, &mut rng)?;
+//! // The table needs 32 + 64 entries, rounded up to a power of two for a Path ORAM.
+//! let table = DefaultOram::<u64>::new(128, &mut rng)?;
+//! let mut oram = RemappedOram::new(data, table, 32)?;
+//! oram.write(3, 42, &mut rng)?;
+//! oram.remap(3, 50, &mut rng)?;
+//! assert_eq!(oram.read(3, &mut rng)?, 42);
+//! oram.compact(&mut rng)?;
+//! assert_eq!(oram.read(3, &mut rng)?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Address, Oram, OramError};
+use alloc::{format, string::ToString};
+use rand::{CryptoRng, RngCore};
+use subtle::{ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+
+// The owner recorded in the reverse entry of a free physical address. Any owner at least the virtual capacity means free.
+const FREE: Address = Address::MAX;
+
+/// An ORAM translating its addresses through a translation table `T` to a data ORAM `O`. See the [module documentation](self).
+#[derive(Debug)]
+pub struct RemappedOram<O: Oram, T: Oram<V = Address>> {
+    data: O,
+    table: T,
+    virtual_capacity: Address,
+    physical_capacity: Address,
+}
+
+impl<O: Oram, T: Oram<V = Address>> RemappedOram<O, T> {
+    /// Returns a `RemappedOram` with `virtual_capacity` addresses, storing its records in `data`,
+    /// with a translation table `table`, which must hold only zeros (e.g., be newly constructed),
+    /// and which must have capacity at least `virtual_capacity` plus the capacity of `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `virtual_capacity` exceeds the capacity of `data`,
+    /// or if `table` is too small.
+    pub fn new(data: O, table: T, virtual_capacity: Address) -> Result<Self, OramError> {
+        let physical_capacity = data.block_capacity()?;
+        if virtual_capacity > physical_capacity {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!(
+                    "Virtual capacity (with physical capacity {physical_capacity})"
+                ),
+                parameter_value: virtual_capacity.to_string(),
+            });
+        }
+        let table_capacity = table.block_capacity()?;
+        if table_capacity.saturating_sub(virtual_capacity) < physical_capacity {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!(
+                    "Translation table capacity (with virtual capacity {virtual_capacity} and physical capacity {physical_capacity})"
+                ),
+                parameter_value: table_capacity.to_string(),
+            });
+        }
+        Ok(Self {
+            data,
+            table,
+            virtual_capacity,
+            physical_capacity,
+        })
+    }
+
+    /// Returns the capacity of the data ORAM, i.e., the number of physical addresses.
+    pub fn physical_capacity(&self) -> Address {
+        self.physical_capacity
+    }
+
+    // Returns the address in the translation table of the reverse entry of `physical_address`.
+    fn reverse_entry(&self, physical_address: Address) -> Address {
+        self.virtual_capacity + physical_address
+    }
+
+    /// Moves the record at `virtual_address` to `physical_address`, which must be free, or already hold that record.
+    /// The physical address it held is freed. Makes four accesses to the translation table and two to the data ORAM,
+    /// unless it fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError`, without accessing either ORAM, if either address is out of bounds,
+    /// and an `AddressCollisionError` (revealing that the physical address is taken), after one access to the translation table,
+    /// if `physical_address` holds the record of another virtual address.
+    pub fn remap<R: RngCore + CryptoRng>(
+        &mut self,
+        virtual_address: Address,
+        physical_address: Address,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        for (attempted, capacity) in [
+            (virtual_address, self.virtual_capacity),
+            (physical_address, self.physical_capacity),
+        ] {
+            if attempted >= capacity {
+                return Err(OramError::AddressOutOfBoundsError {
+                    attempted,
+                    capacity,
+                });
+            }
+        }
+
+        let owner = self.table.read(self.reverse_entry(physical_address), rng)? ^ physical_address;
+        if owner < self.virtual_capacity && owner != virtual_address {
+            return Err(OramError::AddressCollisionError {
+                virtual_address,
+                physical_address,
+            });
+        }
+
+        let old_physical_address =
+            self.table
+                .write(virtual_address, physical_address ^ virtual_address, rng)?
+                ^ virtual_address;
+        self.table.write(
+            self.reverse_entry(old_physical_address),
+            FREE ^ old_physical_address,
+            rng,
+        )?;
+        self.table.write(
+            self.reverse_entry(physical_address),
+            virtual_address ^ physical_address,
+            rng,
+        )?;
+        let value = self.data.read(old_physical_address, rng)?;
+        self.data.write(physical_address, value, rng)?;
+        Ok(())
+    }
+
+    /// Moves the record at every virtual address `v` to physical address `v`, so that the records occupy a dense prefix
+    /// of the data ORAM. Each virtual address in turn swaps its record with whatever the physical address equal to it holds,
+    /// making four accesses to the translation table and three to the data ORAM, whatever the mapping.
+    pub fn compact<R: RngCore + CryptoRng>(&mut self, rng: &mut R) -> Result<(), OramError> {
+        for virtual_address in 0..self.virtual_capacity {
+            // Map `virtual_address` to itself, and find the record displaced from that physical address, if any.
+            let physical_address = self.table.write(virtual_address, 0, rng)? ^ virtual_address;
+            let displaced = self
+                .table
+                .write(self.reverse_entry(virtual_address), 0, rng)?
+                ^ virtual_address;
+            let displaced_is_live =
+                displaced.ct_lt(&self.virtual_capacity) & !displaced.ct_eq(&virtual_address);
+
+            // Swap the two records. If the record is already in place, this rewrites it unchanged.
+            let record = self.data.read(physical_address, rng)?;
+            let displaced_record = self.data.write(virtual_address, record, rng)?;
+            self.data.write(physical_address, displaced_record, rng)?;
+
+            // Map the displaced record's virtual address, if any, to the freed physical address, and record its owner.
+            let new_owner = Address::conditional_select(
+                &FREE,
+                &displaced,
+                displaced.ct_lt(&self.virtual_capacity),
+            );
+            self.table.write(
+                self.reverse_entry(physical_address),
+                new_owner ^ physical_address,
+                rng,
+            )?;
+            self.table.access(
+                Address::conditional_select(&virtual_address, &displaced, displaced_is_live),
+                |entry| {
+                    Address::conditional_select(
+                        entry,
+                        &(physical_address ^ displaced),
+                        displaced_is_live,
+                    )
+                },
+                rng,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the data ORAM and the translation table.
+    pub fn into_inner(self) -> (O, T) {
+        (self.data, self.table)
+    }
+}
+
+impl<O: Oram, T: Oram<V = Address>> Oram for RemappedOram<O, T> {
+    type V = O::V;
+
+    fn block_capacity(&self) -> Result<Address, OramError> {
+        Ok(self.virtual_capacity)
+    }
+
+    fn access<R: RngCore + CryptoRng, F: Fn(&Self::V) -> Self::V>(
+        &mut self,
+        address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<Self::V, OramError> {
+        if address >= self.virtual_capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity: self.virtual_capacity,
+            });
+        }
+        let physical_address = self.table.read(address, rng)? ^ address;
+        self.data.access(physical_address, callback, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, test_utils::*, PathOram};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    type TestOram =
+        RemappedOram<CountingOram<PathOram<u64, 4, 8>>, CountingOram<PathOram<u64, 4, 8>>>;
+
+    fn new_remapped_oram(rng: &mut StdRng) -> TestOram {
+        let data = PathOram::new_with_parameters(64, rng, 40, 1).unwrap();
+        let table = PathOram::new_with_parameters(128, rng, 40, 1).unwrap();
+        RemappedOram::new(CountingOram::new(data), CountingOram::new(table), 32).unwrap()
+    }
+
+    // Checks that the mapping of `oram` is a bijection from the virtual addresses onto the owned physical addresses.
+    fn check_mapping(oram: &mut TestOram, rng: &mut StdRng) {
+        let mut owned = [false; 64];
+        for virtual_address in 0..32 {
+            let physical_address = oram.table.read(virtual_address, rng).unwrap() ^ virtual_address;
+            let owner = oram.table.read(32 + physical_address, rng).unwrap() ^ physical_address;
+            assert_eq!(owner, virtual_address);
+            owned[physical_address as usize] = true;
+        }
+        for physical_address in 0..64 {
+            let owner = oram.table.read(32 + physical_address, rng).unwrap() ^ physical_address;
+            assert_eq!(owner < 32, owned[physical_address as usize]);
+        }
+    }
+
+    #[test]
+    fn remapped_oram_matches_shadow_model() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_remapped_oram(&mut rng);
+        let mut shadow = [0u64; 32];
+        let mut mapping: Vec<Address> = (0..32).collect();
+        for round in 0..1000 {
+            let address = rng.gen_range(0..32);
+            match rng.gen_range(0..4) {
+                0 => assert_eq!(
+                    oram.read(address, &mut rng).unwrap(),
+                    shadow[address as usize]
+                ),
+                1 => {
+                    let value = rng.gen();
+                    let previous = oram.write(address, value, &mut rng).unwrap();
+                    assert_eq!(previous, shadow[address as usize]);
+                    shadow[address as usize] = value;
+                }
+                2 => {
+                    let target = rng.gen_range(0..64);
+                    let owner = mapping.iter().position(|&p| p == target);
+                    let result = oram.remap(address, target, &mut rng);
+                    match owner {
+                        Some(owner) if owner as Address != address => assert!(matches!(
+                            result,
+                            Err(OramError::AddressCollisionError { physical_address, .. }) if physical_address == target
+                        )),
+                        _ => {
+                            result.unwrap();
+                            mapping[address as usize] = target;
+                        }
+                    }
+                }
+                _ if round % 50 == 0 => {
+                    oram.compact(&mut rng).unwrap();
+                    mapping = (0..32).collect();
+                }
+                _ => {}
+            }
+        }
+        check_mapping(&mut oram, &mut rng);
+        for (address, value) in shadow.iter().enumerate() {
+            assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *value);
+        }
+
+        // Compaction leaves every record at the physical address equal to its virtual address.
+        oram.compact(&mut rng).unwrap();
+        check_mapping(&mut oram, &mut rng);
+        for (address, value) in shadow.iter().enumerate() {
+            assert_eq!(
+                oram.data.read(address as Address, &mut rng).unwrap(),
+                *value
+            );
+        }
+    }
+
+    #[test]
+    fn every_access_costs_one_translation_and_one_data_access() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_remapped_oram(&mut rng);
+        let costs = |oram: &TestOram| (oram.table.accesses, oram.data.accesses);
+
+        // The cost is the same through identity, stale, and updated mappings.
+        for _ in 0..3 {
+            for address in 0..32 {
+                let before = costs(&oram);
+                oram.write(address, address, &mut rng).unwrap();
+                oram.read(address, &mut rng).unwrap();
+                assert_eq!(costs(&oram), (before.0 + 2, before.1 + 2));
+            }
+            for address in 0..32 {
+                let before = costs(&oram);
+                oram.remap(address, 63 - address, &mut rng).unwrap();
+                assert_eq!(costs(&oram), (before.0 + 4, before.1 + 2));
+            }
+        }
+
+        let before = costs(&oram);
+        oram.compact(&mut rng).unwrap();
+        assert_eq!(costs(&oram), (before.0 + 4 * 32, before.1 + 3 * 32));
+    }
+
+    #[test]
+    fn invalid_addresses_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_remapped_oram(&mut rng);
+        assert!(matches!(
+            oram.remap(32, 0, &mut rng),
+            Err(OramError::AddressOutOfBoundsError {
+                attempted: 32,
+                capacity: 32
+            })
+        ));
+        assert!(matches!(
+            oram.remap(0, 64, &mut rng),
+            Err(OramError::AddressOutOfBoundsError {
+                attempted: 64,
+                capacity: 64
+            })
+        ));
+        // Physical address 1 initially holds the record of virtual address 1.
+        assert!(matches!(
+            oram.remap(0, 1, &mut rng),
+            Err(OramError::AddressCollisionError {
+                virtual_address: 0,
+                physical_address: 1
+            })
+        ));
+        assert_eq!((oram.table.accesses, oram.data.accesses), (1, 0));
+        out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+
+        let data = LinearTimeOram::<u64>::new(16).unwrap();
+        let table = LinearTimeOram::<u64>::new(24).unwrap();
+        assert!(RemappedOram::new(data, table, 8).is_ok());
+        let data = LinearTimeOram::<u64>::new(16).unwrap();
+        let table = LinearTimeOram::<u64>::new(23).unwrap();
+        assert!(RemappedOram::new(data, table, 8).is_err());
+        let data = LinearTimeOram::<u64>::new(16).unwrap();
+        let table = LinearTimeOram::<u64>::new(64).unwrap();
+        assert!(RemappedOram::new(data, table, 17).is_err());
+    }
+
+    #[test]
+    fn remapped_oram_workloads() {
+        let data = LinearTimeOram::<u64>::new(64).unwrap();
+        let table = LinearTimeOram::<u64>::new(64 + 64).unwrap();
+        let mut oram = RemappedOram::new(data, table, 64).unwrap();
+        random_workload(&mut oram, 1000);
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Protection against replayed RNG state for Path ORAMs. Requires the `std` feature.
+//!
+//! If a process is forked, or a VM snapshot resumed, the state of an RNG can be replayed:
+//! a [`LeafSampler`](crate::leaf_sampler::LeafSampler) held by an ORAM, or an RNG passed to its accesses,
+//! then chooses the same "fresh" random leaves in two copies of the ORAM, correlating their accesses.
+//!
+//! A Path ORAM with a reseed interval set by [`PathOram::set_reseed_interval`] (as are its recursive position map
+//! and the padding levels of its [uniform-depth](crate::uniform_depth) recursion)
+//! masks every leaf it draws with the output of an internal RNG, into which fresh [`OsRng`] entropy is mixed
+//! every `n` accesses. A uniformly random leaf masked with an independent value remains uniformly random,
+//! and two copies of the ORAM choose different leaves from their first reseed on, even if the RNGs they draw from are replayed.
+//! A [`RingOram`] has the same reseed interval, set by [`RingOram::set_reseed_interval`].
+//!
+//! ⚠️ **Warning**: a [snapshot](crate::snapshot) does not include the internal RNG.
+//! An ORAM restored by [`PathOram::import_snapshot`] from a snapshot of an ORAM with a reseed interval
+//! therefore refuses every access with a `ReseedRequired` error until it is given fresh entropy by
+//! [`PathOram::reseed`] or [`PathOram::reseed_from_os_rng`]. Without a reseed interval, restoring a snapshot twice
+//! and accessing both copies with replayed RNGs correlates their accesses.
+//!
+//! ```
+//! use oram::{Oram, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1)?;
+//! oram.set_reseed_interval(1000)?;
+//! oram.write(3, 42, &mut rng)?;
+//!
+//! let mut restored = PathOram::<u64, 4, 8>::import_snapshot(oram.export_snapshot()?)?;
+//! assert!(matches!(restored.read(3, &mut rng), Err(OramError::ReseedRequired)));
+//! restored.reseed_from_os_rng()?;
+//! assert_eq!(restored.read(3, &mut rng)?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    eviction::EvictionStrategy,
+    path_oram::PathOram,
+    position_map::PositionMap,
+    ring_oram::RingOram,
+    tree::{TreeHeight, TreeIndex},
+    utils::IndexWord,
+    BlockSize, BucketSize, OramBlock, OramError,
+};
+use alloc::string::ToString;
+use core::fmt;
+use rand::{rngs::OsRng, rngs::StdRng, Rng, RngCore, SeedableRng};
+
+/// The internal RNG with which a Path ORAM masks the leaves it draws, and the policy for reseeding it.
+pub(crate) struct Reseeder {
+    interval: u64,
+    draws_since_reseed: u64,
+    rng: StdRng,
+    // True if the RNG has not been seeded since the ORAM was restored from a snapshot.
+    awaiting_entropy: bool,
+}
+
+// The RNG state is secret, so is not printed.
+impl fmt::Debug for Reseeder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reseeder")
+            .field("interval", &self.interval)
+            .field("draws_since_reseed", &self.draws_since_reseed)
+            .field("awaiting_entropy", &self.awaiting_entropy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Reseeder {
+    fn new(interval: u64) -> Result<Self, OramError> {
+        Ok(Self {
+            interval,
+            draws_since_reseed: 0,
+            rng: StdRng::from_rng(OsRng)?,
+            awaiting_entropy: false,
+        })
+    }
+
+    /// Returns a reseeder for an ORAM restored from a snapshot, which must be given entropy before it is used.
+    pub fn awaiting_entropy(interval: u64) -> Self {
+        Self {
+            interval,
+            draws_since_reseed: 0,
+            rng: StdRng::from_seed([0; 32]),
+            awaiting_entropy: true,
+        }
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub fn is_awaiting_entropy(&self) -> bool {
+        self.awaiting_entropy
+    }
+
+    // Mixes `entropy` into the RNG.
+    fn mix(&mut self, entropy: [u8; 32]) {
+        let mut seed: [u8; 32] = self.rng.gen();
+        for (byte, entropy_byte) in seed.iter_mut().zip(entropy) {
+            *byte ^= entropy_byte;
+        }
+        self.rng = StdRng::from_seed(seed);
+        self.draws_since_reseed = 0;
+        self.awaiting_entropy = false;
+    }
+
+    /// Masks `leaf`, a leaf of a tree of height `height`, first mixing in fresh entropy if `interval` leaves have been masked
+    /// since the last reseed. Fails with an `RngError`, leaving the RNG unchanged, if `OsRng` does.
+    pub fn mask_leaf(
+        &mut self,
+        leaf: TreeIndex,
+        height: TreeHeight,
+    ) -> Result<TreeIndex, OramError> {
+        if self.draws_since_reseed >= self.interval {
+            self.mix(os_entropy()?);
+        }
+        self.draws_since_reseed += 1;
+        let first_leaf: TreeIndex = 1 << height;
+        Ok(first_leaf + ((leaf - first_leaf) ^ (self.rng.next_u64() & (first_leaf - 1))))
+    }
+}
+
+// Returns 32 bytes drawn from `OsRng`, or an `RngError` if it fails.
+fn os_entropy() -> Result<[u8; 32], OramError> {
+    let mut entropy = [0; 32];
+    OsRng.try_fill_bytes(&mut entropy)?;
+    Ok(entropy)
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Masks every leaf this ORAM, its recursive position map, and its padding levels draw with an internal RNG,
+    /// into which fresh `OsRng` entropy is mixed every `interval` accesses (see the [`reseed`](crate::reseed) module).
+    /// If a reseed interval is already set, changes it, keeping the internal RNG.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `interval` is 0, and an `RngError` if `OsRng` fails to seed the internal RNG.
+    pub fn set_reseed_interval(&mut self, interval: u64) -> Result<(), OramError> {
+        if interval == 0 {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Reseed interval".to_string(),
+                parameter_value: interval.to_string(),
+            });
+        }
+        match &mut self.reseeder {
+            Some(reseeder) => reseeder.interval = interval,
+            None => self.reseeder = Some(Reseeder::new(interval)?),
+        }
+        if let PositionMap::Recursive(block_oram) = &mut self.position_map {
+            block_oram.set_reseed_interval(interval)?;
+        }
+        for padding_oram in &mut self.recursion_padding {
+            padding_oram.set_reseed_interval(interval)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the reseed interval set by [`PathOram::set_reseed_interval`], if any.
+    pub fn reseed_interval(&self) -> Option<u64> {
+        self.reseeder.as_ref().map(Reseeder::interval)
+    }
+
+    /// Mixes `entropy`, which must be fresh and secret, into the internal RNG of this ORAM, of its recursive position map,
+    /// and of its padding levels, so that it again accepts accesses if it was restored from a snapshot.
+    /// Does nothing if no reseed interval is set.
+    pub fn reseed(&mut self, entropy: [u8; 32]) {
+        // Derive independent entropy for each level of the recursion.
+        let mut source = StdRng::from_seed(entropy);
+        if let Some(reseeder) = &mut self.reseeder {
+            reseeder.mix(source.gen());
+        }
+        if let PositionMap::Recursive(block_oram) = &mut self.position_map {
+            block_oram.reseed(source.gen());
+        }
+        for padding_oram in &mut self.recursion_padding {
+            padding_oram.reseed(source.gen());
+        }
+    }
+
+    /// As [`PathOram::reseed`], with entropy drawn from `OsRng`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `RngError`, leaving the ORAM unchanged, if `OsRng` fails.
+    pub fn reseed_from_os_rng(&mut self) -> Result<(), OramError> {
+        self.reseed(os_entropy()?);
+        Ok(())
+    }
+
+    /// Returns whether this ORAM (or its recursive position map, or one of its padding levels) was restored from a snapshot,
+    /// and is refusing accesses until it is reseeded.
+    pub fn is_awaiting_reseed(&self) -> bool {
+        let inner_awaiting = match &self.position_map {
+            PositionMap::Base(_) => false,
+            PositionMap::Recursive(block_oram) => block_oram.is_awaiting_reseed(),
+        };
+        self.reseeder
+            .as_ref()
+            .is_some_and(Reseeder::is_awaiting_entropy)
+            || inner_awaiting
+            || self
+                .recursion_padding
+                .iter()
+                .any(PathOram::is_awaiting_reseed)
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const S: BucketSize, const AB: BlockSize>
+    RingOram<V, Z, S, AB>
+{
+    /// As [`PathOram::set_reseed_interval`]: masks every leaf this ORAM and its recursive position map draw
+    /// with an internal RNG, into which fresh `OsRng` entropy is mixed every `interval` accesses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `interval` is 0, and an `RngError` if `OsRng` fails to seed the internal RNG.
+    pub fn set_reseed_interval(&mut self, interval: u64) -> Result<(), OramError> {
+        if interval == 0 {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Reseed interval".to_string(),
+                parameter_value: interval.to_string(),
+            });
+        }
+        match &mut self.reseeder {
+            Some(reseeder) => reseeder.interval = interval,
+            None => self.reseeder = Some(Reseeder::new(interval)?),
+        }
+        if let PositionMap::Recursive(block_oram) = &mut self.position_map {
+            block_oram.set_reseed_interval(interval)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the reseed interval set by [`RingOram::set_reseed_interval`], if any.
+    pub fn reseed_interval(&self) -> Option<u64> {
+        self.reseeder.as_ref().map(Reseeder::interval)
+    }
+
+    /// As [`PathOram::reseed`]: mixes `entropy`, which must be fresh and secret,
+    /// into the internal RNG of this ORAM and of its recursive position map.
+    pub fn reseed(&mut self, entropy: [u8; 32]) {
+        let mut source = StdRng::from_seed(entropy);
+        if let Some(reseeder) = &mut self.reseeder {
+            reseeder.mix(source.gen());
+        }
+        if let PositionMap::Recursive(block_oram) = &mut self.position_map {
+            block_oram.reseed(source.gen());
+        }
+    }
+
+    /// As [`RingOram::reseed`], with entropy drawn from `OsRng`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `RngError`, leaving the ORAM unchanged, if `OsRng` fails.
+    pub fn reseed_from_os_rng(&mut self) -> Result<(), OramError> {
+        self.reseed(os_entropy()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{leaf_sampler::RngLeafSampler, Oram};
+    use alloc::{string::String, vec, vec::Vec};
+
+    type TestOram = PathOram<u64, 4, 8>;
+
+    // Returns a rendering of the contents of `oram`, but not of its internal RNG.
+    fn state(oram: &TestOram) -> String {
+        oram.debug_dump()
+    }
+
+    // Returns two copies of the same ORAM, as if the process holding it had been forked.
+    fn forked_orams(reseed_interval: Option<u64>) -> (TestOram, TestOram) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        if let Some(interval) = reseed_interval {
+            oram.set_reseed_interval(interval).unwrap();
+        }
+        for address in 0..64 {
+            oram.write(address, address, &mut rng).unwrap();
+        }
+        let snapshot = oram.export_snapshot().unwrap();
+        (
+            TestOram::import_snapshot(snapshot.clone()).unwrap(),
+            TestOram::import_snapshot(snapshot).unwrap(),
+        )
+    }
+
+    #[test]
+    fn replayed_rngs_correlate_accesses_without_reseeding() {
+        let (mut a, mut b) = forked_orams(None);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut replayed_rng = rng.clone();
+        for address in 0..20 {
+            a.read(address, &mut rng).unwrap();
+            b.read(address, &mut replayed_rng).unwrap();
+            assert_eq!(state(&a), state(&b));
+        }
+    }
+
+    #[test]
+    fn reseeding_breaks_the_correlation_of_replayed_rngs() {
+        let (mut a, mut b) = forked_orams(Some(5));
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut replayed_rng = rng.clone();
+
+        // Restored ORAMs refuse accesses, without being poisoned, until they are reseeded.
+        assert!(a.is_awaiting_reseed());
+        assert!(matches!(
+            a.read(0, &mut rng),
+            Err(OramError::ReseedRequired)
+        ));
+        assert!(!a.is_poisoned());
+
+        // Given the same entropy, the copies choose the same leaves until their next reseed from `OsRng`...
+        a.reseed([7; 32]);
+        b.reseed([7; 32]);
+        assert!(!a.is_awaiting_reseed());
+        for address in 0..5 {
+            assert_eq!(a.read(address, &mut rng).unwrap(), address);
+            assert_eq!(b.read(address, &mut replayed_rng).unwrap(), address);
+            assert_eq!(state(&a), state(&b));
+        }
+        // ...after which they diverge.
+        a.read(5, &mut rng).unwrap();
+        b.read(5, &mut replayed_rng).unwrap();
+        assert_ne!(state(&a), state(&b));
+
+        // Given different entropy, they diverge at once.
+        let (mut a, mut b) = forked_orams(Some(5));
+        a.reseed([7; 32]);
+        b.reseed([8; 32]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut replayed_rng = rng.clone();
+        a.read(0, &mut rng).unwrap();
+        b.read(0, &mut replayed_rng).unwrap();
+        assert_ne!(state(&a), state(&b));
+    }
+
+    #[test]
+    fn reseeding_masks_leaves_from_a_sampler() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let sampler = RngLeafSampler(StdRng::seed_from_u64(2));
+        let mut a = TestOram::new_with_leaf_sampler(64, sampler, &mut rng, 40, 64).unwrap();
+        let sampler = RngLeafSampler(StdRng::seed_from_u64(2));
+        let mut b = TestOram::new_with_leaf_sampler(64, sampler, &mut rng, 40, 64).unwrap();
+        assert_eq!(state(&a), state(&b));
+
+        a.set_reseed_interval(1).unwrap();
+        b.set_reseed_interval(1).unwrap();
+        assert_eq!(a.reseed_interval(), Some(1));
+        for address in 0..64 {
+            a.write(address, address, &mut rng).unwrap();
+            b.write(address, address, &mut rng).unwrap();
+        }
+        assert_ne!(state(&a), state(&b));
+        for address in 0..64 {
+            assert_eq!(a.read(address, &mut rng).unwrap(), address);
+        }
+        assert!(a.set_reseed_interval(0).is_err());
+    }
+
+    // Returns the reseed interval of each level of the position map recursion of `oram`, from the top.
+    fn recursion_intervals<V: OramBlock, const AB: BlockSize>(
+        oram: &PathOram<V, 4, AB>,
+    ) -> Vec<Option<u64>> {
+        let mut intervals = vec![oram.reseed_interval()];
+        if let PositionMap::Recursive(block_oram) = &oram.position_map {
+            intervals.extend(recursion_intervals(block_oram));
+        }
+        intervals
+    }
+
+    #[test]
+    fn rebuilds_keep_the_reseed_interval() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let levels = oram.recursion_levels_per_access().unwrap();
+        oram.set_uniform_recursion_depth(levels + 1, &mut rng)
+            .unwrap();
+        oram.set_reseed_interval(5).unwrap();
+        for address in 0..64 {
+            oram.write(address, address, &mut rng).unwrap();
+        }
+
+        oram.rebuild(&mut rng).unwrap();
+        let intervals = recursion_intervals(&oram);
+        assert!(intervals.len() > 1);
+        assert!(intervals.iter().all(|&interval| interval == Some(5)));
+        assert_eq!(oram.recursion_padding[0].reseed_interval(), Some(5));
+        for address in 0..64 {
+            assert_eq!(oram.read(address, &mut rng).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn padding_levels_are_reseeded() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let padding_intervals = |oram: &TestOram| -> Vec<Option<u64>> {
+            oram.recursion_padding
+                .iter()
+                .map(PathOram::reseed_interval)
+                .collect()
+        };
+
+        // Padding levels take the reseed interval whether they are added before or after it is set.
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let levels = oram.recursion_levels_per_access().unwrap();
+        oram.set_uniform_recursion_depth(levels + 2, &mut rng)
+            .unwrap();
+        oram.set_reseed_interval(5).unwrap();
+        assert_eq!(padding_intervals(&oram), [Some(5), Some(5)]);
+
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_reseed_interval(5).unwrap();
+        oram.set_uniform_recursion_depth(levels + 2, &mut rng)
+            .unwrap();
+        assert_eq!(padding_intervals(&oram), [Some(5), Some(5)]);
+        for address in 0..64 {
+            oram.write(address, address, &mut rng).unwrap();
+        }
+
+        // A restored ORAM waits for entropy for its padding levels too, and reseeding gives it to them.
+        let mut restored = TestOram::import_snapshot(oram.export_snapshot().unwrap()).unwrap();
+        assert!(restored
+            .recursion_padding
+            .iter()
+            .all(PathOram::is_awaiting_reseed));
+        restored.reseed_from_os_rng().unwrap();
+        assert!(!restored.is_awaiting_reseed());
+        for address in 0..64 {
+            assert_eq!(restored.read(address, &mut rng).unwrap(), address);
+        }
+    }
+}
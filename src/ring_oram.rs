@@ -0,0 +1,533 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An implementation of Ring ORAM.
+
+#[cfg(feature = "std")]
+use crate::reseed::Reseeder;
+use crate::{
+    bucket::{Bucket, PathOramBlock},
+    leaf_sampler::LeafSampler,
+    oblivious::{bitonic_sort_by_keys, compact},
+    path_oram::{PathOram, DEFAULT_POSITIONS_PER_BLOCK},
+    position_map::PositionMap,
+    stash::ObliviousStash,
+    tree::{reverse_lexicographic_leaf, CompleteBinaryTreeIndex, TreeHeight, TreeIndex},
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError, RecursionCutoff, StashSize,
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+use rand::{CryptoRng, Rng};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+
+/// A doubly oblivious Ring ORAM.
+///
+/// Each bucket holds `Z` slots for real blocks and `S` additional dummy slots,
+/// randomly permuted so that real and dummy slots are indistinguishable.
+/// Per-bucket metadata (the address held by each slot, and which slots have been read since the bucket was last shuffled)
+/// is kept apart from the blocks, so that each access reads the value of exactly one slot per bucket on the path:
+/// the slot holding the requested block if it is present, and otherwise an unread dummy slot chosen uniformly at random.
+/// A slot that has been read is invalid until its bucket is next shuffled.
+///
+/// Every `A = ceil(S / 2)` accesses, the ORAM evicts along the next path in reverse-lexicographic order,
+/// compacting and reshuffling each bucket on that path.
+/// A bucket that has been read `S` times since it was last shuffled is reshuffled early, so that it always has an unread dummy slot.
+///
+/// ## Parameters
+///
+/// - Block type `V`: the type of elements stored by the ORAM.
+/// - Bucket size `Z`: the number of real slots per bucket. Must be at least 2.
+/// - Dummy slots `S`: the number of dummy slots per bucket. Must be at least 1, and `Z + S` must be at most 64.
+///   Larger `S` means less frequent evictions and reshuffles, at the cost of more memory.
+/// - Positions per block `AB`: as for [`PathOram`]. Defaults to [`DEFAULT_POSITIONS_PER_BLOCK`].
+///
+/// The overflow size and recursion cutoff are as for [`PathOram`], and so are the security caveats concerning stash overflow.
+/// As a Path ORAM does, a Ring ORAM is poisoned by a failed access (see [`RingOram::is_poisoned`]),
+/// may draw its leaves from a [`LeafSampler`] (see [`RingOram::new_with_leaf_sampler`]),
+/// and, with the `std` feature, may mask them with a reseeded internal RNG (see [`RingOram::set_reseed_interval`]).
+#[derive(Debug)]
+pub struct RingOram<
+    V: OramBlock,
+    const Z: BucketSize,
+    const S: BucketSize,
+    const AB: BlockSize = DEFAULT_POSITIONS_PER_BLOCK,
+> {
+    /// The slots of the tree. Bucket `b` occupies slots `b * (Z + S)..(b + 1) * (Z + S)`.
+    physical_memory: Vec<PathOramBlock<V>>,
+    /// The address of the block held by each slot, or `Address::MAX` for a dummy slot.
+    slot_addresses: Vec<Address>,
+    /// For each bucket, a bitmask of the slots read since the bucket was last shuffled.
+    read_masks: Vec<u64>,
+    stash: ObliviousStash<V>,
+    pub(crate) position_map: PositionMap<AB, Z>,
+    pub(crate) height: TreeHeight,
+    // The number of accesses since the last eviction, which is less than `EVICTION_PERIOD`.
+    accesses_since_eviction: u64,
+    eviction_count: u64,
+    // The source of new leaves for accessed blocks, or `None` to draw them from the RNG passed to each access.
+    leaf_sampler: Option<Box<dyn LeafSampler>>,
+    // True if an access failed partway, after which every access fails.
+    poisoned: bool,
+    // The internal RNG masking new leaves, if a reseed interval is set.
+    #[cfg(feature = "std")]
+    pub(crate) reseeder: Option<Reseeder>,
+}
+
+impl<V: OramBlock, const Z: BucketSize, const S: BucketSize, const AB: BlockSize>
+    RingOram<V, Z, S, AB>
+{
+    const SLOTS_PER_BUCKET: usize = Z + S;
+    const EVICTION_PERIOD: u64 = S.div_ceil(2) as u64;
+    // As `PathOram::VALID_PARAMETERS`.
+    const VALID_PARAMETERS: () = assert!(
+        S >= 1 && Self::SLOTS_PER_BUCKET <= 64,
+        "the number of dummy slots S must be at least 1, and Z + S at most 64"
+    );
+
+    /// Returns a new `RingOram` mapping addresses `0 <= address < block_capacity` to default `V` values.
+    /// See [`PathOram::new_with_parameters`] for a description of the parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` in the cases listed for [`PathOram::new_with_parameters`].
+    /// As there, invalid values of `Z` fail to compile, and so do an `S` of 0 and a `Z + S` larger than 64.
+    pub fn new_with_parameters<R: Rng + CryptoRng>(
+        block_capacity: Address,
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<Self, OramError> {
+        let path_oram = PathOram::<V, Z, AB>::new_with_parameters(
+            block_capacity,
+            rng,
+            overflow_size,
+            recursion_cutoff,
+        )?;
+        Self::from_path_oram(path_oram, rng, overflow_size)
+    }
+
+    /// As [`RingOram::new_with_parameters`], but drawing the initial placement of blocks, and the new leaf of every accessed block,
+    /// from `leaf_sampler` rather than from `rng`, as [`PathOram::new_with_leaf_sampler`] does.
+    /// `rng` and the RNG passed to each access are still used to shuffle buckets, to choose the dummy slots read,
+    /// and by a recursive position map.
+    ///
+    /// # Errors
+    ///
+    /// As [`RingOram::new_with_parameters`].
+    pub fn new_with_leaf_sampler<L: LeafSampler + 'static, R: Rng + CryptoRng>(
+        block_capacity: Address,
+        leaf_sampler: L,
+        rng: &mut R,
+        overflow_size: StashSize,
+        recursion_cutoff: RecursionCutoff,
+    ) -> Result<Self, OramError> {
+        let path_oram = PathOram::<V, Z, AB>::new_with_leaf_sampler(
+            block_capacity,
+            leaf_sampler,
+            rng,
+            overflow_size,
+            recursion_cutoff,
+        )?;
+        Self::from_path_oram(path_oram, rng, overflow_size)
+    }
+
+    // Path ORAM's initial layout (every block in a leaf bucket, with a matching position map) is also valid for Ring ORAM,
+    // once each bucket is shuffled together with its dummy slots.
+    fn from_path_oram<R: Rng + CryptoRng>(
+        path_oram: PathOram<V, Z, AB>,
+        rng: &mut R,
+        overflow_size: StashSize,
+    ) -> Result<Self, OramError> {
+        let () = Self::VALID_PARAMETERS;
+        log_info!("RingOram::new(capacity = {})", path_oram.block_capacity()?);
+
+        let number_of_buckets = path_oram.physical_memory.len();
+        let number_of_slots = number_of_buckets * Self::SLOTS_PER_BUCKET;
+        let path_size = u64::try_from(Z)? * (path_oram.height + 1);
+
+        let mut result = Self {
+            physical_memory: vec![PathOramBlock::dummy(); number_of_slots],
+            slot_addresses: vec![Address::MAX; number_of_slots],
+            read_masks: vec![0; number_of_buckets],
+            stash: ObliviousStash::new(path_size, overflow_size)?,
+            position_map: path_oram.position_map,
+            height: path_oram.height,
+            accesses_since_eviction: 0,
+            eviction_count: 0,
+            leaf_sampler: path_oram.leaf_sampler,
+            poisoned: false,
+            #[cfg(feature = "std")]
+            reseeder: None,
+        };
+
+        for (bucket_index, bucket) in path_oram.physical_memory.iter().enumerate() {
+            result.write_bucket(bucket_index, bucket, rng);
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the number of evictions performed so far, modulo 2^64.
+    pub fn eviction_count(&self) -> u64 {
+        self.eviction_count
+    }
+
+    /// Returns true if the ORAM is poisoned: an access failed partway, possibly leaving the ORAM inconsistent,
+    /// so every subsequent access fails with a `Poisoned` error. As for [`PathOram::is_poisoned`],
+    /// any error from an access to an address within the capacity poisons the ORAM; an `AddressOutOfBoundsError` does not.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    // Draws a new leaf for an accessed block, from the leaf sampler if there is one and otherwise from `rng`,
+    // masked by the internal RNG if a reseed interval is set.
+    fn sample_new_position<R: Rng + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<TreeIndex, OramError> {
+        let leaf = match &mut self.leaf_sampler {
+            Some(sampler) => sampler.sample_leaf(self.height)?,
+            None => CompleteBinaryTreeIndex::random_leaf(self.height, rng)?,
+        };
+        #[cfg(feature = "std")]
+        if let Some(reseeder) = &mut self.reseeder {
+            return reseeder.mask_leaf(leaf, self.height);
+        }
+        Ok(leaf)
+    }
+
+    // Returns whether slot `slot` of the bucket with read mask `read_mask` has not been read since the bucket was last shuffled.
+    fn ct_is_unread(read_mask: u64, slot: usize) -> Choice {
+        // The masked bit is 0 or 1, so the cast is lossless.
+        let bit = ((read_mask >> slot) & 1) as u8;
+        !Choice::from(bit)
+    }
+
+    // Returns the real blocks held by the unread slots of bucket `bucket_index`, compacted into a `Bucket<V, Z>`.
+    // There are never more than `Z` such blocks, since a bucket is always shuffled from at most `Z` real blocks.
+    fn valid_blocks(&self, bucket_index: usize) -> Bucket<V, Z> {
+        let start = bucket_index * Self::SLOTS_PER_BUCKET;
+        let read_mask = self.read_masks[bucket_index];
+
+        let mut blocks = self.physical_memory[start..start + Self::SLOTS_PER_BUCKET].to_vec();
+        let mut marks = Vec::with_capacity(Self::SLOTS_PER_BUCKET);
+        for (slot, block) in blocks.iter_mut().enumerate() {
+            let is_valid = !self.slot_addresses[start + slot].ct_eq(&Address::MAX)
+                & Self::ct_is_unread(read_mask, slot);
+            block.conditional_assign(&PathOramBlock::dummy(), !is_valid);
+            marks.push(is_valid);
+        }
+
+        compact(&mut blocks, &marks);
+
+        let mut result = Bucket::<V, Z>::default();
+        result.blocks.copy_from_slice(&blocks[..Z]);
+        result
+    }
+
+    // Writes the blocks of `bucket`, together with `S` dummy blocks, to bucket `bucket_index` in a fresh random order,
+    // and marks every slot of the bucket unread.
+    fn write_bucket<R: Rng + CryptoRng>(
+        &mut self,
+        bucket_index: usize,
+        bucket: &Bucket<V, Z>,
+        rng: &mut R,
+    ) {
+        let start = bucket_index * Self::SLOTS_PER_BUCKET;
+
+        let mut blocks = bucket.blocks.to_vec();
+        blocks.resize(Self::SLOTS_PER_BUCKET, PathOramBlock::dummy());
+        let mut keys: Vec<u64> = (0..Self::SLOTS_PER_BUCKET).map(|_| rng.gen()).collect();
+        bitonic_sort_by_keys(&mut blocks, &mut keys);
+
+        for (slot, block) in blocks.iter().enumerate() {
+            self.physical_memory[start + slot] = *block;
+            self.slot_addresses[start + slot] = block.address;
+        }
+        self.read_masks[bucket_index] = 0;
+    }
+
+    // Reads one slot of bucket `bucket_index`: the slot holding the block with address `address` if it is present and unread,
+    // and otherwise a uniformly random unread dummy slot. Returns the block read if it is the requested one, and a dummy otherwise.
+    fn read_slot<R: Rng + CryptoRng>(
+        &mut self,
+        bucket_index: usize,
+        address: Address,
+        rng: &mut R,
+    ) -> PathOramBlock<V> {
+        let start = bucket_index * Self::SLOTS_PER_BUCKET;
+        let read_mask = self.read_masks[bucket_index];
+
+        let mut chosen_slot: u64 = 0;
+        let mut found: Choice = 0.into();
+        let mut smallest_dummy_key = u64::MAX;
+        for slot in 0..Self::SLOTS_PER_BUCKET {
+            let slot_address = &self.slot_addresses[start + slot];
+            let is_unread = Self::ct_is_unread(read_mask, slot);
+            let slot_u64 = slot as u64;
+
+            let is_target = slot_address.ct_eq(&address) & is_unread;
+            chosen_slot.conditional_assign(&slot_u64, is_target);
+            found |= is_target;
+
+            // Among the unread dummy slots, choose the one with the smallest fresh random key.
+            let dummy_key: u64 = rng.gen();
+            let is_better_dummy = slot_address.ct_eq(&Address::MAX)
+                & is_unread
+                & dummy_key.ct_lt(&smallest_dummy_key);
+            smallest_dummy_key.conditional_assign(&dummy_key, is_better_dummy);
+            chosen_slot.conditional_assign(&slot_u64, is_better_dummy & !found);
+        }
+
+        // The slot read is a uniformly random unread slot in either case, so reading it does not reveal whether the block was found.
+        let chosen_slot = chosen_slot as usize;
+        let mut result = self.physical_memory[start + chosen_slot];
+        self.read_masks[bucket_index] |= 1 << chosen_slot;
+
+        result.conditional_assign(&PathOramBlock::dummy(), !found);
+        result
+    }
+
+    // Evicts the stash into the path to `leaf`, compacting and reshuffling every bucket on the path.
+    fn evict<R: Rng + CryptoRng>(&mut self, leaf: u64, rng: &mut R) -> Result<(), OramError> {
+        let mut bucket_indices = Vec::new();
+        for depth in 0..=self.height {
+            bucket_indices.push(usize::try_from(leaf.ct_node_on_path(depth, self.height))?);
+        }
+
+        let mut path_buckets: Vec<Bucket<V, Z>> = bucket_indices
+            .iter()
+            .map(|bucket_index| self.valid_blocks(*bucket_index))
+            .collect();
+
+        self.stash.read_from_path_buckets(&path_buckets)?;
+        self.stash.write_to_path_buckets(&mut path_buckets, leaf)?;
+
+        for (bucket_index, bucket) in bucket_indices.iter().zip(path_buckets.iter()) {
+            self.write_bucket(*bucket_index, bucket, rng);
+        }
+
+        // Each access since the last eviction added one slot to the stash.
+        self.stash
+            .release_slots(usize::try_from(Self::EVICTION_PERIOD)?)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn stash_occupancy(&self) -> StashSize {
+        self.stash.occupancy()
+    }
+
+    // Performs an access as `Oram::access` does, for an address known to be within the capacity.
+    fn access_in_bounds<R: Rng + CryptoRng, F: Fn(&V) -> V>(
+        &mut self,
+        address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<V, OramError> {
+        let new_position = self.sample_new_position(rng)?;
+        let position = self.position_map.write(address, new_position, rng)?;
+
+        // Every position map entry is a leaf unless the position map has been corrupted.
+        if !position.is_leaf(self.height) {
+            return Err(OramError::IntegrityError { address });
+        }
+
+        // Read one slot from each bucket on the path, moving the target block (if found) into the stash.
+        let mut taken = PathOramBlock::<V>::dummy();
+        for depth in 0..=self.height {
+            let bucket_index = usize::try_from(position.ct_node_on_path(depth, self.height))?;
+            let block = self.read_slot(bucket_index, address, rng);
+            taken.conditional_assign(&block, !block.ct_is_dummy());
+        }
+        self.stash.push_block(&taken)?;
+
+        let result = self.stash.access(address, new_position, callback);
+
+        // Reshuffle any bucket on the path that has run out of dummy slots to read.
+        // The number of reads of each bucket depends only on the (public) sequence of paths read.
+        for depth in 0..=self.height {
+            let bucket_index = usize::try_from(position.ct_node_on_path(depth, self.height))?;
+            if self.read_masks[bucket_index].count_ones() >= u32::try_from(S)? {
+                let bucket = self.valid_blocks(bucket_index);
+                self.write_bucket(bucket_index, &bucket, rng);
+            }
+        }
+
+        self.accesses_since_eviction += 1;
+        if self.accesses_since_eviction == Self::EVICTION_PERIOD {
+            self.accesses_since_eviction = 0;
+            let leaf = reverse_lexicographic_leaf(self.eviction_count, self.height);
+            self.evict(leaf, rng)?;
+            // The eviction order repeats with a period (the number of leaves) dividing 2^64, so wrapping is harmless.
+            self.eviction_count = self.eviction_count.wrapping_add(1);
+        }
+
+        result
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const S: BucketSize, const AB: BlockSize> Oram
+    for RingOram<V, Z, S, AB>
+{
+    type V = V;
+
+    fn access<R: Rng + CryptoRng, F: Fn(&V) -> V>(
+        &mut self,
+        address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<V, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+
+        // This operation is not constant-time, but only leaks whether the ORAM index is well-formed or not.
+        if address >= self.block_capacity()? {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity: self.block_capacity()?,
+            });
+        }
+
+        let result = self.access_in_bounds(address, callback, rng);
+        self.poisoned = result.is_err();
+        result
+    }
+
+    fn block_capacity(&self) -> Result<Address, OramError> {
+        Ok(u64::try_from(self.read_masks.len())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bucket::*,
+        leaf_sampler::{RngLeafSampler, ScriptedLeafSampler},
+        test_utils::*,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    create_path_oram_correctness_tests_helper!(RingOram, "_ring_", 4, 6, 1, 40);
+    create_path_oram_correctness_tests_helper!(RingOram, "_ring_", 3, 2, 1, 10);
+
+    #[test]
+    fn ring_oram_reads_one_slot_per_bucket() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram =
+            RingOram::<BlockValue<1>, 4, 6>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let height = oram.height;
+
+        let position = oram.position_map.read(5, &mut rng).unwrap();
+        oram.read(5, &mut rng).unwrap();
+
+        // The first access triggers neither an eviction nor a reshuffle.
+        for (bucket_index, read_mask) in oram.read_masks.iter().enumerate() {
+            let on_path = (0..=height).any(|depth| {
+                position.ct_node_on_path(depth, height) == u64::try_from(bucket_index).unwrap()
+            });
+            assert_eq!(read_mask.count_ones(), u32::from(on_path));
+        }
+        // The block read stays in the stash until an eviction path admits it.
+        assert_eq!(oram.stash_occupancy(), 1);
+    }
+
+    #[test]
+    fn ring_oram_leaf_distribution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram =
+            RingOram::<BlockValue<1>, 4, 6>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let number_of_leaves = 1usize << oram.height;
+
+        // Repeatedly accessing one address should remap it to every leaf about equally often.
+        let trials = 200 * number_of_leaves;
+        let mut leaf_counts = vec![0; number_of_leaves];
+        for _ in 0..trials {
+            oram.read(0, &mut rng).unwrap();
+            let leaf = oram.position_map.read(0, &mut rng).unwrap();
+            assert!(leaf.is_leaf(oram.height));
+            leaf_counts[usize::try_from(leaf).unwrap() - number_of_leaves] += 1;
+        }
+
+        assert!(leaf_counts.iter().all(|count| (140..260).contains(count)));
+    }
+
+    #[test]
+    fn ring_oram_draws_leaves_from_a_sampler() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let sampler = ScriptedLeafSampler::new([33, 60]);
+        let mut oram = RingOram::<BlockValue<1>, 4, 6, 16>::new_with_leaf_sampler(
+            64, sampler, &mut rng, 40, 1,
+        )
+        .unwrap();
+        assert!(matches!(oram.position_map, PositionMap::Recursive(_)));
+
+        oram.read(5, &mut rng).unwrap();
+        oram.read(6, &mut rng).unwrap();
+        assert_eq!(oram.position_map.read(5, &mut rng).unwrap(), 33);
+        assert_eq!(oram.position_map.read(6, &mut rng).unwrap(), 60);
+    }
+
+    #[test]
+    fn ring_oram_is_poisoned_by_a_failed_access() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = RingOram::<u64, 4, 6>::new_with_parameters(64, &mut rng, 40, 16384).unwrap();
+        oram.write(0, 7, &mut rng).unwrap();
+
+        // Corrupt the position map entry of address 3, so that it names the root rather than a leaf.
+        oram.position_map.write(3, 1, &mut rng).unwrap();
+        assert!(matches!(
+            oram.read(3, &mut rng),
+            Err(OramError::IntegrityError { address: 3 })
+        ));
+        assert!(oram.is_poisoned());
+        assert!(matches!(oram.read(0, &mut rng), Err(OramError::Poisoned)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ring_oram_reseeding_masks_leaves_from_a_sampler() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let new_oram = |rng: &mut StdRng| {
+            let sampler = RngLeafSampler(StdRng::seed_from_u64(2));
+            RingOram::<u64, 4, 6>::new_with_leaf_sampler(64, sampler, rng, 40, 1).unwrap()
+        };
+        let mut a = new_oram(&mut rng);
+        let mut b = new_oram(&mut rng);
+
+        a.set_reseed_interval(1).unwrap();
+        b.set_reseed_interval(1).unwrap();
+        assert_eq!(a.reseed_interval(), Some(1));
+        let PositionMap::Recursive(inner) = &a.position_map else {
+            panic!("the position map of a should be recursive");
+        };
+        assert_eq!(inner.reseed_interval(), Some(1));
+
+        for address in 0..64 {
+            a.write(address, address, &mut rng).unwrap();
+            b.write(address, address, &mut rng).unwrap();
+        }
+        let positions = |oram: &mut RingOram<u64, 4, 6>, rng: &mut StdRng| {
+            (0..64)
+                .map(|address| oram.position_map.read(address, rng).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_ne!(positions(&mut a, &mut rng), positions(&mut b, &mut rng));
+        for address in 0..64 {
+            assert_eq!(a.read(address, &mut rng).unwrap(), address);
+        }
+        assert!(a.set_reseed_interval(0).is_err());
+    }
+
+    #[test]
+    fn ring_oram_rejects_out_of_bounds_addresses() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram =
+            RingOram::<BlockValue<1>, 4, 6>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+    }
+}
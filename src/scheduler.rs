@@ -0,0 +1,383 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Round-robin scheduling of accesses to several Path ORAMs, hiding which of them have work.
+//!
+//! An access to one of several ORAMs reveals which ORAM it is to, so the interleaving of accesses across ORAMs
+//! reveals the structure of the workload (e.g., how often each table is queried). An [`InterleavedScheduler`]
+//! instead queues operations per ORAM and performs them in rounds: each call to [`InterleavedScheduler::tick`]
+//! makes exactly one access to every ORAM, in order, performing the next queued operation for that ORAM
+//! if there is one and a dummy read otherwise. Reads, writes, and dummy reads are indistinguishable Path ORAM accesses,
+//! so the sequence of accesses, and of the physical memory each touches, is the same whatever work was queued.
+//!
+//! The number of rounds is public, and each round costs an access to every ORAM, so rounds should be run
+//! on a fixed schedule (e.g., on a timer), not on demand, which would reveal when work arrives.
+//! The queues are bounded: [`InterleavedScheduler::submit`] returns a `QueueFull` error when an ORAM's queue is full,
+//! leaving the caller to retry after a round. Each queued operation is identified by a [`Ticket`],
+//! with which its result is claimed once a round has performed it.
+//!
+//! ```
+//! use oram::{scheduler::{InterleavedScheduler, ScheduledOperation}, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let users = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1)?;
+//! let orders = PathOram::<u64, 4, 8>::new_with_parameters(256, &mut rng, 40, 1)?;
+//! let mut scheduler = InterleavedScheduler::new(vec![users, orders], 16)?;
+//!
+//! let write = scheduler.submit(1, ScheduledOperation::Write(3, 42))?;
+//! let read = scheduler.submit(1, ScheduledOperation::Read(3))?;
+//! // Each round accesses both ORAMs, making a dummy access to `users`, which has no work.
+//! scheduler.tick(&mut rng)?;
+//! assert_eq!(scheduler.take_result(write).unwrap()?, 0);
+//! assert!(scheduler.take_result(read).is_none());
+//! scheduler.tick(&mut rng)?;
+//! assert_eq!(scheduler.take_result(read).unwrap()?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    eviction::{EvictionStrategy, GreedyEviction},
+    path_oram::PathOram,
+    tree::TreeIndex,
+    utils::IndexWord,
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError,
+};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    format,
+    string::ToString,
+    vec::Vec,
+};
+use rand::{CryptoRng, Rng};
+
+/// An operation queued for an ORAM of an [`InterleavedScheduler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduledOperation<V> {
+    /// Reads the value stored at an address.
+    Read(Address),
+    /// Writes a value to an address, returning the value previously stored there.
+    Write(Address, V),
+}
+
+/// Identifies an operation queued by [`InterleavedScheduler::submit`], whose result it claims.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ticket {
+    oram: usize,
+    sequence: u64,
+}
+
+impl Ticket {
+    /// Returns the index of the ORAM the operation was queued for.
+    pub fn oram(&self) -> usize {
+        self.oram
+    }
+}
+
+/// Several Path ORAMs, accessed in rounds that make one access to each. See the [module documentation](self).
+#[derive(Debug)]
+pub struct InterleavedScheduler<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy = GreedyEviction,
+    I: IndexWord = TreeIndex,
+> {
+    orams: Vec<PathOram<V, Z, AB, E, I>>,
+    queues: Vec<VecDeque<(Ticket, ScheduledOperation<V>)>>,
+    results: BTreeMap<Ticket, Result<V, OramError>>,
+    queue_capacity: usize,
+    next_sequence: u64,
+    rounds: u64,
+    // The index of each ORAM accessed, and the blocks read and written by all ORAMs so far, after each access.
+    #[cfg(test)]
+    trace: Vec<(usize, Vec<crate::path_oram::PhysicalCost>)>,
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    InterleavedScheduler<V, Z, AB, E, I>
+{
+    /// Returns a scheduler for `orams`, numbered from 0 in the order given,
+    /// each of whose queues holds at most `queue_capacity` operations.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `orams` is empty or `queue_capacity` is 0.
+    pub fn new(
+        orams: Vec<PathOram<V, Z, AB, E, I>>,
+        queue_capacity: usize,
+    ) -> Result<Self, OramError> {
+        if orams.is_empty() {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Number of scheduled ORAMs".to_string(),
+                parameter_value: "0".to_string(),
+            });
+        }
+        if queue_capacity == 0 {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Scheduler queue capacity".to_string(),
+                parameter_value: "0".to_string(),
+            });
+        }
+        Ok(Self {
+            queues: orams.iter().map(|_| VecDeque::new()).collect(),
+            orams,
+            results: BTreeMap::new(),
+            queue_capacity,
+            next_sequence: 0,
+            rounds: 0,
+            #[cfg(test)]
+            trace: Vec::new(),
+        })
+    }
+
+    /// Queues `operation` for the ORAM with index `oram`, returning the ticket with which to claim its result.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `QueueFull` error if the ORAM's queue already holds `queue_capacity` operations,
+    /// an `InvalidConfigurationError` if there is no ORAM with index `oram`,
+    /// and an `AddressOutOfBoundsError` if the operation's address is beyond the ORAM's capacity.
+    /// Operations are checked when queued, so that every access a round makes succeeds unless the ORAM fails.
+    pub fn submit(
+        &mut self,
+        oram: usize,
+        operation: ScheduledOperation<V>,
+    ) -> Result<Ticket, OramError> {
+        let Some(queue) = self.queues.get_mut(oram) else {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("ORAM index (with {} ORAMs)", self.orams.len()),
+                parameter_value: oram.to_string(),
+            });
+        };
+        if queue.len() >= self.queue_capacity {
+            return Err(OramError::QueueFull {
+                oram,
+                capacity: self.queue_capacity,
+            });
+        }
+        let address = match operation {
+            ScheduledOperation::Read(address) | ScheduledOperation::Write(address, _) => address,
+        };
+        let capacity = self.orams[oram].block_capacity()?;
+        if address >= capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity,
+            });
+        }
+
+        let ticket = Ticket {
+            oram,
+            sequence: self.next_sequence,
+        };
+        self.next_sequence += 1;
+        queue.push_back((ticket, operation));
+        Ok(ticket)
+    }
+
+    /// Runs one round: makes one access to each ORAM in index order, performing the oldest operation queued for it,
+    /// or reading address 0 and discarding the value if none is queued.
+    /// The result of each operation performed is kept until claimed with [`InterleavedScheduler::take_result`].
+    ///
+    /// # Errors
+    ///
+    /// The round accesses every ORAM even if an access fails. An operation that fails has its error as its result;
+    /// if a dummy access fails, returns the first such error once the round is complete.
+    pub fn tick<R: Rng + CryptoRng>(&mut self, rng: &mut R) -> Result<(), OramError> {
+        let mut dummy_error = None;
+        for index in 0..self.orams.len() {
+            let oram = &mut self.orams[index];
+            match self.queues[index].pop_front() {
+                Some((ticket, ScheduledOperation::Read(address))) => {
+                    self.results.insert(ticket, oram.read(address, rng));
+                }
+                Some((ticket, ScheduledOperation::Write(address, value))) => {
+                    self.results.insert(ticket, oram.write(address, value, rng));
+                }
+                None => {
+                    if let Err(error) = oram.read(0, rng) {
+                        dummy_error.get_or_insert(error);
+                    }
+                }
+            }
+            #[cfg(test)]
+            self.trace.push((
+                index,
+                self.orams
+                    .iter()
+                    .map(crate::test_utils::measured_physical_cost)
+                    .collect(),
+            ));
+        }
+        self.rounds += 1;
+        dummy_error.map_or(Ok(()), Err)
+    }
+
+    /// Returns the result of the operation identified by `ticket`, if a round has performed it
+    /// and it has not already been claimed.
+    pub fn take_result(&mut self, ticket: Ticket) -> Option<Result<V, OramError>> {
+        self.results.remove(&ticket)
+    }
+
+    /// Returns the number of operations queued for the ORAM with index `oram` and not yet performed.
+    pub fn queued(&self, oram: usize) -> usize {
+        self.queues.get(oram).map_or(0, VecDeque::len)
+    }
+
+    /// Returns the number of rounds run.
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    /// Returns the scheduled ORAMs, in index order.
+    pub fn orams(&self) -> &[PathOram<V, Z, AB, E, I>] {
+        &self.orams
+    }
+
+    /// Returns the scheduled ORAMs, discarding any queued operations and unclaimed results.
+    pub fn into_orams(self) -> Vec<PathOram<V, Z, AB, E, I>> {
+        self.orams
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    type TestOram = PathOram<u64, 4, 8>;
+
+    fn scheduler(rng: &mut StdRng) -> InterleavedScheduler<u64, 4, 8> {
+        let orams = [16, 64, 256]
+            .into_iter()
+            .map(|capacity| TestOram::new_with_parameters(capacity, rng, 40, 1).unwrap())
+            .collect();
+        InterleavedScheduler::new(orams, 8).unwrap()
+    }
+
+    #[test]
+    fn operations_match_a_shadow_model_per_oram() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut scheduler = scheduler(&mut rng);
+        let capacities = [16, 64, 256];
+        let mut expected: Vec<Vec<u64>> = capacities.iter().map(|c| vec![0; *c]).collect();
+        let mut tickets = Vec::new();
+        for round in 0..300 {
+            // Queue up to three operations, for random ORAMs.
+            for _ in 0..rng.gen_range(0..=3) {
+                let oram = rng.gen_range(0..3);
+                let address = rng.gen_range(0..capacities[oram] as Address);
+                let operation = if rng.gen() {
+                    ScheduledOperation::Write(address, round)
+                } else {
+                    ScheduledOperation::Read(address)
+                };
+                match scheduler.submit(oram, operation) {
+                    Ok(ticket) => tickets.push((ticket, operation)),
+                    Err(OramError::QueueFull { oram: full, .. }) => {
+                        assert_eq!(full, oram);
+                        assert_eq!(scheduler.queued(oram), 8);
+                    }
+                    Err(error) => panic!("{error}"),
+                }
+            }
+            scheduler.tick(&mut rng).unwrap();
+
+            // Each ORAM performs its operations in the order queued, so the shadow model is updated in that order.
+            tickets.retain(|(ticket, operation)| match scheduler.take_result(*ticket) {
+                None => true,
+                Some(result) => {
+                    let memory = &mut expected[ticket.oram()];
+                    let value = match *operation {
+                        ScheduledOperation::Read(address) => memory[address as usize],
+                        ScheduledOperation::Write(address, value) => {
+                            core::mem::replace(&mut memory[address as usize], value)
+                        }
+                    };
+                    assert_eq!(result.unwrap(), value);
+                    false
+                }
+            });
+        }
+        assert_eq!(scheduler.rounds(), 300);
+
+        let mut orams = scheduler.into_orams();
+        for (oram, expected) in orams.iter_mut().zip(&expected) {
+            for (address, value) in expected.iter().enumerate() {
+                assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *value);
+            }
+        }
+    }
+
+    // Runs 50 rounds, in each queueing for every ORAM `i` with `busy(round, i)` a write to a random address,
+    // and returns the trace of the physical memory touched.
+    fn trace<F: Fn(u64, usize) -> bool>(
+        busy: F,
+    ) -> Vec<(usize, Vec<crate::path_oram::PhysicalCost>)> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut address_rng = StdRng::seed_from_u64(2);
+        let mut scheduler = scheduler(&mut rng);
+        for round in 0..50 {
+            for oram in 0..3 {
+                if busy(round, oram) {
+                    let address = address_rng.gen_range(0..16);
+                    scheduler
+                        .submit(oram, ScheduledOperation::Write(address, round))
+                        .unwrap();
+                }
+            }
+            scheduler.tick(&mut rng).unwrap();
+        }
+        scheduler.trace
+    }
+
+    #[test]
+    fn trace_is_independent_of_the_work_queued() {
+        let idle = trace(|_, _| false);
+        assert_eq!(idle.len(), 150);
+        for (step, (index, _)) in idle.iter().enumerate() {
+            assert_eq!(*index, step % 3);
+        }
+        assert_eq!(idle, trace(|_, _| true));
+        assert_eq!(idle, trace(|_, oram| oram == 1));
+        assert_eq!(idle, trace(|round, oram| (round + oram as u64) % 4 == 0));
+    }
+
+    #[test]
+    fn invalid_submissions_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut scheduler = scheduler(&mut rng);
+        assert!(matches!(
+            scheduler.submit(3, ScheduledOperation::Read(0)),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        assert!(matches!(
+            scheduler.submit(0, ScheduledOperation::Read(16)),
+            Err(OramError::AddressOutOfBoundsError { .. })
+        ));
+        for _ in 0..8 {
+            scheduler.submit(0, ScheduledOperation::Read(0)).unwrap();
+        }
+        assert!(matches!(
+            scheduler.submit(0, ScheduledOperation::Read(0)),
+            Err(OramError::QueueFull {
+                oram: 0,
+                capacity: 8
+            })
+        ));
+        scheduler.tick(&mut rng).unwrap();
+        assert_eq!(scheduler.queued(0), 7);
+        scheduler.submit(0, ScheduledOperation::Read(0)).unwrap();
+
+        assert!(InterleavedScheduler::new(Vec::<TestOram>::new(), 8).is_err());
+        assert!(InterleavedScheduler::new(vec![scheduler.into_orams().remove(0)], 0).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A deterministic mode for reproducing bugs, in which an ORAM draws all of its randomness from a seed.
+//! Enabled by the `seeded` feature.
+//!
+//! A [`SeededOram`] owns a [`ChaCha20Rng`] seeded by its caller, and uses it both to initialize its ORAM
+//! and for every subsequent access, so a seed together with a sequence of operations determines all of its internal state.
+//!
+//! This mode is for testing and debugging only. Anyone who knows the seed can compute the position of every block,
+//! and so learn the address of every access from the physical access pattern.
+//!
+//! ```
+//! use oram::{OramError, PathOram};
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let seed = [7; 32];
+//! let mut oram = PathOram::<u64, 4, 8>::new_seeded(64, seed)?;
+//! oram.write(3, 42)?;
+//! assert_eq!(oram.read(3)?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    eviction::EvictionStrategy,
+    path_oram::{default_overflow_size, PathOram, DEFAULT_RECURSION_CUTOFF},
+    utils::IndexWord,
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError,
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+/// An ORAM together with the seeded RNG from which it draws all of its randomness. See the [module documentation](self).
+#[derive(Debug)]
+pub struct SeededOram<O: Oram> {
+    oram: O,
+    rng: ChaCha20Rng,
+}
+
+impl<O: Oram> SeededOram<O> {
+    /// Seeds an RNG with `seed`, and constructs an ORAM by calling `constructor` with it.
+    pub fn new<F: FnOnce(&mut ChaCha20Rng) -> Result<O, OramError>>(
+        seed: [u8; 32],
+        constructor: F,
+    ) -> Result<Self, OramError> {
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        let oram = constructor(&mut rng)?;
+        Ok(Self { oram, rng })
+    }
+
+    /// Returns the capacity in blocks of the ORAM.
+    pub fn block_capacity(&self) -> Result<Address, OramError> {
+        self.oram.block_capacity()
+    }
+
+    /// As [`Oram::access`], drawing randomness from the seeded RNG.
+    pub fn access<F: Fn(&O::V) -> O::V>(
+        &mut self,
+        index: Address,
+        callback: F,
+    ) -> Result<O::V, OramError> {
+        self.oram.access(index, callback, &mut self.rng)
+    }
+
+    /// As [`Oram::read`], drawing randomness from the seeded RNG.
+    pub fn read(&mut self, index: Address) -> Result<O::V, OramError> {
+        self.oram.read(index, &mut self.rng)
+    }
+
+    /// As [`Oram::write`], drawing randomness from the seeded RNG.
+    pub fn write(&mut self, index: Address, new_value: O::V) -> Result<O::V, OramError> {
+        self.oram.write(index, new_value, &mut self.rng)
+    }
+
+    /// Returns a reference to the ORAM.
+    pub fn oram(&self) -> &O {
+        &self.oram
+    }
+
+    /// Returns the ORAM and the seeded RNG, in its current state.
+    pub fn into_parts(self) -> (O, ChaCha20Rng) {
+        (self.oram, self.rng)
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Returns a new `PathOram` with default parameters, whose initialization and accesses draw randomness
+    /// only from a [`ChaCha20Rng`] seeded with `seed`. For testing and debugging only; see [`SeededOram`].
+    pub fn new_seeded(
+        block_capacity: Address,
+        seed: [u8; 32],
+    ) -> Result<SeededOram<Self>, OramError> {
+        SeededOram::new(seed, |rng| {
+            Self::new_with_parameters(
+                block_capacity,
+                rng,
+                default_overflow_size(block_capacity, Z),
+                DEFAULT_RECURSION_CUTOFF,
+            )
+        })
+    }
+
+    /// Returns a new `PathOram` with default parameters, storing `initial[i]` at each address `i`
+    /// (see [`PathOram::new_with_initial_values`]), whose initialization draws randomness only from a [`ChaCha20Rng`]
+    /// seeded with `seed`. Two calls with the same arguments return ORAMs with identical physical memory and position maps,
+    /// so an ORAM can be rebuilt from its initial data and seed alone, e.g., for disaster recovery.
+    ///
+    /// Anyone who knows the seed knows where every block starts, so it must be kept secret.
+    /// Moreover, a rebuilt ORAM must replace the original, never run alongside it:
+    /// two live ORAMs built from the same seed start with identical block placements,
+    /// and accesses to one reveal information about accesses to the other.
+    /// Subsequent accesses draw randomness from the RNG passed to them, as usual.
+    pub fn rebuild_deterministic(initial: &[V], seed: [u8; 32]) -> Result<Self, OramError> {
+        Self::new_with_initial_values(
+            initial,
+            &mut ChaCha20Rng::from_seed(seed),
+            default_overflow_size(initial.len() as Address, Z),
+            DEFAULT_RECURSION_CUTOFF,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bucket::BlockValue, linear_time_oram::LinearTimeOram};
+    use alloc::{string::String, vec::Vec};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    type TestOram = PathOram<BlockValue<8>, 4, 8>;
+
+    // Runs a fixed script of random operations on an ORAM seeded with `seed`,
+    // returning the values read and a rendering of the ORAM's entire final state.
+    fn run_script(seed: [u8; 32]) -> (Vec<BlockValue<8>>, String) {
+        let capacity = 256;
+        let mut oram = TestOram::new_seeded(capacity, seed).unwrap();
+        let mut script_rng = StdRng::seed_from_u64(0);
+        let mut values_read = Vec::new();
+        for _ in 0..1000 {
+            let address = script_rng.gen_range(0..capacity);
+            if script_rng.gen() {
+                values_read.push(oram.read(address).unwrap());
+            } else {
+                oram.write(address, script_rng.gen()).unwrap();
+            }
+        }
+        (values_read, oram.oram().debug_dump())
+    }
+
+    #[test]
+    fn same_seed_and_script_give_identical_state() {
+        assert_eq!(run_script([1; 32]), run_script([1; 32]));
+    }
+
+    #[test]
+    fn different_seeds_give_different_state() {
+        let (values_read, state) = run_script([1; 32]);
+        let (other_values_read, other_state) = run_script([2; 32]);
+        // The values read depend only on the script, but the positions of the blocks depend on the seed.
+        assert_eq!(values_read, other_values_read);
+        assert_ne!(state, other_state);
+    }
+
+    #[test]
+    fn seeded_oram_wraps_any_oram() {
+        let mut oram = SeededOram::new([0; 32], |_| LinearTimeOram::<u32>::new(16)).unwrap();
+        assert_eq!(oram.block_capacity().unwrap(), 16);
+        oram.write(5, 10).unwrap();
+        assert_eq!(oram.access(5, |value| value + 1).unwrap(), 10);
+        assert_eq!(oram.read(5).unwrap(), 11);
+    }
+
+    #[test]
+    fn rebuild_deterministic_is_reproducible() {
+        let mut data_rng = StdRng::seed_from_u64(0);
+        let initial: Vec<BlockValue<8>> = (0..256).map(|_| data_rng.gen()).collect();
+        let seed = [5; 32];
+        let mut a = TestOram::rebuild_deterministic(&initial, seed).unwrap();
+        let mut b = TestOram::rebuild_deterministic(&initial, seed).unwrap();
+        assert_eq!(a.debug_dump(), b.debug_dump());
+        assert_ne!(
+            a.debug_dump(),
+            TestOram::rebuild_deterministic(&initial, [6; 32])
+                .unwrap()
+                .debug_dump()
+        );
+
+        // The ORAMs diverge under different accesses, but both hold the initial data.
+        let mut rng = StdRng::seed_from_u64(1);
+        a.write(0, BlockValue::default(), &mut rng).unwrap();
+        b.read(1, &mut rng).unwrap();
+        assert_ne!(a.debug_dump(), b.debug_dump());
+        for (address, value) in initial.iter().enumerate().skip(1) {
+            assert_eq!(a.read(address as Address, &mut rng).unwrap(), *value);
+            assert_eq!(b.read(address as Address, &mut rng).unwrap(), *value);
+        }
+        assert_eq!(a.read(0, &mut rng).unwrap(), BlockValue::default());
+    }
+
+    #[test]
+    fn rebuild_deterministic_requires_power_of_two_capacity() {
+        assert!(matches!(
+            TestOram::rebuild_deterministic(&[BlockValue::default(); 100], [0; 32]),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+    }
+}
@@ -0,0 +1,441 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A startup self-test of the primitives on which obliviousness depends.
+//!
+//! The ORAMs of this crate are oblivious only if their constant-time building blocks compute what they should:
+//! a miscompiled [`ConditionallySelectable`] implementation, for example, could return the wrong block without any test
+//! of the ORAM's interface failing on a different build. [`self_test`] checks these building blocks, on the CPU and build
+//! it runs on, against simple reference implementations:
+//!
+//! - conditional selection, assignment, and swapping of integers and of the crate's block and bucket types,
+//!   against `if`-`else` on a `bool`;
+//! - the constant-time tree index functions of [`CompleteBinaryTreeIndex`], against brute force on every node
+//!   (and pair of nodes) of a tree of height 6;
+//! - [`bitonic_sort_by_keys`], against the standard library's sort, on fixed vectors of several lengths;
+//! - a round trip of writes and reads through a 64-block Path ORAM with a recursive position map.
+//!
+//! The checks are deterministic and take tens of milliseconds in an optimized build (a second or two in a debug build).
+//! An enclave should run them once at startup, before serving requests, and refuse to serve if they fail:
+//!
+//! ```
+//! # fn main() -> Result<(), oram::self_test::SelfTestFailure> {
+//! let report = oram::self_test()?;
+//! println!("ORAM self-test passed {} checks in {:?}", report.checks, report.elapsed);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The self-test requires the `std` feature, for its timing and for a deterministic RNG for the Path ORAM round trip.
+
+use crate::{
+    bucket::{PathOramBlock, PositionBlock},
+    oblivious::bitonic_sort_by_keys,
+    tracked::Tracked,
+    tree::{CompleteBinaryTreeIndex, TreeHeight, TreeIndex},
+    Address, BlockValue, Bucket, Oram, PathOram,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{fmt, format, string::String, time::Duration, time::Instant, vec::Vec};
+use subtle::{Choice, ConditionallySelectable};
+
+/// A primitive checked by [`self_test`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    /// Conditional selection, assignment, and swapping ([`ConditionallySelectable`]).
+    ConditionalSelection,
+    /// The constant-time tree index functions of [`CompleteBinaryTreeIndex`].
+    TreeIndex,
+    /// Oblivious sorting ([`bitonic_sort_by_keys`]).
+    BitonicSort,
+    /// Writes and reads through a small [`PathOram`].
+    PathOram,
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Primitive::ConditionalSelection => "conditional selection",
+            Primitive::TreeIndex => "tree index functions",
+            Primitive::BitonicSort => "bitonic sort",
+            Primitive::PathOram => "Path ORAM round trip",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The checks [`self_test`] made, all of which passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The number of checks made.
+    pub checks: u64,
+    /// How long the checks took.
+    pub elapsed: Duration,
+}
+
+/// The first check of [`self_test`] to fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestFailure {
+    /// The primitive that failed the check.
+    pub primitive: Primitive,
+    /// What was checked, and with which inputs.
+    pub description: String,
+}
+
+impl fmt::Display for SelfTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ORAM self-test of {} failed: {}",
+            self.primitive, self.description
+        )
+    }
+}
+
+impl std::error::Error for SelfTestFailure {}
+
+/// Checks the primitives on which the obliviousness of this crate's ORAMs depends. See the [module documentation](self).
+///
+/// # Errors
+///
+/// Returns the first check that failed, identifying the primitive that failed it.
+pub fn self_test() -> Result<SelfTestReport, SelfTestFailure> {
+    let start = Instant::now();
+    let mut checker = Checker::default();
+    check_conditional_selection(&mut checker)?;
+    check_tree_index(&mut checker)?;
+    check_bitonic_sort(&mut checker)?;
+    check_path_oram(&mut checker)?;
+    Ok(SelfTestReport {
+        checks: checker.checks,
+        elapsed: start.elapsed(),
+    })
+}
+
+// Counts checks, and turns the first one to fail into a `SelfTestFailure`.
+#[derive(Default)]
+struct Checker {
+    checks: u64,
+}
+
+impl Checker {
+    fn check<F: FnOnce() -> String>(
+        &mut self,
+        passed: bool,
+        primitive: Primitive,
+        describe: F,
+    ) -> Result<(), SelfTestFailure> {
+        self.checks += 1;
+        if passed {
+            Ok(())
+        } else {
+            Err(SelfTestFailure {
+                primitive,
+                description: describe(),
+            })
+        }
+    }
+}
+
+// A fixed stream of test inputs (SplitMix64), so that every run checks the same cases.
+struct Inputs(u64);
+
+impl Inputs {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+// Checks `conditional_select`, `conditional_assign`, and `conditional_swap` of `a` and `b`, with both choices,
+// against selecting with a branch on a `bool`.
+fn check_selection_of<T: ConditionallySelectable + PartialEq>(
+    checker: &mut Checker,
+    a: &T,
+    b: &T,
+) -> Result<(), SelfTestFailure> {
+    let type_name = core::any::type_name::<T>();
+    for choose_b in [false, true] {
+        let choice = Choice::from(u8::from(choose_b));
+        let (expected, other) = if choose_b { (b, a) } else { (a, b) };
+
+        let selected = T::conditional_select(a, b, choice);
+        checker.check(
+            selected == *expected,
+            Primitive::ConditionalSelection,
+            || format!("conditional_select of two {type_name} values with choice {choose_b}"),
+        )?;
+
+        let mut assigned = *a;
+        assigned.conditional_assign(b, choice);
+        checker.check(
+            assigned == *expected,
+            Primitive::ConditionalSelection,
+            || format!("conditional_assign of two {type_name} values with choice {choose_b}"),
+        )?;
+
+        let (mut first, mut second) = (*a, *b);
+        T::conditional_swap(&mut first, &mut second, choice);
+        checker.check(
+            first == *expected && second == *other,
+            Primitive::ConditionalSelection,
+            || format!("conditional_swap of two {type_name} values with choice {choose_b}"),
+        )?;
+    }
+    Ok(())
+}
+
+fn check_conditional_selection(checker: &mut Checker) -> Result<(), SelfTestFailure> {
+    let mut inputs = Inputs(0);
+    check_selection_of(checker, &0u64, &u64::MAX)?;
+    check_selection_of(checker, &u8::MAX, &0u8)?;
+    for _ in 0..16 {
+        check_selection_of(checker, &(inputs.next() as u8), &(inputs.next() as u8))?;
+        check_selection_of(checker, &(inputs.next() as u16), &(inputs.next() as u16))?;
+        check_selection_of(checker, &(inputs.next() as u32), &(inputs.next() as u32))?;
+        check_selection_of(checker, &inputs.next(), &inputs.next())?;
+        check_selection_of(checker, &(inputs.next() as i64), &(inputs.next() as i64))?;
+    }
+
+    for _ in 0..4 {
+        let a = BlockValue::<1>::new([inputs.next() as u8]);
+        let b = BlockValue::<1>::new([inputs.next() as u8]);
+        check_selection_of(checker, &a, &b)?;
+        // Sizes that are, and are not, a multiple of a cache line or of a machine word.
+        let a = BlockValue::<64>::new(core::array::from_fn(|_| inputs.next() as u8));
+        let b = BlockValue::<64>::new(core::array::from_fn(|_| inputs.next() as u8));
+        check_selection_of(checker, &a, &b)?;
+        let a = BlockValue::<100>::new(core::array::from_fn(|_| inputs.next() as u8));
+        let b = BlockValue::<100>::new(core::array::from_fn(|_| inputs.next() as u8));
+        check_selection_of(checker, &a, &b)?;
+
+        let a = PositionBlock::<8> {
+            data: core::array::from_fn(|_| inputs.next()),
+        };
+        let b = PositionBlock::<8> {
+            data: core::array::from_fn(|_| inputs.next()),
+        };
+        check_selection_of(checker, &a, &b)?;
+        let a = PositionBlock::<20> {
+            data: core::array::from_fn(|_| inputs.next()),
+        };
+        let b = PositionBlock::<20> {
+            data: core::array::from_fn(|_| inputs.next()),
+        };
+        check_selection_of(checker, &a, &b)?;
+
+        let a = Tracked::written(inputs.next());
+        check_selection_of(checker, &a, &Tracked::default())?;
+
+        let mut bucket = || {
+            let mut bucket = Bucket::<u64, 4>::default();
+            for block in &mut bucket.blocks {
+                *block = PathOramBlock::new(inputs.next(), inputs.next(), inputs.next() | 1);
+            }
+            bucket
+        };
+        let (a, b) = (bucket(), bucket());
+        check_selection_of(checker, &a.blocks[0], &b.blocks[0])?;
+        check_selection_of(checker, &a, &b)?;
+        check_selection_of(checker, &a, &Bucket::default())?;
+    }
+    Ok(())
+}
+
+// The height of the tree whose every node the tree index functions are checked on.
+const TREE_HEIGHT: TreeHeight = 6;
+
+// The depth of `node`, found by walking up to the root.
+fn reference_depth(node: TreeIndex) -> TreeHeight {
+    let mut depth = 0;
+    let mut ancestor = node;
+    while ancestor > 1 {
+        ancestor /= 2;
+        depth += 1;
+    }
+    depth
+}
+
+// The ancestors of `node`, from `node` itself up to the root.
+fn reference_ancestors(node: TreeIndex) -> Vec<TreeIndex> {
+    let mut ancestors = Vec::new();
+    let mut ancestor = node;
+    while ancestor >= 1 {
+        ancestors.push(ancestor);
+        ancestor /= 2;
+    }
+    ancestors
+}
+
+fn check_tree_index(checker: &mut Checker) -> Result<(), SelfTestFailure> {
+    let nodes = 1..(2 << TREE_HEIGHT);
+    let leaves = (1 << TREE_HEIGHT)..(2 << TREE_HEIGHT);
+
+    for node in nodes.clone() {
+        let depth = reference_depth(node);
+        checker.check(node.ct_depth() == depth, Primitive::TreeIndex, || {
+            format!("ct_depth of node {node}")
+        })?;
+        checker.check(
+            node.is_leaf(TREE_HEIGHT) == (depth == TREE_HEIGHT),
+            Primitive::TreeIndex,
+            || format!("is_leaf of node {node} in a tree of height {TREE_HEIGHT}"),
+        )?;
+        checker.check(
+            node.left_child() == 2 * node
+                && node.right_child() == 2 * node + 1
+                && (node == 1 || node.parent() == node / 2),
+            Primitive::TreeIndex,
+            || format!("children and parent of node {node}"),
+        )?;
+
+        let ancestors = reference_ancestors(node);
+        for other in nodes.clone() {
+            let is_ancestor = reference_ancestors(other).contains(&node);
+            checker.check(
+                bool::from(node.ct_is_ancestor_of(&other)) == is_ancestor,
+                Primitive::TreeIndex,
+                || format!("ct_is_ancestor_of nodes {node} and {other}"),
+            )?;
+        }
+
+        if node.is_leaf(TREE_HEIGHT) {
+            for depth in 0..=TREE_HEIGHT {
+                let expected = ancestors[usize::try_from(TREE_HEIGHT - depth).unwrap()];
+                checker.check(
+                    node.ct_node_on_path(depth, TREE_HEIGHT) == expected,
+                    Primitive::TreeIndex,
+                    || format!("ct_node_on_path of leaf {node} at depth {depth}"),
+                )?;
+            }
+        }
+    }
+
+    for leaf in leaves.clone() {
+        let leaf_ancestors = reference_ancestors(leaf);
+        for other in leaves.clone() {
+            // The deepest common ancestor is the first ancestor of `other` that is also an ancestor of `leaf`.
+            let expected = reference_ancestors(other)
+                .into_iter()
+                .find(|ancestor| leaf_ancestors.contains(ancestor))
+                .unwrap_or(1);
+            checker.check(
+                leaf.ct_common_ancestor_of_two_leaves(&other) == expected,
+                Primitive::TreeIndex,
+                || format!("ct_common_ancestor_of_two_leaves of leaves {leaf} and {other}"),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn check_bitonic_sort(checker: &mut Checker) -> Result<(), SelfTestFailure> {
+    let mut inputs = Inputs(1);
+    // Lengths that are, and are not, powers of two.
+    for len in [0, 1, 2, 3, 5, 7, 8, 16, 31, 64, 100] {
+        for distinct_keys in [2, 1000] {
+            let original_keys: Vec<u64> = (0..len).map(|_| inputs.next() % distinct_keys).collect();
+            let mut keys = original_keys.clone();
+            let mut items: Vec<u64> = (0..len).collect();
+            bitonic_sort_by_keys(&mut items, &mut keys);
+
+            let mut expected_keys = original_keys.clone();
+            expected_keys.sort_unstable();
+            checker.check(keys == expected_keys, Primitive::BitonicSort, || {
+                format!("bitonic_sort_by_keys of {len} keys {original_keys:?}")
+            })?;
+
+            // Each item must have moved with its key, and no item may have been lost or duplicated.
+            let mut moved_with_keys = true;
+            let mut seen = std::vec![false; items.len()];
+            for (item, key) in items.iter().zip(&keys) {
+                let index = usize::try_from(*item).unwrap_or(usize::MAX);
+                match seen.get_mut(index) {
+                    Some(seen) if !*seen && original_keys[index] == *key => *seen = true,
+                    _ => moved_with_keys = false,
+                }
+            }
+            checker.check(moved_with_keys, Primitive::BitonicSort, || {
+                format!("bitonic_sort_by_keys of {len} items by keys {original_keys:?}")
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn check_path_oram(checker: &mut Checker) -> Result<(), SelfTestFailure> {
+    const CAPACITY: Address = 64;
+    let failure = |error: crate::OramError| SelfTestFailure {
+        primitive: Primitive::PathOram,
+        description: format!("an access failed with {error:?}"),
+    };
+
+    let mut rng = StdRng::seed_from_u64(0);
+    // A recursion cutoff of 1 makes the position map a Path ORAM too.
+    let mut oram =
+        PathOram::<u64, 4, 8>::new_with_parameters(CAPACITY, &mut rng, 40, 1).map_err(failure)?;
+    let mut inputs = Inputs(2);
+    let mut expected = [0u64; CAPACITY as usize];
+    for round in 0..4 {
+        for _ in 0..CAPACITY {
+            let address = inputs.next() % CAPACITY;
+            let value = inputs.next();
+            let previous = oram.write(address, value, &mut rng).map_err(failure)?;
+            checker.check(
+                previous == expected[address as usize],
+                Primitive::PathOram,
+                || format!("write to address {address} in round {round}"),
+            )?;
+            expected[address as usize] = value;
+        }
+        for address in 0..CAPACITY {
+            let value = oram.read(address, &mut rng).map_err(failure)?;
+            checker.check(
+                value == expected[address as usize],
+                Primitive::PathOram,
+                || format!("read of address {address} in round {round}"),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wired into the test suite, so that the self-test cannot rot.
+    #[test]
+    fn self_test_passes() {
+        let report = self_test().unwrap();
+        assert!(report.checks > 10_000);
+    }
+
+    // A value whose conditional selection ignores the choice, as a miscompiled one might.
+    #[derive(Clone, Copy, PartialEq)]
+    struct BrokenSelection(u64);
+
+    impl ConditionallySelectable for BrokenSelection {
+        fn conditional_select(a: &Self, _b: &Self, _choice: Choice) -> Self {
+            *a
+        }
+    }
+
+    #[test]
+    fn failures_identify_the_primitive() {
+        let mut checker = Checker::default();
+        check_selection_of(&mut checker, &1u64, &2u64).unwrap();
+        let failure =
+            check_selection_of(&mut checker, &BrokenSelection(1), &BrokenSelection(2)).unwrap_err();
+        assert_eq!(failure.primitive, Primitive::ConditionalSelection);
+        assert!(failure.description.contains("BrokenSelection"));
+        assert!(failure.to_string().contains("conditional selection"));
+    }
+}
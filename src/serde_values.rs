@@ -0,0 +1,321 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Storage of `serde`-serializable records in fixed-size blocks. Enabled by the `serde-values` feature.
+//!
+//! A [`SerdeBlockAdapter<T, B>`](SerdeBlockAdapter) serializes a `T` with [`postcard`] and stores the encoding
+//! as the payload of a [`PaddedCodec<B>`](crate::codec::PaddedCodec) block, so a record fits if its encoding
+//! is at most `B - 4` bytes long. A [`TypedOram`] stores such records in an ORAM of `BlockValue<B>`s.
+//!
+//! Only the access pattern of the ORAM is protected. Every record occupies a block of `B` bytes whatever its encoded length,
+//! so the ORAM's memory reveals nothing about the lengths of the records within it, but `B` itself, the size class
+//! of the records, is public. Serialization and deserialization are not constant-time: how long they take,
+//! and whether a record fits, depend on its contents, so must not be observable where the contents are secret.
+//!
+//! ```
+//! use oram::{serde_values::TypedOram, BlockValue, DefaultOram, OramError};
+//! use rand::rngs::OsRng;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+//! struct Account {
+//!     owner: String,
+//!     balance: u64,
+//! }
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut oram = TypedOram::<Account, 64, _>::new(DefaultOram::<BlockValue<64>>::new(16, &mut rng)?);
+//! let alice = Account { owner: "alice".into(), balance: 10 };
+//! oram.write_t(3, &alice, &mut rng)?;
+//! assert_eq!(oram.read_t(3, &mut rng)?, alice);
+//! // Addresses that have never been written hold the default record.
+//! assert_eq!(oram.read_t(4, &mut rng)?, Account::default());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{codec::PaddedCodec, Address, BlockSize, BlockValue, Oram, OramError};
+use alloc::{format, string::ToString};
+use core::marker::PhantomData;
+use rand::{CryptoRng, RngCore};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encodes `T`s as [`postcard`] encodings stored in [`PaddedCodec<B>`](PaddedCodec) blocks.
+/// The empty payload, which an address that has never been written holds, decodes as `T::default()`.
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerdeBlockAdapter<T, const B: BlockSize>(PhantomData<T>);
+
+impl<T: Serialize + DeserializeOwned + Default, const B: BlockSize> SerdeBlockAdapter<T, B> {
+    /// Returns the encoding of `record` as a `BlockValue<B>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `record` cannot be serialized,
+    /// or if its encoding is longer than `B - 4` bytes.
+    pub fn encode(record: &T) -> Result<BlockValue<B>, OramError> {
+        let payload = postcard::to_allocvec(record).map_err(|error| {
+            OramError::InvalidConfigurationError {
+                parameter_name: "Serialized record".to_string(),
+                parameter_value: error.to_string(),
+            }
+        })?;
+        PaddedCodec::encode(&payload).map_err(|_| OramError::InvalidConfigurationError {
+            parameter_name: format!("Serialized record length (with block size {B})"),
+            parameter_value: payload.len().to_string(),
+        })
+    }
+
+    /// Returns the record encoded in `block`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `block` does not hold an encoding of a `T`,
+    /// e.g., because it was written as a record of another type.
+    pub fn decode(block: &BlockValue<B>) -> Result<T, OramError> {
+        let payload = PaddedCodec::decode(block);
+        if payload.is_empty() {
+            return Ok(T::default());
+        }
+        postcard::from_bytes(&payload).map_err(|error| OramError::InvalidConfigurationError {
+            parameter_name: "Stored record".to_string(),
+            parameter_value: error.to_string(),
+        })
+    }
+}
+
+/// An ORAM of `T` records, stored encoded by a [`SerdeBlockAdapter<T, B>`](SerdeBlockAdapter) in an ORAM `O`.
+/// An address that has never been written holds `T::default()`.
+#[derive(Debug)]
+pub struct TypedOram<T, const B: BlockSize, O: Oram<V = BlockValue<B>>> {
+    oram: O,
+    adapter: PhantomData<SerdeBlockAdapter<T, B>>,
+}
+
+impl<T: Serialize + DeserializeOwned + Default, const B: BlockSize, O: Oram<V = BlockValue<B>>>
+    TypedOram<T, B, O>
+{
+    /// Returns a `TypedOram` storing its records in `oram`.
+    pub fn new(oram: O) -> Self {
+        Self {
+            oram,
+            adapter: PhantomData,
+        }
+    }
+
+    /// Returns the capacity of the ORAM, in records.
+    pub fn block_capacity(&self) -> Result<Address, OramError> {
+        self.oram.block_capacity()
+    }
+
+    /// Returns the record stored at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if the block at `address` does not hold an encoding of a `T`.
+    pub fn read_t<R: RngCore + CryptoRng>(
+        &mut self,
+        address: Address,
+        rng: &mut R,
+    ) -> Result<T, OramError> {
+        SerdeBlockAdapter::decode(&self.oram.read(address, rng)?)
+    }
+
+    /// Stores `record` at `address`, returning the record previously stored there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError`, without accessing the ORAM, if `record` does not fit in a block.
+    /// Returns an `InvalidConfigurationError`, after storing `record`, if the block previously stored at `address`
+    /// does not hold an encoding of a `T`.
+    pub fn write_t<R: RngCore + CryptoRng>(
+        &mut self,
+        address: Address,
+        record: &T,
+        rng: &mut R,
+    ) -> Result<T, OramError> {
+        let block = SerdeBlockAdapter::encode(record)?;
+        SerdeBlockAdapter::decode(&self.oram.write(address, block, rng)?)
+    }
+
+    /// Replaces the record stored at `address` with `f` applied to it, returning the record previously stored there.
+    /// Makes two ORAM accesses to `address`, a read and then a write, since the record is decoded and encoded
+    /// outside the ORAM; the write is not made if the read or the encoding of the new record fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if the block at `address` does not hold an encoding of a `T`,
+    /// or if the new record does not fit in a block, leaving the stored record unchanged in either case.
+    pub fn access_t<R: RngCore + CryptoRng, F: FnOnce(&T) -> T>(
+        &mut self,
+        address: Address,
+        f: F,
+        rng: &mut R,
+    ) -> Result<T, OramError> {
+        let record = self.read_t(address, rng)?;
+        let block = SerdeBlockAdapter::encode(&f(&record))?;
+        self.oram.write(address, block, rng)?;
+        Ok(record)
+    }
+
+    /// Returns a mutable reference to the underlying ORAM of encoded records.
+    pub fn inner_mut(&mut self) -> &mut O {
+        &mut self.oram
+    }
+
+    /// Returns the underlying ORAM of encoded records.
+    pub fn into_inner(self) -> O {
+        self.oram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, PathOram};
+    use alloc::{string::String, vec, vec::Vec};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+    enum Status {
+        #[default]
+        Inactive,
+        Active {
+            since: u32,
+        },
+        Suspended(String),
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+    struct Limits {
+        daily: u64,
+        per_transaction: Option<u32>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+    struct Account {
+        id: u64,
+        owner: String,
+        status: Status,
+        limits: Limits,
+        tags: Vec<u8>,
+    }
+
+    fn account(id: u64, rng: &mut StdRng) -> Account {
+        let status = match rng.gen_range(0..3) {
+            0 => Status::Inactive,
+            1 => Status::Active { since: rng.gen() },
+            _ => Status::Suspended("review".into()),
+        };
+        Account {
+            id,
+            owner: format!("owner {id}"),
+            status,
+            limits: Limits {
+                daily: rng.gen(),
+                per_transaction: rng.gen::<bool>().then(|| rng.gen()),
+            },
+            tags: (0..rng.gen_range(0..8)).map(|_| rng.gen()).collect(),
+        }
+    }
+
+    type Adapter = SerdeBlockAdapter<Account, 64>;
+
+    #[test]
+    fn records_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for id in 0..100 {
+            let record = account(id, &mut rng);
+            assert_eq!(
+                Adapter::decode(&Adapter::encode(&record).unwrap()).unwrap(),
+                record
+            );
+        }
+        assert_eq!(
+            Adapter::decode(&BlockValue::default()).unwrap(),
+            Account::default()
+        );
+        let unit = SerdeBlockAdapter::<(), 4>::encode(&()).unwrap();
+        SerdeBlockAdapter::<(), 4>::decode(&unit).unwrap();
+    }
+
+    #[test]
+    fn too_large_records_are_rejected() {
+        let record = Account {
+            owner: "x".repeat(60),
+            ..Account::default()
+        };
+        assert!(matches!(
+            Adapter::encode(&record),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+
+        // A block that does not hold an encoding of the record type fails to decode.
+        let block = PaddedCodec::<64>::encode(&[0xff; 10]).unwrap();
+        assert!(matches!(
+            Adapter::decode(&block),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+    }
+
+    #[test]
+    fn typed_oram_matches_shadow_model() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let oram = PathOram::<BlockValue<64>, 4, 8>::new_with_parameters(32, &mut rng, 40, 1);
+        let mut oram = TypedOram::<Account, 64, _>::new(oram.unwrap());
+        let mut expected = vec![Account::default(); 32];
+        for i in 0..300 {
+            let address = rng.gen_range(0..32);
+            match rng.gen_range(0..3) {
+                0 => assert_eq!(
+                    oram.read_t(address, &mut rng).unwrap(),
+                    expected[address as usize]
+                ),
+                1 => {
+                    let record = account(i, &mut rng);
+                    let previous = oram.write_t(address, &record, &mut rng).unwrap();
+                    assert_eq!(previous, expected[address as usize]);
+                    expected[address as usize] = record;
+                }
+                _ => {
+                    let previous = oram
+                        .access_t(
+                            address,
+                            |record| Account {
+                                status: Status::Active { since: 7 },
+                                ..record.clone()
+                            },
+                            &mut rng,
+                        )
+                        .unwrap();
+                    assert_eq!(previous, expected[address as usize]);
+                    expected[address as usize].status = Status::Active { since: 7 };
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn failed_writes_leave_records_unchanged() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut oram =
+            TypedOram::<Account, 64, _>::new(LinearTimeOram::<BlockValue<64>>::new(4).unwrap());
+        let kept = account(1, &mut rng);
+        oram.write_t(1, &kept, &mut rng).unwrap();
+
+        let too_large = Account {
+            owner: "x".repeat(60),
+            ..Account::default()
+        };
+        assert!(oram.write_t(1, &too_large, &mut rng).is_err());
+        assert!(oram.access_t(1, |_| too_large.clone(), &mut rng).is_err());
+        assert_eq!(oram.read_t(1, &mut rng).unwrap(), kept);
+        assert_eq!(oram.block_capacity().unwrap(), 4);
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A wrapper for sharing one ORAM between threads. Requires the `std` feature.
+//!
+//! Every ORAM in this crate is `Send` and `Sync` whenever its block type is:
+//! all of their state is owned, and all mutation goes through `&mut self`.
+//! An ORAM can therefore be moved to another thread, or put behind any lock;
+//! [`SharedOram`] is the sanctioned way of doing the latter.
+//!
+//! A `SharedOram` also counts the operations made through it, in atomic counters that [`SharedOram::stats`] reads
+//! without taking the lock, so that another thread (e.g., one exporting metrics) can read the counts while accesses proceed.
+
+use crate::{pool::OramStats, Address, Oram, OramError};
+use rand::{CryptoRng, RngCore};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex, MutexGuard,
+};
+
+/// An ORAM that may be accessed concurrently from several threads, which it serializes with a [`Mutex`].
+///
+/// Each access is exactly as oblivious as an access to the underlying ORAM.
+/// However, sharing introduces a timing side channel: a thread waits for the lock
+/// for as long as the accesses queued before it take, so an adversary that can measure
+/// how long a thread's accesses take learns how many accesses other threads are making concurrently.
+/// It does not learn which addresses any thread accesses.
+/// Applications for which the rate of accesses is sensitive should issue accesses at a fixed rate
+/// (padding with dummy reads as needed), rather than rely on the lock.
+///
+/// # Errors
+///
+/// If a thread panics during an access, the underlying ORAM may be left in an inconsistent state,
+/// so subsequent accesses from any thread, and [`SharedOram::into_inner`], fail with a `Poisoned` error.
+#[derive(Debug)]
+pub struct SharedOram<O: Oram> {
+    oram: Mutex<O>,
+    block_capacity: Address,
+    // Counted with relaxed ordering after each successful operation, outside any ordering with the ORAM itself.
+    reads: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl<O: Oram> SharedOram<O> {
+    /// Wraps `oram` for sharing between threads.
+    pub fn new(oram: O) -> Result<Self, OramError> {
+        Ok(Self {
+            block_capacity: oram.block_capacity()?,
+            oram: Mutex::new(oram),
+            reads: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the underlying ORAM.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Poisoned` error if a thread panicked during an access.
+    pub fn into_inner(self) -> Result<O, OramError> {
+        self.oram.into_inner().map_err(|_| OramError::Poisoned)
+    }
+
+    /// Returns the capacity in blocks of the underlying ORAM.
+    pub fn block_capacity(&self) -> Result<Address, OramError> {
+        Ok(self.block_capacity)
+    }
+
+    /// Returns the number of successful reads, and of successful writes and accesses, made through this `SharedOram`.
+    /// Does not wait for the lock, so may be called from any thread while accesses proceed.
+    ///
+    /// Each count is incremented after its operation completes, and read without synchronizing with the ORAM,
+    /// so it may lag the operations that have completed (but never count one that has not).
+    /// The two counts are read separately, so need not describe the same instant.
+    /// Each count is monotonic, and once the accesses of every thread have been joined (or otherwise synchronized with),
+    /// the counts are exact.
+    pub fn stats(&self) -> OramStats {
+        let reads = self.reads.load(Ordering::Relaxed);
+        let writes = self.writes.load(Ordering::Relaxed);
+        OramStats {
+            reads,
+            writes,
+            block_capacity: self.block_capacity,
+            saturated: reads == u64::MAX || writes == u64::MAX,
+        }
+    }
+
+    /// Performs an access as in [`Oram::access`], waiting for accesses by other threads to finish first.
+    pub fn access<R: RngCore + CryptoRng, F: Fn(&O::V) -> O::V>(
+        &self,
+        index: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<O::V, OramError> {
+        let result = self.lock()?.access(index, callback, rng);
+        Self::count(&self.writes, result)
+    }
+
+    /// Reads the value stored at `index` as in [`Oram::read`], waiting for accesses by other threads to finish first.
+    pub fn read<R: RngCore + CryptoRng>(
+        &self,
+        index: Address,
+        rng: &mut R,
+    ) -> Result<O::V, OramError> {
+        let result = self.lock()?.read(index, rng);
+        Self::count(&self.reads, result)
+    }
+
+    /// Writes the value stored at `index` as in [`Oram::write`], waiting for accesses by other threads to finish first.
+    /// Returns the value previously stored at `index`.
+    pub fn write<R: RngCore + CryptoRng>(
+        &self,
+        index: Address,
+        new_value: O::V,
+        rng: &mut R,
+    ) -> Result<O::V, OramError> {
+        let result = self.lock()?.write(index, new_value, rng);
+        Self::count(&self.writes, result)
+    }
+
+    // Waits for the lock, failing with a `Poisoned` error if a thread panicked while holding it.
+    fn lock(&self) -> Result<MutexGuard<'_, O>, OramError> {
+        self.oram.lock().map_err(|_| OramError::Poisoned)
+    }
+
+    // Increments `counter`, saturating at `u64::MAX`, if `result` is a success.
+    fn count(counter: &AtomicU64, result: Result<O::V, OramError>) -> Result<O::V, OramError> {
+        if result.is_ok() {
+            let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                Some(count.saturating_add(1))
+            });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bucket::BlockValue, deterministic_eviction::DeterministicEvictPathOram,
+        linear_time_oram::LinearTimeOram, position_map::PositionMap, ring_oram::RingOram,
+        stash::ObliviousStash, DefaultOram, PathOram,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use static_assertions::assert_impl_all;
+    use std::{
+        collections::HashMap,
+        sync::{atomic::AtomicBool, Arc},
+        thread,
+    };
+
+    assert_impl_all!(PathOram<BlockValue<64>, 4, 8>: Send, Sync);
+    assert_impl_all!(DefaultOram<BlockValue<64>>: Send, Sync);
+    assert_impl_all!(DeterministicEvictPathOram<BlockValue<64>, 4, 8>: Send, Sync);
+    assert_impl_all!(RingOram<BlockValue<64>, 4, 6>: Send, Sync);
+    assert_impl_all!(LinearTimeOram<BlockValue<64>>: Send, Sync);
+    assert_impl_all!(ObliviousStash<BlockValue<64>>: Send, Sync);
+    assert_impl_all!(PositionMap<8, 4>: Send, Sync);
+    assert_impl_all!(SharedOram<PathOram<BlockValue<64>, 4, 8>>: Send, Sync);
+
+    #[test]
+    fn shared_oram_concurrent_increments() {
+        let capacity = 64;
+        let threads = 8;
+        let operations_per_thread = 200;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        let oram = Arc::new(SharedOram::new(oram).unwrap());
+        // The number of increments made to each address, which the final ORAM contents must match.
+        let mirror = Arc::new(Mutex::new(HashMap::<Address, u64>::new()));
+
+        let handles: Vec<_> = (0..threads)
+            .map(|thread_index| {
+                let oram = Arc::clone(&oram);
+                let mirror = Arc::clone(&mirror);
+                thread::spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(thread_index);
+                    for _ in 0..operations_per_thread {
+                        let address = rng.gen_range(0..capacity);
+                        if rng.gen::<bool>() {
+                            oram.access(address, |count| count + 1, &mut rng).unwrap();
+                            *mirror.lock().unwrap().entry(address).or_insert(0) += 1;
+                        } else {
+                            oram.read(address, &mut rng).unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mirror = mirror.lock().unwrap();
+        let mut oram = Arc::into_inner(oram).unwrap().into_inner().unwrap();
+        for address in 0..capacity {
+            let expected = mirror.get(&address).copied().unwrap_or(0);
+            assert_eq!(oram.read(address, &mut rng).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn stats_read_concurrently_are_monotonic_and_end_exact() {
+        let capacity = 64;
+        let threads = 4;
+        let operations_per_thread = 200;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(capacity, &mut rng, 40, 1).unwrap();
+        let oram = Arc::new(SharedOram::new(oram).unwrap());
+        let done = Arc::new(AtomicBool::new(false));
+
+        // Reads the counts in a loop while the workers access the ORAM, as a metrics exporter would.
+        let exporter = {
+            let oram = Arc::clone(&oram);
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                let mut previous = oram.stats();
+                let mut exports = 0;
+                while !done.load(Ordering::Relaxed) {
+                    let stats = oram.stats();
+                    assert!(stats.reads >= previous.reads);
+                    assert!(stats.writes >= previous.writes);
+                    assert!(stats.reads + stats.writes <= threads * operations_per_thread);
+                    assert_eq!(stats.block_capacity, capacity);
+                    previous = stats;
+                    exports += 1;
+                }
+                exports
+            })
+        };
+
+        let workers: Vec<_> = (0..threads)
+            .map(|thread_index| {
+                let oram = Arc::clone(&oram);
+                thread::spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(thread_index);
+                    let mut reads = 0;
+                    for _ in 0..operations_per_thread {
+                        let address = rng.gen_range(0..capacity);
+                        match rng.gen_range(0..3) {
+                            0 => {
+                                oram.read(address, &mut rng).unwrap();
+                                reads += 1;
+                            }
+                            1 => {
+                                oram.write(address, 1, &mut rng).unwrap();
+                            }
+                            _ => {
+                                oram.access(address, |count| count + 1, &mut rng).unwrap();
+                            }
+                        }
+                    }
+                    // Failed operations are not counted.
+                    assert!(oram.read(capacity, &mut rng).is_err());
+                    reads
+                })
+            })
+            .collect();
+        let reads: u64 = workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .sum();
+        done.store(true, Ordering::Relaxed);
+        assert!(exporter.join().unwrap() > 0);
+
+        let stats = oram.stats();
+        assert_eq!(stats.reads, reads);
+        assert_eq!(stats.writes, threads * operations_per_thread - reads);
+        assert!(!stats.saturated);
+    }
+
+    #[test]
+    fn a_panic_during_an_access_poisons_the_shared_oram() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let oram = Arc::new(SharedOram::new(oram).unwrap());
+        oram.write(3, 7, &mut rng).unwrap();
+
+        let panicking = {
+            let oram = Arc::clone(&oram);
+            thread::spawn(move || {
+                let mut rng = StdRng::seed_from_u64(1);
+                let _ = oram.access(3, |_| panic!("the callback panicked"), &mut rng);
+            })
+        };
+        assert!(panicking.join().is_err());
+
+        assert!(matches!(oram.read(3, &mut rng), Err(OramError::Poisoned)));
+        assert!(matches!(
+            oram.write(3, 8, &mut rng),
+            Err(OramError::Poisoned)
+        ));
+        assert_eq!(oram.stats().writes, 1);
+        assert!(matches!(
+            Arc::into_inner(oram).unwrap().into_inner(),
+            Err(OramError::Poisoned)
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Storage of values of two sizes in two Path ORAMs, hiding which size each operation is for.
+//!
+//! Storing small values (e.g., 8-byte counters) in the blocks of an ORAM sized for large ones (e.g., 4 KiB documents)
+//! wastes bandwidth on every small access, while storing them in a separate ORAM reveals, by which ORAM is accessed,
+//! which size each operation is for. A [`SizeClassedOram`] keeps one Path ORAM per size class, and performs every
+//! [`SizedOperation`] with exactly one access to each, in the same order: the real access to the ORAM of the operation's
+//! class, and a dummy read of address 0 to the other. Reads, writes, and dummy reads are indistinguishable Path ORAM accesses,
+//! so the sequence of accesses, and of the physical memory each touches, is the same whatever the operations' classes.
+//! Each operation therefore costs an access to both ORAMs, but small values no longer occupy large blocks:
+//! the large ORAM holds only the large values, so its tree is smaller and shallower than one holding every value.
+//!
+//! A `SizeClassedOram` has exactly two classes, since each class has its own value type.
+//! An application with more classes must apply the same pattern itself, accessing the ORAM of every class,
+//! in a fixed order, for each operation.
+//!
+//! ```
+//! use oram::{size_classed::{SizeClassedOram, SizedOperation, SizedResult}, BlockValue, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let counters = PathOram::<u64, 4, 8>::new_with_parameters(1024, &mut rng, 40, 1)?;
+//! let documents = PathOram::<BlockValue<4096>, 4, 8>::new_with_parameters(16, &mut rng, 40, 1)?;
+//! let mut oram = SizeClassedOram::new(counters, documents)?;
+//! oram.perform(SizedOperation::WriteSmall(7, 42), &mut rng)?;
+//! oram.perform(SizedOperation::WriteLarge(3, BlockValue::new([1; 4096])), &mut rng)?;
+//! assert_eq!(oram.perform(SizedOperation::ReadSmall(7), &mut rng)?, SizedResult::Small(42));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    eviction::{EvictionStrategy, GreedyEviction},
+    path_oram::PathOram,
+    tree::TreeIndex,
+    utils::IndexWord,
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError,
+};
+use alloc::string::ToString;
+use rand::{CryptoRng, Rng};
+
+/// An operation on a [`SizeClassedOram`], on a value of the small class `S` or the large class `L`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizedOperation<S, L> {
+    /// Reads the small value stored at an address.
+    ReadSmall(Address),
+    /// Writes a small value to an address, returning the value previously stored there.
+    WriteSmall(Address, S),
+    /// Reads the large value stored at an address.
+    ReadLarge(Address),
+    /// Writes a large value to an address, returning the value previously stored there.
+    WriteLarge(Address, L),
+}
+
+/// The result of a [`SizedOperation`]: the value read, or the value replaced, in the operation's class.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizedResult<S, L> {
+    /// A value of the small class.
+    Small(S),
+    /// A value of the large class.
+    Large(L),
+}
+
+/// Two Path ORAMs, of small values `S` and large values `L`, every operation on which accesses both.
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct SizeClassedOram<
+    S: OramBlock,
+    L: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy = GreedyEviction,
+    I: IndexWord = TreeIndex,
+> {
+    small: PathOram<S, Z, AB, E, I>,
+    large: PathOram<L, Z, AB, E, I>,
+    // The class (0 for small, 1 for large) of the ORAM made each access, and the buckets that access read and wrote.
+    #[cfg(test)]
+    trace: alloc::vec::Vec<(usize, crate::test_utils::BucketTrace)>,
+}
+
+impl<
+        S: OramBlock,
+        L: OramBlock,
+        const Z: BucketSize,
+        const AB: BlockSize,
+        E: EvictionStrategy,
+        I: IndexWord,
+    > SizeClassedOram<S, L, Z, AB, E, I>
+{
+    /// Returns a `SizeClassedOram` storing small values in `small` and large values in `large`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if either ORAM has capacity 0, so cannot serve dummy reads.
+    pub fn new(
+        small: PathOram<S, Z, AB, E, I>,
+        large: PathOram<L, Z, AB, E, I>,
+    ) -> Result<Self, OramError> {
+        for (name, capacity) in [
+            ("Small class capacity", small.block_capacity()?),
+            ("Large class capacity", large.block_capacity()?),
+        ] {
+            if capacity == 0 {
+                return Err(OramError::InvalidConfigurationError {
+                    parameter_name: name.to_string(),
+                    parameter_value: capacity.to_string(),
+                });
+            }
+        }
+        Ok(Self {
+            small,
+            large,
+            #[cfg(test)]
+            trace: alloc::vec::Vec::new(),
+        })
+    }
+
+    /// Performs `operation`, accessing the small ORAM and then the large ORAM, one of them for `operation`
+    /// and the other to read address 0, discarding the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError`, without accessing either ORAM, if the operation's address is beyond
+    /// the capacity of its class. Otherwise, returns the first error from either access; both are made in any case.
+    pub fn perform<R: Rng + CryptoRng>(
+        &mut self,
+        operation: SizedOperation<S, L>,
+        rng: &mut R,
+    ) -> Result<SizedResult<S, L>, OramError> {
+        let (address, capacity) = match operation {
+            SizedOperation::ReadSmall(address) | SizedOperation::WriteSmall(address, _) => {
+                (address, self.small.block_capacity()?)
+            }
+            SizedOperation::ReadLarge(address) | SizedOperation::WriteLarge(address, _) => {
+                (address, self.large.block_capacity()?)
+            }
+        };
+        if address >= capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity,
+            });
+        }
+
+        let small = match operation {
+            SizedOperation::ReadSmall(address) => self.small.read(address, rng),
+            SizedOperation::WriteSmall(address, value) => self.small.write(address, value, rng),
+            _ => self.small.read(0, rng),
+        };
+        #[cfg(test)]
+        self.record_access(0);
+        let large = match operation {
+            SizedOperation::ReadLarge(address) => self.large.read(address, rng),
+            SizedOperation::WriteLarge(address, value) => self.large.write(address, value, rng),
+            _ => self.large.read(0, rng),
+        };
+        #[cfg(test)]
+        self.record_access(1);
+
+        let (small, large) = (small?, large?);
+        Ok(match operation {
+            SizedOperation::ReadSmall(_) | SizedOperation::WriteSmall(..) => {
+                SizedResult::Small(small)
+            }
+            SizedOperation::ReadLarge(_) | SizedOperation::WriteLarge(..) => {
+                SizedResult::Large(large)
+            }
+        })
+    }
+
+    /// Returns the ORAM of small values.
+    pub fn small(&self) -> &PathOram<S, Z, AB, E, I> {
+        &self.small
+    }
+
+    /// Returns the ORAM of large values.
+    pub fn large(&self) -> &PathOram<L, Z, AB, E, I> {
+        &self.large
+    }
+
+    /// Returns the ORAMs of small and large values.
+    pub fn into_inner(self) -> (PathOram<S, Z, AB, E, I>, PathOram<L, Z, AB, E, I>) {
+        (self.small, self.large)
+    }
+
+    #[cfg(test)]
+    fn record_access(&mut self, class: usize) {
+        let buckets = match class {
+            0 => crate::test_utils::take_bucket_trace(&mut self.small),
+            _ => crate::test_utils::take_bucket_trace(&mut self.large),
+        };
+        self.trace.push((class, buckets));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{start_bucket_trace, BucketTrace},
+        tree::CompleteBinaryTreeIndex,
+        BlockValue,
+    };
+    use alloc::vec::Vec;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    type TestOram = SizeClassedOram<u64, BlockValue<256>, 4, 8>;
+
+    fn classed_oram(rng: &mut StdRng) -> TestOram {
+        let mut small = PathOram::new_with_parameters(256, rng, 40, 1).unwrap();
+        let mut large = PathOram::new_with_parameters(32, rng, 40, 1).unwrap();
+        start_bucket_trace(&mut small);
+        start_bucket_trace(&mut large);
+        SizeClassedOram::new(small, large).unwrap()
+    }
+
+    #[test]
+    fn each_class_matches_its_model() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = classed_oram(&mut rng);
+        let mut small = [0u64; 256];
+        let mut large = [BlockValue::<256>::default(); 32];
+        for i in 0..600u64 {
+            let (operation, expected) = match rng.gen_range(0..4) {
+                0 => {
+                    let address = rng.gen_range(0..256);
+                    (
+                        SizedOperation::ReadSmall(address),
+                        SizedResult::Small(small[address as usize]),
+                    )
+                }
+                1 => {
+                    let address = rng.gen_range(0..256);
+                    let previous = core::mem::replace(&mut small[address as usize], i);
+                    (
+                        SizedOperation::WriteSmall(address, i),
+                        SizedResult::Small(previous),
+                    )
+                }
+                2 => {
+                    let address = rng.gen_range(0..32);
+                    (
+                        SizedOperation::ReadLarge(address),
+                        SizedResult::Large(large[address as usize]),
+                    )
+                }
+                _ => {
+                    let address = rng.gen_range(0..32);
+                    let value = BlockValue::new([i as u8; 256]);
+                    let previous = core::mem::replace(&mut large[address as usize], value);
+                    (
+                        SizedOperation::WriteLarge(address, value),
+                        SizedResult::Large(previous),
+                    )
+                }
+            };
+            assert_eq!(oram.perform(operation, &mut rng).unwrap(), expected);
+        }
+
+        // Out-of-bounds addresses are checked against their own class, and rejected without an access.
+        let accesses = oram.trace.len();
+        assert!(oram
+            .perform(SizedOperation::ReadSmall(100), &mut rng)
+            .is_ok());
+        assert!(matches!(
+            oram.perform(SizedOperation::ReadLarge(100), &mut rng),
+            Err(OramError::AddressOutOfBoundsError {
+                attempted: 100,
+                capacity: 32
+            })
+        ));
+        assert_eq!(oram.trace.len(), accesses + 2);
+    }
+
+    type Trace = Vec<(usize, BucketTrace)>;
+
+    // Performs 200 operations, on the class given by `large(i)` for the `i`th, at the address given by `address(i)`,
+    // and returns the trace of the buckets each access read and wrote.
+    fn trace<F: Fn(u64) -> bool, A: Fn(u64) -> Address>(large: F, address: A) -> Trace {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut oram = classed_oram(&mut rng);
+        for i in 0..200 {
+            let address = address(i);
+            let operation = match (large(i), i % 3 == 0) {
+                (false, false) => SizedOperation::ReadSmall(address),
+                (false, true) => SizedOperation::WriteSmall(address, i),
+                (true, false) => SizedOperation::ReadLarge(address),
+                (true, true) => SizedOperation::WriteLarge(address, BlockValue::new([1; 256])),
+            };
+            oram.perform(operation, &mut rng).unwrap();
+        }
+        oram.trace
+    }
+
+    // Replaces each bucket of `trace` with its depth in its tree, forgetting which path was read,
+    // as (class, level of the recursion, depth, whether written) tuples.
+    fn depths(trace: &Trace) -> Vec<(usize, usize, u64, bool)> {
+        trace
+            .iter()
+            .flat_map(|(class, buckets)| {
+                buckets
+                    .iter()
+                    .map(|&(level, node, is_write)| (*class, level, node.ct_depth(), is_write))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn trace_is_independent_of_the_classes_accessed() {
+        // Every operation is to address 0, which is also the address of the dummy reads,
+        // so with the same randomness the same buckets must be touched whatever the classes of the operations.
+        let small = trace(|_| false, |_| 0);
+        assert_eq!(small.len(), 400);
+        // Each operation accesses the small ORAM, and then the large one, reading and writing a path of each level of its recursion.
+        for (i, (class, buckets)) in small.iter().enumerate() {
+            assert_eq!(*class, i % 2);
+            assert!(!buckets.is_empty());
+        }
+        assert_eq!(small, trace(|_| true, |_| 0));
+        assert_eq!(small, trace(|i| i % 2 == 0, |_| 0));
+        assert_eq!(small, trace(|i| i % 7 < 2, |_| 0));
+
+        // At other addresses, the paths read are those of other (uniformly random) leaves,
+        // but every access still touches the same number of buckets at each depth, in the same order.
+        let address = |i| (i * 13) % 32;
+        let expected = depths(&small);
+        let classes: [fn(u64) -> bool; 4] = [|_| false, |_| true, |i| i % 2 == 0, |i| i % 7 < 2];
+        for large in classes {
+            assert_eq!(depths(&trace(large, address)), expected);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Snapshots of the complete state of a Path ORAM, from which it can later be restored.
+//!
+//! The state of a [`PathOram`] has two parts with different trust assumptions:
+//!
+//! - the *tree* of buckets, which is the untrusted memory that the ORAM hides its access pattern from;
+//! - the *client state*: the stash, the position map, the tree height, and the paths awaiting eviction.
+//!
+//! The stash is part of the client state, not of the tree. Between the read of a path and its eviction,
+//! or whenever blocks have overflowed the tree, some real blocks are held only in the stash,
+//! so persisting the tree alone would lose them. A [`PathOramSnapshot`] therefore holds every stash slot,
+//! and must be stored with the same protections as the rest of the client state.
+//!
+//! A snapshot does not include a [`LeafSampler`](crate::leaf_sampler::LeafSampler), memory budget, or occupancy observer.
+//! A restored ORAM draws new leaves from the RNG passed to each access.
+//!
+//! ```
+//! use oram::{Oram, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut OsRng, 40, 1)?;
+//! oram.write(3, 42, &mut OsRng)?;
+//! let snapshot = oram.export_snapshot()?;
+//! let mut restored = PathOram::<u64, 4, 8>::import_snapshot(snapshot)?;
+//! assert_eq!(restored.read(3, &mut OsRng)?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "std")]
+use crate::reseed::Reseeder;
+use crate::{
+    bucket::{Bucket, PathOramBlock, PositionBlock},
+    eviction::{EvictionOrder, EvictionStrategy},
+    linear_time_oram::LinearTimeOram,
+    path_oram::PathOram,
+    position_map::PositionMap,
+    rebuild::RebuildSchedule,
+    stash::ObliviousStash,
+    tree::{TreeHeight, TreeIndex},
+    utils::IndexWord,
+    BlockSize, BucketSize, OramBlock, OramError, RecursionCutoff, StashSize,
+};
+use alloc::{boxed::Box, collections::VecDeque, format, string::ToString, vec::Vec};
+use core::fmt;
+
+/// A copy of the state of a [`PathOram`], returned by [`PathOram::export_snapshot`]. See the [module documentation](self).
+#[derive(Clone)]
+pub struct PathOramSnapshot<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    I: IndexWord = TreeIndex,
+> {
+    // The untrusted tree.
+    pub(crate) tree: Vec<Bucket<V, Z, I>>,
+    // The trusted client state.
+    pub(crate) height: TreeHeight,
+    pub(crate) stash: Vec<PathOramBlock<V, I>>,
+    pub(crate) position_map: PositionMapSnapshot<AB, Z>,
+    pub(crate) pending_evictions: Vec<TreeIndex>,
+    pub(crate) max_pending_evictions: usize,
+    pub(crate) eviction_order: EvictionOrder,
+    pub(crate) eviction_count: u64,
+    pub(crate) overflow_size: StashSize,
+    pub(crate) recursion_cutoff: RecursionCutoff,
+    // The padding levels of the position map recursion (see the `uniform_depth` module).
+    pub(crate) recursion_padding: Vec<PathOramSnapshot<PositionBlock<AB>, Z, AB>>,
+    // The reseed interval, but never the internal RNG (see the `reseed` module).
+    #[cfg(feature = "std")]
+    pub(crate) reseed_interval: Option<u64>,
+}
+
+#[derive(Clone)]
+pub(crate) enum PositionMapSnapshot<const AB: BlockSize, const Z: BucketSize> {
+    Base(Vec<PositionBlock<AB>>),
+    Recursive(Box<PathOramSnapshot<PositionBlock<AB>, Z, AB>>),
+}
+
+// The blocks, stash, and positions are secret, so only the sizes and settings are printed, as for `PathOram`.
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, I: IndexWord> fmt::Debug
+    for PathOramSnapshot<V, Z, AB, I>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("PathOramSnapshot");
+        debug
+            .field("buckets", &self.tree.len())
+            .field("height", &self.height)
+            .field("position_map", &self.position_map)
+            .field("pending_evictions", &self.pending_evictions.len())
+            .field("max_pending_evictions", &self.max_pending_evictions)
+            .field("eviction_order", &self.eviction_order)
+            .field("eviction_count", &self.eviction_count)
+            .field("overflow_size", &self.overflow_size)
+            .field("recursion_cutoff", &self.recursion_cutoff)
+            .field("padding_levels", &self.recursion_padding.len());
+        #[cfg(feature = "std")]
+        debug.field("reseed_interval", &self.reseed_interval);
+        debug.finish_non_exhaustive()
+    }
+}
+
+impl<const AB: BlockSize, const Z: BucketSize> fmt::Debug for PositionMapSnapshot<AB, Z> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base(blocks) => f
+                .debug_struct("Base")
+                .field("blocks", &blocks.len())
+                .finish_non_exhaustive(),
+            Self::Recursive(block_oram) => f.debug_tuple("Recursive").field(block_oram).finish(),
+        }
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, I: IndexWord>
+    PathOramSnapshot<V, Z, AB, I>
+{
+    /// Returns the number of real blocks in the stash overflow (as [`PathOram::stash_occupancy`]),
+    /// which a snapshot of the tree alone would lose.
+    pub fn stash_occupancy(&self) -> StashSize {
+        let path_size = Z * (self.height as usize + 1);
+        self.stash
+            .iter()
+            .skip(path_size)
+            .filter(|block| !bool::from(block.ct_is_dummy()))
+            .count() as StashSize
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Returns a snapshot of the state of this ORAM, including its stash and position map.
+    /// Not oblivious in itself: the snapshot must be stored as securely as the client state.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Poisoned` error if the ORAM is poisoned.
+    pub fn export_snapshot(&self) -> Result<PathOramSnapshot<V, Z, AB, I>, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        let position_map = match &self.position_map {
+            PositionMap::Base(linear_oram) => {
+                PositionMapSnapshot::Base(linear_oram.physical_memory.clone())
+            }
+            PositionMap::Recursive(block_oram) => {
+                PositionMapSnapshot::Recursive(Box::new(block_oram.export_snapshot()?))
+            }
+        };
+        let mut recursion_padding = Vec::with_capacity(self.recursion_padding.len());
+        for padding_oram in &self.recursion_padding {
+            recursion_padding.push(padding_oram.export_snapshot()?);
+        }
+        Ok(PathOramSnapshot {
+            tree: self.physical_memory.clone(),
+            height: self.height,
+            stash: self.stash.blocks(),
+            position_map,
+            pending_evictions: self.pending_evictions.iter().copied().collect(),
+            max_pending_evictions: self.max_pending_evictions,
+            eviction_order: self.eviction_order,
+            eviction_count: self.eviction_count,
+            overflow_size: self.overflow_size,
+            recursion_cutoff: self.recursion_cutoff,
+            recursion_padding,
+            #[cfg(feature = "std")]
+            reseed_interval: self.reseed_interval(),
+        })
+    }
+
+    /// Returns an ORAM restored from `snapshot`.
+    ///
+    /// If the snapshotted ORAM had a reseed interval, the restored ORAM refuses accesses until it is reseeded
+    /// (see the [`reseed`](crate::reseed) module). Otherwise, restoring a snapshot more than once
+    /// and accessing the copies with replayed RNG state correlates their accesses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if the snapshot is inconsistent with its own tree height:
+    /// if the tree does not have `2^(height + 1)` buckets, if the stash has fewer slots than a path has blocks,
+    /// or if more paths await eviction than the snapshotted ORAM allowed.
+    pub fn import_snapshot(snapshot: PathOramSnapshot<V, Z, AB, I>) -> Result<Self, OramError> {
+        let PathOramSnapshot {
+            tree,
+            height,
+            stash,
+            position_map,
+            pending_evictions,
+            max_pending_evictions,
+            eviction_order,
+            eviction_count,
+            overflow_size,
+            recursion_cutoff,
+            recursion_padding,
+            #[cfg(feature = "std")]
+            reseed_interval,
+        } = snapshot;
+
+        let number_of_buckets = u32::try_from(height + 1)
+            .ok()
+            .and_then(|exponent| 1u64.checked_shl(exponent));
+        if number_of_buckets != Some(u64::try_from(tree.len())?) {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("Snapshot tree size (with height {height})"),
+                parameter_value: tree.len().to_string(),
+            });
+        }
+        let path_size = u64::try_from(Z)? * (height + 1);
+        let stash = ObliviousStash::from_blocks(path_size, &stash)?;
+        if pending_evictions.len() > max_pending_evictions {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!(
+                    "Snapshot pending evictions (with at most {max_pending_evictions})"
+                ),
+                parameter_value: pending_evictions.len().to_string(),
+            });
+        }
+        let position_map = match position_map {
+            PositionMapSnapshot::Base(physical_memory) => {
+                let mut linear_oram = LinearTimeOram::new(u64::try_from(physical_memory.len())?)?;
+                linear_oram.physical_memory = physical_memory;
+                PositionMap::Base(linear_oram)
+            }
+            PositionMapSnapshot::Recursive(block_snapshot) => {
+                PositionMap::Recursive(Box::new(PathOram::import_snapshot(*block_snapshot)?))
+            }
+        };
+
+        Ok(Self {
+            physical_memory: tree,
+            stash,
+            position_map,
+            height,
+            pending_evictions: VecDeque::from(pending_evictions),
+            max_pending_evictions,
+            eviction_order,
+            eviction_count,
+            leaf_sampler: None,
+            overflow_size,
+            recursion_cutoff,
+            poisoned: false,
+            #[cfg(feature = "std")]
+            reseeder: reseed_interval.map(Reseeder::awaiting_entropy),
+            undo_log: None,
+            recursion_padding: recursion_padding
+                .into_iter()
+                .map(PathOram::import_snapshot)
+                .collect::<Result<_, _>>()?,
+            rebuild_schedule: RebuildSchedule::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bucket::BlockValue, leaf_sampler::ScriptedLeafSampler, Address, Oram};
+    use alloc::string::String;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    type TestOram = PathOram<BlockValue<1>, 2, 8>;
+
+    #[test]
+    fn overflowed_blocks_survive_restore() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // With capacity 8 and the identity permutation, addresses `2i` and `2i + 1` start in leaf `4 + i`.
+        // Remapping addresses 0 through 3 to leaves 6 and 7 fills the root, and leaves addresses 2 and 3 only in the stash.
+        let sampler = ScriptedLeafSampler::new([7, 6, 7, 6]);
+        let mut oram = TestOram::new_with_leaf_sampler(8, sampler, &mut rng, 40, 1).unwrap();
+        for address in 0..4 {
+            oram.write(address, BlockValue::new([address as u8 + 1]), &mut rng)
+                .unwrap();
+        }
+        assert_eq!(oram.stash_occupancy(), 2);
+
+        let snapshot = oram.export_snapshot().unwrap();
+        assert_eq!(snapshot.stash_occupancy(), 2);
+        let tree_addresses: Vec<Address> = snapshot
+            .tree
+            .iter()
+            .flat_map(|bucket| bucket.blocks.iter())
+            .filter(|block| !bool::from(block.ct_is_dummy()))
+            .map(|block| block.address)
+            .collect();
+        assert!(!tree_addresses.contains(&2) && !tree_addresses.contains(&3));
+
+        let mut restored = TestOram::import_snapshot(snapshot).unwrap();
+        assert_eq!(restored.stash_occupancy(), 2);
+        for address in 0..8 {
+            let expected = if address < 4 {
+                BlockValue::new([address as u8 + 1])
+            } else {
+                BlockValue::default()
+            };
+            assert_eq!(restored.read(address, &mut rng).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn restored_oram_matches_original() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // A recursion cutoff of 1 gives a recursive position map, and deferred evictions leave blocks in the path region.
+        let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(256, &mut rng, 40, 1).unwrap();
+        oram.set_max_pending_evictions(3).unwrap();
+        let mut expected = [0u64; 256];
+        for _ in 0..200 {
+            let address = rng.gen_range(0..256);
+            let value = rng.gen();
+            oram.write(address, value, &mut rng).unwrap();
+            expected[address as usize] = value;
+        }
+
+        let mut restored =
+            PathOram::<u64, 4, 8>::import_snapshot(oram.export_snapshot().unwrap()).unwrap();
+        for (address, value) in expected.iter().enumerate() {
+            assert_eq!(restored.read(address as Address, &mut rng).unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn debug_output_omits_positions() {
+        // ORAMs holding the same values at different positions, with a linear and with a recursive position map.
+        for recursion_cutoff in [1, 1 << 10] {
+            let formatted: Vec<String> = (0..2)
+                .map(|seed| {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(
+                        64,
+                        &mut rng,
+                        40,
+                        recursion_cutoff,
+                    )
+                    .unwrap();
+                    for address in 0..64 {
+                        oram.write(address, address, &mut rng).unwrap();
+                    }
+                    format!("{:?}", oram.export_snapshot().unwrap())
+                })
+                .collect();
+            assert_eq!(formatted[0], formatted[1]);
+            assert!(!formatted[0].contains("PositionBlock"));
+        }
+    }
+
+    #[test]
+    fn eviction_order_survives_restore() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_eviction_order(EvictionOrder::ReverseLexicographic);
+        for address in 0..10 {
+            oram.write(address, address + 1, &mut rng).unwrap();
+        }
+
+        let mut restored =
+            PathOram::<u64, 4, 8>::import_snapshot(oram.export_snapshot().unwrap()).unwrap();
+        assert_eq!(restored.eviction_order, EvictionOrder::ReverseLexicographic);
+        assert_eq!(restored.eviction_count(), 10);
+        assert_eq!(restored.read(3, &mut rng).unwrap(), 4);
+        assert_eq!(restored.eviction_count(), 11);
+    }
+
+    #[test]
+    fn inconsistent_snapshots_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let oram = TestOram::new_with_parameters(8, &mut rng, 40, 1).unwrap();
+        let snapshot = oram.export_snapshot().unwrap();
+
+        // A path of a tree of height 2 has 3 buckets of 2 blocks.
+        let mut short_stash = snapshot.clone();
+        short_stash.stash.truncate(5);
+        assert!(matches!(
+            TestOram::import_snapshot(short_stash),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        let mut shortest_valid_stash = snapshot.clone();
+        shortest_valid_stash.stash.truncate(6);
+        assert!(TestOram::import_snapshot(shortest_valid_stash).is_ok());
+
+        let mut taller = snapshot.clone();
+        taller.height += 1;
+        assert!(TestOram::import_snapshot(taller).is_err());
+        let mut too_many_pending = snapshot;
+        too_many_pending.pending_evictions.push(4);
+        assert!(TestOram::import_snapshot(too_many_pending).is_err());
+    }
+}
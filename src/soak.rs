@@ -0,0 +1,454 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A soak-test harness, for finding problems that show up only after millions of operations,
+//! such as stash growth, counter overflow, and allocation creep. Enabled by the `soak` feature.
+//!
+//! [`run`] performs a seeded mix of reads, writes, and read-modify-write accesses against a Path ORAM,
+//! checking every value returned against a shadow model. Every [`SoakConfig::check_interval`] operations,
+//! it also audits the ORAM (see [`PathOram::audit`]), re-reads a sample of addresses,
+//! and records a [`Checkpoint`] of the stash high-water mark, resident memory, and physical memory traffic.
+//!
+//! A run is determined by its seed, so a failure (whose message includes the seed) can be reproduced exactly.
+//! See `examples/soak.rs` for a command-line driver.
+
+use crate::{
+    diagnostics::AuditReport,
+    path_oram::{
+        PathOram, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK, DEFAULT_RECURSION_CUTOFF,
+        DEFAULT_STASH_OVERFLOW_SIZE,
+    },
+    seeded::SeededOram,
+    Address, OramError, StashSize,
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::fmt;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+// The number of addresses re-read at each checkpoint.
+const SAMPLE_SIZE: usize = 256;
+
+type SoakOram = PathOram<u64, DEFAULT_BLOCKS_PER_BUCKET, DEFAULT_POSITIONS_PER_BLOCK>;
+
+/// The parameters of a soak test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoakConfig {
+    /// The seed determining the ORAM's randomness and the workload.
+    pub seed: u64,
+    /// The number of operations to perform.
+    pub operations: u64,
+    /// The capacity of the ORAM. Must be a power of two.
+    pub block_capacity: Address,
+    /// The number of operations between checkpoints.
+    pub check_interval: u64,
+    /// The growth in resident memory, in bytes, beyond which steadily growing memory use is a failure.
+    pub max_memory_growth: u64,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            operations: 5_000_000,
+            block_capacity: 1 << 18,
+            check_interval: 100_000,
+            max_memory_growth: 64 << 20,
+        }
+    }
+}
+
+/// The state of a soak test at a checkpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The number of operations performed so far.
+    pub operations: u64,
+    /// The largest stash occupancy seen so far.
+    pub stash_high_water_mark: StashSize,
+    /// The resident memory of the process in bytes, if it can be measured on this platform.
+    pub resident_bytes: Option<u64>,
+    /// The number of physical accesses (one per ORAM access, including those made by checks) so far.
+    pub physical_accesses: u128,
+    /// The number of bytes of physical memory read and written so far.
+    pub physical_bytes: u128,
+}
+
+/// The ways in which a soak test can fail.
+#[derive(Debug)]
+pub enum SoakError {
+    /// An ORAM operation returned an error.
+    Oram(OramError),
+    /// A value read from the ORAM differed from the shadow model.
+    Drift {
+        /// The address read.
+        address: Address,
+        /// The value in the shadow model.
+        expected: u64,
+        /// The value read.
+        actual: u64,
+    },
+    /// An audit found the ORAM to be inconsistent.
+    Inconsistent(Box<AuditReport>),
+    /// The stash grew beyond its initial overflow size.
+    StashOverflow {
+        /// The stash occupancy reached.
+        occupancy: StashSize,
+    },
+    /// A physical access counter overflowed.
+    CounterOverflow,
+    /// Resident memory grew at every checkpoint, by more than [`SoakConfig::max_memory_growth`] in total.
+    MemoryGrowth {
+        /// The resident memory in bytes at the first checkpoint.
+        baseline: u64,
+        /// The resident memory in bytes now.
+        current: u64,
+    },
+}
+
+impl fmt::Display for SoakError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoakError::Oram(error) => write!(f, "{}", error),
+            SoakError::Drift {
+                address,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "address {} held {}, but the shadow model holds {}",
+                address, actual, expected
+            ),
+            SoakError::Inconsistent(report) => write!(f, "audit failed: {:?}", report),
+            SoakError::StashOverflow { occupancy } => write!(
+                f,
+                "stash occupancy {} exceeded the overflow size {}",
+                occupancy, DEFAULT_STASH_OVERFLOW_SIZE
+            ),
+            SoakError::CounterOverflow => write!(f, "a physical access counter overflowed"),
+            SoakError::MemoryGrowth { baseline, current } => write!(
+                f,
+                "resident memory grew steadily from {} to {} bytes",
+                baseline, current
+            ),
+        }
+    }
+}
+
+/// A failed soak test.
+#[derive(Debug)]
+pub struct SoakFailure {
+    /// The seed of the failed run. Rerunning with it reproduces the failure.
+    pub seed: u64,
+    /// The number of operations completed before the failure.
+    pub operations: u64,
+    /// The checkpoints recorded before the failure.
+    pub checkpoints: Vec<Checkpoint>,
+    /// The cause of the failure.
+    pub error: SoakError,
+}
+
+impl fmt::Display for SoakFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Soak test failed after {} operations: {}. Rerun with seed {} to reproduce.",
+            self.operations, self.error, self.seed
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SoakFailure {}
+
+/// Returns the resident memory of this process in bytes, read from `/proc/self/status`,
+/// or `None` if it cannot be read (e.g., because this is not Linux).
+pub fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    kilobytes.checked_mul(1024)
+}
+
+// The mutable state of a soak test.
+struct Soak {
+    config: SoakConfig,
+    oram: SeededOram<SoakOram>,
+    shadow: Vec<u64>,
+    workload: ChaCha20Rng,
+    physical_bytes_per_access: u128,
+    operations: u64,
+    stash_high_water_mark: StashSize,
+    physical_accesses: u128,
+    physical_bytes: u128,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl Soak {
+    fn new(config: SoakConfig) -> Result<Self, SoakError> {
+        let mut workload = ChaCha20Rng::seed_from_u64(config.seed);
+        let oram = SeededOram::new(workload.gen(), |rng| {
+            SoakOram::new_with_parameters(
+                config.block_capacity,
+                rng,
+                DEFAULT_STASH_OVERFLOW_SIZE,
+                DEFAULT_RECURSION_CUTOFF,
+            )
+        })
+        .map_err(SoakError::Oram)?;
+        let physical_bytes_per_access = oram
+            .oram()
+            .physical_bytes_per_access()
+            .map_err(SoakError::Oram)?
+            .into();
+        let shadow = vec![
+            0;
+            usize::try_from(config.block_capacity)
+                .map_err(|error| SoakError::Oram(error.into()))?
+        ];
+        Ok(Self {
+            config,
+            oram,
+            shadow,
+            workload,
+            physical_bytes_per_access,
+            operations: 0,
+            stash_high_water_mark: 0,
+            physical_accesses: 0,
+            physical_bytes: 0,
+            checkpoints: Vec::new(),
+        })
+    }
+
+    // Performs one access with `callback` and checks the value returned against the shadow model.
+    fn access<F: Fn(&u64) -> u64>(
+        &mut self,
+        address: Address,
+        callback: F,
+    ) -> Result<(), SoakError> {
+        let actual = self
+            .oram
+            .access(address, &callback)
+            .map_err(SoakError::Oram)?;
+
+        self.physical_accesses = self
+            .physical_accesses
+            .checked_add(1)
+            .ok_or(SoakError::CounterOverflow)?;
+        self.physical_bytes = self
+            .physical_bytes
+            .checked_add(self.physical_bytes_per_access)
+            .ok_or(SoakError::CounterOverflow)?;
+
+        let occupancy = self.oram.oram().stash_occupancy();
+        self.stash_high_water_mark = self.stash_high_water_mark.max(occupancy);
+        if occupancy > DEFAULT_STASH_OVERFLOW_SIZE {
+            return Err(SoakError::StashOverflow { occupancy });
+        }
+
+        let expected = &mut self.shadow[address as usize];
+        if actual != *expected {
+            return Err(SoakError::Drift {
+                address,
+                expected: *expected,
+                actual,
+            });
+        }
+        *expected = callback(expected);
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<(), SoakError> {
+        let address = self.workload.gen_range(0..self.config.block_capacity);
+        match self.workload.gen_range(0..10) {
+            0..=3 => self.access(address, |value| *value)?,
+            4..=7 => {
+                let new_value = self.workload.gen();
+                self.access(address, |_| new_value)?;
+            }
+            _ => self.access(address, |value| value.wrapping_add(1))?,
+        }
+        self.operations += 1;
+        Ok(())
+    }
+
+    fn check(&mut self) -> Result<Checkpoint, SoakError> {
+        let report = self.oram.oram().audit().map_err(SoakError::Oram)?;
+        if !report.is_consistent() {
+            return Err(SoakError::Inconsistent(Box::new(report)));
+        }
+        for _ in 0..SAMPLE_SIZE {
+            let address = self.workload.gen_range(0..self.config.block_capacity);
+            self.access(address, |value| *value)?;
+        }
+
+        let checkpoint = Checkpoint {
+            operations: self.operations,
+            stash_high_water_mark: self.stash_high_water_mark,
+            resident_bytes: resident_bytes(),
+            physical_accesses: self.physical_accesses,
+            physical_bytes: self.physical_bytes,
+        };
+        self.check_memory_growth(checkpoint.resident_bytes)?;
+        self.checkpoints.push(checkpoint);
+        Ok(checkpoint)
+    }
+
+    // Fails if resident memory has grown at every checkpoint since the first, and by more than the limit in total.
+    fn check_memory_growth(&self, current: Option<u64>) -> Result<(), SoakError> {
+        let mut previous = match self
+            .checkpoints
+            .first()
+            .and_then(|first| first.resident_bytes)
+        {
+            Some(baseline) => baseline,
+            None => return Ok(()),
+        };
+        let baseline = previous;
+        for resident in self.checkpoints[1..]
+            .iter()
+            .filter_map(|checkpoint| checkpoint.resident_bytes)
+            .chain(current)
+        {
+            if resident <= previous {
+                return Ok(());
+            }
+            previous = resident;
+        }
+        if previous - baseline > self.config.max_memory_growth {
+            return Err(SoakError::MemoryGrowth {
+                baseline,
+                current: previous,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Runs a soak test as described in the [module documentation](self),
+/// calling `on_checkpoint` with each checkpoint as it is recorded. Returns all of the checkpoints.
+pub fn run<F: FnMut(&Checkpoint)>(
+    config: SoakConfig,
+    mut on_checkpoint: F,
+) -> Result<Vec<Checkpoint>, SoakFailure> {
+    let mut soak = match Soak::new(config) {
+        Ok(soak) => soak,
+        Err(error) => {
+            return Err(SoakFailure {
+                seed: config.seed,
+                operations: 0,
+                checkpoints: Vec::new(),
+                error,
+            })
+        }
+    };
+
+    let result = (|| {
+        while soak.operations < config.operations {
+            soak.step()?;
+            if soak.operations % config.check_interval == 0 || soak.operations == config.operations
+            {
+                on_checkpoint(&soak.check()?);
+            }
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => Ok(soak.checkpoints),
+        Err(error) => Err(SoakFailure {
+            seed: config.seed,
+            operations: soak.operations,
+            checkpoints: soak.checkpoints,
+            error,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config(seed: u64) -> SoakConfig {
+        SoakConfig {
+            seed,
+            operations: 20_000,
+            block_capacity: 1 << 10,
+            check_interval: 5_000,
+            ..SoakConfig::default()
+        }
+    }
+
+    #[test]
+    fn short_soak_passes() {
+        let mut observed = 0;
+        let checkpoints = run(small_config(0), |_| observed += 1).unwrap();
+        assert_eq!(observed, 4);
+        assert_eq!(
+            checkpoints.iter().map(|c| c.operations).collect::<Vec<_>>(),
+            [5_000, 10_000, 15_000, 20_000]
+        );
+        let last = checkpoints.last().unwrap();
+        // Every operation, and every sampled read, is one physical access.
+        assert_eq!(last.physical_accesses, 20_000 + 4 * SAMPLE_SIZE as u128);
+        assert!(last.physical_bytes > last.physical_accesses);
+        assert!(last.stash_high_water_mark <= DEFAULT_STASH_OVERFLOW_SIZE);
+    }
+
+    #[test]
+    fn runs_are_reproducible() {
+        let without_memory = |checkpoints: Vec<Checkpoint>| {
+            checkpoints
+                .into_iter()
+                .map(|c| (c.stash_high_water_mark, c.physical_bytes))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            without_memory(run(small_config(1), |_| {}).unwrap()),
+            without_memory(run(small_config(1), |_| {}).unwrap())
+        );
+    }
+
+    #[test]
+    fn invalid_configuration_fails_with_seed() {
+        let config = SoakConfig {
+            seed: 7,
+            block_capacity: 1000,
+            ..small_config(7)
+        };
+        let failure = run(config, |_| {}).unwrap_err();
+        assert!(matches!(failure.error, SoakError::Oram(_)));
+        assert!(failure.to_string().contains("Rerun with seed 7"));
+    }
+
+    #[test]
+    fn steady_memory_growth_fails() {
+        let mut soak = Soak::new(small_config(0)).unwrap();
+        let mut checkpoint = soak.check().unwrap();
+        soak.checkpoints.clear();
+        for resident in [100, 200, 300] {
+            checkpoint.resident_bytes = Some(resident);
+            soak.checkpoints.push(checkpoint);
+        }
+        soak.config.max_memory_growth = 250;
+        // Growth that is not steady is tolerated.
+        assert!(soak.check_memory_growth(Some(300)).is_ok());
+        assert!(soak.check_memory_growth(Some(350)).is_ok());
+        assert!(matches!(
+            soak.check_memory_growth(Some(400)),
+            Err(SoakError::MemoryGrowth {
+                baseline: 100,
+                current: 400
+            })
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn resident_bytes_is_measured_on_linux() {
+        assert!(resident_bytes().unwrap() > 0);
+    }
+}
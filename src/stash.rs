@@ -8,80 +8,238 @@
 //! A trait representing a Path ORAM stash.
 
 use crate::{
-    bucket::{Bucket, PathOramBlock},
-    utils::{bitonic_sort_by_keys, CompleteBinaryTreeIndex, TreeIndex},
+    bucket::{Bucket, IntegrityCheck, PathOramBlock},
+    eviction::{EvictionStrategy, GreedyEviction, UNASSIGNED_LEVEL},
+    oblivious::{bitonic_sort_by_keys_with, conditional_swap_at},
+    tree::{CompleteBinaryTreeIndex, TreeIndex},
+    utils::{ct_eq_each, IndexWord},
     Address, BucketSize, OramBlock, OramError, StashSize,
 };
+use core::marker::PhantomData;
 
+use alloc::{format, string::ToString, vec, vec::Vec};
 use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
 const STASH_GROWTH_INCREMENT: usize = 10;
 
-#[derive(Debug)]
 /// A fixed-size, obliviously accessed Path ORAM stash data structure implemented using oblivious sorting.
-pub struct ObliviousStash<V: OramBlock> {
-    blocks: Vec<PathOramBlock<V>>,
+/// Blocks are assigned to buckets during eviction by the strategy `E`.
+///
+/// The stash stores block metadata (addresses and positions) and block values in parallel arrays,
+/// so that eviction decisions and the scan for a requested address never touch (possibly large) values.
+/// The `i`th stash slot holds the block `(values[i], addresses[i], positions[i])`,
+/// whose metadata is stored as `I`s.
+pub struct ObliviousStash<
+    V: OramBlock,
+    E: EvictionStrategy = GreedyEviction,
+    I: IndexWord = TreeIndex,
+> {
+    addresses: Vec<I>,
+    positions: Vec<I>,
+    values: Vec<V>,
+    // Scratch space for the result of comparing every address to the requested one.
+    address_matches: Vec<u8>,
     path_size: StashSize,
+    eviction_strategy: PhantomData<E>,
+    occupancy_observer: Option<fn(StashSize)>,
+    // The memory budget of the ORAM owning this stash, if any, and the bytes the ORAM holds outside this stash.
+    memory_budget: Option<(u64, u64)>,
+    // The number of tree blocks this stash has read and written, to check `PathOram::expected_physical_accesses_per_op`.
+    #[cfg(test)]
+    pub(crate) physical_reads: u64,
+    #[cfg(test)]
+    pub(crate) physical_writes: u64,
+    // If set, the tree buckets this stash has read (`false`) and written (`true`), in order, for golden-trace tests.
+    #[cfg(test)]
+    pub(crate) bucket_trace: Option<Vec<(TreeIndex, bool)>>,
 }
 
-impl<V: OramBlock> ObliviousStash<V> {
-    fn len(&self) -> usize {
-        self.blocks.len()
+// The stashed blocks are secret, so only the size of the stash is printed.
+impl<V: OramBlock, E: EvictionStrategy, I: IndexWord> core::fmt::Debug for ObliviousStash<V, E, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ObliviousStash")
+            .field("path_size", &self.path_size)
+            .field("len", &self.len())
+            .field("memory_budget", &self.memory_budget)
+            .finish_non_exhaustive()
     }
 }
 
-impl<V: OramBlock> ObliviousStash<V> {
+impl<V: OramBlock, E: EvictionStrategy, I: IndexWord> ObliviousStash<V, E, I> {
     pub fn new(path_size: StashSize, overflow_size: StashSize) -> Result<Self, OramError> {
         let num_stash_blocks: usize = (path_size + overflow_size).try_into()?;
 
-        Ok(Self {
-            blocks: vec![PathOramBlock::<V>::dummy(); num_stash_blocks],
+        let mut result = Self {
+            addresses: Vec::new(),
+            positions: Vec::new(),
+            values: Vec::new(),
+            address_matches: Vec::new(),
             path_size,
-        })
+            eviction_strategy: PhantomData,
+            occupancy_observer: None,
+            memory_budget: None,
+            #[cfg(test)]
+            physical_reads: 0,
+            #[cfg(test)]
+            physical_writes: 0,
+            #[cfg(test)]
+            bucket_trace: None,
+        };
+        result.resize(num_stash_blocks);
+        Ok(result)
+    }
+
+    /// Registers `observer` to be called after each eviction with the number of real blocks left in the stash overflow.
+    /// Without an observer (the default), this count is not computed.
+    pub fn set_occupancy_observer(&mut self, observer: fn(StashSize)) {
+        self.occupancy_observer = Some(observer);
+    }
+
+    /// Limits the stash to `budget - other_bytes` bytes of memory, so that growing beyond that fails
+    /// with a `MemoryBudgetExceeded` error reporting a total of `other_bytes` plus the size of the grown stash.
+    pub fn set_memory_budget(&mut self, budget: u64, other_bytes: u64) {
+        self.memory_budget = Some((budget, other_bytes));
+    }
+
+    /// Returns the number of bytes of memory that a stash of `len` slots holds.
+    pub fn bytes_for_len(len: u64) -> Result<u64, OramError> {
+        let slot_bytes = core::mem::size_of::<V>() + 2 * core::mem::size_of::<I>() + 1;
+        Ok(len * u64::try_from(slot_bytes)?)
+    }
+
+    /// Returns the number of bytes of memory that the stash holds.
+    pub fn memory_footprint(&self) -> Result<u64, OramError> {
+        Self::bytes_for_len(self.len().try_into()?)
+    }
+
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    // Grows the stash to `new_len` slots, unless that would exceed the memory budget.
+    fn grow(&mut self, new_len: usize) -> Result<(), OramError> {
+        if let Some((budget, other_bytes)) = self.memory_budget {
+            let required = other_bytes + Self::bytes_for_len(new_len.try_into()?)?;
+            if required > budget {
+                return Err(OramError::MemoryBudgetExceeded { required, budget });
+            }
+        }
+        self.resize(new_len);
+        Ok(())
+    }
+
+    // Resizes the stash to `new_len` slots, filling any new slots with dummy blocks.
+    fn resize(&mut self, new_len: usize) {
+        let dummy = PathOramBlock::<V, I>::dummy();
+        self.addresses.resize(new_len, dummy.address);
+        self.positions.resize(new_len, dummy.position);
+        self.values.resize(new_len, dummy.value);
+        self.address_matches.resize(new_len, 0);
+    }
+
+    fn block(&self, index: usize) -> PathOramBlock<V, I> {
+        PathOramBlock::new(
+            self.values[index],
+            self.addresses[index],
+            self.positions[index],
+        )
+    }
+
+    fn set_block(&mut self, index: usize, block: &PathOramBlock<V, I>) {
+        self.values[index] = block.value;
+        self.addresses[index] = block.address;
+        self.positions[index] = block.position;
+    }
+
+    // Compares every stash address to `address`, leaving the results in `self.address_matches`.
+    fn match_address(&mut self, address: I) {
+        ct_eq_each(&self.addresses, address, &mut self.address_matches);
+    }
+
+    // Returns whether the block in slot `index` is a real block whose address matched that passed to `match_address`.
+    // Checking the position too means that even an address equal to the dummy address never matches a dummy block.
+    fn is_match(&self, index: usize) -> Choice {
+        Choice::from(self.address_matches[index])
+            & !PathOramBlock::<V, I>::ct_position_is_dummy(&self.positions[index])
     }
 
     pub fn write_to_path<const Z: BucketSize>(
         &mut self,
-        physical_memory: &mut [Bucket<V, Z>],
+        physical_memory: &mut [Bucket<V, Z, I>],
         position: TreeIndex,
     ) -> Result<(), OramError> {
+        self.sort_for_eviction::<Z>(position)?;
+
+        // Write the first Z * height blocks into slots in the tree
         let height = position.ct_depth();
-        let mut level_assignments = vec![TreeIndex::MAX; self.len()];
-        let mut level_counts = vec![0; usize::try_from(height)? + 1];
+        for depth in 0..=height {
+            let node = position.ct_node_on_path(depth, height);
+            let bucket_to_write = &mut physical_memory[usize::try_from(node)?];
+            for slot_number in 0..Z {
+                let stash_index = (usize::try_from(depth)?) * Z + slot_number;
 
-        for (i, block) in self.blocks.iter().enumerate() {
-            // If `block` is a dummy, the rest of this loop iteration will be a no-op, and the values don't matter.
-            let block_is_dummy = block.ct_is_dummy();
+                bucket_to_write.blocks[slot_number] = self.block(stash_index);
+            }
+            #[cfg(test)]
+            {
+                self.physical_writes += u64::try_from(Z)?;
+                self.trace_bucket(node, true);
+            }
+        }
 
-            // Set up valid but meaningless input to the computation in case `block` is a dummy.
-            let an_arbitrary_leaf: TreeIndex = 1 << height;
-            let block_position =
-                TreeIndex::conditional_select(&block.position, &an_arbitrary_leaf, block_is_dummy);
+        Ok(())
+    }
 
-            // Assign the block to a bucket or to the overflow.
-            let mut assigned = Choice::from(0);
-            // Obliviously scan through the buckets from leaf to root,
-            // assigning the block to the first empty bucket satisfying the invariant.
-            for (level, count) in level_counts.iter_mut().enumerate().rev() {
-                let level_bucket_full: Choice = count.ct_eq(&(u64::try_from(Z)?));
+    /// As `write_to_path`, but writes into `path_buckets`, the buckets of the path to `position` ordered from root to leaf,
+    /// rather than into the tree. This supports callers whose tree does not store buckets as `Bucket<V, Z, I>`.
+    pub fn write_to_path_buckets<const Z: BucketSize>(
+        &mut self,
+        path_buckets: &mut [Bucket<V, Z, I>],
+        position: TreeIndex,
+    ) -> Result<(), OramError> {
+        self.sort_for_eviction::<Z>(position)?;
 
-                let level_u64 = u64::try_from(level)?;
-                let level_satisfies_invariant = block_position
-                    .ct_node_on_path(level_u64, height)
-                    .ct_eq(&position.ct_node_on_path(level_u64, height));
+        for (depth, bucket_to_write) in path_buckets.iter_mut().enumerate() {
+            for slot_number in 0..Z {
+                bucket_to_write.blocks[slot_number] = self.block(depth * Z + slot_number);
+            }
+        }
 
-                let should_assign = level_satisfies_invariant
-                    & (!level_bucket_full)
-                    & (!block_is_dummy)
-                    & (!assigned);
-                assigned |= should_assign;
+        Ok(())
+    }
 
-                let level_count_incremented = *count + 1;
-                count.conditional_assign(&level_count_incremented, should_assign);
-                level_assignments[i].conditional_assign(&level_u64, should_assign);
+    /// Obliviously sorts the stash so that the blocks to be written to the bucket at depth `d` of the path to `position`
+    /// occupy slots `d * Z..(d + 1) * Z`, growing the stash if it has overflowed.
+    pub fn sort_for_eviction<const Z: BucketSize>(
+        &mut self,
+        position: TreeIndex,
+    ) -> Result<(), OramError> {
+        let level_assignments = self.assign_levels::<Z>(position)?;
+        self.sort_by_levels(level_assignments);
+
+        if let Some(observer) = self.occupancy_observer {
+            observer(self.occupancy());
+        }
+
+        Ok(())
+    }
+
+    // Assigns every stash block to a level of the path to `position`, or to the overflow,
+    // filling each level with exactly Z blocks (real or dummy) and growing the stash if there are too few dummies to do so.
+    fn assign_levels<const Z: BucketSize>(
+        &mut self,
+        position: TreeIndex,
+    ) -> Result<Vec<TreeIndex>, OramError> {
+        let height = position.ct_depth();
+        let mut level_assignments = E::assign_levels(&self.positions, position, Z)?;
+
+        // Count the blocks assigned to each level.
+        let mut level_counts = vec![0; usize::try_from(height)? + 1];
+        for assignment in &level_assignments {
+            for (level, count) in level_counts.iter_mut().enumerate() {
+                let assigned_here = assignment.ct_eq(&u64::try_from(level)?);
+                count.conditional_assign(&(*count + 1), assigned_here);
             }
-            // If the block was not able to be assigned to any bucket, assign it to the overflow.
-            level_assignments[i].conditional_assign(&(TreeIndex::MAX - 1), !assigned);
         }
 
         // Assign dummy blocks to the remaining non-full buckets until all buckets are full.
@@ -94,13 +252,13 @@ impl<V: OramBlock> ObliviousStash<V> {
         // If the stash is set large enough when the ORAM is initialized,
         // stash overflow will occur only with negligible probability.
         while exists_unfilled_levels.into() {
-            for (i, block) in self
-                .blocks
+            for (i, block_position) in self
+                .positions
                 .iter()
                 .enumerate()
                 .skip(first_unassigned_block_index)
             {
-                let block_free = block.ct_is_dummy();
+                let block_free = PathOramBlock::<V, I>::ct_position_is_dummy(block_position);
 
                 let mut assigned: Choice = 0.into();
                 for (level, count) in level_counts.iter_mut().enumerate() {
@@ -120,38 +278,34 @@ impl<V: OramBlock> ObliviousStash<V> {
             }
 
             if exists_unfilled_levels.into() {
-                first_unassigned_block_index = self.blocks.len();
+                first_unassigned_block_index = self.len();
 
-                self.blocks.resize(
-                    self.blocks.len() + STASH_GROWTH_INCREMENT,
-                    PathOramBlock::<V>::dummy(),
-                );
+                self.grow(self.len() + STASH_GROWTH_INCREMENT)?;
                 level_assignments.resize(
                     level_assignments.len() + STASH_GROWTH_INCREMENT,
-                    TreeIndex::MAX,
+                    UNASSIGNED_LEVEL,
                 );
 
-                log::warn!(
+                log_warn!(
                     "Stash overflow occurred. Stash resized to {} blocks.",
-                    self.blocks.len()
+                    self.len()
                 );
             }
         }
 
-        bitonic_sort_by_keys(&mut self.blocks, &mut level_assignments);
-
-        // Write the first Z * height blocks into slots in the tree
-        for depth in 0..=height {
-            let bucket_to_write =
-                &mut physical_memory[usize::try_from(position.ct_node_on_path(depth, height))?];
-            for slot_number in 0..Z {
-                let stash_index = (usize::try_from(depth)?) * Z + slot_number;
-
-                bucket_to_write.blocks[slot_number] = self.blocks[stash_index];
-            }
-        }
+        Ok(level_assignments)
+    }
 
-        Ok(())
+    // Sorts the stash by `level_assignments`, comparing only the assignments
+    // and applying the same exchanges to the metadata and value arrays.
+    fn sort_by_levels(&mut self, mut level_assignments: Vec<TreeIndex>) {
+        let (addresses, positions, values) =
+            (&mut self.addresses, &mut self.positions, &mut self.values);
+        bitonic_sort_by_keys_with(&mut level_assignments, &mut |i, j, do_swap| {
+            conditional_swap_at(addresses, i, j, do_swap);
+            conditional_swap_at(positions, i, j, do_swap);
+            conditional_swap_at(values, i, j, do_swap);
+        });
     }
 
     pub fn access<F: Fn(&V) -> V>(
@@ -160,55 +314,838 @@ impl<V: OramBlock> ObliviousStash<V> {
         new_position: TreeIndex,
         value_callback: F,
     ) -> Result<V, OramError> {
+        let address = I::from_u64(address)?;
+        let new_position = I::from_u64(new_position)?;
         let mut result: V = V::default();
 
-        for block in &mut self.blocks {
-            let is_requested_index = block.address.ct_eq(&address);
+        self.match_address(address);
+        for i in 0..self.len() {
+            let is_requested_index = self.is_match(i);
 
             // Read current value of target block into `result`.
-            result.conditional_assign(&block.value, is_requested_index);
+            result.conditional_assign(&self.values[i], is_requested_index);
 
             // Write new position into target block.
-            block
-                .position
-                .conditional_assign(&new_position, is_requested_index);
+            self.positions[i].conditional_assign(&new_position, is_requested_index);
 
             // If a write, write new value into target block.
             let value_to_write = value_callback(&result);
 
-            block
-                .value
-                .conditional_assign(&value_to_write, is_requested_index);
+            self.values[i].conditional_assign(&value_to_write, is_requested_index);
         }
         Ok(result)
     }
 
-    #[cfg(test)]
+    /// As `access` with a callback returning its argument, but reads the value of the block with address `address`
+    /// directly into `out` rather than into a temporary. Leaves `out` unchanged if there is no such block.
+    pub fn read_into(
+        &mut self,
+        address: Address,
+        new_position: TreeIndex,
+        out: &mut V,
+    ) -> Result<(), OramError> {
+        let address = I::from_u64(address)?;
+        let new_position = I::from_u64(new_position)?;
+
+        self.match_address(address);
+        for i in 0..self.len() {
+            let is_requested_index = self.is_match(i);
+            out.conditional_assign(&self.values[i], is_requested_index);
+            self.positions[i].conditional_assign(&new_position, is_requested_index);
+        }
+        Ok(())
+    }
+
+    /// As `access` with a callback returning `new_value`, but without reading the block's previous value.
+    pub fn write_from(
+        &mut self,
+        address: Address,
+        new_position: TreeIndex,
+        new_value: &V,
+    ) -> Result<(), OramError> {
+        let address = I::from_u64(address)?;
+        let new_position = I::from_u64(new_position)?;
+
+        self.match_address(address);
+        for i in 0..self.len() {
+            let is_requested_index = self.is_match(i);
+            self.values[i].conditional_assign(new_value, is_requested_index);
+            self.positions[i].conditional_assign(&new_position, is_requested_index);
+        }
+        Ok(())
+    }
+
+    /// Copies the value of the block with address `address` into a local, passes it to `f` to read and modify,
+    /// and obliviously writes it back, also setting the block's position to `new_position`.
+    /// `f` is called exactly once (with a default value if there is no such block), and its result returned.
+    pub fn modify<T, F: FnOnce(&mut V) -> T>(
+        &mut self,
+        address: Address,
+        new_position: TreeIndex,
+        f: F,
+    ) -> Result<T, OramError> {
+        let address = I::from_u64(address)?;
+        let new_position = I::from_u64(new_position)?;
+
+        self.match_address(address);
+        let mut value = V::default();
+        for i in 0..self.len() {
+            value.conditional_assign(&self.values[i], self.is_match(i));
+        }
+        let result = f(&mut value);
+        for i in 0..self.len() {
+            let is_requested_index = self.is_match(i);
+            self.values[i].conditional_assign(&value, is_requested_index);
+            self.positions[i].conditional_assign(&new_position, is_requested_index);
+        }
+        Ok(result)
+    }
+
+    /// Obliviously moves the block with address `address` from the path to `position` (if present there) into the stash,
+    /// leaving the rest of the path in place. Every slot on the path is read and rewritten.
+    /// The stash grows by one slot, which a later `evict_path` reclaims.
+    pub fn take_block<const Z: BucketSize>(
+        &mut self,
+        physical_memory: &mut [Bucket<V, Z, I>],
+        position: TreeIndex,
+        address: Address,
+    ) -> Result<(), OramError> {
+        let address = I::from_u64(address)?;
+        let dummy = PathOramBlock::<V, I>::dummy();
+        let mut taken = dummy;
+
+        let height = position.ct_depth();
+        let mut integrity_check = IntegrityCheck::new();
+        for depth in 0..=height {
+            let node = position.ct_node_on_path(depth, height);
+            let bucket = &mut physical_memory[usize::try_from(node)?];
+            integrity_check.check(bucket, node);
+            for block in bucket.blocks.iter_mut() {
+                let is_target = block.address.ct_eq(&address) & !block.ct_is_dummy();
+                taken.conditional_assign(block, is_target);
+                block.conditional_assign(&dummy, is_target);
+            }
+            #[cfg(test)]
+            {
+                self.physical_reads += u64::try_from(Z)?;
+                self.physical_writes += u64::try_from(Z)?;
+                self.trace_bucket(node, false);
+                self.trace_bucket(node, true);
+            }
+        }
+        integrity_check.result()?;
+
+        self.push_block(&taken)
+    }
+
+    /// Appends `block` (which may be a dummy) to the stash in a new slot, which a later eviction reclaims.
+    pub fn push_block(&mut self, block: &PathOramBlock<V, I>) -> Result<(), OramError> {
+        let index = self.len();
+        self.grow(index + 1)?;
+        self.set_block(index, block);
+        Ok(())
+    }
+
+    /// Evicts blocks from the stash into the path to `position`, which need not be a path previously read.
+    /// The stash then shrinks by one slot, unless it holds too many real blocks to do so.
+    pub fn evict_path<const Z: BucketSize>(
+        &mut self,
+        physical_memory: &mut [Bucket<V, Z, I>],
+        position: TreeIndex,
+    ) -> Result<(), OramError> {
+        self.read_from_path(physical_memory, position)?;
+        self.write_to_path(physical_memory, position)?;
+        self.release_slots(1)
+    }
+
+    /// Completes an eviction performed with `write_to_path` or `write_to_path_buckets`
+    /// by clearing the path region of the stash and then shrinking the stash by `count` slots,
+    /// unless it holds too many real blocks to do so.
+    pub fn release_slots(&mut self, count: usize) -> Result<(), OramError> {
+        let path_size = usize::try_from(self.path_size)?;
+        let target_len = self.len().saturating_sub(count);
+
+        // After `write_to_path`, the first `path_size` slots hold copies of the blocks just written to the tree.
+        // Clear them so that stale copies are never mistaken for stash-resident blocks.
+        for i in 0..path_size {
+            self.set_block(i, &PathOramBlock::dummy());
+        }
+
+        // Real blocks remaining in the stash have been sorted to directly follow the first `path_size` slots,
+        // so truncating the stash discards only dummy blocks.
+        // As with stash growth, the new length reveals only whether the stash has overflowed.
+        let required_len = path_size + usize::try_from(self.occupancy())?;
+        self.resize(target_len.max(required_len));
+        Ok(())
+    }
+
+    /// Returns every block in the stash overflow, including dummies, in slot order.
+    /// The other `path_size` slots hold blocks only during an access, and between accesses may hold stale copies of blocks in the tree.
+    pub fn overflow_blocks(&self) -> impl Iterator<Item = PathOramBlock<V, I>> + '_ {
+        let path_size = usize::try_from(self.path_size).unwrap();
+        (path_size..self.len()).map(|i| self.block(i))
+    }
+
+    /// Returns every block in the stash, including dummies, in slot order.
+    pub fn blocks(&self) -> Vec<PathOramBlock<V, I>> {
+        (0..self.len()).map(|i| self.block(i)).collect()
+    }
+
+    /// Returns a stash whose first `path_size` slots are its path region, holding `blocks` (as returned by [`Self::blocks`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if there are fewer than `path_size` blocks.
+    pub fn from_blocks(
+        path_size: StashSize,
+        blocks: &[PathOramBlock<V, I>],
+    ) -> Result<Self, OramError> {
+        if u64::try_from(blocks.len())? < path_size {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: format!("Stash length (with path size {path_size})"),
+                parameter_value: blocks.len().to_string(),
+            });
+        }
+        let mut result = Self::new(path_size, 0)?;
+        result.resize(blocks.len());
+        for (i, block) in blocks.iter().enumerate() {
+            result.set_block(i, block);
+        }
+        Ok(result)
+    }
+
+    /// Replaces the contents of the stash with `blocks` (as returned by [`Self::blocks`]), resizing it to match.
+    pub fn restore_blocks(&mut self, blocks: &[PathOramBlock<V, I>]) {
+        self.resize(blocks.len());
+        for (i, block) in blocks.iter().enumerate() {
+            self.set_block(i, block);
+        }
+    }
+
+    /// Replaces every block in the stash with a dummy block. Not oblivious; for diagnostics only.
+    #[cfg(any(feature = "diagnostics", test))]
+    pub fn clear(&mut self) {
+        for i in 0..self.len() {
+            self.set_block(i, &PathOramBlock::dummy());
+        }
+    }
+
+    /// Places `block` in the first empty slot of the stash overflow, growing the stash if there is none.
+    /// Not oblivious; for diagnostics only.
+    #[cfg(any(feature = "diagnostics", test))]
+    pub fn insert_block(&mut self, block: &PathOramBlock<V, I>) -> Result<(), OramError> {
+        let path_size = usize::try_from(self.path_size)?;
+        match (path_size..self.len()).find(|i| self.positions[*i] == I::ZERO) {
+            Some(i) => self.set_block(i, block),
+            None => self.push_block(block)?,
+        }
+        Ok(())
+    }
+
     pub fn occupancy(&self) -> StashSize {
         let mut result = 0;
-        for i in self.path_size.try_into().unwrap()..(self.blocks.len()) {
-            if !self.blocks[i].is_dummy() {
-                result += 1;
-            }
+        for position in self
+            .positions
+            .iter()
+            .skip(self.path_size.try_into().unwrap())
+        {
+            let is_real = !PathOramBlock::<V, I>::ct_position_is_dummy(position);
+            result.conditional_assign(&(result + 1), is_real);
         }
         result
     }
 
     pub fn read_from_path<const Z: crate::BucketSize>(
         &mut self,
-        physical_memory: &mut [Bucket<V, Z>],
+        physical_memory: &mut [Bucket<V, Z, I>],
+        position: TreeIndex,
+    ) -> Result<(), OramError> {
+        self.read_path_into(physical_memory, position, 0)
+    }
+
+    /// As `read_from_path`, but reads from `path_buckets`, the buckets of a path ordered from root to leaf.
+    pub fn read_from_path_buckets<const Z: BucketSize>(
+        &mut self,
+        path_buckets: &[Bucket<V, Z, I>],
+    ) -> Result<(), OramError> {
+        for (depth, bucket) in path_buckets.iter().enumerate() {
+            for slot_index in 0..Z {
+                self.set_block(Z * depth + slot_index, &bucket.blocks[slot_index]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies the blocks of `bucket`, which is node `node` of the tree at depth `depth` of a path,
+    /// into the stash slots that `read_from_path` would, verifying it as `read_from_path` does.
+    pub fn read_bucket<const Z: BucketSize>(
+        &mut self,
+        bucket: &Bucket<V, Z, I>,
+        node: TreeIndex,
+        depth: usize,
+    ) -> Result<(), OramError> {
+        let mut integrity_check = IntegrityCheck::new();
+        integrity_check.check(bucket, node);
+        for slot_index in 0..Z {
+            self.set_block(Z * depth + slot_index, &bucket.blocks[slot_index]);
+        }
+        #[cfg(test)]
+        {
+            self.physical_reads += u64::try_from(Z)?;
+            self.trace_bucket(node, false);
+        }
+        integrity_check.result()
+    }
+
+    /// Copies into `bucket`, which is node `node` of the tree at depth `depth` of the path to `position`,
+    /// the blocks that `write_to_path(position)` would, assuming that the stash has already been sorted by `sort_for_eviction(position)`.
+    pub fn write_bucket<const Z: BucketSize>(
+        &mut self,
+        bucket: &mut Bucket<V, Z, I>,
+        // Only recorded in the bucket trace of tests.
+        #[cfg_attr(not(test), allow(unused_variables))] node: TreeIndex,
+        depth: usize,
+    ) -> Result<(), OramError> {
+        for slot_number in 0..Z {
+            bucket.blocks[slot_number] = self.block(depth * Z + slot_number);
+        }
+        #[cfg(test)]
+        {
+            self.physical_writes += u64::try_from(Z)?;
+            self.trace_bucket(node, true);
+        }
+        Ok(())
+    }
+
+    // Copies the blocks on the path to `position` into the `path_size` stash slots beginning at `start`.
+    // Each bucket is borrowed in place rather than copied out of the tree, so that each block is copied only once,
+    // directly into the stash; for large blocks, a bucket is too large to copy cheaply.
+    // With the `checksummed` feature, returns a `BucketIntegrityError` if any bucket on the path fails verification.
+    fn read_path_into<const Z: crate::BucketSize>(
+        &mut self,
+        physical_memory: &[Bucket<V, Z, I>],
         position: TreeIndex,
+        start: usize,
     ) -> Result<(), OramError> {
         let height = position.ct_depth();
+        let mut integrity_check = IntegrityCheck::new();
 
         for i in (0..(self.path_size / u64::try_from(Z)?)).rev() {
             let bucket_index = position.ct_node_on_path(i, height);
-            let bucket = physical_memory[usize::try_from(bucket_index)?];
+            let bucket = &physical_memory[usize::try_from(bucket_index)?];
+            integrity_check.check(bucket, bucket_index);
             for slot_index in 0..Z {
-                self.blocks[Z * (usize::try_from(i)?) + slot_index] = bucket.blocks[slot_index];
+                self.set_block(
+                    start + Z * (usize::try_from(i)?) + slot_index,
+                    &bucket.blocks[slot_index],
+                );
+            }
+            #[cfg(test)]
+            {
+                self.physical_reads += u64::try_from(Z)?;
+                self.trace_bucket(bucket_index, false);
             }
         }
 
-        Ok(())
+        integrity_check.result()
+    }
+
+    #[cfg(test)]
+    fn trace_bucket(&mut self, node: TreeIndex, is_write: bool) {
+        if let Some(trace) = &mut self.bucket_trace {
+            trace.push((node, is_write));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObliviousStash, STASH_GROWTH_INCREMENT};
+    use crate::{
+        bucket::{Bucket, PathOramBlock},
+        leaf_sampler::ScriptedLeafSampler,
+        oblivious::bitonic_sort_by_keys,
+        tree::{CompleteBinaryTreeIndex, TreeIndex},
+        Address, BucketSize, Oram, OramBlock, OramError, PathOram, StashSize,
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    use std::{cell::Cell, vec, vec::Vec};
+
+    type TestOram = PathOram<u64, 4, 8>;
+
+    // A capacity of 64 gives a tree of height 5, with leaves 32..64.
+    // The scripted sampler places addresses `2i` and `2i + 1` in leaf `32 + i` initially.
+    const CAPACITY: Address = 64;
+    const HEIGHT: u64 = 5;
+    const LEFTMOST_LEAF: TreeIndex = 1 << HEIGHT;
+    const RIGHTMOST_LEAF: TreeIndex = (2 << HEIGHT) - 1;
+
+    thread_local! {
+        static MAX_OCCUPANCY: Cell<StashSize> = const { Cell::new(0) };
+    }
+
+    fn monitor_occupancy(occupancy: StashSize) {
+        MAX_OCCUPANCY.with(|max| max.set(max.get().max(occupancy)));
+    }
+
+    // Returns an ORAM whose accessed blocks are assigned `leaves` in order, with a large enough overflow
+    // that forced collisions spill into it without growing the stash.
+    fn scripted_oram(leaves: Vec<TreeIndex>) -> TestOram {
+        scripted_oram_with_overflow(leaves, 64)
+    }
+
+    // As `scripted_oram`, but with an overflow of `overflow_size`, which forced collisions may outgrow.
+    fn scripted_oram_with_overflow(leaves: Vec<TreeIndex>, overflow_size: StashSize) -> TestOram {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_leaf_sampler(
+            CAPACITY,
+            ScriptedLeafSampler::new(leaves),
+            &mut rng,
+            overflow_size,
+            1,
+        )
+        .unwrap();
+        oram.set_occupancy_observer(monitor_occupancy);
+        oram
+    }
+
+    // Returns the number of times the stash of `oram`, created with an overflow of `overflow_size`, has grown.
+    fn growth_events(oram: &TestOram, overflow_size: StashSize) -> usize {
+        let grown_by = oram.stash.len() - 24 - overflow_size as usize;
+        assert_eq!(grown_by % STASH_GROWTH_INCREMENT, 0);
+        grown_by / STASH_GROWTH_INCREMENT
+    }
+
+    // Returns the fewest growth increments that make an overflow of `overflow_size` hold `occupancy` blocks.
+    fn increments_needed(occupancy: StashSize, overflow_size: StashSize) -> usize {
+        (occupancy.saturating_sub(overflow_size) as usize).div_ceil(STASH_GROWTH_INCREMENT)
+    }
+
+    // Returns leaves sending the first `collisions` accessed blocks to the leftmost leaf, and the next `uniform_accesses` anywhere.
+    fn colliding_leaves(
+        collisions: usize,
+        uniform_accesses: usize,
+        rng: &mut StdRng,
+    ) -> Vec<TreeIndex> {
+        let mut leaves = vec![LEFTMOST_LEAF; collisions];
+        leaves.extend((0..uniform_accesses).map(|_| rng.gen_range(LEFTMOST_LEAF..=RIGHTMOST_LEAF)));
+        leaves
+    }
+
+    // Returns the addresses and positions of the real blocks in bucket `node`.
+    fn bucket_contents(oram: &TestOram, node: TreeIndex) -> Vec<(Address, TreeIndex)> {
+        oram.physical_memory[node as usize]
+            .blocks
+            .iter()
+            .filter(|block| !bool::from(block.ct_is_dummy()))
+            .map(|block| (block.address, block.position))
+            .collect()
+    }
+
+    #[test]
+    fn same_leaf_spill_is_absorbed_and_drained() {
+        let collisions = 50;
+        let uniform_accesses = 3000;
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut leaves = vec![LEFTMOST_LEAF; collisions];
+        leaves.extend((0..uniform_accesses).map(|_| rng.gen_range(LEFTMOST_LEAF..=RIGHTMOST_LEAF)));
+        let mut oram = scripted_oram(leaves);
+        MAX_OCCUPANCY.with(|max| max.set(0));
+
+        let mut shadow = vec![0; CAPACITY as usize];
+        for address in 0..collisions as Address {
+            oram.write(address, address + 100, &mut rng).unwrap();
+            shadow[address as usize] = address + 100;
+        }
+        // The path to the leftmost leaf has 24 slots, so at least 26 of the colliding blocks are in the stash,
+        // which absorbs them without growing.
+        let spill = oram.stash_occupancy();
+        assert!((26..=50).contains(&spill));
+        MAX_OCCUPANCY.with(|max| assert!(max.get() <= 50));
+        assert_eq!(oram.stash.len(), 24 + 64);
+
+        for _ in 0..uniform_accesses {
+            let address = rng.gen_range(0..CAPACITY);
+            assert_eq!(
+                oram.read(address, &mut rng).unwrap(),
+                shadow[address as usize]
+            );
+        }
+        assert!(oram.stash_occupancy() < 10);
+        assert_eq!(oram.stash.len(), 24 + 64);
+        assert!(oram.audit().unwrap().is_consistent());
+    }
+
+    #[test]
+    fn forced_overflow_grows_the_stash_without_losing_blocks() {
+        let overflow_size = 2;
+        let collisions = 50;
+        let uniform_accesses = 2000;
+        let mut rng = StdRng::seed_from_u64(4);
+        let leaves = colliding_leaves(collisions, uniform_accesses, &mut rng);
+        let mut oram = scripted_oram_with_overflow(leaves, overflow_size);
+        MAX_OCCUPANCY.with(|max| max.set(0));
+
+        // The stash grows exactly when, and by as many increments as, the blocks left over by an eviction require,
+        // and the grown stash keeps every block where an access will find it.
+        let check_growth = |oram: &TestOram| {
+            let max_occupancy = MAX_OCCUPANCY.with(Cell::get);
+            assert_eq!(
+                growth_events(oram, overflow_size),
+                increments_needed(max_occupancy, overflow_size)
+            );
+            assert!(oram.stash_occupancy() as usize <= oram.stash.len() - 24);
+            assert!(oram.audit().unwrap().is_consistent());
+        };
+
+        let mut shadow = vec![0; CAPACITY as usize];
+        for address in 0..collisions as Address {
+            oram.write(address, address + 100, &mut rng).unwrap();
+            shadow[address as usize] = address + 100;
+            check_growth(&oram);
+        }
+        // The path to the leftmost leaf has 24 slots, so at least 26 of the colliding blocks are left in the stash.
+        assert!(growth_events(&oram, overflow_size) >= increments_needed(26, overflow_size));
+
+        // Uniform accesses read back every value and drain the stash, which never shrinks.
+        for i in 0..uniform_accesses {
+            let address = if i < collisions {
+                i as Address
+            } else {
+                rng.gen_range(0..CAPACITY)
+            };
+            assert_eq!(
+                oram.read(address, &mut rng).unwrap(),
+                shadow[address as usize]
+            );
+            check_growth(&oram);
+        }
+        assert!(oram.stash_occupancy() < 10);
+    }
+
+    #[test]
+    fn growth_beyond_the_memory_budget_fails_and_recovers() {
+        let overflow_size = 2;
+        let collisions = 50;
+        let mut rng = StdRng::seed_from_u64(5);
+        let leaves = colliding_leaves(collisions, 2 * CAPACITY as usize, &mut rng);
+        let mut oram = scripted_oram_with_overflow(leaves, overflow_size);
+        MAX_OCCUPANCY.with(|max| max.set(0));
+
+        // Allow exactly one growth increment.
+        let footprint = oram.memory_footprint().unwrap();
+        let increment_bytes =
+            ObliviousStash::<u64>::bytes_for_len(STASH_GROWTH_INCREMENT as u64).unwrap();
+        oram.set_memory_budget(footprint + increment_bytes).unwrap();
+
+        let mut shadow = vec![0; CAPACITY as usize];
+        let mut failed_write = None;
+        for address in 0..collisions as Address {
+            match oram.write(address, address + 100, &mut rng) {
+                Ok(_) => shadow[address as usize] = address + 100,
+                Err(error) => {
+                    assert!(matches!(error, OramError::MemoryBudgetExceeded { .. }));
+                    failed_write = Some(address);
+                    break;
+                }
+            }
+        }
+        // The second growth was refused, once the leftover blocks no longer fit in the once-grown overflow.
+        let failed_address = failed_write.unwrap();
+        assert_eq!(growth_events(&oram, overflow_size), 1);
+        let max_occupancy = MAX_OCCUPANCY.with(Cell::get);
+        assert_eq!(increments_needed(max_occupancy, overflow_size), 1);
+        assert!(oram.is_poisoned());
+        assert!(matches!(oram.read(0, &mut rng), Err(OramError::Poisoned)));
+
+        // Recovery loses no block, and the failed write either took effect or did not.
+        oram.set_memory_budget(u64::MAX).unwrap();
+        oram.try_recover(&mut rng).unwrap();
+        assert!(!oram.is_poisoned());
+        assert!(oram.audit().unwrap().is_consistent());
+        for (address, expected) in shadow.iter().enumerate() {
+            let value = oram.read(address as Address, &mut rng).unwrap();
+            if address as Address == failed_address {
+                assert!(value == *expected || value == failed_address + 100);
+            } else {
+                assert_eq!(value, *expected);
+            }
+        }
+    }
+
+    #[test]
+    fn alternating_extreme_leaves_meet_only_at_the_root() {
+        assert_eq!(
+            LEFTMOST_LEAF.ct_common_ancestor_of_two_leaves(&RIGHTMOST_LEAF),
+            1
+        );
+        let accesses = 200;
+        // Enough leaves for the writes and for reading back every address.
+        let leaves = (0..accesses + CAPACITY)
+            .map(|i| {
+                if i % 2 == 0 {
+                    LEFTMOST_LEAF
+                } else {
+                    RIGHTMOST_LEAF
+                }
+            })
+            .collect();
+        let mut oram = scripted_oram(leaves);
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let mut shadow = vec![0; CAPACITY as usize];
+        for i in 0..accesses {
+            let address = rng.gen_range(0..CAPACITY);
+            assert_eq!(
+                oram.write(address, i, &mut rng).unwrap(),
+                shadow[address as usize]
+            );
+            shadow[address as usize] = i;
+            // Every block is still on the path to its leaf, so no block was evicted below a common ancestor.
+            assert!(oram.audit().unwrap().is_consistent());
+        }
+        for (address, expected) in shadow.iter().enumerate() {
+            assert_eq!(oram.read(address as Address, &mut rng).unwrap(), *expected);
+        }
+        assert_eq!(oram.stash.len(), 24 + 64);
+    }
+
+    #[test]
+    fn blocks_reach_the_root_and_the_deepest_bucket() {
+        assert_eq!(RIGHTMOST_LEAF.ct_node_on_path(0, HEIGHT), 1);
+        assert_eq!(
+            RIGHTMOST_LEAF.ct_node_on_path(HEIGHT, HEIGHT),
+            RIGHTMOST_LEAF
+        );
+
+        let mut oram = scripted_oram(vec![RIGHTMOST_LEAF, LEFTMOST_LEAF]);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        // Address 10 starts in leaf 37, whose path meets the path to the rightmost leaf only at the root (depth 0).
+        oram.write(10, 7, &mut rng).unwrap();
+        assert_eq!(bucket_contents(&oram, 1), [(10, RIGHTMOST_LEAF)]);
+
+        // Address 62 starts in the rightmost leaf, so accessing it evicts along that path,
+        // and address 10 sinks to the deepest bucket (depth `HEIGHT`), while address 62 takes its place at the root.
+        oram.read(62, &mut rng).unwrap();
+        assert_eq!(bucket_contents(&oram, 1), [(62, LEFTMOST_LEAF)]);
+        assert!(bucket_contents(&oram, RIGHTMOST_LEAF).contains(&(10, RIGHTMOST_LEAF)));
+        assert_eq!(oram.stash_occupancy(), 0);
+    }
+
+    // A plain, non-oblivious statement of greedy eviction, the specification that `write_to_path` implements.
+    // Each bucket of the path to `path`, from the leaf up to the root, takes the first `z` blocks (in stash order)
+    // not yet placed whose own paths pass through it; the blocks left over stay in the stash.
+    // `blocks` holds the address and position of each real block in stash order.
+    // Returns the sorted addresses placed at each depth of the path, and the sorted addresses left over.
+    fn reference_eviction(
+        blocks: &[(Address, TreeIndex)],
+        path: TreeIndex,
+        z: usize,
+    ) -> (Vec<Vec<Address>>, Vec<Address>) {
+        let height = path.ct_depth();
+        let mut placed = vec![false; blocks.len()];
+        let mut buckets = vec![Vec::new(); height as usize + 1];
+        for depth in (0..=height).rev() {
+            let node = path.ct_node_on_path(depth, height);
+            for (i, (address, position)) in blocks.iter().enumerate() {
+                if buckets[depth as usize].len() == z {
+                    break;
+                }
+                if !placed[i] && position.ct_node_on_path(depth, height) == node {
+                    placed[i] = true;
+                    buckets[depth as usize].push(*address);
+                }
+            }
+            buckets[depth as usize].sort_unstable();
+        }
+        let mut left_over: Vec<Address> = blocks
+            .iter()
+            .zip(&placed)
+            .filter(|(_, placed)| !**placed)
+            .map(|((address, _), _)| *address)
+            .collect();
+        left_over.sort_unstable();
+        (buckets, left_over)
+    }
+
+    // Checks that `write_to_path` places the same blocks in each bucket, and leaves the same blocks in the stash,
+    // as `reference_eviction`, for random stashes holding from none to three paths' worth of real blocks.
+    fn check_eviction_matches_reference<const Z: BucketSize>(trials: usize, rng: &mut StdRng) {
+        for _ in 0..trials {
+            let height = rng.gen_range(0..=5);
+            let path = TreeIndex::random_leaf(height, rng).unwrap();
+            let path_size = Z * (height as usize + 1);
+
+            // Real blocks with random positions, scattered among dummies over the path region and the overflow.
+            let real_blocks = rng.gen_range(0..=3 * path_size);
+            let len = (path_size + rng.gen_range(0..=path_size)).max(real_blocks);
+            let mut slots = vec![PathOramBlock::<u64>::dummy(); len];
+            for address in 0..real_blocks as Address {
+                let position = TreeIndex::random_leaf(height, rng).unwrap();
+                slots[address as usize] = PathOramBlock::new(address + 1000, address, position);
+            }
+            slots.shuffle(rng);
+            let blocks: Vec<(Address, TreeIndex)> = slots
+                .iter()
+                .filter(|block| !bool::from(block.ct_is_dummy()))
+                .map(|block| (block.address, block.position))
+                .collect();
+
+            let mut stash =
+                ObliviousStash::<u64>::from_blocks(path_size as StashSize, &slots).unwrap();
+            let mut tree = vec![Bucket::<u64, Z>::default(); 2 << height];
+            stash.write_to_path(&mut tree, path).unwrap();
+
+            let placed: Vec<Vec<Address>> = (0..=height)
+                .map(|depth| {
+                    let bucket = &tree[path.ct_node_on_path(depth, height) as usize];
+                    let mut addresses: Vec<Address> = bucket
+                        .blocks
+                        .iter()
+                        .filter(|block| !bool::from(block.ct_is_dummy()))
+                        .inspect(|block| assert_eq!(block.value, block.address + 1000))
+                        .map(|block| block.address)
+                        .collect();
+                    addresses.sort_unstable();
+                    addresses
+                })
+                .collect();
+            let mut left_over: Vec<Address> = stash
+                .overflow_blocks()
+                .filter(|block| !bool::from(block.ct_is_dummy()))
+                .map(|block| block.address)
+                .collect();
+            left_over.sort_unstable();
+
+            assert_eq!(
+                (placed, left_over),
+                reference_eviction(&blocks, path, Z),
+                "evicting {blocks:?} along the path to {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn eviction_matches_reference() {
+        let mut rng = StdRng::seed_from_u64(6);
+        check_eviction_matches_reference::<1>(1000, &mut rng);
+        check_eviction_matches_reference::<2>(1000, &mut rng);
+        check_eviction_matches_reference::<3>(1000, &mut rng);
+        check_eviction_matches_reference::<4>(1000, &mut rng);
+    }
+
+    // Checks that sorting the stash for eviction leaves every slot bit-identical to sorting an array of whole blocks
+    // by the same level assignments, as the stash did before it kept metadata and values in parallel arrays,
+    // for random stashes holding from none to three paths' worth of real blocks.
+    fn check_parallel_sort_matches_block_sort<V: OramBlock, const Z: BucketSize>(
+        trials: usize,
+        value: fn(Address) -> V,
+        rng: &mut StdRng,
+    ) {
+        for _ in 0..trials {
+            let height = rng.gen_range(0..=5);
+            let path = TreeIndex::random_leaf(height, rng).unwrap();
+            let path_size = Z * (height as usize + 1);
+
+            let real_blocks = rng.gen_range(0..=3 * path_size);
+            let len = path_size + rng.gen_range(0..=path_size);
+            let mut slots = vec![PathOramBlock::<V>::dummy(); len.max(real_blocks)];
+            for address in 0..real_blocks as Address {
+                let position = TreeIndex::random_leaf(height, rng).unwrap();
+                slots[address as usize] = PathOramBlock::new(value(address), address, position);
+            }
+            slots.shuffle(rng);
+
+            let mut stash =
+                ObliviousStash::<V>::from_blocks(path_size as StashSize, &slots).unwrap();
+            let level_assignments = stash.assign_levels::<Z>(path).unwrap();
+            // Taken after assigning levels, which grows the stash if it has overflowed.
+            let mut expected = stash.blocks();
+            bitonic_sort_by_keys(&mut expected, &mut level_assignments.clone());
+            stash.sort_by_levels(level_assignments);
+
+            assert!(stash.blocks() == expected);
+        }
+    }
+
+    #[test]
+    fn parallel_sort_matches_block_sort() {
+        let mut rng = StdRng::seed_from_u64(8);
+        check_parallel_sort_matches_block_sort::<u64, 3>(500, |address| address + 1000, &mut rng);
+        check_parallel_sort_matches_block_sort::<u64, 4>(500, |address| address + 1000, &mut rng);
+        check_parallel_sort_matches_block_sort::<crate::BlockValue<4096>, 4>(
+            20,
+            |address| crate::BlockValue::new([address as u8; 4096]),
+            &mut rng,
+        );
+    }
+
+    #[test]
+    fn path_io_in_place_matches_path_io_on_bucket_copies() {
+        type LargeBlock = crate::BlockValue<4096>;
+        const Z: BucketSize = 4;
+        let mut rng = StdRng::seed_from_u64(7);
+        let height = 4;
+        let path_size = (Z * (height + 1)) as StashSize;
+
+        // A tree whose slots are random mixes of real blocks and dummies.
+        let mut tree = vec![Bucket::<LargeBlock, Z>::default(); 2 << height];
+        let mut address = 0;
+        for bucket in tree.iter_mut().skip(1) {
+            for slot in bucket.blocks.iter_mut() {
+                if rng.gen() {
+                    let position = TreeIndex::random_leaf(height as u64, &mut rng).unwrap();
+                    *slot = PathOramBlock::new(rng.gen(), address, position);
+                    address += 1;
+                }
+            }
+        }
+
+        for _ in 0..20 {
+            let position = TreeIndex::random_leaf(height as u64, &mut rng).unwrap();
+            let nodes: Vec<usize> = (0..=height as u64)
+                .map(|depth| position.ct_node_on_path(depth, height as u64) as usize)
+                .collect();
+
+            // Reading and writing the path in place, as `PathOram` does, matches copying the path's buckets
+            // out of the tree and back, as a backend without a tree of buckets must.
+            let mut in_place = ObliviousStash::<LargeBlock>::new(path_size, 10).unwrap();
+            let mut copied = ObliviousStash::<LargeBlock>::new(path_size, 10).unwrap();
+            in_place.read_from_path(&mut tree, position).unwrap();
+            let mut path_buckets: Vec<Bucket<LargeBlock, Z>> =
+                nodes.iter().map(|node| tree[*node]).collect();
+            copied.read_from_path_buckets(&path_buckets).unwrap();
+            assert_eq!(copied.blocks(), in_place.blocks());
+
+            let mut tree_in_place = tree.clone();
+            in_place
+                .write_to_path(&mut tree_in_place, position)
+                .unwrap();
+            copied
+                .write_to_path_buckets(&mut path_buckets, position)
+                .unwrap();
+            for (node, bucket) in nodes.iter().zip(&path_buckets) {
+                assert_eq!(bucket.blocks, tree_in_place[*node].blocks);
+            }
+            assert_eq!(copied.blocks(), in_place.blocks());
+            tree = tree_in_place;
+        }
+    }
+
+    #[test]
+    fn dummy_address_never_matches_dummy_blocks() {
+        let mut stash = ObliviousStash::<u64>::new(24, 40).unwrap();
+        let dummy_address = PathOramBlock::<u64>::dummy().address;
+        assert_eq!(stash.access(dummy_address, 32, |_| 7).unwrap(), 0);
+        stash.write_from(dummy_address, 32, &7).unwrap();
+        let mut out = 1;
+        stash.read_into(dummy_address, 32, &mut out).unwrap();
+        assert_eq!(out, 1);
+        assert_eq!(stash.occupancy(), 0);
+        assert!(stash
+            .overflow_blocks()
+            .all(|block| bool::from(block.ct_is_dummy())));
     }
 }
@@ -0,0 +1,506 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Incremental Path ORAM accesses, for runtimes that require long operations to yield periodically.
+//!
+//! [`PathOram::access_stepwise`] returns an [`AccessStateMachine`], each call to whose [`step`](AccessStateMachine::step)
+//! performs one bounded unit of work: the scan of a linear position map, the read or write of one bucket,
+//! the scan of the stash, or the oblivious sort preceding eviction. A recursive position map is accessed first,
+//! in steps of its own, and the dummy accesses to any padding levels (see the [`uniform_depth`](crate::uniform_depth) module)
+//! are part of the last step of the position map. Driving the machine to completion has exactly the effect of [`Oram::access`]
+//! with the same arguments, performs the same physical reads and writes in the same order,
+//! and draws the same randomness from the same RNG, so the number of steps reveals nothing beyond the ORAM's parameters.
+//!
+//! With deferred eviction (see [`PathOram::set_max_pending_evictions`]) or a reverse-lexicographic eviction order
+//! (see [`PathOram::set_eviction_order`]), the removal of the target block from its path, and any synchronous eviction, are one step each.
+//!
+//! The ORAM is poisoned while an access is in progress, so an access abandoned partway leaves it poisoned
+//! (see [`PathOram::is_poisoned`]).
+//!
+//! ```
+//! use oram::{stepwise::AccessStep, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1)?;
+//! let mut access = oram.access_stepwise(3, |_| 42, &mut rng)?;
+//! let previous = loop {
+//!     match access.step()? {
+//!         AccessStep::Pending => { /* Yield to the runtime here. */ }
+//!         AccessStep::Done(value) => break value,
+//!     }
+//! };
+//! assert_eq!(previous, 0);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    bucket::PositionBlock,
+    eviction::EvictionStrategy,
+    path_oram::PathOram,
+    position_map::PositionMap,
+    tree::{CompleteBinaryTreeIndex, TreeIndex},
+    utils::IndexWord,
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError,
+};
+use alloc::boxed::Box;
+use rand::{CryptoRng, Rng};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// The outcome of a step of an [`AccessStateMachine`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessStep<V> {
+    /// The access needs more steps.
+    Pending,
+    /// The access is complete, and returned this value.
+    Done(V),
+}
+
+/// An access to a [`PathOram`] in progress, returned by [`PathOram::access_stepwise`]. See the [module documentation](self).
+pub struct AccessStateMachine<
+    'a,
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy,
+    I: IndexWord,
+    F: Fn(&V) -> V,
+    R: Rng + CryptoRng,
+> {
+    oram: &'a mut PathOram<V, Z, AB, E, I>,
+    state: AccessState<V, Z, AB, F>,
+    rng: &'a mut R,
+}
+
+impl<
+        'a,
+        V: OramBlock,
+        const Z: BucketSize,
+        const AB: BlockSize,
+        E: EvictionStrategy,
+        I: IndexWord,
+        F: Fn(&V) -> V,
+        R: Rng + CryptoRng,
+    > AccessStateMachine<'a, V, Z, AB, E, I, F, R>
+{
+    /// Performs the next unit of work of the access, returning the accessed value once the access is complete.
+    /// Once complete, further calls return the same value and do nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error that the corresponding part of [`Oram::access`] would, after which the ORAM is poisoned.
+    pub fn step(&mut self) -> Result<AccessStep<V>, OramError> {
+        self.state.step(self.oram, self.rng)
+    }
+
+    /// Performs all the remaining steps of the access, returning the accessed value.
+    pub fn finish(mut self) -> Result<V, OramError> {
+        loop {
+            if let AccessStep::Done(value) = self.step()? {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Begins an access as in [`Oram::access`], to be performed incrementally by the returned state machine.
+    /// First rebuilds the ORAM if its [rebuild policy](PathOram::set_rebuild_policy) calls for it. Draws the new leaves of the access (including those of a recursive position map) immediately,
+    /// and performs no physical reads or writes until the first step. See the [`stepwise`](crate::stepwise) module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError` if `address` is not less than the capacity,
+    /// a `Poisoned` error if the ORAM is poisoned, and any error from a rebuild.
+    pub fn access_stepwise<'a, F: Fn(&V) -> V, R: Rng + CryptoRng>(
+        &'a mut self,
+        address: Address,
+        callback: F,
+        rng: &'a mut R,
+    ) -> Result<AccessStateMachine<'a, V, Z, AB, E, I, F, R>, OramError> {
+        let state = AccessState::new(self, address, callback, rng)?;
+        Ok(AccessStateMachine {
+            oram: self,
+            state,
+            rng,
+        })
+    }
+}
+
+// The callback with which one level of a recursive position map updates the position block it accesses.
+type PositionBlockCallback<const AB: BlockSize> =
+    Box<dyn Fn(&PositionBlock<AB>) -> PositionBlock<AB>>;
+
+// The state of an access to a recursive position map.
+type PositionMapAccessState<const AB: BlockSize, const Z: BucketSize> =
+    AccessState<PositionBlock<AB>, Z, AB, PositionBlockCallback<AB>>;
+
+enum Phase<const AB: BlockSize, const Z: BucketSize> {
+    // Updating the position map, recursively if there is an inner state.
+    PositionMap(Option<Box<PositionMapAccessState<AB, Z>>>),
+    // Reading the bucket at the given depth of the path (from the leaf up).
+    ReadBucket(usize),
+    // Removing the target block from its path, when eviction is deferred.
+    TakeBlock,
+    // Accessing the target block in the stash.
+    Stash,
+    // Sorting the stash for eviction.
+    Sort,
+    // Writing the bucket at the given depth of the path (from the root down).
+    WriteBucket(usize),
+    // Evicting the oldest pending path if the queue is full, and queueing this one, when eviction is deferred.
+    QueueEviction,
+}
+
+// The state of an access to one level of a Path ORAM, without a borrow of that ORAM,
+// so that the states of the levels of a recursive position map can nest.
+struct AccessState<V: OramBlock, const Z: BucketSize, const AB: BlockSize, F: Fn(&V) -> V> {
+    address: Address,
+    callback: F,
+    new_position: TreeIndex,
+    // The position of the target block before the access, once read from the position map.
+    position: TreeIndex,
+    index_matches: [Choice; AB],
+    phase: Phase<AB, Z>,
+    // The value of the target block, once accessed in the stash.
+    value: Option<V>,
+    done: bool,
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, F: Fn(&V) -> V>
+    AccessState<V, Z, AB, F>
+{
+    // Checks the access as `PathOram::access_with` does, draws its new leaf, and begins any access to a recursive position map.
+    fn new<E: EvictionStrategy, I: IndexWord, R: Rng + CryptoRng>(
+        oram: &mut PathOram<V, Z, AB, E, I>,
+        address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<Self, OramError> {
+        if oram.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        oram.check_reseeded()?;
+        let capacity = oram.block_capacity()?;
+        if address >= capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity,
+            });
+        }
+        oram.rebuild_if_due(rng)?;
+        // The ORAM is inconsistent until the access completes.
+        oram.poisoned = true;
+
+        let new_position = oram.sample_new_position(rng)?;
+        let address_within_block = PositionMap::<AB, Z>::address_within_block(address)?;
+        let index_matches: [Choice; AB] = core::array::from_fn(|i| i.ct_eq(&address_within_block));
+
+        let inner = match &mut oram.position_map {
+            PositionMap::Base(_) => None,
+            PositionMap::Recursive(block_oram) => {
+                let callback: PositionBlockCallback<AB> = Box::new(move |block| {
+                    let mut result = *block;
+                    for (entry, matches) in result.data.iter_mut().zip(index_matches) {
+                        entry.conditional_assign(&new_position, matches);
+                    }
+                    result
+                });
+                Some(Box::new(AccessState::new(
+                    block_oram,
+                    PositionMap::<AB, Z>::address_of_block(address),
+                    callback,
+                    rng,
+                )?))
+            }
+        };
+
+        Ok(Self {
+            address,
+            callback,
+            new_position,
+            position: 0,
+            index_matches,
+            phase: Phase::PositionMap(inner),
+            value: None,
+            done: false,
+        })
+    }
+
+    fn step<E: EvictionStrategy, I: IndexWord, R: Rng + CryptoRng>(
+        &mut self,
+        oram: &mut PathOram<V, Z, AB, E, I>,
+        rng: &mut R,
+    ) -> Result<AccessStep<V>, OramError> {
+        if let (true, Some(value)) = (self.done, self.value) {
+            return Ok(AccessStep::Done(value));
+        }
+        let outcome = self.step_in_progress(oram, rng);
+        match outcome {
+            Ok(AccessStep::Done(_)) => {
+                self.done = true;
+                oram.poisoned = false;
+                // A completed access counts toward the rebuild policy, as one made through `access` does.
+                oram.count_access();
+            }
+            Ok(AccessStep::Pending) => {}
+            Err(_) => oram.poisoned = true,
+        }
+        outcome
+    }
+
+    fn step_in_progress<E: EvictionStrategy, I: IndexWord, R: Rng + CryptoRng>(
+        &mut self,
+        oram: &mut PathOram<V, Z, AB, E, I>,
+        rng: &mut R,
+    ) -> Result<AccessStep<V>, OramError> {
+        let height = usize::try_from(oram.height)?;
+        let path_phase = |evicts_separately| {
+            if evicts_separately {
+                Phase::TakeBlock
+            } else {
+                Phase::ReadBucket(height)
+            }
+        };
+
+        match &mut self.phase {
+            Phase::PositionMap(inner) => {
+                let position = match (&mut oram.position_map, inner) {
+                    (PositionMap::Base(linear_oram), None) => {
+                        let new_position = self.new_position;
+                        let index_matches = self.index_matches;
+                        let block = linear_oram.access(
+                            PositionMap::<AB, Z>::address_of_block(self.address),
+                            |block: &PositionBlock<AB>| {
+                                let mut result = *block;
+                                for (entry, matches) in result.data.iter_mut().zip(index_matches) {
+                                    entry.conditional_assign(&new_position, matches);
+                                }
+                                result
+                            },
+                            rng,
+                        )?;
+                        block.data[PositionMap::<AB, Z>::address_within_block(self.address)?]
+                    }
+                    (PositionMap::Recursive(block_oram), Some(inner)) => {
+                        match inner.step(block_oram, rng)? {
+                            AccessStep::Pending => return Ok(AccessStep::Pending),
+                            AccessStep::Done(block) => {
+                                let mut position = TreeIndex::default();
+                                for (entry, matches) in block.data.iter().zip(self.index_matches) {
+                                    position.conditional_assign(entry, matches);
+                                }
+                                position
+                            }
+                        }
+                    }
+                    _ => unreachable!("the access state does not match the position map"),
+                };
+                assert!(position.is_leaf(oram.height));
+                oram.access_recursion_padding(rng)?;
+                self.position = position;
+                self.phase = path_phase(oram.evicts_separately());
+            }
+            Phase::ReadBucket(depth) => {
+                let node = self.position.ct_node_on_path(*depth as u64, oram.height);
+                let bucket = &oram.physical_memory[usize::try_from(node)?];
+                oram.stash.read_bucket(bucket, node, *depth)?;
+                self.phase = match depth.checked_sub(1) {
+                    Some(next_depth) => Phase::ReadBucket(next_depth),
+                    None => Phase::Stash,
+                };
+            }
+            Phase::TakeBlock => {
+                oram.stash
+                    .take_block(&mut oram.physical_memory, self.position, self.address)?;
+                self.phase = Phase::Stash;
+            }
+            Phase::Stash => {
+                self.value = Some(oram.stash.access(
+                    self.address,
+                    self.new_position,
+                    &self.callback,
+                )?);
+                self.phase = if oram.evicts_separately() {
+                    Phase::QueueEviction
+                } else {
+                    Phase::Sort
+                };
+            }
+            Phase::Sort => {
+                oram.stash.sort_for_eviction::<Z>(self.position)?;
+                self.phase = Phase::WriteBucket(0);
+            }
+            Phase::WriteBucket(depth) => {
+                let node = self.position.ct_node_on_path(*depth as u64, oram.height);
+                oram.stash.write_bucket(
+                    &mut oram.physical_memory[usize::try_from(node)?],
+                    node,
+                    *depth,
+                )?;
+                if *depth == height {
+                    return Ok(AccessStep::Done(self.value.unwrap_or_default()));
+                }
+                *depth += 1;
+            }
+            Phase::QueueEviction => {
+                oram.queue_eviction(self.position)?;
+                return Ok(AccessStep::Done(self.value.unwrap_or_default()));
+            }
+        }
+        Ok(AccessStep::Pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bucket::BlockValue,
+        eviction::EvictionOrder,
+        test_utils::{measured_physical_cost, start_bucket_trace, take_bucket_trace},
+    };
+    use alloc::vec::Vec;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    type TestOram = PathOram<BlockValue<8>, 4, 8>;
+
+    // Runs the same random workload on two copies of an ORAM, accessing one as usual and the other stepwise,
+    // taking a random number of steps before finishing each access,
+    // and checks that the values returned, the buckets read and written (in order), the numbers of blocks read and written,
+    // and the complete states of the copies agree after every access.
+    fn check_stepwise_matches_access(
+        capacity: Address,
+        recursion_cutoff: u64,
+        max_pending_evictions: usize,
+        eviction_order: EvictionOrder,
+    ) {
+        let new_oram = || {
+            let mut oram = TestOram::new_with_parameters(
+                capacity,
+                &mut StdRng::seed_from_u64(0),
+                40,
+                recursion_cutoff,
+            )
+            .unwrap();
+            oram.set_max_pending_evictions(max_pending_evictions)
+                .unwrap();
+            oram.set_eviction_order(eviction_order);
+            start_bucket_trace(&mut oram);
+            oram
+        };
+        let mut expected_oram = new_oram();
+        let mut stepwise_oram = new_oram();
+        let mut expected_rng = StdRng::seed_from_u64(1);
+        let mut stepwise_rng = StdRng::seed_from_u64(1);
+        let mut workload_rng = StdRng::seed_from_u64(2);
+
+        for _ in 0..100 {
+            let address = workload_rng.gen_range(0..capacity);
+            let new_value: BlockValue<8> = workload_rng.gen();
+            let expected = expected_oram
+                .access(address, |_| new_value, &mut expected_rng)
+                .unwrap();
+
+            let mut access = stepwise_oram
+                .access_stepwise(address, |_| new_value, &mut stepwise_rng)
+                .unwrap();
+            let value = match workload_rng.gen_range(0..3) {
+                // Finish immediately.
+                0 => access.finish().unwrap(),
+                // Take a few steps, then finish.
+                1 => {
+                    for _ in 0..workload_rng.gen_range(1..4) {
+                        access.step().unwrap();
+                    }
+                    access.finish().unwrap()
+                }
+                // Step one at a time, and keep stepping after completion.
+                _ => {
+                    let mut steps_after_done = 0;
+                    let mut value = None;
+                    while steps_after_done < 3 {
+                        if let AccessStep::Done(done_value) = access.step().unwrap() {
+                            assert!(value.is_none() || value == Some(done_value));
+                            value = Some(done_value);
+                            steps_after_done += 1;
+                        }
+                    }
+                    value.unwrap()
+                }
+            };
+            assert_eq!(value, expected);
+            assert!(!stepwise_oram.is_poisoned());
+            let expected_trace = take_bucket_trace(&mut expected_oram);
+            assert!(!expected_trace.is_empty());
+            assert_eq!(take_bucket_trace(&mut stepwise_oram), expected_trace);
+            assert_eq!(
+                measured_physical_cost(&stepwise_oram),
+                measured_physical_cost(&expected_oram)
+            );
+            assert_eq!(stepwise_oram.debug_dump(), expected_oram.debug_dump());
+        }
+    }
+
+    #[test]
+    fn stepwise_access_matches_access_with_linear_position_map() {
+        check_stepwise_matches_access(64, 1 << 10, 0, EvictionOrder::ReadPath);
+    }
+
+    #[test]
+    fn stepwise_access_matches_access_with_recursive_position_map() {
+        check_stepwise_matches_access(512, 2, 0, EvictionOrder::ReadPath);
+    }
+
+    #[test]
+    fn stepwise_access_matches_access_with_deferred_eviction() {
+        check_stepwise_matches_access(256, 2, 3, EvictionOrder::ReadPath);
+    }
+
+    #[test]
+    fn stepwise_access_matches_access_with_reverse_lexicographic_eviction() {
+        check_stepwise_matches_access(256, 2, 0, EvictionOrder::ReverseLexicographic);
+        check_stepwise_matches_access(256, 2, 3, EvictionOrder::ReverseLexicographic);
+    }
+
+    #[test]
+    fn number_of_steps_is_independent_of_address() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(512, &mut rng, 40, 2).unwrap();
+        let mut step_counts = Vec::new();
+        for address in [0, 1, 100, 511] {
+            let mut access = oram.access_stepwise(address, |v| *v, &mut rng).unwrap();
+            let mut steps = 1;
+            while access.step().unwrap() == AccessStep::Pending {
+                steps += 1;
+            }
+            step_counts.push(steps);
+        }
+        assert!(step_counts.iter().all(|steps| *steps == step_counts[0]));
+    }
+
+    #[test]
+    fn abandoned_access_poisons() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        assert!(matches!(
+            oram.access_stepwise(64, |v| *v, &mut rng),
+            Err(OramError::AddressOutOfBoundsError { .. })
+        ));
+        assert!(!oram.is_poisoned());
+
+        let mut access = oram.access_stepwise(3, |v| *v, &mut rng).unwrap();
+        access.step().unwrap();
+        drop(access);
+        assert!(oram.is_poisoned());
+        assert!(matches!(oram.read(3, &mut rng), Err(OramError::Poisoned)));
+    }
+}
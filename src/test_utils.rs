@@ -11,7 +11,11 @@
 use std::fmt::Debug;
 use std::sync::Once;
 static INIT: Once = Once::new();
-use crate::path_oram::PathOram;
+use crate::eviction::{EvictionStrategy, GreedyEviction};
+use crate::path_oram::{PathOram, PhysicalCost};
+use crate::position_map::PositionMap;
+use crate::tree::TreeIndex;
+use crate::utils::IndexWord;
 use crate::{
     Address, BlockSize, BucketSize, Oram, OramBlock, OramError, RecursionCutoff, StashSize,
 };
@@ -73,6 +77,92 @@ where
     }
 }
 
+/// Tests the correctness of `read_into` and `write_from` on a workload of random reads and writes,
+/// and checks that failed reads leave their output untouched.
+pub(crate) fn read_into_workload<T: Oram>(oram: &mut T, num_operations: usize)
+where
+    Standard: Distribution<T::V>,
+{
+    init_logger();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let capacity = oram.block_capacity().unwrap();
+    let mut mirror_array = vec![T::V::default(); usize::try_from(capacity).unwrap()];
+
+    for _ in 0..num_operations {
+        let random_index = rng.gen_range(0..capacity);
+        let random_block_value = rng.gen::<T::V>();
+
+        if rng.gen::<bool>() {
+            let mut out = rng.gen::<T::V>();
+            oram.read_into(random_index, &mut out, &mut rng).unwrap();
+            assert_eq!(out, mirror_array[usize::try_from(random_index).unwrap()]);
+        } else {
+            oram.write_from(random_index, &random_block_value, &mut rng)
+                .unwrap();
+            mirror_array[usize::try_from(random_index).unwrap()] = random_block_value;
+        }
+    }
+
+    for index in [capacity, capacity + 1, Address::MAX] {
+        let sentinel = rng.gen::<T::V>();
+        let mut out = sentinel;
+        assert!(matches!(
+            oram.read_into(index, &mut out, &mut rng),
+            Err(OramError::AddressOutOfBoundsError { .. })
+        ));
+        assert_eq!(out, sentinel);
+    }
+
+    for index in 0..capacity {
+        assert_eq!(
+            oram.read(index, &mut rng).unwrap(),
+            mirror_array[usize::try_from(index).unwrap()],
+            "{index}"
+        )
+    }
+}
+
+/// Checks that every access method of `oram` rejects the capacity, `Address::MAX`, and each of `extra_addresses`
+/// (which must be at least the capacity, e.g., an implementation's dummy address) with an `AddressOutOfBoundsError`,
+/// leaving the contents of the ORAM unchanged.
+pub(crate) fn out_of_bounds_addresses_are_rejected<T: Oram>(
+    oram: &mut T,
+    extra_addresses: &[Address],
+) where
+    Standard: Distribution<T::V>,
+{
+    let mut rng = StdRng::seed_from_u64(0);
+    let capacity = oram.block_capacity().unwrap();
+    let mut contents = Vec::new();
+    for address in 0..capacity {
+        let value = rng.gen::<T::V>();
+        oram.write(address, value, &mut rng).unwrap();
+        contents.push(value);
+    }
+
+    let rejected = |result: Result<(), OramError>, address: Address| {
+        assert!(
+            matches!(result, Err(OramError::AddressOutOfBoundsError { attempted, .. }) if attempted == address),
+            "{address}"
+        );
+    };
+    for &address in [capacity, Address::MAX].iter().chain(extra_addresses) {
+        let value = rng.gen::<T::V>();
+        rejected(oram.read(address, &mut rng).map(drop), address);
+        rejected(oram.write(address, value, &mut rng).map(drop), address);
+        rejected(oram.access(address, |_| value, &mut rng).map(drop), address);
+        rejected(oram.write_from(address, &value, &mut rng), address);
+        let mut out = value;
+        rejected(oram.read_into(address, &mut out, &mut rng), address);
+        assert_eq!(out, value);
+    }
+
+    for (address, value) in (0..capacity).zip(contents) {
+        assert_eq!(oram.read(address, &mut rng).unwrap(), value);
+    }
+}
+
 /// Tests the correctness of an `Oram` type T on repeated passes of sequential accesses 0, 1, ..., `capacity`
 pub(crate) fn linear_workload<T: Oram + Debug>(oram: &mut T, num_operations: u64)
 where
@@ -221,30 +311,39 @@ macro_rules! create_path_oram_stash_size_tests {
 }
 
 #[derive(Debug)]
-pub(crate) struct StashSizeMonitor<V: OramBlock, const Z: BucketSize, const AB: BlockSize> {
-    oram: PathOram<V, Z, AB>,
+pub(crate) struct StashSizeMonitor<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy = GreedyEviction,
+> {
+    oram: PathOram<V, Z, AB, E>,
 }
 
-impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> StashSizeMonitor<V, Z, AB> {
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy>
+    StashSizeMonitor<V, Z, AB, E>
+{
     pub(crate) fn new_with_parameters<R: rand::RngCore + rand::CryptoRng>(
         block_capacity: Address,
         rng: &mut R,
         overflow_size: StashSize,
         recursion_cutoff: RecursionCutoff,
     ) -> Result<Self, OramError> {
-        Ok(Self {
-            oram: PathOram::new_with_parameters(
-                block_capacity,
-                rng,
-                overflow_size,
-                recursion_cutoff,
-            )
-            .unwrap(),
-        })
+        Ok(Self::new(
+            PathOram::new_with_parameters(block_capacity, rng, overflow_size, recursion_cutoff)
+                .unwrap(),
+        ))
+    }
+
+    // Monitors an already constructed ORAM, e.g., one with a non-default eviction order.
+    pub(crate) fn new(oram: PathOram<V, Z, AB, E>) -> Self {
+        Self { oram }
     }
 }
 
-impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> Oram for StashSizeMonitor<V, Z, AB> {
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy> Oram
+    for StashSizeMonitor<V, Z, AB, E>
+{
     type V = V;
 
     fn block_capacity(&self) -> Result<Address, OramError> {
@@ -264,7 +363,120 @@ impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize> Oram for StashSizeM
     }
 }
 
+// An ORAM counting the accesses made to it, e.g., to check how many accesses a wrapper makes to its inner ORAMs.
+#[derive(Debug)]
+pub(crate) struct CountingOram<O: Oram> {
+    pub(crate) oram: O,
+    pub(crate) accesses: u64,
+}
+
+impl<O: Oram> CountingOram<O> {
+    pub(crate) fn new(oram: O) -> Self {
+        Self { oram, accesses: 0 }
+    }
+}
+
+impl<O: Oram> Oram for CountingOram<O> {
+    type V = O::V;
+
+    fn block_capacity(&self) -> Result<Address, OramError> {
+        self.oram.block_capacity()
+    }
+
+    fn access<R: rand::RngCore + rand::CryptoRng, F: Fn(&Self::V) -> Self::V>(
+        &mut self,
+        index: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<Self::V, OramError> {
+        self.accesses += 1;
+        self.oram.access(index, callback, rng)
+    }
+}
+
 pub(crate) use create_path_oram_correctness_tests;
 pub(crate) use create_path_oram_correctness_tests_all_parameters;
 pub(crate) use create_path_oram_correctness_tests_helper;
 pub(crate) use create_path_oram_stash_size_tests;
+
+// Returns the numbers of blocks read and written so far by `oram` and by its position map recursion.
+pub(crate) fn measured_physical_cost<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy,
+    I: IndexWord,
+>(
+    oram: &PathOram<V, Z, AB, E, I>,
+) -> PhysicalCost {
+    let mut cost = PhysicalCost {
+        data_reads: oram.stash.physical_reads,
+        data_writes: oram.stash.physical_writes,
+        ..PhysicalCost::default()
+    };
+    let mut position_map = &oram.position_map;
+    loop {
+        match position_map {
+            PositionMap::Base(linear_oram) => {
+                cost.position_map_reads += linear_oram.physical_reads;
+                cost.position_map_writes += linear_oram.physical_writes;
+                return cost;
+            }
+            PositionMap::Recursive(block_oram) => {
+                cost.position_map_reads += block_oram.stash.physical_reads;
+                cost.position_map_writes += block_oram.stash.physical_writes;
+                position_map = &block_oram.position_map;
+            }
+        }
+    }
+}
+
+// The buckets read and written by a Path ORAM and its position map recursion,
+// as (level of the recursion, bucket, whether written) triples.
+pub(crate) type BucketTrace = Vec<(usize, TreeIndex, bool)>;
+
+// Starts recording the buckets read and written by `oram` and by its position map recursion.
+pub(crate) fn start_bucket_trace<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy,
+    I: IndexWord,
+>(
+    oram: &mut PathOram<V, Z, AB, E, I>,
+) {
+    oram.stash.bucket_trace = Some(Vec::new());
+    if let PositionMap::Recursive(block_oram) = &mut oram.position_map {
+        start_bucket_trace(block_oram);
+    }
+}
+
+// Returns the buckets read and written by `oram` and by its position map recursion since the last call
+// (or since `start_bucket_trace`), level by level.
+pub(crate) fn take_bucket_trace<
+    V: OramBlock,
+    const Z: BucketSize,
+    const AB: BlockSize,
+    E: EvictionStrategy,
+    I: IndexWord,
+>(
+    oram: &mut PathOram<V, Z, AB, E, I>,
+) -> BucketTrace {
+    let mut trace: BucketTrace = oram
+        .stash
+        .bucket_trace
+        .replace(Vec::new())
+        .expect("bucket tracing was not started")
+        .into_iter()
+        .map(|(node, is_write)| (0, node, is_write))
+        .collect();
+    if let PositionMap::Recursive(block_oram) = &mut oram.position_map {
+        let inner = take_bucket_trace(block_oram);
+        trace.extend(
+            inner
+                .into_iter()
+                .map(|(level, node, is_write)| (level + 1, node, is_write)),
+        );
+    }
+    trace
+}
@@ -0,0 +1,366 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A conformance suite checking that an [`Oram`] implementation behaves as this crate's ORAMs do.
+//!
+//! [`conformance`] constructs an ORAM of each of several capacities and checks the contract of the [`Oram`] trait:
+//!
+//! - `capacity`: [`Oram::block_capacity`] returns the capacity the ORAM was constructed with.
+//! - `default values`: every address holds `V::default()` before it is first written.
+//! - `read after write`: on a workload of random reads, writes, callback accesses, `read_into`s, and `write_from`s,
+//!   every read returns the value most recently written, and every write returns the value it replaced.
+//! - `callback`: [`Oram::access`] stores the callback applied to the value previously stored, and returns that value.
+//!   The callback is called at least once, but may be called any number of times, on the stored value and on others:
+//!   an oblivious implementation calls it on every value it touches, to hide which is the one accessed.
+//!   Callbacks must therefore be pure functions of their argument.
+//! - `bounds`: every access method rejects the capacity and `Address::MAX` with an `AddressOutOfBoundsError`
+//!   naming the address, leaving the ORAM unchanged and the output of `read_into` untouched.
+//!
+//! Rather than panicking at the first failure, the suite records each in a [`ConformanceReport`] and moves on.
+//! An unexpected error from the ORAM ends the checks of that capacity, whose later results would be meaningless.
+//! The crate's own ORAMs are validated with this suite.
+//!
+//! ```
+//! use oram::{linear_time_oram::LinearTimeOram, testing::conformance};
+//!
+//! let report = conformance(|capacity, _| LinearTimeOram::<u64>::new(capacity), &[1, 2, 17], 200, 0);
+//! assert!(report.passed(), "{report}");
+//! ```
+
+use crate::{Address, Oram, OramError};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{cell::Cell, fmt};
+use rand::{
+    distributions::{Distribution, Standard},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+
+/// A violation of the contract found by [`conformance`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConformanceFailure {
+    /// The capacity of the ORAM that failed.
+    pub capacity: Address,
+    /// The name of the check that failed (see the [module documentation](self)).
+    pub check: &'static str,
+    /// A description of the failure.
+    pub detail: String,
+}
+
+impl fmt::Display for ConformanceFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "capacity {}: {} check failed: {}",
+            self.capacity, self.check, self.detail
+        )
+    }
+}
+
+/// The results of [`conformance`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConformanceReport {
+    /// The number of individual checks made.
+    pub checks: u64,
+    /// The checks that failed, in the order found.
+    pub failures: Vec<ConformanceFailure>,
+}
+
+impl ConformanceReport {
+    /// Returns true if no check failed.
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    // Records the outcome of a check, returning whether it passed.
+    fn check(
+        &mut self,
+        capacity: Address,
+        check: &'static str,
+        passed: bool,
+        detail: impl FnOnce() -> String,
+    ) -> bool {
+        self.checks += 1;
+        if !passed {
+            self.failures.push(ConformanceFailure {
+                capacity,
+                check,
+                detail: detail(),
+            });
+        }
+        passed
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} checks failed",
+            self.failures.len(),
+            self.checks
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n{failure}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the ORAMs returned by `new_oram` for each of `capacities` against the contract of the [`Oram`] trait,
+/// making `ops_per_capacity` random operations on each, with randomness drawn from a `StdRng` seeded with `seed`.
+/// `new_oram` is given the capacity and an RNG, and should return an empty ORAM of that capacity.
+/// See the [module documentation](self) for the checks made.
+pub fn conformance<O: Oram, F: FnMut(Address, &mut StdRng) -> Result<O, OramError>>(
+    mut new_oram: F,
+    capacities: &[Address],
+    ops_per_capacity: usize,
+    seed: u64,
+) -> ConformanceReport
+where
+    Standard: Distribution<O::V>,
+{
+    let mut report = ConformanceReport::default();
+    let mut rng = StdRng::seed_from_u64(seed);
+    for &capacity in capacities {
+        let oram = new_oram(capacity, &mut rng);
+        let constructed = report.check(capacity, "construction", oram.is_ok(), || {
+            format!("{}", oram.as_ref().err().unwrap())
+        });
+        if let (true, Ok(mut oram)) = (constructed, oram) {
+            // An unexpected error ends the checks of this capacity.
+            if let Err(error) =
+                check_capacity(&mut oram, capacity, ops_per_capacity, &mut rng, &mut report)
+            {
+                report.check(capacity, "unexpected error", false, || error.to_string());
+            }
+        }
+    }
+    report
+}
+
+fn check_capacity<O: Oram>(
+    oram: &mut O,
+    capacity: Address,
+    ops: usize,
+    rng: &mut StdRng,
+    report: &mut ConformanceReport,
+) -> Result<(), OramError>
+where
+    Standard: Distribution<O::V>,
+{
+    let reported = oram.block_capacity()?;
+    report.check(capacity, "capacity", reported == capacity, || {
+        format!("block_capacity returned {reported}")
+    });
+
+    let mut expected = vec![O::V::default(); usize::try_from(capacity)?];
+    for address in 0..capacity {
+        let value = oram.read(address, rng)?;
+        report.check(capacity, "default values", value == O::V::default(), || {
+            format!("address {address} held {value:?} before being written")
+        });
+    }
+
+    for _ in 0..ops {
+        if capacity == 0 {
+            break;
+        }
+        let address = rng.gen_range(0..capacity);
+        let slot = &mut expected[usize::try_from(address)?];
+        let value: O::V = rng.gen();
+        match rng.gen_range(0..5) {
+            0 => {
+                let read = oram.read(address, rng)?;
+                report.check(capacity, "read after write", read == *slot, || {
+                    format!("read of address {address} returned {read:?}, not {slot:?}")
+                });
+            }
+            1 => {
+                let previous = oram.write(address, value, rng)?;
+                report.check(capacity, "read after write", previous == *slot, || {
+                    format!("write to address {address} returned {previous:?}, not {slot:?}")
+                });
+                *slot = value;
+            }
+            2 => {
+                let mut out = rng.gen();
+                oram.read_into(address, &mut out, rng)?;
+                report.check(capacity, "read after write", out == *slot, || {
+                    format!("read_into of address {address} gave {out:?}, not {slot:?}")
+                });
+            }
+            3 => {
+                oram.write_from(address, &value, rng)?;
+                *slot = value;
+            }
+            _ => {
+                // The callback distinguishes the stored value from any other, so that the value stored afterwards
+                // shows whether it was applied to the right one.
+                let other: O::V = rng.gen();
+                let calls = Cell::new(0u64);
+                let stored = *slot;
+                let previous = oram.access(
+                    address,
+                    |current| {
+                        calls.set(calls.get() + 1);
+                        if *current == stored {
+                            value
+                        } else {
+                            other
+                        }
+                    },
+                    rng,
+                )?;
+                report.check(capacity, "callback", previous == stored, || {
+                    format!("access to address {address} returned {previous:?}, not {stored:?}")
+                });
+                report.check(capacity, "callback", calls.get() > 0, || {
+                    format!("access to address {address} never called its callback")
+                });
+                *slot = value;
+            }
+        }
+    }
+
+    check_bounds(oram, capacity, rng, report);
+
+    for (address, value) in (0..capacity).zip(&expected) {
+        let read = oram.read(address, rng)?;
+        report.check(capacity, "read after write", read == *value, || {
+            format!("final read of address {address} returned {read:?}, not {value:?}")
+        });
+    }
+    Ok(())
+}
+
+// Checks that every access method rejects out-of-bounds addresses. The final reads of `check_capacity`
+// check that the rejected accesses left the ORAM unchanged.
+fn check_bounds<O: Oram>(
+    oram: &mut O,
+    capacity: Address,
+    rng: &mut StdRng,
+    report: &mut ConformanceReport,
+) where
+    Standard: Distribution<O::V>,
+{
+    for address in [capacity, Address::MAX] {
+        let value: O::V = rng.gen();
+        let mut out = value;
+        let results = [
+            ("read", oram.read(address, rng).map(drop)),
+            ("write", oram.write(address, value, rng).map(drop)),
+            ("access", oram.access(address, |_| value, rng).map(drop)),
+            ("write_from", oram.write_from(address, &value, rng)),
+            ("read_into", oram.read_into(address, &mut out, rng)),
+        ];
+        for (method, result) in results {
+            let rejected = matches!(
+                result,
+                Err(OramError::AddressOutOfBoundsError { attempted, .. }) if attempted == address
+            );
+            report.check(capacity, "bounds", rejected, || {
+                format!("{method} of address {address} returned {result:?}")
+            });
+        }
+        report.check(capacity, "bounds", out == value, || {
+            format!("rejected read_into of address {address} changed its output to {out:?}")
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, BlockValue};
+
+    // An ORAM that breaks the contract in the ways given, wrapping a correct one.
+    #[derive(Debug)]
+    struct FaultyOram {
+        inner: LinearTimeOram<u64>,
+        misreport_capacity: bool,
+        drop_writes_to: Option<Address>,
+    }
+
+    impl Oram for FaultyOram {
+        type V = u64;
+
+        fn block_capacity(&self) -> Result<Address, OramError> {
+            Ok(self.inner.block_capacity()? + u64::from(self.misreport_capacity))
+        }
+
+        fn access<R: rand::RngCore + rand::CryptoRng, F: Fn(&u64) -> u64>(
+            &mut self,
+            index: Address,
+            callback: F,
+            rng: &mut R,
+        ) -> Result<u64, OramError> {
+            if Some(index) == self.drop_writes_to {
+                self.inner.read(index, rng)
+            } else {
+                self.inner.access(index, callback, rng)
+            }
+        }
+    }
+
+    #[test]
+    fn faults_are_reported_without_panicking() {
+        let report = conformance(
+            |capacity, _| {
+                Ok(FaultyOram {
+                    inner: LinearTimeOram::new(capacity)?,
+                    misreport_capacity: capacity == 4,
+                    drop_writes_to: Some(1),
+                })
+            },
+            &[4, 8],
+            300,
+            0,
+        );
+        assert!(!report.passed());
+        let failed = |capacity, check| {
+            report
+                .failures
+                .iter()
+                .any(|failure| failure.capacity == capacity && failure.check == check)
+        };
+        assert!(failed(4, "capacity"));
+        assert!(!failed(8, "capacity"));
+        assert!(failed(4, "read after write"));
+        assert!(failed(8, "read after write"));
+        assert!(report
+            .to_string()
+            .contains("capacity 4: capacity check failed"));
+    }
+
+    #[test]
+    fn construction_failures_are_reported() {
+        let report = conformance(
+            |capacity, _| {
+                if capacity == 3 {
+                    Err(OramError::InvalidConfigurationError {
+                        parameter_name: "Capacity".to_string(),
+                        parameter_value: capacity.to_string(),
+                    })
+                } else {
+                    LinearTimeOram::<BlockValue<4>>::new(capacity)
+                }
+            },
+            &[2, 3, 4],
+            50,
+            1,
+        );
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].check, "construction");
+        assert_eq!(report.failures[0].capacity, 3);
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A two-tier ORAM: a small front tier of recently accessed values, scanned in full on every access,
+//! in front of a back-tier ORAM holding every address.
+//!
+//! Every access to a [`TieredOram`] scans every entry of the front tier, and makes exactly one access to the back tier,
+//! so its physical access pattern is independent of which tier holds the accessed value.
+//! On a front-tier hit, the value is updated in the front tier, and the back-tier access writes back
+//! a front-tier entry chosen round-robin (or, if that entry has nothing to write back, rewrites the accessed address unchanged).
+//! On a miss, the back-tier access performs the access itself, and the result is admitted to the front tier
+//! (as allowed by the [`TierPolicy`]) in place of the round-robin entry, if that entry has been written back.
+//!
+//! Front-tier entries with changes not yet written back are written back by [`TieredOram::flush`].
+//!
+//! ```
+//! use oram::{tiered::{TierPolicy, TieredOram}, DefaultOram, Oram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let back = DefaultOram::<u64>::new(1024, &mut OsRng)?;
+//! let mut oram = TieredOram::new(back, 8, TierPolicy::AdmitAll)?;
+//! oram.write(3, 42, &mut OsRng)?;
+//! assert_eq!(oram.read(3, &mut OsRng)?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Address, Oram, OramBlock, OramError};
+use alloc::{string::ToString, vec, vec::Vec};
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+
+/// Which addresses a [`TieredOram`] admits to its front tier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TierPolicy {
+    /// Every address is admitted.
+    AdmitAll,
+    /// Only addresses less than the given bound are admitted, e.g., if the hottest values are stored at the lowest addresses.
+    AdmitBelow(Address),
+}
+
+impl TierPolicy {
+    fn admits(&self, address: Address) -> Choice {
+        match self {
+            TierPolicy::AdmitAll => Choice::from(1),
+            TierPolicy::AdmitBelow(bound) => address.ct_lt(bound),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct FrontEntry<V: OramBlock> {
+    address: Address,
+    value: V,
+    // Whether the entry holds a value, and whether that value has changed since it was last written to the back tier.
+    valid: Choice,
+    dirty: Choice,
+}
+
+impl<V: OramBlock> FrontEntry<V> {
+    fn empty() -> Self {
+        Self {
+            address: 0,
+            value: V::default(),
+            valid: Choice::from(0),
+            dirty: Choice::from(0),
+        }
+    }
+}
+
+impl<V: OramBlock> ConditionallySelectable for FrontEntry<V> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self {
+            address: Address::conditional_select(&a.address, &b.address, choice),
+            value: V::conditional_select(&a.value, &b.value, choice),
+            valid: Choice::conditional_select(&a.valid, &b.valid, choice),
+            dirty: Choice::conditional_select(&a.dirty, &b.dirty, choice),
+        }
+    }
+}
+
+/// An ORAM with a small front tier in front of a back-tier ORAM `O`. See the [module documentation](self).
+#[derive(Debug)]
+pub struct TieredOram<O: Oram> {
+    back: O,
+    front: Vec<FrontEntry<O::V>>,
+    policy: TierPolicy,
+    // The index of the front-tier entry to be written back or replaced next. Advances on every access, so is public.
+    cursor: usize,
+}
+
+impl<O: Oram> TieredOram<O> {
+    /// Returns a `TieredOram` with a front tier of `front_capacity` entries (initially empty), admitting addresses by `policy`,
+    /// in front of `back`, which holds the initial value of every address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `front_capacity` is 0.
+    pub fn new(back: O, front_capacity: usize, policy: TierPolicy) -> Result<Self, OramError> {
+        if front_capacity == 0 {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Front tier capacity".to_string(),
+                parameter_value: front_capacity.to_string(),
+            });
+        }
+        Ok(Self {
+            back,
+            front: vec![FrontEntry::empty(); front_capacity],
+            policy,
+            cursor: 0,
+        })
+    }
+
+    /// Returns the number of entries in the front tier.
+    pub fn front_capacity(&self) -> usize {
+        self.front.len()
+    }
+
+    /// Writes back every front-tier entry with changes not yet written back, making one back-tier access per entry
+    /// (whether or not it has changes). The front tier keeps its entries.
+    pub fn flush<R: RngCore + CryptoRng>(&mut self, rng: &mut R) -> Result<(), OramError> {
+        for entry in self.front.iter_mut() {
+            let write_back = entry.valid & entry.dirty;
+            self.back.access(
+                entry.address,
+                |value| O::V::conditional_select(value, &entry.value, write_back),
+                rng,
+            )?;
+            entry.dirty = Choice::from(0);
+        }
+        Ok(())
+    }
+
+    /// Flushes the front tier, and returns the back-tier ORAM, which then holds the latest value of every address.
+    pub fn into_inner<R: RngCore + CryptoRng>(mut self, rng: &mut R) -> Result<O, OramError> {
+        self.flush(rng)?;
+        Ok(self.back)
+    }
+}
+
+impl<O: Oram> Oram for TieredOram<O> {
+    type V = O::V;
+
+    fn block_capacity(&self) -> Result<Address, OramError> {
+        self.back.block_capacity()
+    }
+
+    fn access<R: RngCore + CryptoRng, F: Fn(&Self::V) -> Self::V>(
+        &mut self,
+        address: Address,
+        callback: F,
+        rng: &mut R,
+    ) -> Result<Self::V, OramError> {
+        let capacity = self.block_capacity()?;
+        if address >= capacity {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: address,
+                capacity,
+            });
+        }
+
+        // Probe every front-tier entry, updating the accessed value if it is present.
+        let mut hit = Choice::from(0);
+        let mut front_value = Self::V::default();
+        for entry in &self.front {
+            let matches = entry.valid & entry.address.ct_eq(&address);
+            hit |= matches;
+            front_value.conditional_assign(&entry.value, matches);
+        }
+        let new_front_value = callback(&front_value);
+        for entry in self.front.iter_mut() {
+            let matches = entry.valid & entry.address.ct_eq(&address);
+            entry.value.conditional_assign(&new_front_value, matches);
+            entry.dirty.conditional_assign(&Choice::from(1), matches);
+        }
+
+        // On a hit, write back the round-robin entry if it has changes; on a miss, perform the access on the back tier.
+        let victim = self.front[self.cursor];
+        let write_back = hit & victim.valid & victim.dirty;
+        let back_address = Address::conditional_select(&address, &victim.address, write_back);
+        let back_value = self.back.access(
+            back_address,
+            |value| {
+                let mut result = *value;
+                result.conditional_assign(&callback(value), !hit);
+                result.conditional_assign(&victim.value, write_back);
+                result
+            },
+            rng,
+        )?;
+
+        // On a miss, admit the accessed value in place of the round-robin entry, unless that entry has changes.
+        let admit = !hit & self.policy.admits(address) & !(victim.valid & victim.dirty);
+        let admitted = FrontEntry {
+            address,
+            value: callback(&back_value),
+            valid: Choice::from(1),
+            dirty: Choice::from(0),
+        };
+        let entry = &mut self.front[self.cursor];
+        entry.dirty.conditional_assign(&Choice::from(0), write_back);
+        entry.conditional_assign(&admitted, admit);
+        self.cursor = (self.cursor + 1) % self.front.len();
+
+        Ok(Self::V::conditional_select(&back_value, &front_value, hit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, test_utils::*, PathOram};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn new_tiered_oram(
+        front_capacity: usize,
+        policy: TierPolicy,
+        rng: &mut StdRng,
+    ) -> TieredOram<CountingOram<PathOram<u64, 4, 8>>> {
+        let back = PathOram::new_with_parameters(256, rng, 40, 1).unwrap();
+        TieredOram::new(CountingOram::new(back), front_capacity, policy).unwrap()
+    }
+
+    #[test]
+    fn tiered_oram_matches_shadow_model() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for policy in [TierPolicy::AdmitAll, TierPolicy::AdmitBelow(16)] {
+            let mut oram = new_tiered_oram(8, policy, &mut rng);
+            let mut shadow = [0u64; 256];
+            for _ in 0..2000 {
+                // Mostly accesses to a hot set of 16 addresses.
+                let address = if rng.gen_bool(0.8) {
+                    rng.gen_range(0..16)
+                } else {
+                    rng.gen_range(0..256)
+                };
+                let increment = rng.gen_range(0..100);
+                let previous = oram
+                    .access(address, |value| value + increment, &mut rng)
+                    .unwrap();
+                assert_eq!(previous, shadow[address as usize]);
+                shadow[address as usize] += increment;
+            }
+
+            let mut back = oram.into_inner(&mut rng).unwrap();
+            for (address, value) in shadow.iter().enumerate() {
+                assert_eq!(back.read(address as Address, &mut rng).unwrap(), *value);
+            }
+        }
+    }
+
+    #[test]
+    fn every_access_costs_one_back_tier_access() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = new_tiered_oram(4, TierPolicy::AdmitAll, &mut rng);
+
+        // Hits only, after the first access.
+        for i in 0..100 {
+            oram.write(7, i, &mut rng).unwrap();
+        }
+        assert_eq!(oram.back.accesses, 100);
+
+        // Misses only.
+        for address in 0..100 {
+            oram.read(100 + address, &mut rng).unwrap();
+        }
+        assert_eq!(oram.back.accesses, 200);
+
+        // Flushing costs one back-tier access per front-tier entry, whatever the entries hold.
+        oram.flush(&mut rng).unwrap();
+        assert_eq!(oram.back.accesses, 204);
+    }
+
+    #[test]
+    fn tiered_oram_workloads() {
+        let back = LinearTimeOram::<u64>::new(64).unwrap();
+        let mut oram = TieredOram::new(back, 4, TierPolicy::AdmitAll).unwrap();
+        random_workload(&mut oram, 1000);
+        out_of_bounds_addresses_are_rejected(&mut oram, &[]);
+
+        let back = LinearTimeOram::<u64>::new(64).unwrap();
+        assert!(TieredOram::new(back, 0, TierPolicy::AdmitAll).is_err());
+    }
+}
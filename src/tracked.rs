@@ -0,0 +1,191 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! ORAMs that record whether each address has been written, to tell a real previous value from a default one.
+//!
+//! [`Oram::write`] returns the value previously stored at an address, which for an address never written
+//! is `V::default()`, indistinguishable from a default value that was written. An ORAM of [`Tracked`] values
+//! stores a written bit with each value, and [`TrackedOram::write_returning`] returns that bit along with the previous value.
+//! The bit is read and updated within the same single access as the value, by a callback taking time independent of both,
+//! so it is revealed only through the return value.
+//!
+//! The ORAMs in this crate do not themselves track which addresses have been written (see also [`PathOram::migrate`](crate::path_oram::PathOram::migrate)),
+//! so rather than a method on [`Oram`], `write_returning` is provided by [`TrackedOram`] for any ORAM whose values are [`Tracked`],
+//! which carry the written bit in the block itself. [`Oram::write`] keeps returning `V::default()` for unwritten addresses.
+//!
+//! ```
+//! use oram::{tracked::{Tracked, TrackedOram}, DefaultOram, OramError};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut oram = DefaultOram::<Tracked<u64>>::new(64, &mut OsRng)?;
+//! let (existed, previous) = oram.write_returning(3, 0, &mut OsRng)?;
+//! assert!(!bool::from(existed));
+//! assert_eq!(previous, 0);
+//! let (existed, previous) = oram.write_returning(3, 7, &mut OsRng)?;
+//! assert!(bool::from(existed));
+//! assert_eq!(previous, 0);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{checksum::Checksum, Address, Oram, OramBlock, OramError};
+use core::fmt;
+use rand::{CryptoRng, RngCore};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// A value of type `V` and whether it has been written.
+/// The default holds `V::default()` and has not been written, so a newly constructed ORAM holds no written values.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Tracked<V> {
+    value: V,
+    // 1 if the value has been written (and not since deleted), and 0 otherwise.
+    written: u8,
+}
+
+impl<V: OramBlock> Tracked<V> {
+    /// Returns `value`, marked as written.
+    pub fn written(value: V) -> Self {
+        Self { value, written: 1 }
+    }
+
+    /// Returns the value, which is `V::default()` if it has not been written.
+    pub fn value(&self) -> V {
+        self.value
+    }
+
+    /// Returns whether the value has been written, in constant time.
+    pub fn ct_is_written(&self) -> Choice {
+        self.written.ct_eq(&1)
+    }
+}
+
+// A value, and even whether it has been written, are secret, so are not printed.
+impl<V> fmt::Debug for Tracked<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracked").finish_non_exhaustive()
+    }
+}
+
+impl<V: OramBlock> ConditionallySelectable for Tracked<V> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self {
+            value: V::conditional_select(&a.value, &b.value, choice),
+            written: u8::conditional_select(&a.written, &b.written, choice),
+        }
+    }
+
+    fn conditional_assign(&mut self, other: &Self, choice: Choice) {
+        self.value.conditional_assign(&other.value, choice);
+        self.written.conditional_assign(&other.written, choice);
+    }
+
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+        V::conditional_swap(&mut a.value, &mut b.value, choice);
+        u8::conditional_swap(&mut a.written, &mut b.written, choice);
+    }
+}
+
+impl<V: OramBlock> OramBlock for Tracked<V> {
+    fn update_checksum(&self, checksum: &mut Checksum) {
+        checksum.update(&[self.written]);
+        self.value.update_checksum(checksum);
+    }
+}
+
+/// Operations on ORAMs of [`Tracked`] values, returning whether the address accessed had been written.
+/// Implemented for every such [`Oram`]. Each operation is a single access, as physically indistinguishable
+/// from any other as [`Oram::access`] is.
+pub trait TrackedOram<V: OramBlock>: Oram<V = Tracked<V>> {
+    /// Obliviously writes `value` to `index`. Returns whether `index` had been written, and the value previously stored there,
+    /// which is `V::default()` if it had not been.
+    /// Unlike [`Oram::write`], distinguishes a first write from an overwrite of a default value.
+    fn write_returning<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        value: V,
+        rng: &mut R,
+    ) -> Result<(Choice, V), OramError> {
+        let previous = self.write(index, Tracked::written(value), rng)?;
+        Ok((previous.ct_is_written(), previous.value))
+    }
+
+    /// Obliviously reads the value stored at `index`. Returns whether `index` has been written, and the value,
+    /// which is `V::default()` if it has not been.
+    fn read_tracked<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        rng: &mut R,
+    ) -> Result<(Choice, V), OramError> {
+        let stored = self.read(index, rng)?;
+        Ok((stored.ct_is_written(), stored.value))
+    }
+
+    /// Obliviously deletes the value stored at `index`, so that it is as if it had never been written.
+    /// Returns whether `index` had been written, and the value previously stored there, as [`TrackedOram::write_returning`].
+    fn delete<R: RngCore + CryptoRng>(
+        &mut self,
+        index: Address,
+        rng: &mut R,
+    ) -> Result<(Choice, V), OramError> {
+        let previous = self.write(index, Tracked::default(), rng)?;
+        Ok((previous.ct_is_written(), previous.value))
+    }
+}
+
+impl<V: OramBlock, O: Oram<V = Tracked<V>>> TrackedOram<V> for O {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{linear_time_oram::LinearTimeOram, PathOram};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn check_written_bits<O: TrackedOram<u32>>(oram: &mut O) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let unwrap = |(existed, value): (Choice, u32)| (bool::from(existed), value);
+
+        // A first write of the default value is distinguishable from an overwrite.
+        assert_eq!(unwrap(oram.read_tracked(2, &mut rng).unwrap()), (false, 0));
+        assert_eq!(
+            unwrap(oram.write_returning(2, 0, &mut rng).unwrap()),
+            (false, 0)
+        );
+        assert_eq!(
+            unwrap(oram.write_returning(2, 5, &mut rng).unwrap()),
+            (true, 0)
+        );
+        assert_eq!(
+            unwrap(oram.write_returning(2, 6, &mut rng).unwrap()),
+            (true, 5)
+        );
+        assert_eq!(unwrap(oram.read_tracked(2, &mut rng).unwrap()), (true, 6));
+
+        // A deleted address reads as never written, until it is written again.
+        assert_eq!(unwrap(oram.delete(2, &mut rng).unwrap()), (true, 6));
+        assert_eq!(unwrap(oram.read_tracked(2, &mut rng).unwrap()), (false, 0));
+        assert_eq!(unwrap(oram.delete(2, &mut rng).unwrap()), (false, 0));
+        assert_eq!(
+            unwrap(oram.write_returning(2, 9, &mut rng).unwrap()),
+            (false, 0)
+        );
+        assert_eq!(unwrap(oram.read_tracked(2, &mut rng).unwrap()), (true, 9));
+
+        // Other addresses are unaffected.
+        assert_eq!(unwrap(oram.read_tracked(3, &mut rng).unwrap()), (false, 0));
+        assert!(oram.write_returning(64, 1, &mut rng).is_err());
+    }
+
+    #[test]
+    fn written_bits() {
+        let mut rng = StdRng::seed_from_u64(0);
+        check_written_bits(&mut LinearTimeOram::<Tracked<u32>>::new(64).unwrap());
+        check_written_bits(
+            &mut PathOram::<Tracked<u32>, 4, 8>::new_with_parameters(64, &mut rng, 40, 1).unwrap(),
+        );
+    }
+}
@@ -0,0 +1,355 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! All-or-nothing multi-address writes to a Path ORAM.
+//!
+//! [`PathOram::transact`] writes several addresses, e.g., a record together with an index entry, as a unit:
+//! if any of its accesses fails (say, with a `MemoryBudgetExceeded` error on stash growth, or an error from a recursive position map),
+//! the ORAM is rolled back to its state before the transaction, rather than left holding some of the writes and poisoned.
+//!
+//! During a transaction, each level of the position map recursion keeps an undo log: a copy of its stash,
+//! its queue of paths awaiting eviction, its eviction count, and a linear position map, taken when the transaction begins,
+//! together with a copy of each bucket on every path it writes, taken before the path is first written.
+//! A transaction of `k` writes performs exactly the `k` accesses that `k` separate writes would,
+//! so its physical access pattern depends only on `k`. Only a failed transaction also writes back the logged buckets,
+//! all of which lie on paths that its accesses have already revealed.
+//!
+//! ```
+//! use oram::{Oram, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut oram = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1)?;
+//! oram.write(3, 1, &mut rng)?;
+//! let previous = oram.transact(&[(3, 10), (4, 20)], &mut rng)?;
+//! assert_eq!(previous, [1, 0]);
+//! assert_eq!(oram.read(4, &mut rng)?, 20);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    bucket::{Bucket, PathOramBlock, PositionBlock},
+    eviction::EvictionStrategy,
+    path_oram::PathOram,
+    position_map::PositionMap,
+    tree::{CompleteBinaryTreeIndex, TreeIndex},
+    utils::IndexWord,
+    Address, BlockSize, BucketSize, Oram, OramBlock, OramError,
+};
+use alloc::{collections::VecDeque, vec::Vec};
+use rand::{CryptoRng, Rng};
+
+/// The state of one level of a Path ORAM needed to roll back a transaction.
+#[derive(Debug)]
+pub(crate) struct UndoLog<V: OramBlock, const Z: BucketSize, const AB: BlockSize, I: IndexWord> {
+    // The original contents of each bucket written, in the order logged. A bucket may be logged more than once,
+    // so buckets are restored in reverse order, leaving each with the contents it was first logged with.
+    buckets: Vec<(usize, Bucket<V, Z, I>)>,
+    stash: Vec<PathOramBlock<V, I>>,
+    pending_evictions: VecDeque<TreeIndex>,
+    // The count of reverse-lexicographic evictions, which chooses the next eviction path.
+    eviction_count: u64,
+    base_position_map: Option<Vec<PositionBlock<AB>>>,
+}
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Writes `ops[i].1` to address `ops[i].0` for each `i` in order, returning the value each write replaced,
+    /// as `ops.len()` calls to [`Oram::write`] would. If any of the writes fails,
+    /// the ORAM is rolled back to its state before the call, which then returns the error,
+    /// and the ORAM is not poisoned. See the [`transaction`](crate::transaction) module.
+    /// Each of the writes counts toward the [rebuild policy](PathOram::set_rebuild_policy), which is checked once, before the first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressOutOfBoundsError`, without accessing the ORAM, if any address is out of bounds,
+    /// and otherwise any error returned by an access.
+    pub fn transact<R: Rng + CryptoRng>(
+        &mut self,
+        ops: &[(Address, V)],
+        rng: &mut R,
+    ) -> Result<Vec<V>, OramError> {
+        if self.poisoned {
+            return Err(OramError::Poisoned);
+        }
+        self.check_reseeded()?;
+        let capacity = self.block_capacity()?;
+        // As for a single access, this reveals only whether the addresses are well-formed.
+        if let Some((address, _)) = ops.iter().find(|(address, _)| *address >= capacity) {
+            return Err(OramError::AddressOutOfBoundsError {
+                attempted: *address,
+                capacity,
+            });
+        }
+
+        // The rebuild policy is checked once, before the transaction, so that a rebuild is never rolled back.
+        self.rebuild_if_due(rng)?;
+        self.begin_undo_log();
+        let mut previous_values = Vec::with_capacity(ops.len());
+        for (address, value) in ops {
+            let result = self.access_in_bounds(
+                *address,
+                |stash, address, new_position| stash.access(address, new_position, |_| *value),
+                rng,
+            );
+            match result {
+                Ok(previous_value) => {
+                    previous_values.push(previous_value);
+                    self.count_access();
+                }
+                Err(error) => {
+                    self.roll_back();
+                    return Err(error);
+                }
+            }
+        }
+        self.end_undo_log();
+        Ok(previous_values)
+    }
+
+    // Starts an undo log at every level of the position map recursion.
+    fn begin_undo_log(&mut self) {
+        let base_position_map = match &mut self.position_map {
+            PositionMap::Base(linear_oram) => Some(linear_oram.physical_memory.clone()),
+            PositionMap::Recursive(block_oram) => {
+                block_oram.begin_undo_log();
+                None
+            }
+        };
+        self.undo_log = Some(UndoLog {
+            buckets: Vec::new(),
+            stash: self.stash.blocks(),
+            pending_evictions: self.pending_evictions.clone(),
+            eviction_count: self.eviction_count,
+            base_position_map,
+        });
+    }
+
+    // Discards the undo log at every level of the position map recursion.
+    fn end_undo_log(&mut self) {
+        self.undo_log = None;
+        if let PositionMap::Recursive(block_oram) = &mut self.position_map {
+            block_oram.end_undo_log();
+        }
+    }
+
+    // Restores every level of the position map recursion to its state when its undo log began, and discards the log.
+    fn roll_back(&mut self) {
+        if let Some(log) = self.undo_log.take() {
+            for (index, bucket) in log.buckets.into_iter().rev() {
+                self.physical_memory[index] = bucket;
+            }
+            self.stash.restore_blocks(&log.stash);
+            self.pending_evictions = log.pending_evictions;
+            self.eviction_count = log.eviction_count;
+            match &mut self.position_map {
+                PositionMap::Base(linear_oram) => {
+                    if let Some(physical_memory) = log.base_position_map {
+                        linear_oram.physical_memory = physical_memory;
+                    }
+                }
+                PositionMap::Recursive(block_oram) => block_oram.roll_back(),
+            }
+        }
+        self.poisoned = false;
+    }
+
+    /// If an undo log is being kept, logs the buckets on the path to `position`, which is about to be written.
+    pub(crate) fn log_path(&mut self, position: TreeIndex) -> Result<(), OramError> {
+        if let Some(log) = &mut self.undo_log {
+            for depth in 0..=self.height {
+                let index = usize::try_from(position.ct_node_on_path(depth, self.height))?;
+                log.buckets.push((index, self.physical_memory[index]));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eviction::EvictionOrder, leaf_sampler::LeafSampler, stash::ObliviousStash, tree::TreeHeight,
+    };
+    use alloc::{string::ToString, vec};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    type TestOram = PathOram<u64, 4, 8>;
+
+    // A leaf sampler that fails on its `fail_at`th call (counting from 0), and otherwise draws from an RNG.
+    #[derive(Debug)]
+    struct FaultyLeafSampler {
+        rng: StdRng,
+        calls: usize,
+        fail_at: usize,
+    }
+
+    impl LeafSampler for FaultyLeafSampler {
+        fn sample_leaf(&mut self, height: TreeHeight) -> Result<TreeIndex, OramError> {
+            self.calls += 1;
+            if self.calls - 1 == self.fail_at {
+                return Err(OramError::InvalidConfigurationError {
+                    parameter_name: "Injected fault".to_string(),
+                    parameter_value: self.fail_at.to_string(),
+                });
+            }
+            Ok(TreeIndex::random_leaf(height, &mut self.rng)?)
+        }
+
+        fn sample_permutation(&mut self, n: u64) -> Vec<u64> {
+            (0..n).collect()
+        }
+    }
+
+    // Returns a copy of the tree and stash of `oram`.
+    fn state(oram: &TestOram) -> (Vec<Bucket<u64, 4>>, Vec<PathOramBlock<u64>>) {
+        (oram.physical_memory.clone(), oram.stash.blocks())
+    }
+
+    fn assert_contents(oram: &mut TestOram, expected: &[u64], rng: &mut StdRng) {
+        for (address, value) in expected.iter().enumerate() {
+            assert_eq!(oram.read(address as Address, rng).unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn transact_matches_sequential_writes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let mut expected = vec![0; 64];
+        for round in 0..20 {
+            let ops: Vec<(Address, u64)> = (0..5)
+                .map(|i| ((round * 7 + i * 3) % 64, round * 10 + i))
+                .collect();
+            let mut expected_previous = Vec::new();
+            for (address, value) in &ops {
+                expected_previous.push(expected[*address as usize]);
+                expected[*address as usize] = *value;
+            }
+            assert_eq!(oram.transact(&ops, &mut rng).unwrap(), expected_previous);
+        }
+        // A transaction may write an address more than once.
+        assert_eq!(
+            oram.transact(&[(1, 100), (1, 101)], &mut rng).unwrap(),
+            [expected[1], 100]
+        );
+        expected[1] = 101;
+        assert_contents(&mut oram, &expected, &mut rng);
+        assert!(oram.undo_log.is_none());
+    }
+
+    #[test]
+    fn failure_at_each_write_rolls_back() {
+        let ops: Vec<(Address, u64)> = (0..6).map(|i| (i * 11 % 64, 1000 + i)).collect();
+        for fail_at in 64..64 + ops.len() {
+            let mut rng = StdRng::seed_from_u64(0);
+            // The first 64 leaves are drawn while populating the ORAM.
+            let sampler = FaultyLeafSampler {
+                rng: StdRng::seed_from_u64(1),
+                calls: 0,
+                fail_at,
+            };
+            // A recursion cutoff of 1 gives a recursive position map, whose accesses must also be rolled back.
+            let mut oram = TestOram::new_with_leaf_sampler(64, sampler, &mut rng, 40, 1).unwrap();
+            let expected: Vec<u64> = (0..64).collect();
+            for (address, value) in expected.iter().enumerate() {
+                oram.write(address as Address, *value, &mut rng).unwrap();
+            }
+            let before = state(&oram);
+
+            assert!(matches!(
+                oram.transact(&ops, &mut rng),
+                Err(OramError::InvalidConfigurationError { .. })
+            ));
+            assert!(!oram.is_poisoned());
+            assert_eq!(state(&oram), before);
+            assert_contents(&mut oram, &expected, &mut rng);
+        }
+    }
+
+    // Returns the eviction count of each level of the position map recursion of `oram`, from the top.
+    fn eviction_counts<V: OramBlock, E: EvictionStrategy>(oram: &PathOram<V, 4, 8, E>) -> Vec<u64> {
+        let mut counts = vec![oram.eviction_count()];
+        if let PositionMap::Recursive(block_oram) = &oram.position_map {
+            counts.extend(eviction_counts(block_oram));
+        }
+        counts
+    }
+
+    #[test]
+    fn failure_restores_the_eviction_count() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let sampler = FaultyLeafSampler {
+            rng: StdRng::seed_from_u64(1),
+            calls: 0,
+            // The first 20 leaves are drawn by the writes below, and the next two by the transaction.
+            fail_at: 20 + 2,
+        };
+        let mut oram = TestOram::new_with_leaf_sampler(64, sampler, &mut rng, 40, 1).unwrap();
+        oram.set_eviction_order(EvictionOrder::ReverseLexicographic);
+        for address in 0..20 {
+            oram.write(address, address, &mut rng).unwrap();
+        }
+        let before = eviction_counts(&oram);
+        assert_eq!(before[0], 20);
+        assert!(before.len() > 1);
+
+        // The first two writes evict before the third fails.
+        assert!(oram
+            .transact(&[(0, 100), (1, 101), (2, 102)], &mut rng)
+            .is_err());
+        assert_eq!(eviction_counts(&oram), before);
+        let expected: Vec<u64> = (0..64).map(|i| if i < 20 { i } else { 0 }).collect();
+        assert_contents(&mut oram, &expected, &mut rng);
+        assert_eq!(oram.eviction_count(), 20 + 64);
+    }
+
+    #[test]
+    fn stash_growth_failure_rolls_back() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let expected: Vec<u64> = (0..64).map(|i| i * 2).collect();
+        for (address, value) in expected.iter().enumerate() {
+            oram.write(address as Address, *value, &mut rng).unwrap();
+        }
+        // With deferred eviction, each access grows the stash by a slot, which the budget allows only twice.
+        oram.set_max_pending_evictions(8).unwrap();
+        let footprint = oram.memory_footprint().unwrap();
+        let slot_bytes = ObliviousStash::<u64>::bytes_for_len(1).unwrap();
+        oram.set_memory_budget(footprint + 2 * slot_bytes).unwrap();
+        let before = state(&oram);
+
+        let ops: Vec<(Address, u64)> = (0..4).map(|i| (i, 500 + i)).collect();
+        assert!(matches!(
+            oram.transact(&ops, &mut rng),
+            Err(OramError::MemoryBudgetExceeded { .. })
+        ));
+        assert!(!oram.is_poisoned());
+        assert_eq!(state(&oram), before);
+        assert_eq!(oram.pending_evictions(), 0);
+
+        oram.set_max_pending_evictions(0).unwrap();
+        assert_contents(&mut oram, &expected, &mut rng);
+    }
+
+    #[test]
+    fn out_of_bounds_transactions_do_nothing() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        let before = state(&oram);
+        assert!(matches!(
+            oram.transact(&[(0, 1), (64, 2)], &mut rng),
+            Err(OramError::AddressOutOfBoundsError { attempted: 64, .. })
+        ));
+        assert_eq!(state(&oram), before);
+        assert!(!oram.is_poisoned());
+    }
+}
@@ -0,0 +1,478 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Index arithmetic on the complete binary trees underlying tree-based ORAMs,
+//! for use in implementing custom stashes and eviction strategies.
+//!
+//! Nodes are numbered in breadth-first order starting from 1 at the root, so that the children of node `i`
+//! are `2i` and `2i + 1`, and a tree of height `h` has its leaves at `2^h..2^(h + 1)`. 0 is not a valid index.
+//!
+//! Positions of blocks in an ORAM are secret, so the functions here that take a position
+//! (those prefixed with `ct_`, and `parent`, `left_child`, and `right_child`)
+//! take time independent of it, and never branch on it or use it to index memory.
+//! They are built only from shifts, bitwise operations, additions, and `subtle`'s branch-free selections:
+//! in particular, they do not call `leading_zeros`, which targets without a count-leading-zeros instruction
+//! may implement with a loop or a table lookup. (Shifts by a secret amount are assumed to be constant-time,
+//! as they are on every mainstream target.) With the `timing` feature, a test compares their running times
+//! on extreme and random positions.
+
+use rand::{CryptoRng, Rng, RngCore};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+
+use core::num::TryFromIntError;
+
+/// The index of a node in a complete binary tree.
+pub type TreeIndex = u64;
+/// The height of a complete binary tree, or the depth of a node in one. A tree with only a root has height 0.
+pub type TreeHeight = u64;
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::TreeIndex {}
+}
+
+/// Index arithmetic on nodes of a complete binary tree. Implemented for [`TreeIndex`].
+///
+/// Every method assumes that the node it is called on is valid (nonzero), and panics if it is not.
+pub trait CompleteBinaryTreeIndex: sealed::Sealed + Sized {
+    /// Returns the node at `depth` on the path from the root to this node, which must be a leaf of a tree of height `height`.
+    ///
+    /// Constant-time in this node, which is only shifted by the public `height - depth`.
+    fn ct_node_on_path(&self, depth: TreeHeight, height: TreeHeight) -> Self;
+
+    /// Returns a uniformly random leaf of a tree of height `tree_height`.
+    fn random_leaf<R: RngCore + CryptoRng>(
+        tree_height: TreeHeight,
+        rng: &mut R,
+    ) -> Result<Self, TryFromIntError>;
+
+    /// Returns the depth of this node. The root has depth 0.
+    ///
+    /// Constant-time in this node: the depth is found by a fixed sequence of six shift-and-select steps.
+    fn ct_depth(&self) -> TreeHeight;
+
+    /// Returns true if this node is a leaf of a tree of height `height`. Not constant-time.
+    fn is_leaf(&self, height: TreeHeight) -> bool;
+
+    /// Returns the parent of this node. Assumes that this node is not the root.
+    fn parent(&self) -> Self;
+
+    /// Returns the left child of this node.
+    fn left_child(&self) -> Self;
+
+    /// Returns the right child of this node.
+    fn right_child(&self) -> Self;
+
+    /// Returns true if this node is an ancestor of `other`. Every node is an ancestor of itself.
+    ///
+    /// Constant-time in both nodes.
+    fn ct_is_ancestor_of(&self, other: &Self) -> Choice;
+
+    /// As [`CompleteBinaryTreeIndex::ct_is_ancestor_of`], but not constant-time.
+    fn is_ancestor_of(&self, other: &Self) -> bool {
+        self.ct_is_ancestor_of(other).into()
+    }
+
+    /// Returns the deepest common ancestor of this leaf and `other`, which must be leaves of the same tree.
+    ///
+    /// Constant-time in both leaves: as for [`CompleteBinaryTreeIndex::ct_depth`],
+    /// the most significant bit in which they differ is found by a fixed sequence of shift-and-select steps.
+    fn ct_common_ancestor_of_two_leaves(&self, other: &Self) -> Self;
+}
+
+// Returns the number of bits needed to represent `x` (0 for 0), i.e., 64 minus the number of its leading zeros.
+// Halves the range in which the most significant set bit of `x` can lie at each of a fixed six steps, without branching.
+#[inline]
+fn ct_bit_length(x: u64) -> u64 {
+    let mut remaining = x;
+    let mut length = 0;
+    for shift in [32, 16, 8, 4, 2, 1] {
+        let high_bits_are_set = !(remaining >> shift).ct_eq(&0);
+        let step = u64::conditional_select(&0, &shift, high_bits_are_set);
+        remaining >>= step;
+        length += step;
+    }
+    // `remaining` is now the most significant set bit of `x`, if any, shifted down to bit 0.
+    length + remaining
+}
+
+impl CompleteBinaryTreeIndex for TreeIndex {
+    // A TreeIndex can have any nonzero value.
+    #[inline]
+    fn ct_node_on_path(&self, depth: TreeHeight, height: TreeHeight) -> Self {
+        // We maintain the invariant that all TreeIndex values are nonzero.
+        assert_ne!(*self, 0);
+        // We only call this method when the receiver is a leaf.
+        // This check branches on the depth of the receiver, but the branch is taken only if the invariant is broken.
+        assert!(self.is_leaf(height));
+
+        let shift = height - depth;
+        self >> shift
+    }
+
+    fn random_leaf<R: RngCore + CryptoRng>(
+        tree_height: TreeHeight,
+        rng: &mut R,
+    ) -> Result<Self, TryFromIntError> {
+        let tree_height: u32 = tree_height.try_into()?;
+        let result = 2u64.pow(tree_height) + rng.gen_range(0..2u64.pow(tree_height));
+        // The value we've just generated is at least the first summand, which is at least 1.
+        assert_ne!(result, 0);
+        Ok(result)
+    }
+
+    #[inline]
+    fn ct_depth(&self) -> TreeHeight {
+        // We maintain the invariant that all TreeIndex values are nonzero.
+        assert_ne!(*self, 0);
+
+        ct_bit_length(*self) - 1
+    }
+
+    fn is_leaf(&self, height: TreeHeight) -> bool {
+        // We maintain the invariant that all TreeIndex values are nonzero.
+        assert_ne!(*self, 0);
+
+        self.ct_depth() == height
+    }
+
+    fn parent(&self) -> Self {
+        assert_ne!(*self, 0);
+        self >> 1
+    }
+
+    fn left_child(&self) -> Self {
+        assert_ne!(*self, 0);
+        self << 1
+    }
+
+    fn right_child(&self) -> Self {
+        assert_ne!(*self, 0);
+        (self << 1) | 1
+    }
+
+    #[inline]
+    fn ct_is_ancestor_of(&self, other: &Self) -> Choice {
+        let depth = self.ct_depth();
+        let other_depth = other.ct_depth();
+        // If `other` is shallower, the shift is meaningless, but its result is discarded.
+        let shift = other_depth.wrapping_sub(depth) & (u64::BITS - 1) as u64;
+        !other_depth.ct_lt(&depth) & (other >> shift).ct_eq(self)
+    }
+
+    #[inline]
+    fn ct_common_ancestor_of_two_leaves(&self, other: &Self) -> Self {
+        assert_ne!(*self, 0);
+        assert_ne!(*other, 0);
+        // The paths to two leaves at the same depth diverge below the most significant bit in which the leaves differ.
+        let differing_bits = ct_bit_length(self ^ other);
+        // Shifting by 64 is not allowed, but two valid leaves cannot differ in their top bit.
+        self >> (differing_bits & u64::from(u64::BITS - 1))
+    }
+}
+
+/// Returns the `counter`th leaf (modulo the number of leaves) of a tree of height `height`
+/// in reverse-lexicographic order, i.e., the leaf whose path is given by the bits of `counter` from least to most significant.
+/// Consecutive leaves in this order have paths that diverge as close to the root as possible.
+pub fn reverse_lexicographic_leaf(counter: u64, height: TreeHeight) -> TreeIndex {
+    if height == 0 {
+        return 1;
+    }
+    let reversed = counter.reverse_bits() >> (u64::BITS - u32::try_from(height).unwrap());
+    (1 << height) | reversed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use static_assertions::const_assert_eq;
+    use std::collections::BTreeSet;
+    use std::mem::size_of;
+    use std::vec::Vec;
+
+    const MAX_HEIGHT: TreeHeight = 4;
+
+    // A tree of height `MAX_HEIGHT` built by explicitly linking nodes numbered in breadth-first order.
+    struct ReferenceTree {
+        // `parents[i]` and `children[i]` are the parent and children of node `i`. Node 0 is unused.
+        parents: Vec<TreeIndex>,
+        children: Vec<Option<(TreeIndex, TreeIndex)>>,
+    }
+
+    impl ReferenceTree {
+        fn new() -> Self {
+            let number_of_nodes = (1 << (MAX_HEIGHT + 1)) - 1;
+            let mut parents = vec![0; number_of_nodes + 1];
+            let mut children = vec![None; number_of_nodes + 1];
+            // Hand out node numbers to the children of each node in turn, in breadth-first order.
+            let mut next = 2;
+            for (node, node_children) in children.iter_mut().enumerate().skip(1) {
+                if next < number_of_nodes {
+                    *node_children = Some((next as TreeIndex, next as TreeIndex + 1));
+                    parents[next] = node as TreeIndex;
+                    parents[next + 1] = node as TreeIndex;
+                    next += 2;
+                }
+            }
+            Self { parents, children }
+        }
+
+        // Returns the path from `node` up to the root, starting with `node`.
+        fn ancestors(&self, mut node: TreeIndex) -> Vec<TreeIndex> {
+            let mut ancestors = vec![node];
+            while node != 1 {
+                node = self.parents[node as usize];
+                ancestors.push(node);
+            }
+            ancestors
+        }
+
+        fn depth(&self, node: TreeIndex) -> TreeHeight {
+            self.ancestors(node).len() as TreeHeight - 1
+        }
+
+        fn nodes(&self) -> impl Iterator<Item = TreeIndex> {
+            1..self.parents.len() as TreeIndex
+        }
+
+        fn leaves(&self, height: TreeHeight) -> Vec<TreeIndex> {
+            self.nodes()
+                .filter(|node| self.depth(*node) == height)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn check_size_of_tree_index() {
+        const_assert_eq!(size_of::<TreeIndex>(), 8);
+    }
+
+    #[test]
+    fn depth_and_leaves_match_reference() {
+        let tree = ReferenceTree::new();
+        for node in tree.nodes() {
+            assert_eq!(node.ct_depth(), tree.depth(node));
+            for height in 0..=MAX_HEIGHT {
+                assert_eq!(node.is_leaf(height), tree.depth(node) == height);
+            }
+        }
+    }
+
+    #[test]
+    fn parent_and_children_match_reference() {
+        let tree = ReferenceTree::new();
+        for node in tree.nodes() {
+            if node != 1 {
+                assert_eq!(node.parent(), tree.parents[node as usize]);
+            }
+            if let Some((left, right)) = tree.children[node as usize] {
+                assert_eq!(node.left_child(), left);
+                assert_eq!(node.right_child(), right);
+            }
+        }
+    }
+
+    #[test]
+    fn ancestors_match_reference() {
+        let tree = ReferenceTree::new();
+        for node in tree.nodes() {
+            let ancestors: BTreeSet<_> = tree.ancestors(node).into_iter().collect();
+            for other in tree.nodes() {
+                assert_eq!(other.is_ancestor_of(&node), ancestors.contains(&other));
+                assert_eq!(
+                    bool::from(other.ct_is_ancestor_of(&node)),
+                    ancestors.contains(&other)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn node_on_path_matches_reference() {
+        let tree = ReferenceTree::new();
+        for height in 0..=MAX_HEIGHT {
+            for leaf in tree.leaves(height) {
+                // The path from the root down to `leaf`.
+                let path: Vec<_> = tree.ancestors(leaf).into_iter().rev().collect();
+                for depth in 0..=height {
+                    assert_eq!(leaf.ct_node_on_path(depth, height), path[depth as usize]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn common_ancestor_matches_reference() {
+        let tree = ReferenceTree::new();
+        for height in 0..=MAX_HEIGHT {
+            let leaves = tree.leaves(height);
+            for a in &leaves {
+                for b in &leaves {
+                    let common_ancestors: Vec<_> = tree
+                        .ancestors(*a)
+                        .into_iter()
+                        .filter(|node| tree.ancestors(*b).contains(node))
+                        .collect();
+                    let deepest = *common_ancestors
+                        .iter()
+                        .max_by_key(|node| tree.depth(**node))
+                        .unwrap();
+                    assert_eq!(a.ct_common_ancestor_of_two_leaves(b), deepest);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bit_length_matches_leading_zeros() {
+        let mut values: Vec<u64> = (0..1 << 12).collect();
+        for shift in 0..64 {
+            let power = 1u64 << shift;
+            values.extend([
+                power - 1,
+                power,
+                power + 1,
+                power | (power - 1),
+                u64::MAX >> shift,
+            ]);
+        }
+        for value in values {
+            assert_eq!(
+                ct_bit_length(value),
+                u64::from(u64::BITS - value.leading_zeros())
+            );
+        }
+    }
+
+    // Checks the `ct_` functions against direct definitions on every leaf (and pair of leaves) of trees up to height 7,
+    // and on the extreme leaves of taller trees.
+    #[test]
+    fn ct_functions_match_definitions_exhaustively() {
+        let mut cases: Vec<(TreeHeight, Vec<TreeIndex>)> = (0..=7)
+            .map(|height| (height, ((1 << height)..(2 << height)).collect()))
+            .collect();
+        for height in [31, 32, 62, 63] {
+            let first: TreeIndex = 1 << height;
+            let last = first | (first - 1);
+            cases.push((
+                height,
+                vec![first, first + 1, first | (first >> 1), last - 1, last],
+            ));
+        }
+
+        for (height, leaves) in cases {
+            for &leaf in &leaves {
+                assert_eq!(leaf.ct_depth(), height);
+                for depth in 0..=height {
+                    let node = leaf.ct_node_on_path(depth, height);
+                    assert_eq!(node.ct_depth(), depth);
+                    assert!(node.is_ancestor_of(&leaf));
+                }
+                for &other in &leaves {
+                    // The deepest node on the path to `leaf` that is also on the path to `other`.
+                    let expected = (0..=height)
+                        .rev()
+                        .map(|depth| leaf.ct_node_on_path(depth, height))
+                        .find(|node| node.is_ancestor_of(&other))
+                        .unwrap();
+                    assert_eq!(leaf.ct_common_ancestor_of_two_leaves(&other), expected);
+                }
+            }
+        }
+    }
+
+    // Compares, by Welch's t-test, the running times of the `ct_` functions on extreme positions
+    // (the first and last leaves, and pairs of leaves diverging at the root or not at all) and on random positions.
+    // A t statistic far from 0 suggests a data-dependent running time. Meaningful only in an optimized build on a quiet machine:
+    // `cargo test --release --features timing --lib timing`.
+    #[cfg(feature = "timing")]
+    #[test]
+    fn timing_is_independent_of_positions() {
+        use core::hint::black_box;
+        use rand::Rng;
+        use std::time::Instant;
+
+        const HEIGHT: TreeHeight = 40;
+        const SAMPLES: usize = 20_000;
+        const BATCH: usize = 64;
+
+        // Returns the time taken by `BATCH` calls of each `ct_` function on `leaf` and `other`.
+        fn time(leaf: TreeIndex, other: TreeIndex) -> f64 {
+            let start = Instant::now();
+            for _ in 0..BATCH {
+                let leaf = black_box(leaf);
+                let other = black_box(other);
+                black_box(leaf.ct_depth());
+                black_box(leaf.ct_node_on_path(black_box(HEIGHT / 2), HEIGHT));
+                black_box(leaf.ct_common_ancestor_of_two_leaves(&other));
+                black_box(leaf.ct_is_ancestor_of(&other));
+            }
+            start.elapsed().as_nanos() as f64
+        }
+
+        // Returns the t statistic of Welch's test for a difference between the means of `a` and `b`.
+        fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+            let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+            let variance = |x: &[f64], m: f64| {
+                x.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (x.len() - 1) as f64
+            };
+            let (mean_a, mean_b) = (mean(a), mean(b));
+            let (variance_a, variance_b) = (variance(a, mean_a), variance(b, mean_b));
+            (mean_a - mean_b) / (variance_a / a.len() as f64 + variance_b / b.len() as f64).sqrt()
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let first: TreeIndex = 1 << HEIGHT;
+        let last = first | (first - 1);
+        let extremes = [(first, first), (last, last), (first, last), (last, first)];
+
+        // Interleave the two classes at random, so that drift in the machine's speed affects both alike.
+        let mut extreme_times = Vec::with_capacity(SAMPLES);
+        let mut random_times = Vec::with_capacity(SAMPLES);
+        for sample in 0..2 * SAMPLES {
+            if rng.gen() {
+                let (leaf, other) = extremes[sample % extremes.len()];
+                extreme_times.push(time(leaf, other));
+            } else {
+                let leaf = TreeIndex::random_leaf(HEIGHT, &mut rng).unwrap();
+                let other = TreeIndex::random_leaf(HEIGHT, &mut rng).unwrap();
+                random_times.push(time(leaf, other));
+            }
+        }
+
+        // Discard the slowest tenth of each class, which is dominated by interrupts and preemption.
+        for times in [&mut extreme_times, &mut random_times] {
+            times.sort_by(f64::total_cmp);
+            times.truncate(times.len() * 9 / 10);
+        }
+        let t = welch_t(&extreme_times, &random_times);
+        // The threshold used by dudect to flag a leak with high confidence.
+        assert!(t.abs() < 10.0, "t = {t}");
+    }
+
+    #[test]
+    fn random_leaf_covers_leaves() {
+        let tree = ReferenceTree::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        for height in 0..=MAX_HEIGHT {
+            let sampled: BTreeSet<_> = (0..1000)
+                .map(|_| TreeIndex::random_leaf(height, &mut rng).unwrap())
+                .collect();
+            let leaves: BTreeSet<_> = tree.leaves(height).into_iter().collect();
+            assert_eq!(sampled, leaves);
+        }
+    }
+
+    #[test]
+    fn test_reverse_lexicographic_leaf() {
+        assert_eq!(reverse_lexicographic_leaf(5, 0), 1);
+        let order: Vec<TreeIndex> = (0..8).map(|g| reverse_lexicographic_leaf(g, 3)).collect();
+        assert_eq!(order, vec![8, 12, 10, 14, 9, 13, 11, 15]);
+        assert_eq!(reverse_lexicographic_leaf(8, 3), 8);
+    }
+}
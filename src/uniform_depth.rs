@@ -0,0 +1,263 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Padding of the position map recursion of a Path ORAM to a uniform depth.
+//!
+//! The number of levels of a Path ORAM's position map recursion (see [`PathOram::recursion_profile`]) depends on its capacity,
+//! so if an application serves accesses from several ORAMs of different capacities, the number of levels an access touches
+//! can reveal which ORAM it was made to. After [`PathOram::set_uniform_recursion_depth`], every access to a Path ORAM
+//! touches the same number of levels, `target_levels`, whatever its capacity: the levels the recursion lacks are made up by
+//! *padding levels*, each a Path ORAM of capacity 2 (a tree of one bucket) to which every access makes a dummy access,
+//! reading and rewriting its one path without consulting a position map. Two ORAMs padded to the same number of levels,
+//! with the same `Z`, therefore make the same number of Path ORAM accesses, and the same number of linear scans, per access.
+//!
+//! Padding equalizes the number of levels, not their sizes: the trees of the ORAMs and of their position maps,
+//! and so the number of blocks an access reads and writes at each level, still depend on the capacities.
+//!
+//! ```
+//! use oram::{Oram, OramError, PathOram};
+//! use rand::rngs::OsRng;
+//!
+//! # fn main() -> Result<(), OramError> {
+//! let mut rng = OsRng;
+//! let mut small = PathOram::<u64, 4, 8>::new_with_parameters(64, &mut rng, 40, 1)?;
+//! let mut large = PathOram::<u64, 4, 8>::new_with_parameters(4096, &mut rng, 40, 1)?;
+//! assert_eq!(small.recursion_profile()?.len(), 2);
+//! assert_eq!(large.recursion_profile()?.len(), 4);
+//!
+//! small.set_uniform_recursion_depth(4, &mut rng)?;
+//! large.set_uniform_recursion_depth(4, &mut rng)?;
+//! assert_eq!(small.recursion_levels_per_access()?, large.recursion_levels_per_access()?);
+//! small.write(3, 42, &mut rng)?;
+//! assert_eq!(small.read(3, &mut rng)?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    bucket::PositionBlock, eviction::EvictionStrategy, path_oram::PathOram, utils::IndexWord,
+    BlockSize, BucketSize, OramBlock, OramError,
+};
+use alloc::{string::ToString, vec::Vec};
+use rand::{CryptoRng, Rng};
+
+/// The ORAM whose tree a padding level accesses.
+pub(crate) type PaddingOram<const Z: BucketSize, const AB: BlockSize> =
+    PathOram<PositionBlock<AB>, Z, AB>;
+
+impl<V: OramBlock, const Z: BucketSize, const AB: BlockSize, E: EvictionStrategy, I: IndexWord>
+    PathOram<V, Z, AB, E, I>
+{
+    /// Pads the position map recursion of this ORAM with dummy levels, so that every access touches `target_levels` levels
+    /// (see the [`uniform_depth`](crate::uniform_depth) module). Replaces any padding set before.
+    /// A `target_levels` equal to the number of levels of the [`PathOram::recursion_profile`] removes the padding.
+    ///
+    /// Each padding level adds `Z` blocks read and written to every access, and `Z` blocks to the stash
+    /// (as counted by [`PathOram::expected_physical_accesses_per_op`] and [`PathOram::memory_footprint`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidConfigurationError` if `target_levels` is less than the number of levels of the recursion,
+    /// in which case the padding is left unchanged.
+    pub fn set_uniform_recursion_depth<R: Rng + CryptoRng>(
+        &mut self,
+        target_levels: usize,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        let natural_levels = self.recursion_profile()?.len();
+        if target_levels < natural_levels {
+            return Err(OramError::InvalidConfigurationError {
+                parameter_name: "Uniform recursion depth (in levels)".to_string(),
+                parameter_value: target_levels.to_string(),
+            });
+        }
+        let mut padding = Vec::with_capacity(target_levels - natural_levels);
+        for _ in natural_levels..target_levels {
+            padding.push(PaddingOram::new_with_parameters(
+                2,
+                rng,
+                self.overflow_size,
+                1,
+            )?);
+        }
+        // Padding levels draw their leaves through their own reseeders, as the levels of the recursion do.
+        #[cfg(feature = "std")]
+        if let Some(interval) = self.reseed_interval() {
+            for padding_oram in &mut padding {
+                padding_oram.set_reseed_interval(interval)?;
+            }
+        }
+        self.recursion_padding = padding;
+        Ok(())
+    }
+
+    /// Returns the number of levels of the position map recursion that each access touches:
+    /// the levels of the [`PathOram::recursion_profile`], and any padding levels set by [`PathOram::set_uniform_recursion_depth`].
+    pub fn recursion_levels_per_access(&self) -> Result<usize, OramError> {
+        Ok(self.recursion_profile()?.len() + self.recursion_padding.len())
+    }
+
+    // Makes a dummy access to each padding level, reading its one path into its stash and evicting it back.
+    pub(crate) fn access_recursion_padding<R: Rng + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<(), OramError> {
+        for padding_oram in &mut self.recursion_padding {
+            let leaf = padding_oram.sample_new_position(rng)?;
+            padding_oram
+                .stash
+                .read_from_path(&mut padding_oram.physical_memory, leaf)?;
+            padding_oram
+                .stash
+                .write_to_path(&mut padding_oram.physical_memory, leaf)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{position_map::PositionMap, test_utils::*, Address, Oram};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    type TestOram = PathOram<u64, 4, 8>;
+
+    // The numbers of Path ORAM accesses and linear scans that `oram` has made so far to its own tree,
+    // its position map recursion, and its padding levels, identified by which of their physical read counters have advanced.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct LevelsTouched {
+        path_accesses: usize,
+        linear_scans: usize,
+    }
+
+    fn read_counters(oram: &TestOram) -> (Vec<u64>, Vec<u64>) {
+        let mut path_reads = vec![oram.stash.physical_reads];
+        let mut linear_reads = Vec::new();
+        let mut position_map = &oram.position_map;
+        loop {
+            match position_map {
+                PositionMap::Base(linear_oram) => {
+                    linear_reads.push(linear_oram.physical_reads);
+                    break;
+                }
+                PositionMap::Recursive(block_oram) => {
+                    path_reads.push(block_oram.stash.physical_reads);
+                    position_map = &block_oram.position_map;
+                }
+            }
+        }
+        for padding_oram in &oram.recursion_padding {
+            path_reads.push(padding_oram.stash.physical_reads);
+        }
+        (path_reads, linear_reads)
+    }
+
+    fn levels_touched_by_read(
+        oram: &mut TestOram,
+        address: Address,
+        rng: &mut StdRng,
+    ) -> LevelsTouched {
+        let (path_before, linear_before) = read_counters(oram);
+        oram.read(address, rng).unwrap();
+        let (path_after, linear_after) = read_counters(oram);
+        let advanced = |before: Vec<u64>, after: Vec<u64>| {
+            before.iter().zip(&after).filter(|(b, a)| a > b).count()
+        };
+        LevelsTouched {
+            path_accesses: advanced(path_before, path_after),
+            linear_scans: advanced(linear_before, linear_after),
+        }
+    }
+
+    #[test]
+    fn padded_orams_of_different_capacities_touch_the_same_levels() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // A linear position map of 8 blocks, and recursive position maps of 512, 64, and 8 blocks over a linear map of 1.
+        let mut small = TestOram::new_with_parameters(64, &mut rng, 40, 16384).unwrap();
+        let mut large = TestOram::new_with_parameters(4096, &mut rng, 40, 1).unwrap();
+        assert_eq!(small.recursion_levels_per_access().unwrap(), 1);
+        assert_eq!(large.recursion_levels_per_access().unwrap(), 4);
+        assert_ne!(
+            levels_touched_by_read(&mut small, 0, &mut rng),
+            levels_touched_by_read(&mut large, 0, &mut rng)
+        );
+
+        small.set_uniform_recursion_depth(4, &mut rng).unwrap();
+        large.set_uniform_recursion_depth(4, &mut rng).unwrap();
+        assert_eq!(small.recursion_levels_per_access().unwrap(), 4);
+        for address in 0..64 {
+            let small_levels = levels_touched_by_read(&mut small, address, &mut rng);
+            let large_levels = levels_touched_by_read(&mut large, address * 64, &mut rng);
+            assert_eq!(small_levels, large_levels);
+            assert_eq!(
+                small_levels,
+                LevelsTouched {
+                    path_accesses: 4,
+                    linear_scans: 1
+                }
+            );
+        }
+
+        // Padding adds `Z` blocks to every access, as the expected cost reports.
+        let expected = small.expected_physical_accesses_per_op().unwrap();
+        assert_eq!(expected.position_map_reads, 8 + 3 * 4);
+        let before: u64 = read_counters(&small).0[1..].iter().sum::<u64>()
+            + read_counters(&small).1.iter().sum::<u64>();
+        small.read(1, &mut rng).unwrap();
+        let after: u64 = read_counters(&small).0[1..].iter().sum::<u64>()
+            + read_counters(&small).1.iter().sum::<u64>();
+        assert_eq!(after - before, expected.position_map_reads);
+    }
+
+    #[test]
+    fn padded_orams_remain_correct() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_uniform_recursion_depth(5, &mut rng).unwrap();
+        random_workload(&mut oram, 500);
+
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_uniform_recursion_depth(5, &mut rng).unwrap();
+        oram.set_max_pending_evictions(3).unwrap();
+        random_workload(&mut oram, 500);
+    }
+
+    #[test]
+    fn target_below_the_natural_depth_is_rejected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(4096, &mut rng, 40, 1).unwrap();
+        oram.set_uniform_recursion_depth(6, &mut rng).unwrap();
+        assert!(matches!(
+            oram.set_uniform_recursion_depth(3, &mut rng),
+            Err(OramError::InvalidConfigurationError { .. })
+        ));
+        assert_eq!(oram.recursion_levels_per_access().unwrap(), 6);
+
+        // Setting the natural depth removes the padding.
+        oram.set_uniform_recursion_depth(4, &mut rng).unwrap();
+        assert!(oram.recursion_padding.is_empty());
+    }
+
+    #[test]
+    fn padding_survives_snapshots_and_copies() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut oram = TestOram::new_with_parameters(64, &mut rng, 40, 1).unwrap();
+        oram.set_uniform_recursion_depth(4, &mut rng).unwrap();
+        oram.write(5, 55, &mut rng).unwrap();
+
+        let mut restored = TestOram::import_snapshot(oram.export_snapshot().unwrap()).unwrap();
+        assert_eq!(restored.recursion_levels_per_access().unwrap(), 4);
+        assert_eq!(restored.read(5, &mut rng).unwrap(), 55);
+
+        let mut frozen = oram.freeze(&mut rng).unwrap();
+        assert_eq!(frozen.recursion_levels_per_access().unwrap(), 4);
+        assert_eq!(frozen.read(5, &mut rng).unwrap(), 55);
+        let migrated = oram.migrate(|value| value + 1, &mut rng).unwrap();
+        assert_eq!(migrated.recursion_levels_per_access().unwrap(), 4);
+    }
+}
@@ -8,148 +8,156 @@
 //! Utilities.
 
 use crate::OramError;
-use rand::seq::SliceRandom;
-use rand::{CryptoRng, Rng, RngCore};
+use alloc::vec::Vec;
 
-use subtle::{Choice, ConditionallySelectable, ConstantTimeGreater, ConstantTimeLess};
+use subtle::{ConditionallySelectable, ConstantTimeEq, ConstantTimeGreater, ConstantTimeLess};
 
-use std::num::TryFromIntError;
+use core::{fmt::Debug, num::TryFromIntError};
 
-pub(crate) type TreeIndex = u64;
-pub(crate) type TreeHeight = u64;
-
-pub(crate) trait CompleteBinaryTreeIndex
-where
-    Self: Sized,
-{
-    fn ct_node_on_path(&self, depth: TreeHeight, height: TreeHeight) -> Self;
-    fn random_leaf<R: RngCore + CryptoRng>(
-        tree_height: TreeHeight,
-        rng: &mut R,
-    ) -> Result<Self, TryFromIntError>;
-    fn ct_depth(&self) -> TreeHeight;
-    fn is_leaf(&self, height: TreeHeight) -> bool;
-}
-
-impl CompleteBinaryTreeIndex for TreeIndex {
-    // A TreeIndex can have any nonzero value.
-    fn ct_node_on_path(&self, depth: TreeHeight, height: TreeHeight) -> Self {
-        // We maintain the invariant that all TreeIndex values are nonzero.
-        assert_ne!(*self, 0);
-        // We only call this method when the receiver is a leaf.
-        assert!(self.is_leaf(height));
-
-        let shift = height - depth;
-        self >> shift
-    }
-
-    fn random_leaf<R: RngCore + CryptoRng>(
-        tree_height: TreeHeight,
-        rng: &mut R,
-    ) -> Result<Self, TryFromIntError> {
-        let tree_height: u32 = tree_height.try_into()?;
-        let result = 2u64.pow(tree_height) + rng.gen_range(0..2u64.pow(tree_height));
-        // The value we've just generated is at least the first summand, which is at least 1.
-        assert_ne!(result, 0);
-        Ok(result)
+mod sealed {
+    pub trait Sealed: Sized {
+        /// Sets `matches[i]` to 1 if `words[i] == target` and to 0 otherwise, without branching on any word.
+        /// Assumes that `matches.len() == words.len()`.
+        fn ct_eq_each(words: &[Self], target: Self, matches: &mut [u8]);
     }
 
-    fn ct_depth(&self) -> TreeHeight {
-        // We maintain the invariant that all TreeIndex values are nonzero.
-        assert_ne!(*self, 0);
-
-        let leading_zeroes: u64 = self.leading_zeros().into();
-        let index_bitlength = 64;
-        index_bitlength - leading_zeroes - 1
+    // Compares `$lanes` words at a time. With the `simd` feature, each group of lanes is compared with a few vector operations;
+    // otherwise, the comparisons are written branch-free in a form the compiler can vectorize.
+    macro_rules! impl_sealed {
+        ($word: ty, $lanes: literal) => {
+            impl Sealed for $word {
+                fn ct_eq_each(words: &[Self], target: Self, matches: &mut [u8]) {
+                    let mut word_chunks = words.chunks_exact($lanes);
+                    let mut match_chunks = matches.chunks_exact_mut($lanes);
+                    for (chunk, match_chunk) in (&mut word_chunks).zip(&mut match_chunks) {
+                        #[cfg(feature = "simd")]
+                        {
+                            use core::simd::Simd;
+                            // As `ct_is_zero`, on all lanes at once.
+                            let x = Simd::<$word, $lanes>::from_slice(chunk) ^ Simd::splat(target);
+                            let nonzero = (x | (Simd::splat(0) - x))
+                                >> Simd::splat((<$word>::BITS - 1) as $word);
+                            let equal = nonzero ^ Simd::splat(1);
+                            for (m, lane) in match_chunk.iter_mut().zip(equal.to_array()) {
+                                *m = lane as u8;
+                            }
+                        }
+                        #[cfg(not(feature = "simd"))]
+                        for (m, word) in match_chunk.iter_mut().zip(chunk) {
+                            *m = ct_is_zero(word ^ target);
+                        }
+                    }
+                    for (m, word) in match_chunks
+                        .into_remainder()
+                        .iter_mut()
+                        .zip(word_chunks.remainder())
+                    {
+                        *m = ct_is_zero(word ^ target);
+                    }
+                }
+            }
+        };
     }
 
-    fn is_leaf(&self, height: TreeHeight) -> bool {
-        // We maintain the invariant that all TreeIndex values are nonzero.
-        assert_ne!(*self, 0);
+    impl_sealed!(u32, 8);
+    impl_sealed!(u64, 8);
 
-        self.ct_depth() == height
+    // Returns 1 if `x == 0` and 0 otherwise: `x | -x` has its top bit set exactly when `x` is nonzero.
+    fn ct_is_zero<W: Into<u64>>(x: W) -> u8 {
+        let x: u64 = x.into();
+        (((x | x.wrapping_neg()) >> 63) ^ 1) as u8
     }
 }
 
-/// Sorts `items` in ascending order of `keys`, obliviously and in constant time.
-/// Assumes that `keys.len() == items.len()`.
-/// The algorithm is bitonic sort, based on code written by Hans Werner Lang
-/// and available [here](https://hwlang.de/algorithmen/sortieren/bitonic/oddn.htm).
-pub(crate) fn bitonic_sort_by_keys<
-    T: ConditionallySelectable,
-    K: Ord + ConditionallySelectable + ConstantTimeGreater + ConstantTimeLess,
->(
-    items: &mut [T],
-    keys: &mut [K],
-) {
-    let ascending: Choice = 1.into();
-    helper_bitonic_sort_by_keys(0, items.len(), items, keys, ascending);
+/// The unsigned integer type in which Path ORAM blocks store their metadata (their address and their position in the tree).
+/// Implemented for `u64`, the default, and `u32`, which halves the metadata of each block
+/// but limits the ORAM capacity to `u32::MAX` blocks.
+///
+/// Tree arithmetic is carried out on positions widened to `u64`; only their storage uses the narrower type.
+pub trait IndexWord:
+    sealed::Sealed
+    + Copy
+    + Debug
+    + Default
+    + Eq
+    + Ord
+    + Into<u64>
+    + ConditionallySelectable
+    + ConstantTimeEq
+    + ConstantTimeGreater
+    + ConstantTimeLess
+    + Send
+    + Sync
+    + 'static
+{
+    /// The value 0, marking the position of a dummy block.
+    const ZERO: Self;
+    /// The largest value of the type, marking the address of a dummy block.
+    const MAX: Self;
+    /// The width of the type in bits.
+    const BITS: u32;
+
+    /// Converts `value` to this type, failing if it does not fit.
+    fn from_u64(value: u64) -> Result<Self, TryFromIntError>;
 }
 
-fn helper_bitonic_sort_by_keys<
-    T: ConditionallySelectable,
-    K: Ord + ConditionallySelectable + ConstantTimeGreater + ConstantTimeLess,
->(
-    lo: usize,
-    n: usize,
-    items: &mut [T],
-    keys: &mut [K],
-    direction: Choice,
-) {
-    if n > 1 {
-        let m = n / 2;
-        helper_bitonic_sort_by_keys(lo, m, items, keys, !direction);
-        helper_bitonic_sort_by_keys(lo + m, n - m, items, keys, direction);
-        helper_bitonic_merge_by_keys(lo, n, items, keys, direction);
+impl IndexWord for u32 {
+    const ZERO: Self = 0;
+    const MAX: Self = u32::MAX;
+    const BITS: u32 = u32::BITS;
+
+    fn from_u64(value: u64) -> Result<Self, TryFromIntError> {
+        u32::try_from(value)
     }
 }
 
-fn helper_bitonic_merge_by_keys<
-    T: ConditionallySelectable,
-    K: Ord + ConditionallySelectable + ConstantTimeGreater + ConstantTimeLess,
->(
-    lo: usize,
-    n: usize,
-    items: &mut [T],
-    keys: &mut [K],
-    direction: Choice,
-) {
-    if n > 1 {
-        let m = n.next_power_of_two() >> 1;
-        for i in lo..(lo + n - m) {
-            let j = i + m;
-            let jlti = keys[j].ct_lt(&keys[i]);
-            let do_swap = !(jlti ^ direction);
-            let (items_i, items_j) = items.split_at_mut(i + 1);
-            T::conditional_swap(&mut items_i[i], &mut items_j[j - (i + 1)], do_swap);
-            let (keys_i, keys_j) = keys.split_at_mut(i + 1);
-            K::conditional_swap(&mut keys_i[i], &mut keys_j[j - (i + 1)], do_swap);
-        }
+impl IndexWord for u64 {
+    const ZERO: Self = 0;
+    const MAX: Self = u64::MAX;
+    const BITS: u32 = u64::BITS;
 
-        helper_bitonic_merge_by_keys(lo, m, items, keys, direction);
-        helper_bitonic_merge_by_keys(lo + m, n - m, items, keys, direction);
+    fn from_u64(value: u64) -> Result<Self, TryFromIntError> {
+        Ok(value)
     }
 }
 
-/// Returns a random permutation of 0 through n.
-pub(crate) fn random_permutation_of_0_through_n_exclusive<R: RngCore + CryptoRng>(
-    n: u64,
-    rng: &mut R,
-) -> Vec<u64> {
-    let permuted_addresses = 0..n;
-    let mut permuted_addresses = Vec::from_iter(permuted_addresses);
-    let permuted_addresses = permuted_addresses.as_mut_slice();
-    permuted_addresses.shuffle(rng);
-    Vec::from(permuted_addresses)
+/// Sets `matches[i]` to 1 if `words[i] == target` and to 0 otherwise, in constant time.
+/// With the `simd` feature, several words are compared per vector instruction.
+pub(crate) fn ct_eq_each<I: IndexWord>(words: &[I], target: I, matches: &mut [u8]) {
+    debug_assert_eq!(words.len(), matches.len());
+    I::ct_eq_each(words, target, matches);
 }
 
-/// Given a permutation, inverts it using oblivious (data-independent) operations.
-pub(crate) fn invert_permutation_oblivious(permutation: &[u64]) -> Result<Vec<u64>, OramError> {
-    let n: u64 = permutation.len().try_into()?;
-    let mut copied = permutation.to_owned();
-    let mut result = Vec::from_iter(0u64..n);
-    bitonic_sort_by_keys(&mut result, &mut copied);
-    Ok(result)
+/// Advises the kernel to back `memory` with huge pages, reducing TLB misses on accesses to large ORAMs.
+/// Should be called before `memory` is first written, since pages already faulted in are not necessarily remapped.
+/// Only the whole pages within `memory` are advised. Does nothing on platforms other than Linux.
+#[cfg(feature = "hugepages")]
+pub(crate) fn advise_huge_pages<T>(memory: &[T]) {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: `sysconf` has no preconditions.
+        let page_size = match usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }) {
+            Ok(page_size) if page_size > 0 => page_size,
+            _ => return,
+        };
+        let start = memory.as_ptr() as usize;
+        let end = start + core::mem::size_of_val(memory);
+        let first_page = start.div_ceil(page_size) * page_size;
+        let end_of_last_page = end / page_size * page_size;
+        if first_page < end_of_last_page {
+            // SAFETY: the advised range lies within `memory`, and `MADV_HUGEPAGE` does not change its contents.
+            // The advice is best-effort, so failure (e.g., on kernels without transparent huge pages) is ignored.
+            unsafe {
+                libc::madvise(
+                    first_page as *mut libc::c_void,
+                    end_of_last_page - first_page,
+                    libc::MADV_HUGEPAGE,
+                );
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = memory;
 }
 
 /// Converts a `Vec<u64>` to a `Vec<usize>`.
@@ -164,47 +172,30 @@ pub(crate) fn to_usize_vec(source: Vec<u64>) -> Result<Vec<usize>, OramError> {
 
 #[cfg(test)]
 mod tests {
-    use super::TreeIndex;
-    use rand::{rngs::StdRng, SeedableRng};
-    use static_assertions::const_assert_eq;
-    use std::mem::size_of;
-
-    use super::{
-        bitonic_sort_by_keys, invert_permutation_oblivious,
-        random_permutation_of_0_through_n_exclusive,
-    };
-
-    #[test]
-    fn check_size_of_tree_index() {
-        const_assert_eq!(size_of::<TreeIndex>(), 8);
-    }
+    use super::IndexWord;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
-    #[test]
-    fn test_invert_permutation_oblivious() {
-        let n = 16;
+    // Checks `ct_eq_each` against `ct_eq` on random words drawn from a small range, so that many words match.
+    fn check_ct_eq_each<I: IndexWord>() {
         let mut rng = StdRng::seed_from_u64(0);
-        let permutation = random_permutation_of_0_through_n_exclusive(n, &mut rng);
-        let inverse = invert_permutation_oblivious(&permutation).unwrap();
-        for i in 0..n {
-            assert_eq!(i, inverse[permutation[i as usize] as usize]);
+        for len in 0..40 {
+            for _ in 0..10 {
+                let words: Vec<I> = (0..len)
+                    .map(|_| I::from_u64(rng.gen_range(0..4)).unwrap())
+                    .collect();
+                let target = I::from_u64(rng.gen_range(0..4)).unwrap();
+                let mut matches = vec![0xff; len];
+                I::ct_eq_each(&words, target, &mut matches);
+                for (word, m) in words.iter().zip(matches) {
+                    assert_eq!(m, word.ct_eq(&target).unwrap_u8());
+                }
+            }
         }
     }
 
     #[test]
-    fn test_bitonic_sort() {
-        let mut rng = StdRng::seed_from_u64(0);
-        let mut items: Vec<u64> = Vec::new();
-        let mut keys: Vec<u64> = Vec::new();
-        let n = 128;
-        for e in random_permutation_of_0_through_n_exclusive(n, &mut rng) {
-            items.push(e as u64);
-            keys.push((e + (2 * n)) as u64);
-        }
-
-        bitonic_sort_by_keys(&mut items, &mut keys);
-        for i in 0..(items.len() - 1) {
-            assert!(keys[i] <= keys[i + 1]);
-            assert_eq!(keys[i], items[i] + (2 * (n as u64)));
-        }
+    fn ct_eq_each_matches_ct_eq() {
+        check_ct_eq_each::<u32>();
+        check_ct_eq_each::<u64>();
     }
 }
@@ -0,0 +1,70 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! JavaScript bindings, built with `wasm-bindgen`. Enabled by the `wasm` feature.
+//!
+//! ⚠️ **Warning**: browser memory is not an enclave. The browser, its extensions, and the operating system
+//! can all observe the memory of a WebAssembly module, so these bindings are suitable for demonstrations
+//! and experiments but provide none of the security guarantees of this crate.
+//!
+//! Addresses and capacities are JavaScript numbers (`u32`), which avoids `BigInt` in the JavaScript API.
+//! WebAssembly's 4 GiB memory limit is reached long before `u32` capacities are exhausted:
+//! a Path ORAM uses about `capacity * 4 * (block_size + 16)` bytes.
+
+use crate::{byte_oram::ByteOram, Address};
+use wasm_bindgen::prelude::*;
+
+pub use crate::byte_oram::SUPPORTED_BLOCK_SIZES;
+
+/// An ORAM storing byte blocks of one of the [`SUPPORTED_BLOCK_SIZES`].
+/// Randomness is drawn from the browser's `crypto.getRandomValues`.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WasmOram(ByteOram);
+
+#[wasm_bindgen]
+impl WasmOram {
+    /// Returns a new ORAM storing `capacity` zeroed blocks of `block_size` bytes.
+    /// Throws if `block_size` is unsupported or `capacity` is invalid (see [`crate::DefaultOram::new`]).
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: u32, block_size: u32) -> Result<WasmOram, JsError> {
+        let block_size = usize::try_from(block_size)?;
+        match ByteOram::new(Address::from(capacity), block_size)? {
+            Some(oram) => Ok(Self(oram)),
+            None => Err(JsError::new(&format!(
+                "Unsupported block size {block_size}. Supported block sizes are {SUPPORTED_BLOCK_SIZES:?}."
+            ))),
+        }
+    }
+
+    /// The size in bytes of the blocks stored by this ORAM.
+    #[wasm_bindgen(getter, js_name = blockSize)]
+    pub fn block_size(&self) -> u32 {
+        // Every supported block size fits in a `u32`.
+        self.0.block_size() as u32
+    }
+
+    /// Obliviously reads the block at `address`, returned as a `Uint8Array`.
+    pub fn read(&mut self, address: u32) -> Result<Vec<u8>, JsError> {
+        let mut out = vec![0; self.0.block_size()];
+        self.0.read(Address::from(address), &mut out)?;
+        Ok(out)
+    }
+
+    /// Obliviously writes `data` (a `Uint8Array` of length `blockSize`) to the block at `address`.
+    pub fn write(&mut self, address: u32, data: &[u8]) -> Result<(), JsError> {
+        if data.len() != self.0.block_size() {
+            return Err(JsError::new(&format!(
+                "Expected {} bytes, but got {}.",
+                self.0.block_size(),
+                data.len()
+            )));
+        }
+        self.0.write(Address::from(address), data)?;
+        Ok(())
+    }
+}
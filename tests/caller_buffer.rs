@@ -0,0 +1,82 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Checks, with a counting global allocator, that a Path ORAM constructed over a buffer supplied by the caller
+//! makes no large allocation of its own, neither when constructed nor when accessed.
+
+use oram::{linear_time_oram::LinearTimeOram, Bucket, Oram, PathOram};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts the allocations (and reallocations) of at least `LARGE_ALLOCATION_BYTES`.
+struct CountingAllocator;
+
+static LARGE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+const CAPACITY: u64 = 1 << 12;
+type TestOram = PathOram<u64, 4, 8>;
+// A quarter of the tree of a `TestOram` of capacity `CAPACITY`.
+const LARGE_ALLOCATION_BYTES: usize = CAPACITY as usize * std::mem::size_of::<Bucket<u64, 4>>() / 4;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() >= LARGE_ALLOCATION_BYTES {
+            LARGE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size >= LARGE_ALLOCATION_BYTES {
+            LARGE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn large_allocations() -> usize {
+    LARGE_ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+// A single test, so that no other test allocates concurrently.
+#[test]
+fn orams_in_caller_buffers_make_no_large_allocations() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    // Constructing over an internally allocated tree allocates it.
+    let before = large_allocations();
+    let oram = TestOram::new_with_parameters(CAPACITY, &mut rng, 40, 1 << 14).unwrap();
+    assert_eq!(large_allocations() - before, 1);
+    drop(oram);
+
+    let buffer = vec![Bucket::default(); CAPACITY as usize];
+    let before = large_allocations();
+    let mut oram = TestOram::new_in_buffer(CAPACITY, buffer, &mut rng, 40, 1 << 14).unwrap();
+    for _ in 0..1000 {
+        let address = rng.gen_range(0..CAPACITY);
+        oram.write(address, address, &mut rng).unwrap();
+        assert_eq!(oram.read(address, &mut rng).unwrap(), address);
+    }
+    let buffer = oram.into_inner();
+    assert_eq!(large_allocations(), before);
+    assert_eq!(buffer.len(), CAPACITY as usize);
+
+    let values = vec![0u64; LARGE_ALLOCATION_BYTES];
+    let before = large_allocations();
+    let mut oram = LinearTimeOram::from_vec(values).unwrap();
+    oram.write(5, 1, &mut rng).unwrap();
+    assert_eq!(oram.into_inner()[5], 1);
+    assert_eq!(large_allocations(), before);
+}
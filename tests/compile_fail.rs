@@ -0,0 +1,75 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Checks that ORAMs with invalid const generic parameters fail to compile, with readable errors.
+//!
+//! The parameters are checked by constants evaluated during monomorphization, which `cargo check` does not perform,
+//! so each program in `tests/ui` is built with `cargo build` as a binary of a scratch crate depending on this one.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// Each program in `tests/ui`, with the message its build must fail with.
+const CASES: [(&str, &str); 4] = [
+    (
+        "path_oram_bucket_size_one",
+        "the bucket size Z must be at least 2",
+    ),
+    (
+        "path_oram_position_block_size_not_power_of_two",
+        "the position block size AB must be a power of two and at least 2",
+    ),
+    (
+        "ring_oram_no_dummy_slots",
+        "the number of dummy slots S must be at least 1, and Z + S at most 64",
+    ),
+    (
+        "ring_oram_too_many_slots",
+        "the number of dummy slots S must be at least 1, and Z + S at most 64",
+    ),
+];
+
+#[test]
+fn invalid_parameters_fail_to_compile() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let crate_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compile_fail");
+    fs::create_dir_all(&crate_dir).unwrap();
+
+    let mut manifest = format!(
+        "[package]\nname = \"compile-fail\"\nversion = \"0.0.0\"\nedition = \"2021\"\n\n\
+         [dependencies]\noram = {{ path = {:?} }}\nrand = \"0.8\"\n\n[workspace]\n",
+        manifest_dir
+    );
+    for (name, _) in CASES {
+        let path = manifest_dir.join("tests/ui").join(format!("{}.rs", name));
+        manifest += &format!("\n[[bin]]\nname = {:?}\npath = {:?}\n", name, path);
+    }
+    fs::write(crate_dir.join("Cargo.toml"), manifest).unwrap();
+    // Reuse this crate's dependency versions, so that the scratch crate builds without network access.
+    fs::copy(
+        manifest_dir.join("Cargo.lock"),
+        crate_dir.join("Cargo.lock"),
+    )
+    .unwrap();
+
+    for (name, expected_error) in CASES {
+        let output = Command::new(env!("CARGO"))
+            .args(["build", "--offline", "--bin", name])
+            .current_dir(&crate_dir)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{} compiled", name);
+        assert!(
+            stderr.contains(expected_error),
+            "{} failed to compile without the expected error: {}",
+            name,
+            stderr
+        );
+    }
+}
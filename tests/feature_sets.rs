@@ -0,0 +1,75 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Checks that the crate compiles with each meaningful combination of features,
+//! including as `no_std` when the `std` feature is disabled, and that its core depends only on `rand` and `subtle`.
+
+use std::path::Path;
+use std::process::Command;
+
+// The feature sets to check, each passed with `--no-default-features`.
+// `simd` requires a nightly toolchain, so is left out.
+const FEATURE_SETS: [&str; 7] = [
+    "",
+    "logging",
+    "std",
+    "std,logging",
+    "diagnostics,seeded",
+    "std,diagnostics,seeded,soak",
+    "std,logging,ffi,wasm,diagnostics,hugepages,seeded,soak",
+];
+
+// Runs `cargo <args>` on this crate and returns its standard output.
+fn cargo(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO"))
+        .args(args)
+        .arg("--manifest-path")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "`cargo {}` failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn builds_with_each_feature_set() {
+    for features in FEATURE_SETS {
+        let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("feature_sets");
+        cargo(&[
+            "check",
+            "--lib",
+            "--no-default-features",
+            "--features",
+            features,
+            "--target-dir",
+            target_dir.to_str().unwrap(),
+        ]);
+    }
+}
+
+#[test]
+fn core_depends_only_on_rand_and_subtle() {
+    let tree = cargo(&[
+        "tree",
+        "--no-default-features",
+        "--edges",
+        "normal",
+        "--prefix",
+        "none",
+        "--format",
+        "{lib}",
+    ]);
+    let mut dependencies: Vec<&str> = tree.lines().filter(|line| !line.is_empty()).collect();
+    dependencies.sort_unstable();
+    dependencies.dedup();
+    assert_eq!(dependencies, ["oram", "rand", "rand_core", "subtle"]);
+}
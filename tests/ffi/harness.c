@@ -0,0 +1,66 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+// Exercises the C ABI of the `oram` crate. Exits with status 0 on success.
+
+#include <stdio.h>
+#include <string.h>
+
+#include "oram.h"
+
+#define CHECK(condition)                                                     \
+  do {                                                                       \
+    if (!(condition)) {                                                      \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,       \
+              #condition);                                                   \
+      return 1;                                                              \
+    }                                                                        \
+  } while (0)
+
+static int check_block_size(uint64_t capacity, size_t block_size) {
+  unsigned char data[4096];
+  unsigned char out[4096];
+
+  OramHandle *oram = oram_new(capacity, block_size);
+  CHECK(oram != NULL);
+
+  for (uint64_t address = 0; address < capacity; address += 7) {
+    memset(data, (int)(address % 251), block_size);
+    CHECK(oram_write(oram, address, data, block_size) == ORAM_STATUS_OK);
+  }
+  for (uint64_t address = 0; address < capacity; address += 7) {
+    memset(data, (int)(address % 251), block_size);
+    CHECK(oram_read(oram, address, out, block_size) == ORAM_STATUS_OK);
+    CHECK(memcmp(data, out, block_size) == 0);
+  }
+
+  CHECK(oram_read(oram, capacity, out, block_size) ==
+        ORAM_STATUS_ADDRESS_OUT_OF_BOUNDS_ERROR);
+  CHECK(oram_read(oram, 0, out, block_size - 1) ==
+        ORAM_STATUS_INVALID_ARGUMENT);
+  CHECK(oram_write(oram, 0, NULL, block_size) ==
+        ORAM_STATUS_INVALID_ARGUMENT);
+
+  oram_free(oram);
+  return 0;
+}
+
+int main(void) {
+  CHECK(oram_new(64, 100) == NULL);
+  CHECK(oram_new(3000, 64) == NULL);
+  CHECK(oram_read(NULL, 0, NULL, 0) == ORAM_STATUS_INVALID_ARGUMENT);
+  oram_free(NULL);
+
+  // Capacity 64 uses a linear-time ORAM; capacity 2048 uses Path ORAM.
+  CHECK(check_block_size(64, 64) == 0);
+  CHECK(check_block_size(64, 256) == 0);
+  CHECK(check_block_size(64, 4096) == 0);
+  CHECK(check_block_size(2048, 64) == 0);
+
+  printf("ok\n");
+  return 0;
+}
@@ -0,0 +1,146 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Drives the `kv_store` example end to end, so that it keeps building and working.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+
+// Builds the example (once per test run) and returns the path of its binary.
+fn kv_store_binary() -> &'static Path {
+    static BINARY: OnceLock<PathBuf> = OnceLock::new();
+    BINARY.get_or_init(|| {
+        let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("kv_store");
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "--example", "kv_store", "--target-dir"])
+            .arg(&target_dir)
+            .arg("--manifest-path")
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "building the kv_store example failed");
+        target_dir
+            .join("debug")
+            .join("examples")
+            .join(format!("kv_store{}", std::env::consts::EXE_SUFFIX))
+    })
+}
+
+// Returns a path for a store file that does not yet exist.
+fn store_path(name: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.kv"));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn run(store: &Path, args: &[&str]) -> Output {
+    Command::new(kv_store_binary())
+        .arg(store)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn commands_persist_across_invocations() {
+    let store = store_path("persist");
+    let output = run(
+        &store,
+        &["put", "alice", "42", "put", "bob", "7", "get", "alice"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "inserted alice\ninserted bob\n42\n");
+
+    let output = run(
+        &store,
+        &[
+            "get", "bob", "put", "bob", "8", "del", "alice", "get", "alice",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "7\nupdated bob (was 7)\ndeleted alice\nalice not found\n"
+    );
+
+    let output = run(&store, &["get", "bob", "stats"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = stdout(&output);
+    assert!(output.starts_with("8\ncapacity: 1024 slots"), "{output}");
+    assert!(output.contains("commands: 1\n"), "{output}");
+    assert!(output.contains("stash occupancy: "), "{output}");
+}
+
+#[test]
+fn commands_are_read_from_standard_input() {
+    let store = store_path("stdin");
+    let mut child = Command::new(kv_store_binary())
+        .arg(&store)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"put command get\n\nget command\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "inserted command\nget\n");
+}
+
+#[test]
+fn failures_are_reported_without_losing_the_store() {
+    let store = store_path("failures");
+    let too_long = "x".repeat(64);
+    let output = run(
+        &store,
+        &[
+            "frobnicate",
+            "put",
+            "kept",
+            "1",
+            "put",
+            "big",
+            &too_long,
+            "get",
+            "kept",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "inserted kept\n1\n");
+    let errors = stderr(&output);
+    assert!(
+        errors.contains("error: the record is too large"),
+        "{errors}"
+    );
+    assert!(
+        errors.contains("error: unknown command \"frobnicate\""),
+        "{errors}"
+    );
+
+    let output = run(&store, &["get", "kept", "get", "big"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1\nbig not found\n");
+
+    let output = Command::new(kv_store_binary()).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("usage: kv_store"));
+}
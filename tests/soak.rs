@@ -0,0 +1,29 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Runs the default soak test. Built only with the `soak` feature, and slow: run it in release mode, e.g.,
+//! `cargo test --release --features soak --test soak`. The environment variables `ORAM_SOAK_SEED`
+//! and `ORAM_SOAK_OPERATIONS` override the seed and the number of operations.
+
+use oram::soak::{self, SoakConfig};
+
+fn env_override(name: &str, default: u64) -> u64 {
+    std::env::var(name).map_or(default, |value| value.parse().unwrap())
+}
+
+#[test]
+fn soak() {
+    let default = SoakConfig::default();
+    let config = SoakConfig {
+        seed: env_override("ORAM_SOAK_SEED", default.seed),
+        operations: env_override("ORAM_SOAK_OPERATIONS", default.operations),
+        ..default
+    };
+    if let Err(failure) = soak::run(config, |checkpoint| println!("{:?}", checkpoint)) {
+        panic!("{}", failure);
+    }
+}
@@ -0,0 +1,13 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+use oram::ring_oram::RingOram;
+use rand::rngs::OsRng;
+
+fn main() {
+    RingOram::<u64, 4, 61>::new_with_parameters(64, &mut OsRng, 40, 1).unwrap();
+}
@@ -0,0 +1,56 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Runs a randomized workload against the JavaScript bindings in a headless browser.
+//! Run with `wasm-pack test --headless --firefox --chrome -- --features wasm`.
+
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use oram::wasm::{WasmOram, SUPPORTED_BLOCK_SIZES};
+use rand::{rngs::OsRng, Rng};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn random_workload(capacity: u32, block_size: u32) {
+    let mut rng = OsRng;
+    let mut oram = WasmOram::new(capacity, block_size).unwrap();
+    let mut mirror = vec![vec![0u8; block_size as usize]; capacity as usize];
+
+    for _ in 0..200 {
+        let address = rng.gen_range(0..capacity);
+        if rng.gen::<bool>() {
+            assert_eq!(oram.read(address).unwrap(), mirror[address as usize]);
+        } else {
+            let mut data = vec![0u8; block_size as usize];
+            rng.fill(&mut data[..]);
+            oram.write(address, &data).unwrap();
+            mirror[address as usize] = data;
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_linear_oram_random_workload() {
+    for block_size in SUPPORTED_BLOCK_SIZES {
+        random_workload(64, block_size as u32);
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_path_oram_random_workload() {
+    random_workload(2048, 64);
+}
+
+#[wasm_bindgen_test]
+fn wasm_rejects_invalid_arguments() {
+    assert!(WasmOram::new(64, 100).is_err());
+    let mut oram = WasmOram::new(64, 64).unwrap();
+    assert_eq!(oram.block_size(), 64);
+    assert!(oram.read(64).is_err());
+    assert!(oram.write(0, &[0; 63]).is_err());
+}